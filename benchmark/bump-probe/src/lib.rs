@@ -0,0 +1,54 @@
+//! Checks that the first account is a PDA, either the way the vault does it
+//! with the bump stored in the account or with a search for the bump. The
+//! benchmark runs both on the same seeds to measure what the stored bumps
+//! save, the vault itself only has the stored bump check.
+//!
+//! Instruction data: `[search: u8][program_id: 32][bump: u8]` followed by
+//! the seeds, each prefixed with its length as a `u8`. The PDA is derived
+//! for `program_id`, so the probe checks the addresses of another program.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey,
+};
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Instruction data of a check
+pub fn instruction_data(search: bool, program_id: &Pubkey, bump: u8, seeds: &[&[u8]]) -> Vec<u8> {
+    let mut data = vec![search as u8];
+    data.extend_from_slice(program_id.as_ref());
+    data.push(bump);
+    for seed in seeds {
+        data.push(seed.len() as u8);
+        data.extend_from_slice(seed);
+    }
+    data
+}
+
+pub fn process_instruction(_probe_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let key = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?.key;
+    let (&search, data) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+    let (program_id, data) = data.split_at_checked(32).ok_or(ProgramError::InvalidInstructionData)?;
+    let program_id = Pubkey::try_from(program_id).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let (&bump, mut data) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    let mut seeds = Vec::new();
+    while let Some((&len, rest)) = data.split_first() {
+        let (seed, rest) = rest.split_at_checked(len as usize).ok_or(ProgramError::InvalidInstructionData)?;
+        seeds.push(seed);
+        data = rest;
+    }
+
+    let is_pda = if search != 0 {
+        Pubkey::find_program_address(&seeds, &program_id) == (*key, bump)
+    } else {
+        let bump = [bump];
+        seeds.push(&bump);
+        Pubkey::create_program_address(&seeds, &program_id).is_ok_and(|pda| pda == *key)
+    };
+    if !is_pda {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
@@ -0,0 +1,431 @@
+//! Benchmark of every build of the escrow and the vault.
+//!
+//! Runs every instruction of each build on LiteSVM, once per token program,
+//! and records the compute units it uses, the size and rent of the accounts
+//! the program creates and the size of the `.so`. Build the programs as
+//! described in the crate docs, then from this directory
+//!
+//! ```text
+//! cargo run --release --bin bench -- --out report --baseline baseline.json
+//! ```
+//!
+//! writes `benchmark.md` and `benchmark.json` into the directory. With
+//! `--baseline <benchmark.json>` the run fails when an instruction uses more
+//! compute units, or a binary or an account got bigger, than in the baseline.
+//! `--tolerance <percent>` allows some growth before it counts as a
+//! regression.
+//!
+//! `report/` holds the report of the current programs and `baseline.json` the
+//! numbers runs are checked against. Commit the new report with every change
+//! to a program. When a cost grew on purpose, copy `report/benchmark.json`
+//! over `baseline.json` in the same commit.
+//!
+//! MigrateUserState and UpgradeAccount need accounts written by older
+//! versions of the vault and aren't benchmarked.
+
+use benchmark::{Bench, ESCROW_BUILDS, ESCROW_SO, VAULT_BUILDS};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use vault_client::{ConfigParams, LockupTier, Vault};
+
+const DAY: i64 = 24 * 60 * 60;
+/// Tokens of every mint the wallets start with
+const BALANCE: u64 = 1_000_000;
+
+#[derive(Serialize, Deserialize)]
+struct Report {
+    suites: Vec<Suite>,
+}
+
+/// One program with one token program, measured for every build
+#[derive(Serialize, Deserialize)]
+struct Suite {
+    program: String,
+    token_program: String,
+    builds: Vec<Build>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Build {
+    name: String,
+    binary_size: u64,
+    /// In the order the benchmark runs them
+    instructions: Vec<Measurement>,
+    accounts: Vec<AccountCost>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Measurement {
+    instruction: String,
+    compute_units: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AccountCost {
+    account: String,
+    size: usize,
+    rent: u64,
+}
+
+// Collects the numbers of one build while its scenario runs
+struct Recorder {
+    build: Build,
+}
+
+impl Recorder {
+    fn new(build: &benchmark::Build) -> Self {
+        let binary_size = std::fs::metadata(build.path)
+            .unwrap_or_else(|_| panic!("build {} first, see the crate docs", build.path))
+            .len();
+        Recorder {
+            build: Build {
+                name: build.name.to_string(),
+                binary_size,
+                instructions: Vec::new(),
+                accounts: Vec::new(),
+            },
+        }
+    }
+
+    fn measure(&mut self, bench: &mut Bench, instruction: &str, instructions: &[Instruction]) {
+        let compute_units = bench
+            .try_send(instructions)
+            .unwrap_or_else(|error| panic!("{} {instruction}: {error}", self.build.name));
+        self.build.instructions.push(Measurement {
+            instruction: instruction.to_string(),
+            compute_units,
+        });
+    }
+
+    fn account(&mut self, bench: &Bench, account: &str, address: &Pubkey) {
+        let size = bench
+            .svm
+            .get_account(address)
+            .expect("benchmarked accounts exist")
+            .data
+            .len();
+        self.build.accounts.push(AccountCost {
+            account: account.to_string(),
+            size,
+            rent: bench.rent(size),
+        });
+    }
+}
+
+// No caps, minimums, pauses or rate limits, and the 5% penalty the vault is initialized with
+fn open_config() -> ConfigParams {
+    ConfigParams {
+        user_deposit_cap: u64::MAX,
+        tvl_cap: u64::MAX,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: 0,
+        user_window_limit: u64::MAX,
+        vault_window_limit: u64::MAX,
+        early_exit_penalty_bps: 500,
+    }
+}
+
+fn bench_escrow(build: &benchmark::Build, token_program: Pubkey) -> Build {
+    let mut recorder = Recorder::new(build);
+    let mut bench = Bench::new();
+    bench.load(escrow_client::ID, build.path);
+    // The maker holds mint A and the taker mint B, neither has an account of the other mint yet
+    let (maker, taker) = (bench.wallet(), bench.wallet());
+    let (mint_a, mint_b) = (bench.create_mint(&token_program), bench.create_mint(&token_program));
+    bench.mint_to(&maker, &mint_a, BALANCE);
+    bench.mint_to(&taker, &mint_b, BALANCE);
+    let make = |id, offered, wanted| {
+        escrow_client::instruction::make_offer(&maker, &mint_a, &mint_b, &token_program, id, offered, wanted)
+    };
+    let take = |id| escrow_client::instruction::take_offer(&taker, &maker, &mint_a, &mint_b, &token_program, id);
+
+    recorder.measure(&mut bench, "make_offer", &[make(1, 400_000, 50_000)]);
+    recorder.account(&bench, "offer", &escrow_client::offer_address(&maker, 1));
+    recorder.account(&bench, "vault", &escrow_client::vault_address(&maker, 1, &mint_a, &token_program));
+
+    // The first take creates the taker's account of mint A and the maker's of mint B
+    recorder.measure(&mut bench, "take_offer (new token accounts)", &[take(1)]);
+    bench.send(&[make(2, 400_000, 50_000)]);
+    recorder.measure(&mut bench, "take_offer", &[take(2)]);
+
+    bench.send(&[make(3, 100_000, 50_000)]);
+    let ix = escrow_client::instruction::refund_offer(&maker, &mint_a, &mint_b, &token_program, 3);
+    recorder.measure(&mut bench, "refund_offer", &[ix]);
+    recorder.build
+}
+
+// One build of the vault and the Anchor escrow loaded, with an admin, alice
+// and bob. The admin is the upgrade authority of the vault. Alice and bob hold
+// the vault's mint, bob holds mint B too. Alice has an empty mint B account
+// for the proceeds of the escrow offers.
+struct VaultScenario {
+    bench: Bench,
+    vault: Vault,
+    admin: Pubkey,
+    alice: Pubkey,
+    bob: Pubkey,
+    mint_b: Pubkey,
+}
+
+impl VaultScenario {
+    fn new(build: &benchmark::Build, token_program: Pubkey) -> Self {
+        let mut bench = Bench::new();
+        let program_id = Pubkey::new_from_array([1; 32]);
+        bench.load(program_id, build.path);
+        bench.load(escrow_client::ID, ESCROW_SO);
+        let (admin, alice, bob) = (bench.wallet(), bench.wallet(), bench.wallet());
+        bench.set_upgrade_authority(&program_id, &admin);
+        let (mint, mint_b) = (bench.create_mint(&token_program), bench.create_mint(&token_program));
+        bench.mint_to(&alice, &mint, BALANCE);
+        bench.mint_to(&bob, &mint, BALANCE);
+        bench.mint_to(&bob, &mint_b, BALANCE);
+        bench.mint_to(&alice, &mint_b, 0);
+        let vault = Vault::new(program_id, mint, token_program);
+        VaultScenario { bench, vault, admin, alice, bob, mint_b }
+    }
+}
+
+fn bench_vault(build: &benchmark::Build, token_program: Pubkey) -> Build {
+    let mut recorder = Recorder::new(build);
+    let VaultScenario { mut bench, vault, admin, alice, bob, mint_b } = VaultScenario::new(build, token_program);
+
+    recorder.measure(&mut bench, "initialize_registry", &[vault.initialize_registry(&admin)]);
+    recorder.measure(&mut bench, "initialize_vault", &[vault.initialize_vault(&admin, 500, 0)]);
+    recorder.measure(&mut bench, "register_vault", &[vault.register_vault(&admin)]);
+    recorder.measure(&mut bench, "update_config", &[vault.update_config(&admin, open_config())]);
+    recorder.measure(&mut bench, "set_flash_loan_fee", &[vault.set_flash_loan_fee(&admin, 30)]);
+    recorder.account(&bench, "registry", &vault.registry());
+    recorder.account(&bench, "vault_state", &vault.state());
+    recorder.account(&bench, "vault_config", &vault.config());
+    recorder.account(&bench, "vault_ata", &vault.ata());
+
+    recorder.measure(&mut bench, "initialize_user", &[vault.initialize_user(&alice)]);
+    recorder.account(&bench, "user_state", &vault.user_state(&alice));
+    bench.send(&[vault.initialize_user(&bob)]);
+
+    recorder.measure(&mut bench, "deposit", &[vault.deposit(&alice, 500_000, LockupTier::None)]);
+    recorder.measure(&mut bench, "deposit_for", &[vault.deposit_for(&bob, &alice, 100_000)]);
+    recorder.measure(&mut bench, "withdraw", &[vault.withdraw(&alice, &alice, 50_000, false)]);
+    recorder.measure(&mut bench, "withdraw_early", &[vault.withdraw_early(&alice, 50_000)]);
+
+    recorder.measure(&mut bench, "set_delegate", &[vault.set_delegate(&alice, &bob, None)]);
+    recorder.measure(&mut bench, "withdraw (delegate)", &[vault.withdraw(&bob, &alice, 10_000, false)]);
+    recorder.measure(&mut bench, "revoke_delegate", &[vault.revoke_delegate(&alice)]);
+
+    let instructions = [vault.flash_loan(&bob, 100_000), vault.flash_repay(&bob, 100_000)];
+    recorder.measure(&mut bench, "flash_loan + flash_repay", &instructions);
+    recorder.measure(&mut bench, "reconcile", &[vault.reconcile(false)]);
+
+    let ix = vault.make_escrow_offer(&alice, &mint_b, 1, 100_000, 50_000);
+    recorder.measure(&mut bench, "make_escrow_offer", &[ix]);
+    let ix = vault.take_escrow_offer(&bob, &alice, &mint_b, 1);
+    recorder.measure(&mut bench, "take_escrow_offer", &[ix]);
+    bench.send(&[vault.make_escrow_offer(&alice, &mint_b, 2, 100_000, 50_000)]);
+    let ix = vault.refund_escrow_offer(&alice, &mint_b, 2);
+    recorder.measure(&mut bench, "refund_escrow_offer", &[ix]);
+
+    // Bob's early exit leaves 5_000 tokens in the treasury
+    let instructions = [vault.deposit(&bob, 100_000, LockupTier::Days30), vault.withdraw_early(&bob, 100_000)];
+    bench.send(&instructions);
+    let ix = vault.withdraw_treasury(&admin, &vault.user_ata(&bob), 5_000);
+    recorder.measure(&mut bench, "withdraw_treasury", &[ix]);
+
+    recorder.measure(&mut bench, "transfer_admin", &[vault.transfer_admin(&admin, &bob)]);
+    recorder.measure(&mut bench, "accept_admin", &[vault.accept_admin(&bob)]);
+    recorder.measure(&mut bench, "close_user", &[vault.close_user(&bob)]);
+    recorder.measure(&mut bench, "unregister_vault", &[vault.unregister_vault(&admin)]);
+
+    // Tickets need a vault with a cooldown
+    let VaultScenario { mut bench, vault, admin, alice, .. } = VaultScenario::new(build, token_program);
+    let instructions = [
+        vault.initialize_registry(&admin),
+        vault.initialize_vault(&admin, 0, DAY),
+        vault.register_vault(&admin),
+        vault.initialize_user(&alice),
+        vault.deposit(&alice, 500_000, LockupTier::None),
+    ];
+    bench.send(&instructions);
+    recorder.measure(&mut bench, "request_withdraw", &[vault.request_withdraw(&alice, 0, 100_000)]);
+    recorder.account(&bench, "ticket", &vault.ticket(0));
+    bench.warp(DAY);
+    recorder.measure(&mut bench, "claim_withdraw", &[vault.claim_withdraw(&alice, 0)]);
+    recorder.build
+}
+
+fn token_program_name(token_program: &Pubkey) -> &'static str {
+    if *token_program == spl_token::id() {
+        "SPL Token"
+    } else {
+        "Token-2022"
+    }
+}
+
+fn run() -> Report {
+    let mut suites = Vec::new();
+    for token_program in [spl_token::id(), spl_token_2022::id()] {
+        suites.push(Suite {
+            program: "escrow".to_string(),
+            token_program: token_program_name(&token_program).to_string(),
+            builds: ESCROW_BUILDS
+                .iter()
+                .map(|build| bench_escrow(build, token_program))
+                .collect(),
+        });
+        suites.push(Suite {
+            program: "vault".to_string(),
+            token_program: token_program_name(&token_program).to_string(),
+            builds: VAULT_BUILDS
+                .iter()
+                .map(|build| bench_vault(build, token_program))
+                .collect(),
+        });
+    }
+    Report { suites }
+}
+
+// One table per suite, a column per build
+fn markdown(report: &Report) -> String {
+    let mut out = String::from("# Benchmark\n");
+    for suite in &report.suites {
+        let names: Vec<&str> = suite
+            .builds
+            .iter()
+            .map(|build| build.name.as_str())
+            .collect();
+        writeln!(out, "\n## {} ({})\n", suite.program, suite.token_program).unwrap();
+        writeln!(out, "| | {} |", names.join(" | ")).unwrap();
+        writeln!(out, "|---|{}", "---:|".repeat(names.len())).unwrap();
+
+        let row = |out: &mut String, label: &str, value: &dyn Fn(&Build) -> String| {
+            let values: Vec<String> = suite.builds.iter().map(value).collect();
+            writeln!(out, "| {label} | {} |", values.join(" | ")).unwrap();
+        };
+        row(&mut out, "binary size (bytes)", &|build: &Build| {
+            build.binary_size.to_string()
+        });
+        for (index, measurement) in suite.builds[0].instructions.iter().enumerate() {
+            row(
+                &mut out,
+                &format!("{} (CU)", measurement.instruction),
+                &|build: &Build| build.instructions[index].compute_units.to_string(),
+            );
+        }
+        for (index, cost) in suite.builds[0].accounts.iter().enumerate() {
+            row(
+                &mut out,
+                &format!("{} (bytes / rent)", cost.account),
+                &|build: &Build| {
+                    let cost = &build.accounts[index];
+                    format!("{} / {}", cost.size, cost.rent)
+                },
+            );
+        }
+    }
+    out
+}
+
+// Everything which grew by more than the tolerance since the baseline
+fn regressions(report: &Report, baseline: &Report, tolerance: f64) -> Vec<String> {
+    let grew = |old: u64, new: u64| new as f64 > old as f64 * (1.0 + tolerance / 100.0);
+    let mut regressions = Vec::new();
+    for suite in &report.suites {
+        let Some(old_suite) = baseline
+            .suites
+            .iter()
+            .find(|old| old.program == suite.program && old.token_program == suite.token_program)
+        else {
+            continue;
+        };
+        for build in &suite.builds {
+            let Some(old) = old_suite.builds.iter().find(|old| old.name == build.name) else {
+                continue;
+            };
+            let prefix = format!("{} ({}) {}", suite.program, suite.token_program, build.name);
+            if grew(old.binary_size, build.binary_size) {
+                regressions.push(format!(
+                    "{prefix}: binary size {} -> {}",
+                    old.binary_size, build.binary_size
+                ));
+            }
+            for measurement in &build.instructions {
+                let Some(before) = old
+                    .instructions
+                    .iter()
+                    .find(|old| old.instruction == measurement.instruction)
+                else {
+                    continue;
+                };
+                if grew(before.compute_units, measurement.compute_units) {
+                    regressions.push(format!(
+                        "{prefix}: {} {} -> {} CU",
+                        measurement.instruction, before.compute_units, measurement.compute_units
+                    ));
+                }
+            }
+            for cost in &build.accounts {
+                let Some(before) = old.accounts.iter().find(|old| old.account == cost.account)
+                else {
+                    continue;
+                };
+                if grew(before.size as u64, cost.size as u64) {
+                    regressions.push(format!(
+                        "{prefix}: {} {} -> {} bytes",
+                        cost.account, before.size, cost.size
+                    ));
+                }
+            }
+        }
+    }
+    regressions
+}
+
+fn main() -> ExitCode {
+    let mut out = PathBuf::from("target/benchmark");
+    let mut baseline = None;
+    let mut tolerance = 0.0;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--out" => out = PathBuf::from(value()),
+            "--baseline" => baseline = Some(PathBuf::from(value())),
+            "--tolerance" => tolerance = value().parse().expect("--tolerance is a percentage"),
+            _ => {
+                eprintln!(
+                    "usage: bench [--out <dir>] [--baseline <benchmark.json>] [--tolerance <percent>]"
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let report = run();
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join("benchmark.md"), markdown(&report)).unwrap();
+    std::fs::write(
+        out.join("benchmark.json"),
+        serde_json::to_string_pretty(&report).unwrap(),
+    )
+    .unwrap();
+    println!("wrote {}", out.display());
+
+    let Some(baseline) = baseline else {
+        return ExitCode::SUCCESS;
+    };
+    let baseline: Report = serde_json::from_slice(&std::fs::read(&baseline).unwrap()).unwrap();
+    let regressions = regressions(&report, &baseline, tolerance);
+    for regression in &regressions {
+        println!("regression: {regression}");
+    }
+    if regressions.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
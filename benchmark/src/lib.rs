@@ -0,0 +1,255 @@
+//! Compute units of the SBF builds of the programs.
+//!
+//! The measurements here load the `.so` files into LiteSVM, like the vault
+//! tests, the CLI tests and the conformance suite do. Build them first from
+//! the repository root with
+//!
+//! ```text
+//! (cd token-escrow/anchor && anchor build)
+//! cargo build-sbf --manifest-path token-escrow/native/program/Cargo.toml
+//! cargo build-sbf --manifest-path token-escrow/pinocchio/program/Cargo.toml --sbf-out-dir target/deploy
+//! cargo build-sbf --manifest-path token-vault/native/program/Cargo.toml
+//! cargo build-sbf --manifest-path token-vault/pinocchio/program/Cargo.toml --sbf-out-dir target/deploy
+//! cargo build-sbf --manifest-path benchmark/bump-probe/Cargo.toml --sbf-out-dir target/deploy
+//! ```
+//!
+//! and then run `cargo test -- --nocapture` from this directory. The `bench`
+//! binary runs every instruction of every build and writes a report, see its
+//! docs.
+
+use litesvm::LiteSVM;
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::instruction as system_instruction;
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::instruction as token_instruction;
+use vault_client::{LockupTier, Vault, program_data_address};
+
+/// One SBF build of a program
+pub struct Build {
+    pub name: &'static str,
+    pub path: &'static str,
+}
+
+/// Every build of the escrow, all of them run at the Anchor program's id
+pub static ESCROW_BUILDS: &[Build] = &[
+    Build { name: "anchor", path: ESCROW_SO },
+    Build { name: "native", path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_escrow.so") },
+    Build {
+        name: "pinocchio",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_escrow_pinocchio.so"),
+    },
+];
+
+/// Every build of the vault
+pub static VAULT_BUILDS: &[Build] = &[
+    Build { name: "native", path: VAULT_SO },
+    Build {
+        name: "pinocchio",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_vault_pinocchio.so"),
+    },
+];
+
+/// The Anchor escrow, the vault's escrow instructions call it
+pub const ESCROW_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../token-escrow/anchor/target/deploy/escrow.so");
+
+pub const VAULT_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_vault.so");
+
+/// Checks a PDA with its stored bump or with find_program_address, see `bump-probe`
+pub const BUMP_PROBE_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/bump_probe.so");
+
+/// Lamports every wallet starts with
+pub const WALLET_LAMPORTS: u64 = 10_000_000_000;
+
+/// LiteSVM with a funded fee payer, the payer is the mint authority of every
+/// mint. Every keypair the bench creates signs wherever an instruction needs
+/// it. Keypairs are derived from a counter, so the accounts are at the same
+/// addresses for every build.
+pub struct Bench {
+    pub svm: LiteSVM,
+    pub payer: Keypair,
+    signers: Vec<Keypair>,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bench {
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new_from_array([1; 32]);
+        svm.airdrop(&payer.pubkey(), 100_000_000_000).unwrap();
+        Bench { svm, payer, signers: Vec::new() }
+    }
+
+    pub fn load(&mut self, program_id: Pubkey, path: &str) {
+        self.svm
+            .add_program_from_file(program_id, path)
+            .unwrap_or_else(|_| panic!("build {path} first, see the crate docs"));
+    }
+
+    // LiteSVM loads programs without the upgradeable loader, give the program
+    // the ProgramData account it has on chain with this upgrade authority
+    pub fn set_upgrade_authority(&mut self, program_id: &Pubkey, authority: &Pubkey) {
+        // `UpgradeableLoaderState::ProgramData` at slot 0 with `Some(authority)`
+        let mut data = vec![0; 45];
+        data[..4].copy_from_slice(&3u32.to_le_bytes());
+        data[12] = 1;
+        data[13..].copy_from_slice(authority.as_ref());
+        let account = Account {
+            lamports: self.rent(data.len()),
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(program_data_address(program_id), account).unwrap();
+    }
+
+    // Keypair which signs wherever an instruction needs it, e.g. for a new mint
+    pub fn signer(&mut self) -> Pubkey {
+        let mut secret = [0; 32];
+        secret[..8].copy_from_slice(&(self.signers.len() as u64 + 2).to_le_bytes());
+        self.adopt(Keypair::new_from_array(secret))
+    }
+
+    // Sign with this keypair too
+    pub fn adopt(&mut self, signer: Keypair) -> Pubkey {
+        let address = signer.pubkey();
+        self.signers.push(signer);
+        address
+    }
+
+    // New wallet with enough SOL to pay for its own accounts
+    pub fn wallet(&mut self) -> Pubkey {
+        let address = self.signer();
+        self.svm.airdrop(&address, WALLET_LAMPORTS).unwrap();
+        address
+    }
+
+    pub fn create_mint(&mut self, token_program: &Pubkey) -> Pubkey {
+        let mint = self.signer();
+        let space = spl_token_2022::state::Mint::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint,
+                self.rent(space),
+                space as u64,
+                token_program,
+            ),
+            token_instruction::initialize_mint2(token_program, &mint, &self.payer.pubkey(), None, 6).unwrap(),
+        ];
+        self.send(&instructions);
+        mint
+    }
+
+    // Mint tokens to the owner's associated token account, creating it if needed
+    pub fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let token_program = self.svm.get_account(mint).expect("the mint exists").owner;
+        let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
+        let instructions = [
+            create_associated_token_account_idempotent(&self.payer.pubkey(), owner, mint, &token_program),
+            token_instruction::mint_to(&token_program, mint, &ata, &self.payer.pubkey(), &[], amount).unwrap(),
+        ];
+        self.send(&instructions);
+    }
+
+    // Send the instructions, returns the compute units the transaction used
+    pub fn send(&mut self, instructions: &[Instruction]) -> u64 {
+        self.try_send(instructions).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    // Same as `send`, a failure comes back with the program logs
+    pub fn try_send(&mut self, instructions: &[Instruction]) -> Result<u64, String> {
+        let mut signers = vec![&self.payer];
+        for signer in &self.signers {
+            let signs = instructions
+                .iter()
+                .flat_map(|ix| &ix.accounts)
+                .any(|meta| meta.is_signer && meta.pubkey == signer.pubkey());
+            if signs {
+                signers.push(signer);
+            }
+        }
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &signers,
+            self.svm.latest_blockhash(),
+        );
+        let result = self.svm.send_transaction(tx);
+        // Identical transactions would otherwise be rejected as already processed
+        self.svm.expire_blockhash();
+        match result {
+            Ok(meta) => Ok(meta.compute_units_consumed),
+            Err(failed) => Err(format!("{:?}\n{}", failed.err, failed.meta.logs.join("\n"))),
+        }
+    }
+
+    // Move the clock forward, e.g. past a cooldown
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp += seconds;
+        self.svm.set_sysvar(&clock);
+    }
+
+    pub fn rent(&self, size: usize) -> u64 {
+        self.svm.minimum_balance_for_rent_exemption(size)
+    }
+}
+
+/// One build of the vault with an SPL Token mint and a vault for it, the
+/// payer is the admin of everything
+pub struct VaultBench {
+    pub bench: Bench,
+    pub vault: Vault,
+}
+
+impl VaultBench {
+    pub fn new(path: &str) -> Self {
+        let mut bench = Bench::new();
+        let program_id = Pubkey::new_from_array([1; 32]);
+        bench.load(program_id, path);
+        let payer = bench.payer.pubkey();
+        bench.set_upgrade_authority(&program_id, &payer);
+        let mint = bench.create_mint(&spl_token::id());
+        let mut vault_bench = VaultBench { bench, vault: Vault::new(program_id, mint, spl_token::id()) };
+
+        let (payer, vault) = (vault_bench.bench.payer.pubkey(), vault_bench.vault);
+        let instructions =
+            [vault.initialize_registry(&payer), vault.initialize_vault(&payer, 0, 0), vault.register_vault(&payer)];
+        vault_bench.bench.send(&instructions);
+        vault_bench
+    }
+
+    /// User with its state initialized and `amount` tokens in its ATA
+    pub fn user(&mut self, user: Keypair, amount: u64) -> Pubkey {
+        let user = self.bench.adopt(user);
+        self.bench.svm.airdrop(&user, WALLET_LAMPORTS).unwrap();
+        self.bench.mint_to(&user, &self.vault.mint, amount);
+        self.bench.send(&[self.vault.initialize_user(&user)]);
+        user
+    }
+
+    pub fn deposit(&mut self, user: &Pubkey, amount: u64) -> u64 {
+        self.bench.send(&[self.vault.deposit(user, amount, LockupTier::None)])
+    }
+
+    pub fn withdraw(&mut self, user: &Pubkey, amount: u64) -> u64 {
+        self.bench.send(&[self.vault.withdraw(user, user, amount, false)])
+    }
+}
@@ -0,0 +1,98 @@
+use benchmark::{BUMP_PROBE_SO, VAULT_SO, VaultBench};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Where the bench loads the probe
+const PROBE_ID: Pubkey = Pubkey::new_from_array([2; 32]);
+
+// Prints the compute units of the hot path instructions, run with `--nocapture`
+#[test]
+fn deposit_and_withdraw_compute_units() {
+    let mut bench = VaultBench::new(VAULT_SO);
+    let user = bench.user(Keypair::new(), 1_000_000);
+
+    let deposit = bench.deposit(&user, 500_000);
+    let withdraw = bench.withdraw(&user, 250_000);
+
+    println!("| instruction | compute units |");
+    println!("|-------------|---------------|");
+    println!("| deposit     | {deposit:>13} |");
+    println!("| withdraw    | {withdraw:>13} |");
+}
+
+// New user whose user state PDA has a canonical bump matching the filter
+fn user_with_bump(bench: &VaultBench, filter: impl Fn(u8) -> bool) -> (Keypair, u8) {
+    loop {
+        let user = Keypair::new();
+        let bump = Pubkey::find_program_address(
+            &[b"user", user.pubkey().as_ref(), bench.vault.mint.as_ref()],
+            &bench.vault.program_id,
+        )
+        .1;
+        if filter(bump) {
+            return (user, bump);
+        }
+    }
+}
+
+// Deposit and withdraw of each user on a fresh vault
+fn deposit_and_withdraw(users: &[&Keypair]) -> Vec<(u64, u64)> {
+    let mut bench = VaultBench::new(VAULT_SO);
+    users
+        .iter()
+        .map(|user| {
+            let user = bench.user(user.insecure_clone(), 1_000_000);
+            (bench.deposit(&user, 500_000), bench.withdraw(&user, 250_000))
+        })
+        .collect()
+}
+
+// Compute units of the probe checking the user state PDA of the user, with
+// the stored bump or with a bump search
+fn check_user_state(bench: &mut VaultBench, user: &Keypair, bump: u8, search: bool) -> u64 {
+    let (program_id, mint, user) = (bench.vault.program_id, bench.vault.mint, user.pubkey());
+    let seeds: &[&[u8]] = &[b"user", user.as_ref(), mint.as_ref()];
+    let ix = Instruction::new_with_bytes(
+        PROBE_ID,
+        &bump_probe::instruction_data(search, &program_id, bump, seeds),
+        vec![AccountMeta::new_readonly(bench.vault.user_state(&user), false)],
+    );
+    bench.bench.send(&[ix])
+}
+
+// Handlers verify their PDAs with the bump stored in the account. The probe
+// checks the same PDA with find_program_address too, which pays for every
+// bump it skips, so users far from bump 255 cost the search more.
+#[test]
+fn stored_bumps_save_the_bump_search() {
+    let mut bench = VaultBench::new(VAULT_SO);
+    bench.bench.load(PROBE_ID, BUMP_PROBE_SO);
+    let (cheap_user, cheap_bump) = user_with_bump(&bench, |bump| bump == u8::MAX);
+    let (costly_user, costly_bump) = user_with_bump(&bench, |bump| bump <= u8::MAX - 5);
+
+    let mut probe = |user: &Keypair, bump: u8| {
+        (check_user_state(&mut bench, user, bump, false), check_user_state(&mut bench, user, bump, true))
+    };
+    let checks = [probe(&cheap_user, cheap_bump), probe(&costly_user, costly_bump)];
+    let vault = deposit_and_withdraw(&[&cheap_user, &costly_user]);
+
+    println!("| user bump | stored bump check | bump search | deposit | withdraw |");
+    println!("|-----------|-------------------|-------------|---------|----------|");
+    for (index, bump) in [cheap_bump, costly_bump].into_iter().enumerate() {
+        let ((stored, search), (deposit, withdraw)) = (checks[index], vault[index]);
+        println!("| {bump:>9} | {stored:>17} | {search:>11} | {deposit:>7} | {withdraw:>8} |");
+    }
+
+    // The search costs more the further the bump is from 255, the stored
+    // bump costs the same for every bump and so do the vault's instructions
+    let (cheap, costly) = (checks[0], checks[1]);
+    assert!(costly.1 > costly.0, "the search costs no more than the stored bump");
+    let search_spread = costly.1 - cheap.1;
+    assert!(cheap.0.abs_diff(costly.0) < search_spread, "the stored bump check depends on the bump");
+    let (deposits, withdraws) = (vault[0].0.abs_diff(vault[1].0), vault[0].1.abs_diff(vault[1].1));
+    assert!(deposits < search_spread, "deposit depends on the bump");
+    assert!(withdraws < search_spread, "withdraw depends on the bump");
+}
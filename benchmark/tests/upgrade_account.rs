@@ -0,0 +1,206 @@
+use benchmark::{VAULT_BUILDS, VaultBench};
+use solana_sdk::{
+    account::Account,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_token_vault::{
+    error::VaultError,
+    header::{AccountHeader, AccountKind, HEADER_LEN, VaultAccount, decode},
+    legacy::{
+        UserStateV0, UserStateV1, UserStateV2, UserStateV3, UserStateV4, UserStateV5, UserStateV6,
+        VaultConfigV0, VaultStateV0, WithdrawTicketV0,
+    },
+    state::{LockupTier, RateWindow, UNSYNCED_FEE_INDEX, UserState, VaultConfig, VaultState, WithdrawTicket},
+};
+
+// UpgradeAccount of every build of the vault, on SBF. Each build must write
+// the bytes the native decoding of the older layout gives.
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+// Account data as the given version of the program wrote it, unversioned
+// accounts were written before headers existed
+fn account_data<T: VaultAccount>(version: Option<u8>, bump: u8, body: &[u8]) -> Vec<u8> {
+    let mut data = match version {
+        Some(version) => {
+            let header = AccountHeader { discriminator: T::DISCRIMINATOR, version, bump };
+            borsh::to_vec(&header).unwrap()
+        }
+        None => Vec::new(),
+    };
+    data.extend_from_slice(body);
+    data
+}
+
+// Replace the account with older data, funded for its own size only
+fn set_old_account(bench: &mut VaultBench, address: &Pubkey, data: Vec<u8>) {
+    let account = Account {
+        lamports: bench.bench.rent(data.len()),
+        data,
+        owner: bench.vault.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    bench.bench.svm.set_account(*address, account).unwrap();
+}
+
+// Write the older data, upgrade it and compare with the native decoding
+fn upgrade<T: VaultAccount>(bench: &mut VaultBench, kind: AccountKind, address: &Pubkey, bump: u8, old: Vec<u8>) {
+    let (version, body) = match old.get(..8) {
+        Some(discriminator) if *discriminator == T::DISCRIMINATOR => (Some(old[8]), &old[HEADER_LEN..]),
+        _ => (None, &old[..]),
+    };
+    let mut expected = account_data::<T>(Some(T::VERSION), bump, &vec![0; T::LEN]);
+    let mut body = T::from_version(version, body).unwrap();
+    body.find_bumps(&bench.vault.program_id, address);
+    body.pack(&mut expected[HEADER_LEN..]).unwrap();
+
+    set_old_account(bench, address, old);
+    let payer = bench.bench.payer.pubkey();
+    bench.bench.send(&[bench.vault.upgrade_account(&payer, address, kind)]);
+
+    let account = bench.bench.svm.get_account(address).unwrap();
+    assert_eq!(account.data, expected);
+    assert_eq!(account.lamports, bench.bench.rent(T::SPACE));
+}
+
+// Body of a user state as each older version laid it out
+fn old_user_state(version: Option<u8>, user: Pubkey, mint: Pubkey) -> Vec<u8> {
+    let (amount, unlock_ts, boost_bps) = (700, 1_234, 12_500);
+    let v2 = UserStateV2 { user, mint, amount: amount.into(), unlock_ts: unlock_ts.into(), boost_bps: boost_bps.into() };
+    let v3 = UserStateV3 { fee_index: UNSYNCED_FEE_INDEX.into(), ..v2.into() };
+    let v4 = UserStateV4 { delegate: Pubkey::new_unique(), delegate_allowance: 50u64.into(), ..v3.into() };
+    let v5 = UserStateV5 { outflow: RateWindow { start: 9i64.into(), withdrawn: 10u64.into() }, ..v4.into() };
+    let v6 = UserStateV6 { earmarked: 40u64.into(), ..v5.into() };
+    match version {
+        None => {
+            let v0 = UserStateV0 { user, mint, amount, is_initialized: true, unlock_ts, boost_bps };
+            borsh::to_vec(&v0).unwrap()
+        }
+        Some(1) => borsh::to_vec(&UserStateV1 { user, mint, amount, unlock_ts, boost_bps }).unwrap(),
+        Some(2) => bytemuck::bytes_of(&v2).to_vec(),
+        Some(3) => bytemuck::bytes_of(&v3).to_vec(),
+        Some(4) => bytemuck::bytes_of(&v4).to_vec(),
+        Some(5) => bytemuck::bytes_of(&v5).to_vec(),
+        Some(6) => bytemuck::bytes_of(&v6).to_vec(),
+        Some(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn every_build_upgrades_every_user_state_version() {
+    for build in VAULT_BUILDS {
+        for version in [None, Some(1), Some(2), Some(3), Some(4), Some(5), Some(6)] {
+            let mut bench = VaultBench::new(build.path);
+            let user = bench.user(Keypair::new(), 100);
+            let address = bench.vault.user_state(&user);
+            let (_, bump) = Pubkey::find_program_address(
+                &[b"user", user.as_ref(), bench.vault.mint.as_ref()],
+                &bench.vault.program_id,
+            );
+            let old = account_data::<UserState>(version, bump, &old_user_state(version, user, bench.vault.mint));
+
+            // Outdated accounts are rejected until they're upgraded
+            set_old_account(&mut bench, &address, old.clone());
+            let deposit = bench.vault.deposit(&user, 100, LockupTier::None);
+            let error = bench.bench.try_send(&[deposit]).unwrap_err();
+            if version.is_some() {
+                assert!(error.contains(&custom_error(VaultError::OutdatedAccount)), "{}: {error}", build.name);
+            }
+
+            upgrade::<UserState>(&mut bench, AccountKind::UserState, &address, bump, old);
+            bench.deposit(&user, 100);
+            let data = bench.bench.svm.get_account(&address).unwrap().data;
+            assert_eq!(u64::from(decode::<UserState>(&data).unwrap().amount), 800, "{}", build.name);
+        }
+    }
+}
+
+#[test]
+fn every_build_upgrades_unversioned_vault_accounts() {
+    for build in VAULT_BUILDS {
+        let mut bench = VaultBench::new(build.path);
+        let (program_id, mint, payer) = (bench.vault.program_id, bench.vault.mint, bench.bench.payer.pubkey());
+        let vault = bench.vault.state();
+
+        let (_, vault_bump) = Pubkey::find_program_address(&[b"vault", mint.as_ref()], &program_id);
+        let v0 = VaultStateV0 {
+            mint,
+            early_exit_penalty_bps: 250,
+            treasury: 0,
+            is_initialized: true,
+            withdraw_cooldown: 0,
+            next_ticket_id: 5,
+            queued_total: 0,
+            claimed_total: 0,
+        };
+        upgrade::<VaultState>(&mut bench, AccountKind::VaultState, &vault, vault_bump, borsh::to_vec(&v0).unwrap());
+
+        let (config, config_bump) = Pubkey::find_program_address(&[b"config", vault.as_ref()], &program_id);
+        let v0 = VaultConfigV0 {
+            vault,
+            admin: payer,
+            pending_admin: Pubkey::default(),
+            user_deposit_cap: 1_000,
+            tvl_cap: 5_000,
+            min_deposit: 10,
+            min_withdraw: 20,
+            deposit_paused: false,
+            withdraw_paused: false,
+            is_initialized: true,
+        };
+        upgrade::<VaultConfig>(&mut bench, AccountKind::VaultConfig, &config, config_bump, borsh::to_vec(&v0).unwrap());
+
+        let (ticket, ticket_bump) =
+            Pubkey::find_program_address(&[b"ticket", vault.as_ref(), &4u64.to_le_bytes()], &program_id);
+        let v0 = WithdrawTicketV0 {
+            owner: Pubkey::new_unique(),
+            mint,
+            id: 4,
+            amount: 300,
+            queue_end: 900,
+            claimable_after: 77,
+            is_initialized: true,
+        };
+        let old = borsh::to_vec(&v0).unwrap();
+        upgrade::<WithdrawTicket>(&mut bench, AccountKind::WithdrawTicket, &ticket, ticket_bump, old);
+
+        // The upgraded vault takes deposits within the carried over config
+        let user = bench.user(Keypair::new(), 100);
+        bench.deposit(&user, 100);
+        let below_minimum = bench.vault.deposit(&user, 9, LockupTier::None);
+        let error = bench.bench.try_send(&[below_minimum]).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::BelowMinimum)), "{}: {error}", build.name);
+    }
+}
+
+#[test]
+fn every_build_only_upgrades_older_accounts_at_their_own_address() {
+    for build in VAULT_BUILDS {
+        let mut bench = VaultBench::new(build.path);
+        let user = bench.user(Keypair::new(), 0);
+        let address = bench.vault.user_state(&user);
+        let payer = bench.bench.payer.pubkey();
+
+        let upgrade = bench.vault.upgrade_account(&payer, &address, AccountKind::UserState);
+        let error = bench.bench.try_send(std::slice::from_ref(&upgrade)).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::AccountUpToDate)), "{}: {error}", build.name);
+
+        // Written by a newer program version
+        let mut account = bench.bench.svm.get_account(&address).unwrap();
+        let bump = account.data[9];
+        account.data[8] = UserState::VERSION + 1;
+        bench.bench.svm.set_account(address, account).unwrap();
+        let error = bench.bench.try_send(std::slice::from_ref(&upgrade)).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::UnsupportedAccountVersion)), "{}: {error}", build.name);
+
+        // The position of someone else doesn't belong at this address
+        let body = old_user_state(Some(2), Pubkey::new_unique(), bench.vault.mint);
+        set_old_account(&mut bench, &address, account_data::<UserState>(Some(2), bump, &body));
+        let error = bench.bench.try_send(&[upgrade]).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::InvalidDiscriminator)), "{}: {error}", build.name);
+    }
+}
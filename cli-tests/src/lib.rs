@@ -0,0 +1 @@
+//! Only holds `tests/cli.rs`, see there.
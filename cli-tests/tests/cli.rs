@@ -0,0 +1,309 @@
+//! The commands against the SBF builds of the Anchor escrow and the native
+//! vault on LiteSVM. Build them as described in the docs of `benchmark/`,
+//! then run `cargo test` from this directory.
+
+use clap::Parser;
+use escrow_vault_cli::{
+    Cli, Result,
+    backend::{Backend, Memcmp, SendError, Simulation},
+    run,
+};
+use litesvm::LiteSVM;
+use serde_json::Value;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer, write_keypair_file},
+    transaction::Transaction,
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::instruction as system_instruction;
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions, instruction as token_instruction, state::Account as TokenAccount,
+};
+
+// The SVM has no index of the accounts of a program, `get_program_accounts`
+// looks through every account a transaction used
+struct Svm {
+    svm: LiteSVM,
+    seen: Vec<Pubkey>,
+}
+
+impl Backend for Svm {
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.svm.get_account(address).filter(|account| account.lamports > 0))
+    }
+
+    fn get_program_accounts(&self, program_id: &Pubkey, filters: &[Memcmp]) -> Result<Vec<(Pubkey, Account)>> {
+        Ok(self
+            .seen
+            .iter()
+            .filter_map(|address| Some((*address, self.get_account(address).ok()??)))
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| filters.iter().all(|filter| filter.matches(&account.data)))
+            .collect())
+    }
+
+    fn latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.svm.latest_blockhash())
+    }
+
+    fn send(&mut self, transaction: &Transaction) -> std::result::Result<Signature, SendError> {
+        for address in &transaction.message.account_keys {
+            if !self.seen.contains(address) {
+                self.seen.push(*address);
+            }
+        }
+        let result = self.svm.send_transaction(transaction.clone());
+        // Identical transactions would otherwise be rejected as already processed
+        self.svm.expire_blockhash();
+        match result {
+            Ok(_) => Ok(transaction.signatures[0]),
+            Err(failed) => Err(SendError::Failed(failed.err)),
+        }
+    }
+
+    fn simulate(&self, transaction: &Transaction) -> Result<Simulation> {
+        Ok(match self.svm.simulate_transaction(transaction.clone()) {
+            Ok(info) => Simulation {
+                err: None,
+                logs: info.meta.logs,
+                units_consumed: Some(info.meta.compute_units_consumed),
+            },
+            Err(failed) => Simulation {
+                err: Some(failed.err),
+                logs: failed.meta.logs,
+                units_consumed: Some(failed.meta.compute_units_consumed),
+            },
+        })
+    }
+}
+
+const ESCROW_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../token-escrow/anchor/target/deploy/escrow.so");
+
+const VAULT_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_vault.so");
+
+struct Env {
+    backend: Svm,
+    vault_program: Pubkey,
+    authority: Keypair,
+}
+
+impl Env {
+    fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        svm.add_program_from_file(escrow_client::ID, ESCROW_SO)
+            .expect("build the escrow with anchor build first");
+        let vault_program = Pubkey::new_unique();
+        svm.add_program_from_file(vault_program, VAULT_SO)
+            .expect("build the vault with cargo build-sbf first");
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 100_000_000_000).unwrap();
+        Env { backend: Svm { svm, seen: Vec::new() }, vault_program, authority }
+    }
+
+    // LiteSVM loads programs without a ProgramData account, write the one
+    // `solana program deploy` leaves behind
+    fn set_upgrade_authority(&mut self, authority: &Pubkey) {
+        // `UpgradeableLoaderState::ProgramData` at slot 0 with `Some(authority)`
+        let mut data = vec![0; 45];
+        data[..4].copy_from_slice(&3u32.to_le_bytes());
+        data[12] = 1;
+        data[13..].copy_from_slice(authority.as_ref());
+        let account = Account {
+            lamports: self.backend.svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        let program_data =
+            Pubkey::find_program_address(&[self.vault_program.as_ref()], &bpf_loader_upgradeable::id()).0;
+        self.backend.svm.set_account(program_data, account).unwrap();
+    }
+
+    fn wallet(&mut self) -> Keypair {
+        let wallet = Keypair::new();
+        self.backend.svm.airdrop(&wallet.pubkey(), 10_000_000_000).unwrap();
+        wallet
+    }
+
+    fn create_mint(&mut self, token_program: &Pubkey) -> Pubkey {
+        let mint = Keypair::new();
+        let space = spl_token_2022::state::Mint::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &self.authority.pubkey(),
+                &mint.pubkey(),
+                self.backend.svm.minimum_balance_for_rent_exemption(space),
+                space as u64,
+                token_program,
+            ),
+            token_instruction::initialize_mint2(token_program, &mint.pubkey(), &self.authority.pubkey(), None, 6)
+                .unwrap(),
+        ];
+        self.send(&instructions, &[&mint]);
+        mint.pubkey()
+    }
+
+    fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let token_program = self.backend.svm.get_account(mint).unwrap().owner;
+        let payer = self.authority.pubkey();
+        let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
+        let instructions = [
+            create_associated_token_account_idempotent(&payer, owner, mint, &token_program),
+            token_instruction::mint_to(&token_program, mint, &ata, &payer, &[], amount).unwrap(),
+        ];
+        self.send(&instructions, &[]);
+    }
+
+    fn balance(&self, owner: &Pubkey, mint: &Pubkey) -> u64 {
+        let token_program = self.backend.svm.get_account(mint).unwrap().owner;
+        let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
+        self.backend.svm.get_account(&ata).map_or(0, |account| {
+            StateWithExtensions::<TokenAccount>::unpack(&account.data).unwrap().base.amount
+        })
+    }
+
+    fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) {
+        let mut all_signers = vec![&self.authority];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.authority.pubkey()),
+            &all_signers,
+            self.backend.svm.latest_blockhash(),
+        );
+        self.backend.send(&transaction).unwrap();
+    }
+
+    // Run the command with the wallet as payer, as `escrow-vault --output json <args>`
+    fn run(&mut self, wallet: &Keypair, args: &[&str]) -> Result<Value> {
+        let cli = Cli::try_parse_from(["escrow-vault", "--output", "json"].iter().chain(args))?;
+        run(&cli, &mut self.backend, wallet)
+    }
+
+    fn vault(&mut self, wallet: &Keypair, args: &[&str]) -> Result<Value> {
+        let program_id = self.vault_program.to_string();
+        let args: Vec<&str> = ["vault", "--program-id", &program_id].iter().chain(args).copied().collect();
+        self.run(wallet, &args)
+    }
+}
+
+#[test]
+fn escrow_make_list_show_refund() {
+    let mut env = Env::new();
+    let maker = env.wallet();
+    let mint_a = env.create_mint(&spl_token::id());
+    let mint_b = env.create_mint(&spl_token::id());
+    env.mint_to(&maker.pubkey(), &mint_a, 1_000);
+    let (a, b) = (mint_a.to_string(), mint_b.to_string());
+
+    let made = env
+        .run(&maker, &["escrow", "make", "--mint-a", &a, "--mint-b", &b, "--amount", "300", "--wanted", "50"])
+        .unwrap();
+    assert_eq!(made["id"], 0);
+    assert!(made["signature"].is_string());
+    // The next free id is picked
+    let made = env
+        .run(&maker, &["escrow", "make", "--mint-a", &a, "--mint-b", &b, "--amount", "200", "--wanted", "40"])
+        .unwrap();
+    assert_eq!(made["id"], 1);
+    assert_eq!(env.balance(&maker.pubkey(), &mint_a), 500);
+
+    let listed = env.run(&maker, &["escrow", "list"]).unwrap();
+    let offers = listed.as_array().unwrap();
+    assert_eq!(offers.len(), 2);
+    assert_eq!((offers[0]["id"].clone(), offers[0]["offered"].clone()), (0.into(), 300.into()));
+    assert_eq!((offers[1]["id"].clone(), offers[1]["wanted"].clone()), (1.into(), 40.into()));
+    let stranger = env.wallet();
+    assert_eq!(env.run(&stranger, &["escrow", "list"]).unwrap(), Value::Array(Vec::new()));
+    assert_eq!(env.run(&stranger, &["escrow", "list", "--all"]).unwrap().as_array().unwrap().len(), 2);
+
+    let maker_address = maker.pubkey().to_string();
+    let shown = env.run(&stranger, &["escrow", "show", "--maker", &maker_address, "--id", "1"]).unwrap();
+    assert_eq!(shown["held"], 200);
+    assert_eq!(shown["mint_b"], b);
+
+    // A dry run changes nothing
+    let simulated = env.run(&maker, &["--dry-run", "escrow", "refund", "--id", "0"]).unwrap();
+    assert_eq!(simulated["success"], true);
+    assert!(!simulated["logs"].as_array().unwrap().is_empty());
+    assert_eq!(env.balance(&maker.pubkey(), &mint_a), 500);
+
+    env.run(&maker, &["escrow", "refund", "--id", "0"]).unwrap();
+    assert_eq!(env.balance(&maker.pubkey(), &mint_a), 800);
+    assert_eq!(env.run(&maker, &["escrow", "list"]).unwrap().as_array().unwrap().len(), 1);
+    let error = env.run(&maker, &["escrow", "show", "--id", "0"]).unwrap_err();
+    assert_eq!(error.to_string(), format!("{maker_address} has no open offer 0"));
+}
+
+#[test]
+fn escrow_take_with_the_maker_keypair() {
+    let mut env = Env::new();
+    let maker = env.wallet();
+    let taker = env.wallet();
+    let mint_a = env.create_mint(&spl_token_2022::id());
+    let mint_b = env.create_mint(&spl_token_2022::id());
+    env.mint_to(&maker.pubkey(), &mint_a, 1_000);
+    env.mint_to(&taker.pubkey(), &mint_b, 1_000);
+    let (a, b) = (mint_a.to_string(), mint_b.to_string());
+    env.run(&maker, &["escrow", "make", "--mint-a", &a, "--mint-b", &b, "--amount", "300", "--wanted", "50", "--id", "7"])
+        .unwrap();
+
+    let maker_keypair = std::env::temp_dir().join(format!("escrow-vault-maker-{}.json", maker.pubkey()));
+    write_keypair_file(&maker, &maker_keypair).unwrap();
+    let maker_keypair = maker_keypair.to_str().unwrap();
+    env.run(&taker, &["escrow", "take", "--maker-keypair", maker_keypair, "--id", "7"])
+        .unwrap();
+
+    assert_eq!(env.balance(&taker.pubkey(), &mint_a), 300);
+    assert_eq!(env.balance(&taker.pubkey(), &mint_b), 950);
+    assert_eq!(env.balance(&maker.pubkey(), &mint_b), 50);
+    // Taken offers are closed
+    let error = env.run(&taker, &["escrow", "take", "--maker-keypair", maker_keypair, "--id", "7"]).unwrap_err();
+    assert!(error.to_string().ends_with("has no open offer 7"), "{error}");
+    std::fs::remove_file(maker_keypair).unwrap();
+}
+
+#[test]
+fn vault_deposit_balance_withdraw() {
+    let mut env = Env::new();
+    let admin = env.wallet();
+    let user = env.wallet();
+    env.set_upgrade_authority(&admin.pubkey());
+    let mint = env.create_mint(&spl_token::id());
+    env.mint_to(&user.pubkey(), &mint, 1_000);
+    let mint = mint.to_string();
+
+    let created = env.vault(&admin, &["init", "--mint", &mint]).unwrap();
+    assert_eq!(created["registered"], true);
+    env.vault(&user, &["init-user", "--mint", &mint]).unwrap();
+    env.vault(&user, &["deposit", "--mint", &mint, "--amount", "400"]).unwrap();
+
+    let balance = env.vault(&user, &["balance", "--mint", &mint]).unwrap();
+    assert_eq!(balance["initialized"], true);
+    assert_eq!(balance["deposited"], 400);
+    assert_eq!(balance["wallet"], 600);
+    assert_eq!(balance["earmarked"], 0);
+
+    // Errors of the vault are named
+    let error = env.vault(&user, &["withdraw", "--mint", &mint, "--amount", "401"]).unwrap_err();
+    assert!(error.to_string().contains("vault error: Insufficient user balance"), "{error}");
+    let simulated = env.vault(&user, &["--dry-run", "withdraw", "--mint", &mint, "--amount", "401"]).unwrap();
+    assert_eq!(simulated["success"], false);
+    assert!(simulated["error"].as_str().unwrap().contains("Insufficient user balance"));
+
+    env.vault(&user, &["withdraw", "--mint", &mint, "--amount", "400", "--close"]).unwrap();
+    let balance = env.vault(&user, &["balance", "--mint", &mint]).unwrap();
+    assert_eq!(balance["initialized"], false);
+    assert_eq!(balance["deposited"], 0);
+    assert_eq!(balance["wallet"], 1_000);
+}
@@ -0,0 +1,369 @@
+//! Access to the cluster the commands run against
+
+use crate::Result;
+use base64::prelude::{BASE64_STANDARD, Engine};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentLevel,
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use std::time::{Duration, Instant};
+use ureq::{Agent, AgentBuilder};
+
+/// Filter of `get_program_accounts`, the data at `offset` starts with `bytes`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memcmp {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Memcmp {
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.get(self.offset..).is_some_and(|data| data.starts_with(&self.bytes))
+    }
+}
+
+/// Outcome of a simulated transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+/// Why a transaction didn't go through
+#[derive(Debug)]
+pub enum SendError {
+    /// The transaction ran and failed
+    Failed(TransactionError),
+    /// It couldn't be sent, e.g. the RPC node is unreachable
+    Client(Box<dyn std::error::Error>),
+}
+
+pub trait Backend {
+    /// Account at the address, `None` when it doesn't exist
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>>;
+
+    /// Every account of the program matching all the filters
+    fn get_program_accounts(&self, program_id: &Pubkey, filters: &[Memcmp]) -> Result<Vec<(Pubkey, Account)>>;
+
+    fn latest_blockhash(&self) -> Result<Hash>;
+
+    /// Send the transaction and wait until it's confirmed
+    fn send(&mut self, transaction: &Transaction) -> std::result::Result<Signature, SendError>;
+
+    fn simulate(&self, transaction: &Transaction) -> Result<Simulation>;
+}
+
+/// JSON-RPC client of a Solana node, for the methods the commands use
+pub struct Rpc {
+    url: String,
+    commitment: CommitmentLevel,
+    agent: Agent,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    result: Value,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+// What the methods reading state return, the slot is left out
+#[derive(Deserialize)]
+struct WithContext<T> {
+    value: T,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UiAccount {
+    lamports: u64,
+    /// Base64 of the data and the encoding
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+}
+
+impl UiAccount {
+    fn decode(self) -> Result<Account> {
+        Ok(Account {
+            lamports: self.lamports,
+            data: BASE64_STANDARD.decode(self.data.0)?,
+            owner: self.owner.parse()?,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct KeyedAccount {
+    pubkey: String,
+    account: UiAccount,
+}
+
+#[derive(Deserialize)]
+struct Blockhash {
+    blockhash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureStatus {
+    err: Option<TransactionError>,
+    confirmation_status: Option<CommitmentLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulationResult {
+    err: Option<TransactionError>,
+    logs: Option<Vec<String>>,
+    units_consumed: Option<u64>,
+}
+
+/// How long `send` waits for the transaction to reach the commitment
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(90);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Processed, confirmed and finalized in the order they're reached
+fn rank(commitment: CommitmentLevel) -> u8 {
+    match commitment {
+        CommitmentLevel::Processed => 0,
+        CommitmentLevel::Confirmed => 1,
+        CommitmentLevel::Finalized => 2,
+    }
+}
+
+impl Rpc {
+    pub fn new(url: String, commitment: CommitmentLevel) -> Self {
+        let agent = AgentBuilder::new().timeout(Duration::from_secs(30)).build();
+        Rpc { url, commitment, agent }
+    }
+
+    // The raw response, transport errors are errors, those of the method are in the response
+    fn request(&self, method: &str, params: Value) -> Result<Response> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response = match self.agent.post(&self.url).send_json(body) {
+            Ok(response) => response,
+            // The node answers errors of the method with a status and a JSON-RPC error
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(format!("{}: {error}", self.url).into()),
+        };
+        Ok(response.into_json()?)
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let response = self.request(method, params)?;
+        if let Some(error) = response.error {
+            return Err(format!("{method} failed: {} ({})", error.message, error.code).into());
+        }
+        Ok(serde_json::from_value(response.result)?)
+    }
+
+    fn encode(transaction: &Transaction) -> Result<String> {
+        Ok(BASE64_STANDARD.encode(bincode::serialize(transaction)?))
+    }
+
+    // Poll the status of the signature until it reaches the commitment or fails
+    fn confirm(&self, signature: &Signature) -> std::result::Result<(), SendError> {
+        let started = Instant::now();
+        while started.elapsed() < CONFIRM_TIMEOUT {
+            let statuses: WithContext<Vec<Option<SignatureStatus>>> = self
+                .call("getSignatureStatuses", json!([[signature.to_string()]]))
+                .map_err(SendError::Client)?;
+            if let Some(Some(status)) = statuses.value.into_iter().next() {
+                if let Some(error) = status.err {
+                    return Err(SendError::Failed(error));
+                }
+                if status.confirmation_status.is_some_and(|reached| rank(reached) >= rank(self.commitment)) {
+                    return Ok(());
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Err(SendError::Client(format!("transaction {signature} wasn't confirmed in time").into()))
+    }
+}
+
+impl Backend for Rpc {
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        let config = json!({ "encoding": "base64", "commitment": self.commitment });
+        let account: WithContext<Option<UiAccount>> =
+            self.call("getAccountInfo", json!([address.to_string(), config]))?;
+        account.value.map(UiAccount::decode).transpose()
+    }
+
+    fn get_program_accounts(&self, program_id: &Pubkey, filters: &[Memcmp]) -> Result<Vec<(Pubkey, Account)>> {
+        let filters: Vec<Value> = filters
+            .iter()
+            .map(|memcmp| {
+                json!({ "memcmp": {
+                    "offset": memcmp.offset,
+                    "bytes": BASE64_STANDARD.encode(&memcmp.bytes),
+                    "encoding": "base64",
+                } })
+            })
+            .collect();
+        let config = json!({ "encoding": "base64", "commitment": self.commitment, "filters": filters });
+        let accounts: Vec<KeyedAccount> = self.call("getProgramAccounts", json!([program_id.to_string(), config]))?;
+        accounts
+            .into_iter()
+            .map(|keyed| Ok((keyed.pubkey.parse()?, keyed.account.decode()?)))
+            .collect()
+    }
+
+    fn latest_blockhash(&self) -> Result<Hash> {
+        let latest: WithContext<Blockhash> =
+            self.call("getLatestBlockhash", json!([{ "commitment": self.commitment }]))?;
+        Ok(latest.value.blockhash.parse()?)
+    }
+
+    fn send(&mut self, transaction: &Transaction) -> std::result::Result<Signature, SendError> {
+        let encoded = Self::encode(transaction).map_err(SendError::Client)?;
+        let config = json!({ "encoding": "base64", "preflightCommitment": self.commitment });
+        let response = self
+            .request("sendTransaction", json!([encoded, config]))
+            .map_err(SendError::Client)?;
+        if let Some(error) = response.error {
+            // A failed preflight simulation comes with the error of the transaction
+            let failed = error.data.and_then(|data| serde_json::from_value(data["err"].clone()).ok());
+            return Err(match failed {
+                Some(failed) => SendError::Failed(failed),
+                None => SendError::Client(format!("sendTransaction failed: {} ({})", error.message, error.code).into()),
+            });
+        }
+        let signature = serde_json::from_value::<String>(response.result)
+            .map_err(|error| SendError::Client(error.into()))?
+            .parse::<Signature>()
+            .map_err(|error| SendError::Client(error.into()))?;
+        self.confirm(&signature)?;
+        Ok(signature)
+    }
+
+    fn simulate(&self, transaction: &Transaction) -> Result<Simulation> {
+        let config = json!({ "encoding": "base64", "commitment": self.commitment });
+        let result: WithContext<SimulationResult> =
+            self.call("simulateTransaction", json!([Self::encode(transaction)?, config]))?;
+        Ok(Simulation {
+            err: result.value.err,
+            logs: result.value.logs.unwrap_or_default(),
+            units_consumed: result.value.units_consumed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::InstructionError;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // Node answering every call of a method with the same `result` or
+    // `error`, returns its URL
+    fn node(answers: Vec<(&'static str, Value)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+
+                let (_, answer) = answers.iter().find(|(method, _)| request["method"] == *method).unwrap();
+                let mut response = json!({ "jsonrpc": "2.0", "id": request["id"] });
+                response.as_object_mut().unwrap().extend(answer.as_object().unwrap().clone());
+                let body = response.to_string();
+                let mut stream = reader.into_inner();
+                let headers = format!("Content-Type: application/json\r\nContent-Length: {}", body.len());
+                write!(stream, "HTTP/1.1 200 OK\r\n{headers}\r\nConnection: close\r\n\r\n{body}").unwrap();
+            }
+        });
+        url
+    }
+
+    fn transaction() -> Transaction {
+        Transaction::new_with_payer(&[], Some(&Pubkey::new_unique()))
+    }
+
+    #[test]
+    fn accounts_are_decoded() {
+        let owner = Pubkey::new_unique();
+        let account = json!({ "result": { "context": { "slot": 1 }, "value": {
+            "data": [BASE64_STANDARD.encode([1, 2, 3]), "base64"],
+            "executable": false,
+            "lamports": 1_000,
+            "owner": owner.to_string(),
+            "rentEpoch": u64::MAX,
+            "space": 3,
+        } } });
+        let rpc = Rpc::new(node(vec![("getAccountInfo", account)]), CommitmentLevel::Confirmed);
+        let account = rpc.get_account(&Pubkey::new_unique()).unwrap().unwrap();
+        let expected = Account { lamports: 1_000, data: vec![1, 2, 3], owner, executable: false, rent_epoch: u64::MAX };
+        assert_eq!(account, expected);
+
+        let missing = json!({ "result": { "context": { "slot": 1 }, "value": null } });
+        let rpc = Rpc::new(node(vec![("getAccountInfo", missing)]), CommitmentLevel::Confirmed);
+        assert_eq!(rpc.get_account(&Pubkey::new_unique()).unwrap(), None);
+    }
+
+    #[test]
+    fn sent_transactions_wait_for_the_commitment() {
+        let signature = Signature::new_unique();
+        let rpc = node(vec![
+            ("sendTransaction", json!({ "result": signature.to_string() })),
+            ("getSignatureStatuses", json!({ "result": { "context": { "slot": 1 }, "value": [{
+                "slot": 1,
+                "confirmations": null,
+                "err": null,
+                "status": { "Ok": null },
+                "confirmationStatus": "finalized",
+            }] } })),
+        ]);
+        let mut rpc = Rpc::new(rpc, CommitmentLevel::Confirmed);
+        assert_eq!(rpc.send(&transaction()).unwrap(), signature);
+    }
+
+    #[test]
+    fn failed_preflights_are_failed_transactions() {
+        let error = json!({ "error": {
+            "code": -32002,
+            "message": "Transaction simulation failed: Error processing Instruction 0: custom program error: 0x1771",
+            "data": { "err": { "InstructionError": [0, { "Custom": 6001 }] }, "logs": [] },
+        } });
+        let mut rpc = Rpc::new(node(vec![("sendTransaction", error)]), CommitmentLevel::Confirmed);
+        let Err(SendError::Failed(error)) = rpc.send(&transaction()) else {
+            panic!("the transaction failed");
+        };
+        assert_eq!(error, TransactionError::InstructionError(0, InstructionError::Custom(6001)));
+    }
+}
@@ -0,0 +1,152 @@
+//! `escrow` subcommands. Amounts are in base units of the mints.
+
+use crate::{Context, Result, backend::Memcmp, with};
+use clap::Subcommand;
+use escrow_client::{
+    ID, Offer, instruction, offer_address,
+    state::{MAKER_OFFSET, OFFER_DISCRIMINATOR, decode_offer, fetch_offer},
+    vault_address,
+};
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Signer, read_keypair_file},
+};
+
+#[derive(Debug, Subcommand)]
+pub enum EscrowCommand {
+    /// Offer tokens of mint A for tokens of mint B
+    Make {
+        #[arg(long)]
+        mint_a: Pubkey,
+        #[arg(long)]
+        mint_b: Pubkey,
+        /// Tokens of mint A offered
+        #[arg(long)]
+        amount: u64,
+        /// Tokens of mint B wanted in exchange
+        #[arg(long)]
+        wanted: u64,
+        /// Id of the offer, the lowest one without an open offer by default
+        #[arg(long)]
+        id: Option<u64>,
+    },
+    /// Take an offer, the maker signs too
+    Take {
+        /// Keypair file of the maker
+        #[arg(long)]
+        maker_keypair: String,
+        #[arg(long)]
+        id: u64,
+    },
+    /// Close one of your offers and get the offered tokens back
+    Refund {
+        #[arg(long)]
+        id: u64,
+    },
+    /// Open offers of a maker, yours by default
+    List {
+        #[arg(long, conflicts_with = "all")]
+        maker: Option<Pubkey>,
+        /// Open offers of every maker
+        #[arg(long)]
+        all: bool,
+    },
+    /// One offer and the tokens it holds
+    Show {
+        /// Yours by default
+        #[arg(long)]
+        maker: Option<Pubkey>,
+        #[arg(long)]
+        id: u64,
+    },
+}
+
+pub fn run(context: &mut Context, command: &EscrowCommand) -> Result<Value> {
+    let payer = context.payer.pubkey();
+    match command {
+        EscrowCommand::Make { mint_a, mint_b, amount, wanted, id } => {
+            let token_program = context.token_program(mint_a)?;
+            if context.token_program(mint_b)? != token_program {
+                return Err("mint A and mint B must belong to the same token program".into());
+            }
+            let id = match id {
+                Some(id) => *id,
+                None => next_id(context, &payer)?,
+            };
+            let make = instruction::make_offer(&payer, mint_a, mint_b, &token_program, id, *amount, *wanted);
+            let output = context.execute(&[make], &[])?;
+            Ok(with(output, json!({ "offer": offer_address(&payer, id).to_string(), "id": id })))
+        }
+        EscrowCommand::Take { maker_keypair, id } => {
+            let maker = read_keypair_file(maker_keypair)
+                .map_err(|error| format!("can't read keypair {maker_keypair}: {error}"))?;
+            let offer = open_offer(context, &maker.pubkey(), *id)?;
+            let token_program = context.token_program(&offer.token_mint_a)?;
+            let take = instruction::take_fetched_offer(&payer, &offer, &token_program);
+            let output = context.execute(&[take], &[&maker])?;
+            Ok(with(output, json!({ "offer": offer_address(&offer.maker, *id).to_string() })))
+        }
+        EscrowCommand::Refund { id } => {
+            let offer = open_offer(context, &payer, *id)?;
+            let token_program = context.token_program(&offer.token_mint_a)?;
+            let refund = instruction::refund_fetched_offer(&offer, &token_program);
+            let output = context.execute(&[refund], &[])?;
+            Ok(with(output, json!({ "offer": offer_address(&payer, *id).to_string() })))
+        }
+        EscrowCommand::List { maker, all } => {
+            let mut filters = vec![Memcmp { offset: 0, bytes: OFFER_DISCRIMINATOR.to_vec() }];
+            if !all {
+                let maker = maker.unwrap_or(payer);
+                filters.push(Memcmp { offset: MAKER_OFFSET, bytes: maker.to_bytes().to_vec() });
+            }
+            let mut offers = context
+                .backend
+                .get_program_accounts(&ID, &filters)?
+                .into_iter()
+                .map(|(address, account)| decode_offer(&account).map(|offer| (address, offer)))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            offers.sort_by_key(|(_, offer)| (offer.maker, offer.id));
+            Ok(Value::Array(offers.iter().map(|(address, offer)| offer_json(address, offer)).collect()))
+        }
+        EscrowCommand::Show { maker, id } => {
+            let maker = maker.unwrap_or(payer);
+            let offer = open_offer(context, &maker, *id)?;
+            let token_program = context.token_program(&offer.token_mint_a)?;
+            let vault = vault_address(&maker, *id, &offer.token_mint_a, &token_program);
+            let held = context.token_balance(&vault)?;
+            Ok(with(
+                offer_json(&offer_address(&maker, *id), &offer),
+                json!({ "vault": vault.to_string(), "held": held }),
+            ))
+        }
+    }
+}
+
+fn open_offer(context: &Context, maker: &Pubkey, id: u64) -> Result<Offer> {
+    let account = context.account(&offer_address(maker, id))?;
+    fetch_offer(|_| account, maker, id)?.ok_or_else(|| format!("{maker} has no open offer {id}").into())
+}
+
+// Lowest id the maker has no open offer with
+fn next_id(context: &Context, maker: &Pubkey) -> Result<u64> {
+    for id in 0.. {
+        let account = context.account(&offer_address(maker, id))?;
+        if fetch_offer(|_| account, maker, id)?.is_none() {
+            return Ok(id);
+        }
+    }
+    unreachable!()
+}
+
+fn offer_json(address: &Pubkey, offer: &Offer) -> Value {
+    json!({
+        "offer": address.to_string(),
+        "id": offer.id,
+        "maker": offer.maker.to_string(),
+        "mint_a": offer.token_mint_a.to_string(),
+        "mint_b": offer.token_mint_b.to_string(),
+        "offered": offer.token_a_offered_amount,
+        "wanted": offer.token_b_wanted_amount,
+    })
+}
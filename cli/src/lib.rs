@@ -0,0 +1,214 @@
+//! Command line tool for the token escrow and the native token vault.
+//!
+//! ```text
+//! escrow-vault escrow make|take|refund|list|show
+//! escrow-vault vault --program-id <ID> init|init-user|deposit|withdraw|balance
+//! ```
+//!
+//! The RPC URL, keypair and commitment come from the Solana CLI config, like
+//! for `solana` itself, and `--url` and `--keypair` override them. With
+//! `--dry-run` transactions are simulated and their logs printed instead of
+//! being sent. `--output json` prints the result of a command as JSON.
+//!
+//! Commands only talk to the cluster through a `Backend`. The binary uses
+//! the JSON-RPC API of a node, the tests in `cli-tests/` LiteSVM.
+
+pub mod backend;
+pub mod escrow;
+pub mod vault;
+
+use backend::{Backend, SendError};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{Map, Value, json};
+use solana_sdk::{
+    account::Account,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+use std::fmt::Write;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Manage escrow offers and vault positions")]
+pub struct Cli {
+    /// Solana CLI config file, the default one when omitted
+    #[arg(long, short = 'C', global = true)]
+    pub config: Option<String>,
+    /// RPC URL or moniker (localhost, devnet, testnet, mainnet-beta), overrides the config
+    #[arg(long, short = 'u', global = true)]
+    pub url: Option<String>,
+    /// Keypair which pays and signs, overrides the config
+    #[arg(long, short = 'k', global = true)]
+    pub keypair: Option<String>,
+    /// Simulate the transaction and print its logs instead of sending it
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub output: OutputFormat,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Offers of the token escrow
+    #[command(subcommand)]
+    Escrow(escrow::EscrowCommand),
+    /// Positions in the native token vault
+    Vault(vault::VaultArgs),
+}
+
+/// Run a command with the keypair as payer and signer, returns what it prints
+pub fn run(cli: &Cli, backend: &mut dyn Backend, payer: &Keypair) -> Result<Value> {
+    let mut context = Context { backend, payer, dry_run: cli.dry_run, vault_program: None };
+    match &cli.command {
+        Command::Escrow(command) => escrow::run(&mut context, command),
+        Command::Vault(args) => vault::run(&mut context, args),
+    }
+}
+
+/// What a command runs with
+pub struct Context<'a> {
+    pub backend: &'a mut dyn Backend,
+    pub payer: &'a Keypair,
+    pub dry_run: bool,
+    /// Program id of the vault, to name its errors
+    pub vault_program: Option<Pubkey>,
+}
+
+impl Context<'_> {
+    /// Send the instructions, or simulate them with `--dry-run`. The payer
+    /// signs with the extra `signers`.
+    pub fn execute(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Value> {
+        let mut all_signers = vec![self.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            self.backend.latest_blockhash()?,
+        );
+
+        if self.dry_run {
+            let simulation = self.backend.simulate(&transaction)?;
+            return Ok(json!({
+                "dry_run": true,
+                "success": simulation.err.is_none(),
+                "error": simulation.err.map(|error| self.describe(&error, instructions)),
+                "units_consumed": simulation.units_consumed,
+                "logs": simulation.logs,
+            }));
+        }
+        match self.backend.send(&transaction) {
+            Ok(signature) => Ok(json!({ "signature": signature.to_string() })),
+            Err(SendError::Failed(error)) => Err(self.describe(&error, instructions).into()),
+            Err(SendError::Client(error)) => Err(error),
+        }
+    }
+
+    /// Message of a failed transaction, with the escrow and vault errors named
+    pub fn describe(&self, error: &TransactionError, instructions: &[Instruction]) -> String {
+        let TransactionError::InstructionError(index, InstructionError::Custom(code)) = error else {
+            return error.to_string();
+        };
+        let program_id = instructions.get(usize::from(*index)).map(|instruction| instruction.program_id);
+        let named = match program_id {
+            Some(id) if id == escrow_client::ID => {
+                escrow_client::error::escrow_error(*code).map(|error| format!("escrow error: {error}"))
+            }
+            Some(id) if Some(id) == self.vault_program => {
+                vault_client::error::vault_error(*code).map(|error| format!("vault error: {error}"))
+            }
+            _ => None,
+        };
+        match named {
+            Some(named) => format!("instruction {index} failed with {named} ({code})"),
+            None => error.to_string(),
+        }
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        self.backend.get_account(address)
+    }
+
+    /// Token program owning the mint, SPL Token or Token-2022
+    pub fn token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        let account = self.account(mint)?.ok_or_else(|| format!("mint {mint} doesn't exist"))?;
+        if account.owner != spl_token::id() && account.owner != spl_token_2022::id() {
+            return Err(format!("{mint} is not a mint of SPL Token or Token-2022").into());
+        }
+        Ok(account.owner)
+    }
+
+    /// Balance of a token account, 0 when it doesn't exist
+    pub fn token_balance(&self, token_account: &Pubkey) -> Result<u64> {
+        let Some(account) = self.account(token_account)? else {
+            return Ok(0);
+        };
+        Ok(StateWithExtensions::<TokenAccount>::unpack(&account.data)?.base.amount)
+    }
+}
+
+// Add fields to the object a command prints
+fn with(mut value: Value, fields: Value) -> Value {
+    if let (Value::Object(object), Value::Object(fields)) = (&mut value, fields) {
+        object.extend(fields);
+    }
+    value
+}
+
+/// Format what a command returned
+pub fn render(value: &Value, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value).unwrap(),
+        OutputFormat::Text => {
+            let mut text = String::new();
+            render_text(value, 0, &mut text);
+            text.trim_end().to_string()
+        }
+    }
+}
+
+// `key: value` lines, nested objects and lists indented under their key
+fn render_text(value: &Value, indent: usize, text: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(object) => render_object(object, indent, text),
+        Value::Array(items) if items.is_empty() => writeln!(text, "{pad}(none)").unwrap(),
+        Value::Array(items) => {
+            for item in items {
+                render_text(item, indent, text);
+                if item.is_object() {
+                    text.push('\n');
+                }
+            }
+        }
+        Value::String(string) => writeln!(text, "{pad}{string}").unwrap(),
+        other => writeln!(text, "{pad}{other}").unwrap(),
+    }
+}
+
+fn render_object(object: &Map<String, Value>, indent: usize, text: &mut String) {
+    let pad = " ".repeat(indent);
+    for (key, value) in object {
+        match value {
+            Value::Null => {}
+            Value::Object(_) | Value::Array(_) => {
+                writeln!(text, "{pad}{key}:").unwrap();
+                render_text(value, indent + 2, text);
+            }
+            Value::String(string) => writeln!(text, "{pad}{key}: {string}").unwrap(),
+            other => writeln!(text, "{pad}{key}: {other}").unwrap(),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use clap::Parser;
+use escrow_vault_cli::{Cli, Result, backend::Rpc, render, run};
+use serde::Deserialize;
+use serde_json::Value;
+use solana_sdk::{commitment_config::CommitmentLevel, signature::read_keypair_file};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+/// The settings of the Solana CLI config file the commands use, with the
+/// same defaults
+#[derive(Deserialize)]
+#[serde(default)]
+struct Config {
+    json_rpc_url: String,
+    keypair_path: String,
+    commitment: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            json_rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            keypair_path: solana_home().join("id.json").to_string_lossy().into_owned(),
+            commitment: "confirmed".to_string(),
+        }
+    }
+}
+
+impl Config {
+    fn load(path: &Path) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+// Where the Solana CLI keeps its config and the default keypair
+fn solana_home() -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    home.join(".config").join("solana")
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match connect_and_run(&cli) {
+        Ok(output) => {
+            println!("{}", render(&output, cli.output));
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Same settings as the Solana CLI: the config file, overridden by the flags
+fn connect_and_run(cli: &Cli) -> Result<Value> {
+    let config = match &cli.config {
+        Some(path) => Config::load(Path::new(path)).map_err(|error| format!("can't read config {path}: {error}"))?,
+        // The defaults are only for a missing file, a broken one must not switch clusters
+        None => {
+            let path = solana_home().join("cli").join("config.yml");
+            if path.try_exists()? {
+                Config::load(&path).map_err(|error| format!("can't read config {}: {error}", path.display()))?
+            } else {
+                Config::default()
+            }
+        }
+    };
+    // A typo must not silently change how final the results are
+    let commitment = CommitmentLevel::from_str(&config.commitment)
+        .map_err(|_| format!("invalid commitment {:?} in the config", config.commitment))?;
+    let url = match &cli.url {
+        Some(url) => moniker_url(url),
+        None => config.json_rpc_url,
+    };
+    let keypair_path = cli.keypair.clone().unwrap_or(config.keypair_path);
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|error| format!("can't read keypair {keypair_path}: {error}"))?;
+
+    let mut rpc = Rpc::new(url, commitment);
+    run(cli, &mut rpc, &payer)
+}
+
+// The cluster monikers `solana --url` accepts
+fn moniker_url(url: &str) -> String {
+    match url {
+        "localhost" | "l" => "http://localhost:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "t" => "https://api.testnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
@@ -0,0 +1,171 @@
+//! `vault` subcommands. Amounts are in base units of the mint.
+
+use crate::{Context, Result, with};
+use clap::{Args, Subcommand, ValueEnum};
+use serde_json::{Value, json};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+use vault_client::{
+    LockupTier, Vault, VaultRegistry,
+    state::{decode_account, fetch_user_state, upgrade_authority},
+};
+
+#[derive(Debug, Args)]
+pub struct VaultArgs {
+    /// Address the vault program is deployed at
+    #[arg(long, env = "VAULT_PROGRAM_ID")]
+    pub program_id: Pubkey,
+    #[command(subcommand)]
+    pub command: VaultCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VaultCommand {
+    /// Create the vault of a mint, and the registry when there is none yet. Only
+    /// the registry admin or the upgrade authority of the program can, you
+    /// become the admin of the vault.
+    Init {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Penalty of early exits from locked positions, in basis points
+        #[arg(long, default_value_t = 0)]
+        penalty_bps: u16,
+        /// Seconds withdrawals wait in a ticket, 0 for instant withdrawals
+        #[arg(long, default_value_t = 0)]
+        cooldown: i64,
+    },
+    /// Create your position in the vault of a mint
+    InitUser {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    Deposit {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, value_enum, default_value_t = Lockup::None)]
+        lockup: Lockup,
+    },
+    Withdraw {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Close the position once it's empty and get its rent back
+        #[arg(long)]
+        close: bool,
+        /// Withdraw from this user's position as its delegate
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Position of a user in the vault of a mint, yours by default
+    Balance {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        user: Option<Pubkey>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Lockup {
+    None,
+    #[value(name = "30d")]
+    Days30,
+    #[value(name = "90d")]
+    Days90,
+    #[value(name = "180d")]
+    Days180,
+}
+
+impl From<Lockup> for LockupTier {
+    fn from(lockup: Lockup) -> Self {
+        match lockup {
+            Lockup::None => LockupTier::None,
+            Lockup::Days30 => LockupTier::Days30,
+            Lockup::Days90 => LockupTier::Days90,
+            Lockup::Days180 => LockupTier::Days180,
+        }
+    }
+}
+
+pub fn run(context: &mut Context, args: &VaultArgs) -> Result<Value> {
+    context.vault_program = Some(args.program_id);
+    let payer = context.payer.pubkey();
+
+    match &args.command {
+        VaultCommand::Init { mint, penalty_bps, cooldown } => {
+            let vault = vault(context, &args.program_id, mint)?;
+            let mut instructions = Vec::new();
+            // Deposits need the vault in the registry, only the registry admin can add it and
+            // only the upgrade authority of the program can create the registry
+            let registered = match context.account(&vault.registry())? {
+                None => {
+                    let program_data = context.account(&vault.program_data())?;
+                    let creates = program_data.and_then(|account| upgrade_authority(&account)) == Some(payer);
+                    if creates {
+                        instructions.push(vault.initialize_registry(&payer));
+                    }
+                    creates
+                }
+                Some(account) => decode_account::<VaultRegistry>(&args.program_id, &account)?.admin == payer,
+            };
+            instructions.push(vault.initialize_vault(&payer, *penalty_bps, *cooldown));
+            if registered {
+                instructions.push(vault.register_vault(&payer));
+            }
+            let output = context.execute(&instructions, &[])?;
+            Ok(with(
+                output,
+                json!({
+                    "vault_state": vault.state().to_string(),
+                    "vault_ata": vault.ata().to_string(),
+                    "config": vault.config().to_string(),
+                    "registered": registered,
+                }),
+            ))
+        }
+        VaultCommand::InitUser { mint } => {
+            let vault = vault(context, &args.program_id, mint)?;
+            let output = context.execute(&[vault.initialize_user(&payer)], &[])?;
+            Ok(with(output, json!({ "user_state": vault.user_state(&payer).to_string() })))
+        }
+        VaultCommand::Deposit { mint, amount, lockup } => {
+            let vault = vault(context, &args.program_id, mint)?;
+            context.execute(&[vault.deposit(&payer, *amount, (*lockup).into())], &[])
+        }
+        VaultCommand::Withdraw { mint, amount, close, owner } => {
+            let vault = vault(context, &args.program_id, mint)?;
+            let owner = owner.unwrap_or(payer);
+            context.execute(&[vault.withdraw(&payer, &owner, *amount, *close)], &[])
+        }
+        VaultCommand::Balance { mint, user } => {
+            let vault = vault(context, &args.program_id, mint)?;
+            let user = user.unwrap_or(payer);
+            let account = context.account(&vault.user_state(&user))?;
+            let state = fetch_user_state(|_| account, &args.program_id, &user, mint)?;
+            let output = json!({
+                "user": user.to_string(),
+                "user_state": vault.user_state(&user).to_string(),
+                "initialized": state.is_some(),
+                "deposited": state.map_or(0, |state| u64::from(state.amount)),
+                "wallet": context.token_balance(&vault.user_ata(&user))?,
+            });
+            Ok(match state {
+                Some(state) => with(
+                    output,
+                    json!({
+                        "earmarked": u64::from(state.earmarked),
+                        "unlock_ts": i64::from(state.unlock_ts),
+                        "delegate": state.has_delegate().then(|| state.delegate.to_string()),
+                    }),
+                ),
+                None => output,
+            })
+        }
+    }
+}
+
+fn vault(context: &Context, program_id: &Pubkey, mint: &Pubkey) -> Result<Vault> {
+    Ok(Vault::new(*program_id, *mint, context.token_program(mint)?))
+}
@@ -0,0 +1,37 @@
+//! How the binary reads the Solana CLI config. The commands themselves are
+//! tested against the SBF builds in `cli-tests/`.
+
+use solana_sdk::pubkey::Pubkey;
+
+// A default config which doesn't parse fails the command instead of falling
+// back to mainnet-beta
+#[test]
+fn broken_default_config() {
+    let stderr = run_with_default_config("json_rpc_url: [");
+    assert!(stderr.starts_with("error: can't read config"), "{stderr}");
+}
+
+// Nor does a commitment with a typo fall back to `confirmed`
+#[test]
+fn invalid_commitment() {
+    let stderr = run_with_default_config("commitment: finalised");
+    assert!(stderr.starts_with("error: invalid commitment \"finalised\""), "{stderr}");
+}
+
+// Runs a command with `config` as the default config, it must fail before
+// anything is sent. Returns stderr.
+fn run_with_default_config(config: &str) -> String {
+    let home = std::env::temp_dir().join(format!("escrow-vault-home-{}", Pubkey::new_unique()));
+    let config_dir = home.join(".config").join("solana").join("cli");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.yml"), config).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_escrow-vault"))
+        .args(["escrow", "list", "--all"])
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
@@ -0,0 +1,247 @@
+//! Environment of the escrow table: a maker, a taker and a stranger, and the
+//! mints of one token program.
+
+use crate::{Env, Implementation};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Signer,
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::instruction as token_instruction;
+use spl_token_escrow::{processor::EscrowInstruction, state::Offer};
+use spl_token_vault::escrow::ESCROW_PROGRAM_ID;
+use std::ops::{Deref, DerefMut};
+
+/// Every build of the escrow, all of them run at the Anchor program's id
+pub static ESCROW_BUILDS: &[Implementation] = &[
+    Implementation {
+        name: "anchor",
+        path: ESCROW_SO,
+        anchor: true,
+    },
+    Implementation {
+        name: "native",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_escrow.so"),
+        anchor: false,
+    },
+    Implementation {
+        name: "pinocchio",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_escrow_pinocchio.so"),
+        anchor: false,
+    },
+];
+
+/// The Anchor escrow, the vault's escrow instructions call it
+pub const ESCROW_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../token-escrow/anchor/target/deploy/escrow.so");
+
+pub const DECIMALS: u8 = 6;
+/// Tokens of every mint the wallets start with
+pub const BALANCE: u64 = 1_000_000;
+
+pub struct EscrowEnv {
+    env: Env,
+    pub token_program: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub stranger: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Third mint of the token program
+    pub mint_c: Pubkey,
+    /// Mint of the other token program
+    pub foreign_mint: Pubkey,
+}
+
+impl EscrowEnv {
+    // The maker holds mint A, the taker mints B and C and the stranger mints A and B.
+    // The taker has no account for mint A and the maker none for mint B yet.
+    pub fn new(implementation: &'static Implementation, token_program: Pubkey) -> Self {
+        let env = Env::new(implementation, ESCROW_PROGRAM_ID);
+        let foreign_program = if token_program == spl_token::id() {
+            spl_token_2022::id()
+        } else {
+            spl_token::id()
+        };
+        let mut escrow = EscrowEnv {
+            env,
+            token_program,
+            maker: Pubkey::default(),
+            taker: Pubkey::default(),
+            stranger: Pubkey::default(),
+            mint_a: Pubkey::default(),
+            mint_b: Pubkey::default(),
+            mint_c: Pubkey::default(),
+            foreign_mint: Pubkey::default(),
+        };
+        escrow.maker = escrow.wallet("maker");
+        escrow.taker = escrow.wallet("taker");
+        escrow.stranger = escrow.wallet("stranger");
+        escrow.mint_a = escrow.create_mint("mint a", &token_program);
+        escrow.mint_b = escrow.create_mint("mint b", &token_program);
+        escrow.mint_c = escrow.create_mint("mint c", &token_program);
+        escrow.foreign_mint = escrow.create_mint("foreign mint", &foreign_program);
+
+        let (maker, taker, stranger) = (escrow.maker, escrow.taker, escrow.stranger);
+        let (mint_a, mint_b, mint_c) = (escrow.mint_a, escrow.mint_b, escrow.mint_c);
+        escrow.mint_to(&maker, &mint_a, BALANCE);
+        escrow.mint_to(&taker, &mint_b, BALANCE);
+        escrow.mint_to(&taker, &mint_c, BALANCE);
+        escrow.mint_to(&stranger, &mint_a, BALANCE);
+        escrow.mint_to(&stranger, &mint_b, BALANCE);
+        escrow
+    }
+
+    pub fn create_mint(&mut self, name: &str, token_program: &Pubkey) -> Pubkey {
+        let mint = self.signer(name);
+        let space = spl_token_2022::state::Mint::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint,
+                self.rent(space),
+                space as u64,
+                token_program,
+            ),
+            token_instruction::initialize_mint2(
+                token_program,
+                &mint,
+                &self.payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions);
+        mint
+    }
+
+    // Mint tokens to the owner's ATA, creating it when needed. The mint's token program is looked up.
+    pub fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let token_program = self.svm.get_account(mint).unwrap().owner;
+        let payer = self.payer.pubkey();
+        let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
+        let instructions = [
+            create_associated_token_account_idempotent(&payer, owner, mint, &token_program),
+            token_instruction::mint_to(&token_program, mint, &ata, &payer, &[], amount).unwrap(),
+        ];
+        self.send(&instructions);
+    }
+
+    pub fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, mint, &self.token_program)
+    }
+
+    pub fn offer(&self, id: u64) -> Pubkey {
+        Offer::find_address(&ESCROW_PROGRAM_ID, &self.maker, id).0
+    }
+
+    // Token account of the offer holding the offered tokens
+    pub fn vault(&self, id: u64) -> Pubkey {
+        self.ata(&self.offer(id), &self.mint_a)
+    }
+
+    // Token balance of an account of the token program, 0 when it doesn't exist
+    pub fn balance(&self, token_account: &Pubkey) -> u64 {
+        self.svm.get_account(token_account).map_or(0, |account| {
+            spl_token_2022::extension::StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+                .unwrap()
+                .base
+                .amount
+        })
+    }
+
+    // Rent of the account, 0 when it doesn't exist
+    pub fn rent_of(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .map_or(0, |account| self.rent(account.data.len()))
+    }
+
+    pub fn make_offer_ix(&self, id: u64, offered: u64, wanted: u64) -> Instruction {
+        Instruction {
+            program_id: ESCROW_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.maker, true),
+                AccountMeta::new_readonly(self.mint_a, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new(self.ata(&self.maker, &self.mint_a), false),
+                AccountMeta::new(self.offer(id), false),
+                AccountMeta::new(self.vault(id), false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::MakeOffer {
+                id,
+                token_a_offered_amount: offered,
+                token_b_wanted_amount: wanted,
+            }
+            .pack(),
+        }
+    }
+
+    pub fn take_offer_ix(&self, id: u64) -> Instruction {
+        Instruction {
+            program_id: ESCROW_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.taker, true),
+                AccountMeta::new(self.maker, true),
+                AccountMeta::new_readonly(self.mint_a, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new(self.ata(&self.taker, &self.mint_a), false),
+                AccountMeta::new(self.ata(&self.taker, &self.mint_b), false),
+                AccountMeta::new(self.ata(&self.maker, &self.mint_b), false),
+                AccountMeta::new(self.offer(id), false),
+                AccountMeta::new(self.vault(id), false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::TakeOffer { id }.pack(),
+        }
+    }
+
+    pub fn refund_offer_ix(&self, id: u64) -> Instruction {
+        Instruction {
+            program_id: ESCROW_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.maker, true),
+                AccountMeta::new_readonly(self.mint_a, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new(self.ata(&self.maker, &self.mint_a), false),
+                AccountMeta::new(self.offer(id), false),
+                AccountMeta::new(self.vault(id), false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: EscrowInstruction::RefundOffer { id }.pack(),
+        }
+    }
+}
+
+impl Deref for EscrowEnv {
+    type Target = Env;
+
+    fn deref(&self) -> &Env {
+        &self.env
+    }
+}
+
+impl DerefMut for EscrowEnv {
+    fn deref_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+impl From<EscrowEnv> for Env {
+    fn from(escrow: EscrowEnv) -> Env {
+        escrow.env
+    }
+}
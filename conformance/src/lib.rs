@@ -0,0 +1,449 @@
+//! Conformance suite for the implementations of the escrow and the vault.
+//!
+//! Every scenario of a table runs once per build of a program, each time on a
+//! fresh LiteSVM with the same wallets, mints and instructions, so every
+//! account ends up at the same address. A scenario fails when a build doesn't
+//! behave the way the table expects, and when the builds diverge from each
+//! other: any step with another result, or any account the scenario touched
+//! with other lamports, owner or data at the end.
+//!
+//! The programs run as the SBF builds they are deployed as, the Anchor,
+//! native and pinocchio builds alike. Build them as described in the docs of
+//! `benchmark/`, then run `cargo test` from this directory. Besides the
+//! scenario tables, `tests/escrow_fuzz.rs` runs random sequences
+//! of escrow instructions against every build and checks that no value is
+//! created or lost. What the builds cost is measured by the `bench` binary of
+//! `benchmark/`.
+
+pub mod escrow;
+pub mod vault;
+
+use litesvm::LiteSVM;
+use solana_sdk::{
+    account::Account,
+    hash::hash,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::ops::DerefMut;
+
+/// Lamports every wallet starts with
+pub const WALLET_LAMPORTS: u64 = 10_000_000_000;
+
+/// One build of a program
+pub struct Implementation {
+    pub name: &'static str,
+    /// Its SBF build, see the crate docs
+    pub path: &'static str,
+    /// Built with Anchor, its account validation fails with Anchor's own error codes
+    pub anchor: bool,
+}
+
+/// What a step of a scenario must do
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expect {
+    Success,
+    Failure(InstructionError),
+    /// The ports fail with the first error. Anchor validates the accounts in
+    /// another order or with its own codes and fails with the second one.
+    AnchorFailure(InstructionError, InstructionError),
+}
+
+/// A named scenario of a table, run against every implementation
+pub struct Scenario<E> {
+    pub name: &'static str,
+    pub run: fn(&mut E),
+}
+
+/// Result of a step, after mapping the Anchor errors a scenario expects to the ports' ones
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub label: String,
+    pub result: Result<(), TransactionError>,
+}
+
+/// State of an account at the end of a scenario
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountState {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// Everything one implementation did in a scenario
+pub struct Run {
+    pub implementation: &'static str,
+    pub steps: Vec<Step>,
+    pub accounts: BTreeMap<Pubkey, AccountState>,
+    /// Steps and checks which didn't go the way the scenario expects
+    pub failures: Vec<String>,
+}
+
+/// LiteSVM with one implementation loaded. Every wallet is derived from its
+/// name and signs for itself, every account an instruction uses is compared
+/// at the end of the scenario.
+pub struct Env {
+    pub svm: LiteSVM,
+    pub implementation: &'static Implementation,
+    pub payer: Keypair,
+    wallets: Vec<Keypair>,
+    steps: Vec<Step>,
+    // Shared so a check can read the SVM in its arguments
+    failures: RefCell<Vec<String>>,
+    accounts: Vec<Pubkey>,
+}
+
+impl Env {
+    // Fresh SVM with the implementation loaded at `program_id` and a funded fee payer
+    pub fn new(implementation: &'static Implementation, program_id: Pubkey) -> Self {
+        let mut svm = LiteSVM::new();
+        load(&mut svm, program_id, implementation.path);
+
+        let payer = keypair("payer");
+        svm.airdrop(&payer.pubkey(), 100_000_000_000).unwrap();
+        Env {
+            svm,
+            implementation,
+            payer,
+            wallets: Vec::new(),
+            steps: Vec::new(),
+            failures: RefCell::default(),
+            accounts: Vec::new(),
+        }
+    }
+
+    // New wallet with enough SOL to pay for its own accounts
+    pub fn wallet(&mut self, name: &str) -> Pubkey {
+        let address = self.signer(name);
+        self.svm.airdrop(&address, WALLET_LAMPORTS).unwrap();
+        address
+    }
+
+    // Keypair which signs wherever an instruction needs it, e.g. for a new mint
+    pub fn signer(&mut self, name: &str) -> Pubkey {
+        let signer = keypair(name);
+        let address = signer.pubkey();
+        self.wallets.push(signer);
+        self.watch(address);
+        address
+    }
+
+    // Compare the account at the end of the scenario, even if no instruction uses it
+    pub fn watch(&mut self, address: Pubkey) {
+        if !self.accounts.contains(&address) {
+            self.accounts.push(address);
+        }
+    }
+
+    // Send setup instructions, the scenario can't go on when they fail
+    pub fn send(&mut self, instructions: &[Instruction]) {
+        match self.execute(instructions) {
+            Ok(_) => {}
+            Err((error, logs)) => panic!(
+                "[{}] transaction failed: {:?}\n{}",
+                self.implementation.name,
+                error,
+                logs.join("\n")
+            ),
+        }
+    }
+
+    // Send the instructions as a step of the scenario and check its result
+    pub fn step(&mut self, label: &str, instructions: &[Instruction], expect: Expect) {
+        let (result, logs) = match self.execute(instructions) {
+            Ok(_) => (Ok(()), Vec::new()),
+            Err((error, logs)) => (Err(error), logs),
+        };
+        let result = self.normalize(result, &expect);
+
+        let matches = match (&result, &expect) {
+            (Ok(()), Expect::Success) => true,
+            (
+                Err(TransactionError::InstructionError(_, error)),
+                Expect::Failure(expected) | Expect::AnchorFailure(expected, _),
+            ) => error == expected,
+            _ => false,
+        };
+        if !matches {
+            self.failures.get_mut().push(format!(
+                "step `{label}`: expected {expect:?}, got {result:?}\n{}",
+                logs.join("\n")
+            ));
+        }
+        self.steps.push(Step {
+            label: label.to_string(),
+            result,
+        });
+    }
+
+    // Send the instructions outside of any scenario step and return their result, for the fuzzer
+    pub fn try_send(&mut self, instructions: &[Instruction]) -> Result<(), TransactionError> {
+        self.execute(instructions).map_err(|(error, _logs)| error)
+    }
+
+    // Record a failed check of the scenario
+    pub fn check(&self, what: &str, actual: u64, expected: u64) {
+        if actual != expected {
+            self.failures
+                .borrow_mut()
+                .push(format!("{what}: expected {expected}, got {actual}"));
+        }
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub fn exists(&self, address: &Pubkey) -> bool {
+        self.lamports(address) > 0
+    }
+
+    pub fn rent(&self, space: usize) -> u64 {
+        self.svm.minimum_balance_for_rent_exemption(space)
+    }
+
+    // The error comes with the program logs
+    fn execute(&mut self, instructions: &[Instruction]) -> Result<(), (TransactionError, Vec<String>)> {
+        let mut signers = vec![&self.payer];
+        for instruction in instructions {
+            for meta in &instruction.accounts {
+                if !meta.is_signer || signers.iter().any(|signer| signer.pubkey() == meta.pubkey) {
+                    continue;
+                }
+                let wallet = self
+                    .wallets
+                    .iter()
+                    .find(|wallet| wallet.pubkey() == meta.pubkey);
+                signers.push(wallet.expect("signers are wallets of the scenario"));
+            }
+        }
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &signers,
+            self.svm.latest_blockhash(),
+        );
+
+        for instruction in instructions {
+            for meta in &instruction.accounts {
+                if !self.accounts.contains(&meta.pubkey) {
+                    self.accounts.push(meta.pubkey);
+                }
+            }
+        }
+        let result = self.svm.send_transaction(tx);
+        // Identical transactions would otherwise be rejected as already processed
+        self.svm.expire_blockhash();
+        result.map(|_| ()).map_err(|failed| (failed.err, failed.meta.logs))
+    }
+
+    // Anchor fails with its own code where the scenario says so, map it to the ports' one
+    fn normalize(
+        &self,
+        result: Result<(), TransactionError>,
+        expect: &Expect,
+    ) -> Result<(), TransactionError> {
+        match (result, expect) {
+            (
+                Err(TransactionError::InstructionError(index, error)),
+                Expect::AnchorFailure(ports, anchor),
+            ) if self.implementation.anchor && error == *anchor => {
+                Err(TransactionError::InstructionError(index, ports.clone()))
+            }
+            (result, _) => result,
+        }
+    }
+
+    // Programs and sysvars are the same for every implementation, closed accounts are gone
+    fn into_run(self) -> Run {
+        let accounts = self
+            .accounts
+            .iter()
+            .filter_map(|address| {
+                let Account {
+                    lamports,
+                    owner,
+                    data,
+                    executable,
+                    ..
+                } = self.svm.get_account(address)?;
+                (lamports > 0 && !executable && owner != sysvar::id()).then_some((
+                    *address,
+                    AccountState {
+                        lamports,
+                        owner,
+                        data,
+                    },
+                ))
+            })
+            .collect();
+        Run {
+            implementation: self.implementation.name,
+            steps: self.steps,
+            accounts,
+            failures: self.failures.into_inner(),
+        }
+    }
+}
+
+// Load an SBF build
+pub fn load(svm: &mut LiteSVM, program_id: Pubkey, path: &str) {
+    svm.add_program_from_file(program_id, path)
+        .unwrap_or_else(|_| panic!("build {path} first, see the crate docs"));
+}
+
+// Keypair derived from a name, the same in every run
+pub fn keypair(name: &str) -> Keypair {
+    Keypair::new_from_array(hash(name.as_bytes()).to_bytes())
+}
+
+pub fn custom(code: u32) -> InstructionError {
+    InstructionError::Custom(code)
+}
+
+// Same instruction with another account in place of `old`
+pub fn swap(mut instruction: Instruction, old: &Pubkey, new: Pubkey) -> Instruction {
+    for meta in instruction
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == *old)
+    {
+        meta.pubkey = new;
+    }
+    instruction
+}
+
+// Same instruction with the account passed read only
+pub fn readonly(mut instruction: Instruction, account: &Pubkey) -> Instruction {
+    for meta in instruction
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == *account)
+    {
+        meta.is_writable = false;
+    }
+    instruction
+}
+
+// Same instruction without the signature of the account
+pub fn unsigned(mut instruction: Instruction, account: &Pubkey) -> Instruction {
+    for meta in instruction
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == *account)
+    {
+        meta.is_signer = false;
+    }
+    instruction
+}
+
+/// Run every scenario against every implementation and panic with a report of
+/// all failed expectations and divergences. `setup` builds the environment of
+/// the table around a fresh `Env` of an implementation.
+pub fn check_conformance<E: DerefMut<Target = Env> + Into<Env>>(
+    implementations: &'static [Implementation],
+    scenarios: &[Scenario<E>],
+    setup: impl Fn(&'static Implementation) -> E,
+) {
+    let mut report = String::new();
+    for scenario in scenarios {
+        let runs: Vec<Run> = implementations
+            .iter()
+            .map(|implementation| {
+                let mut env = setup(implementation);
+                (scenario.run)(&mut env);
+                env.into().into_run()
+            })
+            .collect();
+
+        for run in &runs {
+            for failure in &run.failures {
+                writeln!(
+                    report,
+                    "{} [{}] {failure}",
+                    scenario.name, run.implementation
+                )
+                .unwrap();
+            }
+        }
+        let (reference, others) = runs.split_first().unwrap();
+        for run in others {
+            for divergence in diverge(reference, run) {
+                writeln!(report, "{} diverges: {divergence}", scenario.name).unwrap();
+            }
+        }
+    }
+    assert!(report.is_empty(), "\n{report}");
+}
+
+// Every difference between the steps and accounts of two runs
+fn diverge(reference: &Run, run: &Run) -> Vec<String> {
+    let (a, b) = (reference.implementation, run.implementation);
+    let mut divergences = Vec::new();
+
+    if reference.steps.len() != run.steps.len() {
+        divergences.push(format!(
+            "{a} ran {} steps, {b} ran {}",
+            reference.steps.len(),
+            run.steps.len()
+        ));
+    }
+    for (left, right) in reference.steps.iter().zip(&run.steps) {
+        if left != right {
+            divergences.push(format!(
+                "step `{}`: {a} {:?}, {b} {:?}",
+                left.label, left.result, right.result
+            ));
+        }
+    }
+
+    let addresses: std::collections::BTreeSet<_> = reference
+        .accounts
+        .keys()
+        .chain(run.accounts.keys())
+        .collect();
+    for address in addresses {
+        match (reference.accounts.get(address), run.accounts.get(address)) {
+            (Some(left), Some(right)) if left != right => {
+                let mut fields = Vec::new();
+                if left.lamports != right.lamports {
+                    fields.push(format!("lamports {} vs {}", left.lamports, right.lamports));
+                }
+                if left.owner != right.owner {
+                    fields.push(format!("owner {} vs {}", left.owner, right.owner));
+                }
+                if left.data.len() != right.data.len() {
+                    fields.push(format!(
+                        "data length {} vs {}",
+                        left.data.len(),
+                        right.data.len()
+                    ));
+                } else if let Some(offset) =
+                    left.data.iter().zip(&right.data).position(|(l, r)| l != r)
+                {
+                    fields.push(format!("data differs from byte {offset}"));
+                }
+                divergences.push(format!(
+                    "account {address} ({a} vs {b}): {}",
+                    fields.join(", ")
+                ));
+            }
+            (Some(_), None) => {
+                divergences.push(format!("account {address} exists for {a} but not for {b}"))
+            }
+            (None, Some(_)) => {
+                divergences.push(format!("account {address} exists for {b} but not for {a}"))
+            }
+            _ => {}
+        }
+    }
+    divergences
+}
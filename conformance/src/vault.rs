@@ -0,0 +1,731 @@
+//! Environment of the vault table: an admin, two users and the mints of one
+//! token program, with the escrow loaded for the escrow bridge.
+
+use crate::{Env, Implementation, escrow::ESCROW_SO, keypair, load};
+use borsh::BorshSerialize;
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Signer,
+    sysvar,
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::instruction as token_instruction;
+use spl_token_vault::{
+    escrow::{self, ESCROW_PROGRAM_ID},
+    legacy::{LegacyLayout, UserBalances},
+    processor::VaultInstruction,
+    state::{ConfigParams, LockupTier},
+};
+use std::ops::{Deref, DerefMut};
+
+/// Every build of the vault
+pub static VAULT_BUILDS: &[Implementation] = &[
+    Implementation {
+        name: "native",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_vault.so"),
+        anchor: false,
+    },
+    Implementation {
+        name: "pinocchio",
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/spl_token_vault_pinocchio.so"),
+        anchor: false,
+    },
+];
+
+pub const DECIMALS: u8 = 6;
+/// Tokens of every mint the users start with
+pub const BALANCE: u64 = 1_000_000;
+
+/// Config which lets everything through, what `InitializeVault` starts with
+pub fn open_config() -> ConfigParams {
+    ConfigParams {
+        user_deposit_cap: u64::MAX,
+        tvl_cap: u64::MAX,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: 0,
+        user_window_limit: u64::MAX,
+        vault_window_limit: u64::MAX,
+        early_exit_penalty_bps: 0,
+    }
+}
+
+pub struct VaultEnv {
+    env: Env,
+    pub program_id: Pubkey,
+    pub token_program: Pubkey,
+    pub admin: Pubkey,
+    pub alice: Pubkey,
+    pub bob: Pubkey,
+    /// Mint of the vault
+    pub mint: Pubkey,
+    /// Mint the escrow offers want, and a mint without a vault
+    pub mint_b: Pubkey,
+}
+
+impl VaultEnv {
+    // Alice and Bob hold the vault's mint and Bob also mint B, the admin is the
+    // upgrade authority of the program. Nothing of the vault exists yet,
+    // scenarios start with `setup_vault`.
+    pub fn new(implementation: &'static Implementation, token_program: Pubkey) -> Self {
+        let program_id = keypair("vault program").pubkey();
+        let mut env = Env::new(implementation, program_id);
+        // The escrow bridge calls the Anchor build
+        load(&mut env.svm, ESCROW_PROGRAM_ID, ESCROW_SO);
+
+        let mut vault = VaultEnv {
+            env,
+            program_id,
+            token_program,
+            admin: Pubkey::default(),
+            alice: Pubkey::default(),
+            bob: Pubkey::default(),
+            mint: Pubkey::default(),
+            mint_b: Pubkey::default(),
+        };
+        vault.admin = vault.wallet("admin");
+        let admin = vault.admin;
+        vault.set_upgrade_authority(&admin);
+        vault.alice = vault.wallet("alice");
+        vault.bob = vault.wallet("bob");
+        vault.mint = vault.create_mint("mint");
+        vault.mint_b = vault.create_mint("mint b");
+
+        let (alice, bob, mint, mint_b) = (vault.alice, vault.bob, vault.mint, vault.mint_b);
+        vault.mint_to(&alice, &mint, BALANCE);
+        vault.mint_to(&bob, &mint, BALANCE);
+        vault.mint_to(&bob, &mint_b, BALANCE);
+        vault
+    }
+
+    pub fn create_mint(&mut self, name: &str) -> Pubkey {
+        let mint = self.signer(name);
+        let space = spl_token_2022::state::Mint::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint,
+                self.rent(space),
+                space as u64,
+                &self.token_program,
+            ),
+            token_instruction::initialize_mint2(
+                &self.token_program,
+                &mint,
+                &self.payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions);
+        mint
+    }
+
+    // Mint tokens to the owner's ATA, creating it when needed
+    pub fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let payer = self.payer.pubkey();
+        let instructions = [
+            create_associated_token_account_idempotent(&payer, owner, mint, &self.token_program),
+            token_instruction::mint_to(
+                &self.token_program,
+                mint,
+                &self.ata(owner, mint),
+                &payer,
+                &[],
+                amount,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions);
+    }
+
+    // Registry, vault and config for the mint, the admin owns all of them
+    pub fn setup_vault(&mut self, early_exit_penalty_bps: u16, withdraw_cooldown: i64) {
+        let instructions = [
+            self.initialize_registry_ix(&self.admin),
+            self.initialize_vault_ix(&self.admin, early_exit_penalty_bps, withdraw_cooldown),
+            self.register_vault_ix(&self.admin),
+        ];
+        self.send(&instructions);
+    }
+
+    // Position of the user written by the `src/lib.rs` vault, as if this
+    // program was deployed over it
+    pub fn set_legacy_balance(&mut self, user: &Pubkey, balance: u64) {
+        let legacy = UserBalances { wallet: *user, balance };
+        let account = Account {
+            lamports: self.rent(UserBalances::LEN),
+            data: borsh::to_vec(&legacy).unwrap(),
+            owner: self.program_id,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let address = self.legacy_state(user);
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    // LiteSVM loads programs without the upgradeable loader, give the program
+    // the ProgramData account it has on chain with this upgrade authority
+    pub fn set_upgrade_authority(&mut self, authority: &Pubkey) {
+        // `UpgradeableLoaderState::ProgramData` at slot 0 with `Some(authority)`
+        let mut data = vec![0; 45];
+        data[..4].copy_from_slice(&3u32.to_le_bytes());
+        data[12] = 1;
+        data[13..].copy_from_slice(authority.as_ref());
+        let account = Account {
+            lamports: self.rent(data.len()),
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        let program_data = self.program_data();
+        self.svm.set_account(program_data, account).unwrap();
+    }
+
+    // Move the clock forward, e.g. past a lockup or a cooldown
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp += seconds;
+        self.svm.set_sysvar(&clock);
+    }
+
+    pub fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, mint, &self.token_program)
+    }
+
+    pub fn vault_state(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault", self.mint.as_ref()], &self.program_id).0
+    }
+
+    pub fn vault_config(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"config", self.vault_state().as_ref()], &self.program_id).0
+    }
+
+    pub fn registry(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"registry"], &self.program_id).0
+    }
+
+    pub fn vault_ata(&self) -> Pubkey {
+        self.ata(&self.vault_state(), &self.mint)
+    }
+
+    pub fn program_data(&self) -> Pubkey {
+        Pubkey::find_program_address(&[self.program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+    }
+
+    pub fn user_state(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"user", user.as_ref(), self.mint.as_ref()],
+            &self.program_id,
+        )
+        .0
+    }
+
+    pub fn legacy_state(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"state", user.as_ref()], &self.program_id).0
+    }
+
+    pub fn ticket(&self, id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"ticket", self.vault_state().as_ref(), &id.to_le_bytes()],
+            &self.program_id,
+        )
+        .0
+    }
+
+    pub fn escrow_maker(&self, user: &Pubkey) -> Pubkey {
+        escrow::escrow_maker_address(&self.program_id, &self.user_state(user)).0
+    }
+
+    pub fn escrow_offer(&self, user: &Pubkey, id: u64) -> Pubkey {
+        escrow::offer_address(&self.escrow_maker(user), id)
+    }
+
+    // Token balance of an account of the token program, 0 when it doesn't exist
+    pub fn balance(&self, token_account: &Pubkey) -> u64 {
+        self.svm.get_account(token_account).map_or(0, |account| {
+            spl_token_2022::extension::StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+                .unwrap()
+                .base
+                .amount
+        })
+    }
+
+    fn vault_instruction(
+        &self,
+        instruction: VaultInstruction,
+        accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let mut data = Vec::new();
+        instruction.serialize(&mut data).unwrap();
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        }
+    }
+
+    pub fn initialize_registry_ix(&self, payer: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::InitializeRegistry,
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn initialize_vault_ix(
+        &self,
+        payer: &Pubkey,
+        early_exit_penalty_bps: u16,
+        withdraw_cooldown: i64,
+    ) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::InitializeVault {
+                early_exit_penalty_bps,
+                withdraw_cooldown,
+            },
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new(self.vault_config(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.registry(), false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn register_vault_ix(&self, admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::RegisterVault,
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn unregister_vault_ix(&self, admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::UnregisterVault,
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    pub fn update_config_ix(&self, admin: &Pubkey, params: ConfigParams) -> Instruction {
+        let mut accounts = self.admin_accounts(admin);
+        accounts.push(AccountMeta::new(self.vault_state(), false));
+        self.vault_instruction(VaultInstruction::UpdateConfig { params }, accounts)
+    }
+
+    pub fn transfer_admin_ix(&self, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::TransferAdmin {
+                new_admin: *new_admin,
+            },
+            self.admin_accounts(admin),
+        )
+    }
+
+    pub fn accept_admin_ix(&self, pending_admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::AcceptAdmin,
+            self.admin_accounts(pending_admin),
+        )
+    }
+
+    fn admin_accounts(&self, admin: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(self.vault_config(), false),
+            AccountMeta::new_readonly(self.mint, false),
+        ]
+    }
+
+    pub fn set_flash_loan_fee_ix(&self, admin: &Pubkey, fee_bps: u16) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::SetFlashLoanFee { fee_bps },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    pub fn initialize_user_ix(&self, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::InitializeUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        )
+    }
+
+    pub fn close_user_ix(&self, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::CloseUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    pub fn migrate_user_state_ix(&self, authority: &Pubkey, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::MigrateUserState {
+                layout: LegacyLayout::UserBalances,
+            },
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new(*user, false),
+                AccountMeta::new(self.legacy_state(user), false),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn deposit_ix(&self, user: &Pubkey, amount: u64, lockup: LockupTier) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::Deposit { amount, lockup },
+            self.deposit_accounts(user, user),
+        )
+    }
+
+    // Deposit from the payer's ATA into the position of `owner`
+    pub fn deposit_for_ix(&self, payer: &Pubkey, owner: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::DepositFor {
+                owner: *owner,
+                amount,
+            },
+            self.deposit_accounts(payer, owner),
+        )
+    }
+
+    fn deposit_accounts(&self, payer: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(self.ata(payer, &self.mint), false),
+            AccountMeta::new(self.vault_ata(), false),
+            AccountMeta::new(self.user_state(owner), false),
+            AccountMeta::new(self.vault_state(), false),
+            AccountMeta::new_readonly(self.vault_config(), false),
+            AccountMeta::new_readonly(self.registry(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ]
+    }
+
+    // Withdraw from the position of `owner` into the signer's ATA, the signer is the owner or its delegate
+    pub fn withdraw_ix(
+        &self,
+        signer: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+        close_if_empty: bool,
+    ) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::Withdraw {
+                amount,
+                close_if_empty,
+            },
+            self.withdraw_accounts(signer, owner),
+        )
+    }
+
+    pub fn withdraw_early_ix(&self, user: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::WithdrawEarly { amount },
+            self.withdraw_accounts(user, user),
+        )
+    }
+
+    fn withdraw_accounts(&self, signer: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*signer, true),
+            AccountMeta::new(self.ata(signer, &self.mint), false),
+            AccountMeta::new(self.vault_ata(), false),
+            AccountMeta::new(self.user_state(owner), false),
+            AccountMeta::new(self.vault_state(), false),
+            AccountMeta::new_readonly(self.vault_config(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ]
+    }
+
+    // Request a withdrawal into the ticket with the vault's next ticket id
+    pub fn request_withdraw_ix(&self, user: &Pubkey, ticket_id: u64, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::RequestWithdraw { amount },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    pub fn claim_withdraw_ix(&self, user: &Pubkey, ticket_id: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::ClaimWithdraw,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.ata(user, &self.mint), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn set_delegate_ix(
+        &self,
+        user: &Pubkey,
+        delegate: &Pubkey,
+        allowance: Option<u64>,
+    ) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::SetDelegate {
+                delegate: *delegate,
+                allowance,
+            },
+            self.delegate_accounts(user),
+        )
+    }
+
+    pub fn revoke_delegate_ix(&self, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::RevokeDelegate,
+            self.delegate_accounts(user),
+        )
+    }
+
+    fn delegate_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(self.user_state(user), false),
+            AccountMeta::new_readonly(self.mint, false),
+        ]
+    }
+
+    // Lends to the borrower's ATA, send it together with `flash_repay_ix`
+    pub fn flash_loan_ix(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::FlashLoan { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.ata(borrower, &self.mint), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ],
+        )
+    }
+
+    pub fn flash_repay_ix(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::FlashRepay { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.ata(borrower, &self.mint), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn reconcile_ix(&self, pause_if_insolvent: bool) -> Instruction {
+        let vault_config = if pause_if_insolvent {
+            AccountMeta::new(self.vault_config(), false)
+        } else {
+            AccountMeta::new_readonly(self.vault_config(), false)
+        };
+        self.vault_instruction(
+            VaultInstruction::Reconcile { pause_if_insolvent },
+            vec![
+                AccountMeta::new_readonly(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                vault_config,
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    // Offer part of the user's position for `wanted_amount` of mint B
+    pub fn make_escrow_offer_ix(
+        &self,
+        user: &Pubkey,
+        id: u64,
+        amount: u64,
+        wanted_amount: u64,
+    ) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = self.escrow_offer(user, id);
+        self.vault_instruction(
+            VaultInstruction::MakeEscrowOffer {
+                id,
+                amount,
+                wanted_amount,
+            },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(self.ata(&escrow_maker, &self.mint), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.ata(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    pub fn take_escrow_offer_ix(&self, taker: &Pubkey, owner: &Pubkey, id: u64) -> Instruction {
+        let escrow_maker = self.escrow_maker(owner);
+        let offer = self.escrow_offer(owner, id);
+        self.vault_instruction(
+            VaultInstruction::TakeEscrowOffer { id },
+            vec![
+                AccountMeta::new(*taker, true),
+                AccountMeta::new(self.user_state(owner), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(*owner, false),
+                AccountMeta::new(self.ata(taker, &self.mint), false),
+                AccountMeta::new(self.ata(taker, &self.mint_b), false),
+                AccountMeta::new(self.ata(&escrow_maker, &self.mint_b), false),
+                AccountMeta::new(self.ata(owner, &self.mint_b), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.ata(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    pub fn refund_escrow_offer_ix(&self, user: &Pubkey, id: u64) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = self.escrow_offer(user, id);
+        self.vault_instruction(
+            VaultInstruction::RefundEscrowOffer { id },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(self.ata(&escrow_maker, &self.mint), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.ata(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    pub fn withdraw_treasury_ix(&self, admin: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::WithdrawTreasury { amount },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+}
+
+impl Deref for VaultEnv {
+    type Target = Env;
+
+    fn deref(&self) -> &Env {
+        &self.env
+    }
+}
+
+impl DerefMut for VaultEnv {
+    fn deref_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+impl From<VaultEnv> for Env {
+    fn from(vault: VaultEnv) -> Env {
+        vault.env
+    }
+}
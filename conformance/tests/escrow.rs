@@ -0,0 +1,881 @@
+use anchor_lang::error::ErrorCode as AnchorError;
+use conformance_tests::{
+    Expect, Scenario, WALLET_LAMPORTS, check_conformance, custom,
+    escrow::{BALANCE, ESCROW_BUILDS, EscrowEnv},
+    readonly, swap, unsigned,
+};
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+use solana_system_interface::{
+    error::SystemError, instruction as system_instruction, program as system_program,
+};
+use spl_token_escrow::{error::EscrowError, processor::EscrowInstruction, state::Offer};
+
+const OFFERED: u64 = 400_000;
+const WANTED: u64 = 50_000;
+
+fn fails(error: EscrowError) -> Expect {
+    Expect::Failure(custom(error as u32))
+}
+
+// The ports fail with `error`, Anchor's account validation with its own `anchor` error
+fn anchor_fails(error: EscrowError, anchor: AnchorError) -> Expect {
+    Expect::AnchorFailure(custom(error as u32), custom(anchor.into()))
+}
+
+fn make(env: &mut EscrowEnv, id: u64) {
+    let ix = env.make_offer_ix(id, OFFERED, WANTED);
+    env.step("make", &[ix], Expect::Success);
+}
+
+static SCENARIOS: &[Scenario<EscrowEnv>] = &[
+    Scenario {
+        name: "make an offer",
+        run: make_offer,
+    },
+    Scenario {
+        name: "take an offer",
+        run: take_offer,
+    },
+    Scenario {
+        name: "take into existing token accounts",
+        run: take_into_existing_accounts,
+    },
+    Scenario {
+        name: "refund an offer",
+        run: refund_offer,
+    },
+    Scenario {
+        name: "offers of a maker are independent",
+        run: independent_offers,
+    },
+    Scenario {
+        name: "make at an address holding lamports",
+        run: make_at_funded_address,
+    },
+    Scenario {
+        name: "make an id again after a refund",
+        run: make_again_after_refund,
+    },
+    Scenario {
+        name: "make without the maker's signature",
+        run: make_unsigned,
+    },
+    Scenario {
+        name: "make with the maker read only",
+        run: make_readonly_maker,
+    },
+    Scenario {
+        name: "make with the maker's token account read only",
+        run: make_readonly_maker_ata,
+    },
+    Scenario {
+        name: "make with the offer read only",
+        run: make_readonly_offer,
+    },
+    Scenario {
+        name: "make with the vault read only",
+        run: make_readonly_vault,
+    },
+    Scenario {
+        name: "make with another token program",
+        run: make_invalid_token_program,
+    },
+    Scenario {
+        name: "make for a mint of the other token program",
+        run: make_foreign_mint,
+    },
+    Scenario {
+        name: "make from another wallet's token account",
+        run: make_from_foreign_ata,
+    },
+    Scenario {
+        name: "make at another offer address",
+        run: make_wrong_offer,
+    },
+    Scenario {
+        name: "make with another vault",
+        run: make_wrong_vault,
+    },
+    Scenario {
+        name: "make an id twice",
+        run: make_twice,
+    },
+    Scenario {
+        name: "make offering nothing",
+        run: make_offering_nothing,
+    },
+    Scenario {
+        name: "make wanting nothing",
+        run: make_wanting_nothing,
+    },
+    Scenario {
+        name: "make offering more than the balance",
+        run: make_over_balance,
+    },
+    Scenario {
+        name: "make for the same mint",
+        run: make_same_mint,
+    },
+    Scenario {
+        name: "make with truncated arguments",
+        run: make_truncated,
+    },
+    Scenario {
+        name: "make with missing accounts",
+        run: make_missing_accounts,
+    },
+    Scenario {
+        name: "unknown instruction",
+        run: unknown_instruction,
+    },
+    Scenario {
+        name: "take without the maker's signature",
+        run: take_without_maker,
+    },
+    Scenario {
+        name: "take without the taker's signature",
+        run: take_without_taker,
+    },
+    Scenario {
+        name: "take with the maker read only",
+        run: take_readonly_maker,
+    },
+    Scenario {
+        name: "take with the offer read only",
+        run: take_readonly_offer,
+    },
+    Scenario {
+        name: "take with the vault read only",
+        run: take_readonly_vault,
+    },
+    Scenario {
+        name: "take with another token program",
+        run: take_invalid_token_program,
+    },
+    Scenario {
+        name: "take an offer twice",
+        run: take_twice,
+    },
+    Scenario {
+        name: "take a refunded offer",
+        run: take_refunded,
+    },
+    Scenario {
+        name: "take without enough of mint B",
+        run: take_over_balance,
+    },
+    Scenario {
+        name: "take for another mint B",
+        run: take_other_mint_b,
+    },
+    Scenario {
+        name: "take with another offer id",
+        run: take_wrong_id,
+    },
+    Scenario {
+        name: "take paying from another wallet's token account",
+        run: take_from_foreign_ata,
+    },
+    Scenario {
+        name: "take into another wallet's token account",
+        run: take_into_foreign_ata,
+    },
+    Scenario {
+        name: "take from another vault",
+        run: take_wrong_vault,
+    },
+    Scenario {
+        name: "refund without the maker's signature",
+        run: refund_unsigned,
+    },
+    Scenario {
+        name: "refund with the maker read only",
+        run: refund_readonly_maker,
+    },
+    Scenario {
+        name: "refund with the maker's token account read only",
+        run: refund_readonly_maker_ata,
+    },
+    Scenario {
+        name: "refund with the offer read only",
+        run: refund_readonly_offer,
+    },
+    Scenario {
+        name: "refund with the vault read only",
+        run: refund_readonly_vault,
+    },
+    Scenario {
+        name: "refund with another token program",
+        run: refund_invalid_token_program,
+    },
+    Scenario {
+        name: "refund by another wallet",
+        run: refund_by_stranger,
+    },
+    Scenario {
+        name: "refund an offer twice",
+        run: refund_twice,
+    },
+    Scenario {
+        name: "refund a taken offer",
+        run: refund_taken,
+    },
+    Scenario {
+        name: "refund with another offer id",
+        run: refund_wrong_id,
+    },
+    Scenario {
+        name: "refund into another wallet's token account",
+        run: refund_into_foreign_ata,
+    },
+];
+
+#[test]
+fn escrow_builds_conform_with_spl_token() {
+    check_conformance(ESCROW_BUILDS, SCENARIOS, |build| {
+        EscrowEnv::new(build, spl_token::id())
+    });
+}
+
+#[test]
+fn escrow_builds_conform_with_token_2022() {
+    check_conformance(ESCROW_BUILDS, SCENARIOS, |build| {
+        EscrowEnv::new(build, spl_token_2022::id())
+    });
+}
+
+// Happy paths
+
+fn make_offer(env: &mut EscrowEnv) {
+    make(env, 1);
+
+    let (maker, vault, offer) = (env.maker, env.vault(1), env.offer(1));
+    let maker_ata_a = env.ata(&maker, &env.mint_a);
+    env.check("vault balance", env.balance(&vault), OFFERED);
+    env.check(
+        "maker balance of A",
+        env.balance(&maker_ata_a),
+        BALANCE - OFFERED,
+    );
+    env.check("offer rent", env.lamports(&offer), env.rent(Offer::SPACE));
+    // The maker pays the rent of the offer and its vault
+    let rent = env.rent(Offer::SPACE) + env.rent_of(&vault);
+    env.check(
+        "maker lamports",
+        env.lamports(&maker),
+        WALLET_LAMPORTS - rent,
+    );
+}
+
+fn take_offer(env: &mut EscrowEnv) {
+    make(env, 1);
+    let vault_rent = env.rent_of(&env.vault(1));
+    let ix = env.take_offer_ix(1);
+    env.step("take", &[ix], Expect::Success);
+
+    let (maker, taker) = (env.maker, env.taker);
+    let (taker_ata_a, taker_ata_b) = (env.ata(&taker, &env.mint_a), env.ata(&taker, &env.mint_b));
+    let maker_ata_b = env.ata(&maker, &env.mint_b);
+    env.check("taker balance of A", env.balance(&taker_ata_a), OFFERED);
+    env.check(
+        "taker balance of B",
+        env.balance(&taker_ata_b),
+        BALANCE - WANTED,
+    );
+    env.check("maker balance of B", env.balance(&maker_ata_b), WANTED);
+    env.check("offer lamports", env.lamports(&env.offer(1)), 0);
+    env.check("vault lamports", env.lamports(&env.vault(1)), 0);
+    // The offer's rent goes back to the maker, the vault's to the taker,
+    // who pays for the token accounts which didn't exist
+    env.check(
+        "maker lamports",
+        env.lamports(&maker),
+        WALLET_LAMPORTS - vault_rent,
+    );
+    let created = env.rent_of(&taker_ata_a) + env.rent_of(&maker_ata_b);
+    env.check(
+        "taker lamports",
+        env.lamports(&taker),
+        WALLET_LAMPORTS - created + vault_rent,
+    );
+}
+
+fn take_into_existing_accounts(env: &mut EscrowEnv) {
+    let (maker, taker, mint_a, mint_b) = (env.maker, env.taker, env.mint_a, env.mint_b);
+    env.mint_to(&taker, &mint_a, 0);
+    env.mint_to(&maker, &mint_b, 0);
+    make(env, 1);
+    let vault_rent = env.rent_of(&env.vault(1));
+    let ix = env.take_offer_ix(1);
+    env.step("take", &[ix], Expect::Success);
+
+    env.check(
+        "taker balance of A",
+        env.balance(&env.ata(&taker, &mint_a)),
+        OFFERED,
+    );
+    env.check(
+        "maker balance of B",
+        env.balance(&env.ata(&maker, &mint_b)),
+        WANTED,
+    );
+    env.check(
+        "taker lamports",
+        env.lamports(&taker),
+        WALLET_LAMPORTS + vault_rent,
+    );
+}
+
+fn refund_offer(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.refund_offer_ix(1);
+    env.step("refund", &[ix], Expect::Success);
+
+    let maker = env.maker;
+    env.check(
+        "maker balance of A",
+        env.balance(&env.ata(&maker, &env.mint_a)),
+        BALANCE,
+    );
+    env.check("offer lamports", env.lamports(&env.offer(1)), 0);
+    env.check("vault lamports", env.lamports(&env.vault(1)), 0);
+    env.check("maker lamports", env.lamports(&maker), WALLET_LAMPORTS);
+}
+
+fn independent_offers(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.make_offer_ix(2, 100_000, 10);
+    env.step("make another", &[ix], Expect::Success);
+    let ix = env.take_offer_ix(1);
+    env.step("take the first", &[ix], Expect::Success);
+    env.check("second vault balance", env.balance(&env.vault(2)), 100_000);
+    let ix = env.refund_offer_ix(2);
+    env.step("refund the second", &[ix], Expect::Success);
+
+    let maker = env.maker;
+    env.check(
+        "maker balance of A",
+        env.balance(&env.ata(&maker, &env.mint_a)),
+        BALANCE - OFFERED,
+    );
+}
+
+fn make_at_funded_address(env: &mut EscrowEnv) {
+    // Anyone can send lamports to the address of an offer before it's made,
+    // at least the rent of an empty account or the runtime refuses the transfer
+    let offer = env.offer(1);
+    let payer = env.payer.pubkey();
+    let funded = env.rent(0);
+    env.send(&[system_instruction::transfer(&payer, &offer, funded)]);
+    make(env, 1);
+
+    env.check("offer rent", env.lamports(&offer), env.rent(Offer::SPACE));
+    let ix = env.refund_offer_ix(1);
+    env.step("refund", &[ix], Expect::Success);
+    // The lamports sent beforehand end up with the maker
+    let maker = env.maker;
+    env.check(
+        "maker lamports",
+        env.lamports(&maker),
+        WALLET_LAMPORTS + funded,
+    );
+}
+
+fn make_again_after_refund(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.refund_offer_ix(1);
+    env.step("refund", &[ix], Expect::Success);
+    make(env, 1);
+    env.check("vault balance", env.balance(&env.vault(1)), OFFERED);
+}
+
+// Making an offer
+
+fn make_unsigned(env: &mut EscrowEnv) {
+    let ix = unsigned(env.make_offer_ix(1, OFFERED, WANTED), &env.maker);
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(EscrowError::NotSigner, AnchorError::AccountNotSigner),
+    );
+}
+
+fn make_readonly_maker(env: &mut EscrowEnv) {
+    // Anchor creates the offer first, which the read only maker can't pay for
+    let ix = readonly(env.make_offer_ix(1, OFFERED, WANTED), &env.maker);
+    let expect = Expect::AnchorFailure(
+        custom(EscrowError::AccountNotMutable as u32),
+        InstructionError::PrivilegeEscalation,
+    );
+    env.step("make", &[ix], expect);
+}
+
+fn make_readonly_maker_ata(env: &mut EscrowEnv) {
+    let maker_ata = env.ata(&env.maker, &env.mint_a);
+    let ix = readonly(env.make_offer_ix(1, OFFERED, WANTED), &maker_ata);
+    env.step("make", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn make_readonly_offer(env: &mut EscrowEnv) {
+    let ix = readonly(env.make_offer_ix(1, OFFERED, WANTED), &env.offer(1));
+    let expect = Expect::AnchorFailure(
+        custom(EscrowError::AccountNotMutable as u32),
+        InstructionError::PrivilegeEscalation,
+    );
+    env.step("make", &[ix], expect);
+}
+
+fn make_readonly_vault(env: &mut EscrowEnv) {
+    let ix = readonly(env.make_offer_ix(1, OFFERED, WANTED), &env.vault(1));
+    let expect = Expect::AnchorFailure(
+        custom(EscrowError::AccountNotMutable as u32),
+        InstructionError::PrivilegeEscalation,
+    );
+    env.step("make", &[ix], expect);
+}
+
+fn make_invalid_token_program(env: &mut EscrowEnv) {
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &env.token_program,
+        system_program::ID,
+    );
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenProgram,
+            AnchorError::InvalidProgramId,
+        ),
+    );
+}
+
+fn make_foreign_mint(env: &mut EscrowEnv) {
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &env.mint_b,
+        env.foreign_mint,
+    );
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidMint,
+            AnchorError::ConstraintMintTokenProgram,
+        ),
+    );
+}
+
+fn make_from_foreign_ata(env: &mut EscrowEnv) {
+    let (maker_ata, stranger_ata) = (
+        env.ata(&env.maker, &env.mint_a),
+        env.ata(&env.stranger, &env.mint_a),
+    );
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &maker_ata,
+        stranger_ata,
+    );
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenAccount,
+            AnchorError::ConstraintTokenOwner,
+        ),
+    );
+}
+
+fn make_wrong_offer(env: &mut EscrowEnv) {
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &env.offer(1),
+        env.offer(2),
+    );
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(EscrowError::InvalidOffer, AnchorError::ConstraintSeeds),
+    );
+}
+
+fn make_wrong_vault(env: &mut EscrowEnv) {
+    // Anchor creates the vault through the associated token program with the
+    // address it derives, which isn't among the accounts of the transaction
+    let other_vault = env.ata(&env.offer(1), &env.mint_b);
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &env.vault(1),
+        other_vault,
+    );
+    let expect = Expect::AnchorFailure(
+        custom(EscrowError::InvalidTokenAccount as u32),
+        InstructionError::MissingAccount,
+    );
+    env.step("make", &[ix], expect);
+}
+
+fn make_twice(env: &mut EscrowEnv) {
+    make(env, 1);
+    // Anchor tries to allocate the existing offer through the system program
+    let ix = env.make_offer_ix(1, OFFERED, WANTED);
+    let expect = Expect::AnchorFailure(
+        custom(EscrowError::OfferAlreadyExists as u32),
+        custom(SystemError::AccountAlreadyInUse as u32),
+    );
+    env.step("make again", &[ix], expect);
+}
+
+fn make_offering_nothing(env: &mut EscrowEnv) {
+    let ix = env.make_offer_ix(1, 0, WANTED);
+    env.step("make", &[ix], fails(EscrowError::InvalidAmount));
+}
+
+fn make_wanting_nothing(env: &mut EscrowEnv) {
+    let ix = env.make_offer_ix(1, OFFERED, 0);
+    env.step("make", &[ix], fails(EscrowError::InvalidAmount));
+}
+
+fn make_over_balance(env: &mut EscrowEnv) {
+    let ix = env.make_offer_ix(1, BALANCE + 1, WANTED);
+    env.step("make", &[ix], fails(EscrowError::InsufficientMakerBalance));
+}
+
+fn make_same_mint(env: &mut EscrowEnv) {
+    let ix = swap(
+        env.make_offer_ix(1, OFFERED, WANTED),
+        &env.mint_b,
+        env.mint_a,
+    );
+    env.step("make", &[ix], fails(EscrowError::InvalidTokenMint));
+}
+
+fn make_truncated(env: &mut EscrowEnv) {
+    let mut ix = env.make_offer_ix(1, OFFERED, WANTED);
+    ix.data.truncate(8 + 16);
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidInstruction,
+            AnchorError::InstructionDidNotDeserialize,
+        ),
+    );
+}
+
+fn make_missing_accounts(env: &mut EscrowEnv) {
+    let mut ix = env.make_offer_ix(1, OFFERED, WANTED);
+    ix.accounts.truncate(6);
+    let expect = Expect::AnchorFailure(
+        InstructionError::NotEnoughAccountKeys,
+        custom(AnchorError::AccountNotEnoughKeys.into()),
+    );
+    env.step("make", &[ix], expect);
+}
+
+fn unknown_instruction(env: &mut EscrowEnv) {
+    let mut ix = env.make_offer_ix(1, OFFERED, WANTED);
+    ix.data[..8].fill(0);
+    env.step(
+        "make",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidInstruction,
+            AnchorError::InstructionFallbackNotFound,
+        ),
+    );
+}
+
+// Taking an offer
+
+fn take_without_maker(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = unsigned(env.take_offer_ix(1), &env.maker);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(EscrowError::NotSigner, AnchorError::AccountNotSigner),
+    );
+}
+
+fn take_without_taker(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = unsigned(env.take_offer_ix(1), &env.taker);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(EscrowError::NotSigner, AnchorError::AccountNotSigner),
+    );
+}
+
+fn take_readonly_maker(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.take_offer_ix(1), &env.maker);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(EscrowError::AccountNotMutable, AnchorError::ConstraintMut),
+    );
+}
+
+fn take_readonly_offer(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.take_offer_ix(1), &env.offer(1));
+    env.step("take", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn take_readonly_vault(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.take_offer_ix(1), &env.vault(1));
+    env.step("take", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn take_invalid_token_program(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = swap(env.take_offer_ix(1), &env.token_program, system_program::ID);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenProgram,
+            AnchorError::InvalidProgramId,
+        ),
+    );
+}
+
+fn take_twice(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.take_offer_ix(1);
+    env.step("take", std::slice::from_ref(&ix), Expect::Success);
+    env.step(
+        "take again",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidOffer,
+            AnchorError::AccountNotInitialized,
+        ),
+    );
+}
+
+fn take_refunded(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.refund_offer_ix(1);
+    env.step("refund", &[ix], Expect::Success);
+    let ix = env.take_offer_ix(1);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidOffer,
+            AnchorError::AccountNotInitialized,
+        ),
+    );
+}
+
+fn take_over_balance(env: &mut EscrowEnv) {
+    let ix = env.make_offer_ix(1, OFFERED, BALANCE + 1);
+    env.step("make", &[ix], Expect::Success);
+    let ix = env.take_offer_ix(1);
+    env.step("take", &[ix], fails(EscrowError::InsufficientTakerBalance));
+}
+
+fn take_other_mint_b(env: &mut EscrowEnv) {
+    make(env, 1);
+    let (maker, taker, mint_b, mint_c) = (env.maker, env.taker, env.mint_b, env.mint_c);
+    let mut ix = env.take_offer_ix(1);
+    ix = swap(ix, &env.ata(&taker, &mint_b), env.ata(&taker, &mint_c));
+    ix = swap(ix, &env.ata(&maker, &mint_b), env.ata(&maker, &mint_c));
+    ix = swap(ix, &mint_b, mint_c);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(EscrowError::InvalidOffer, AnchorError::ConstraintHasOne),
+    );
+}
+
+fn take_wrong_id(env: &mut EscrowEnv) {
+    make(env, 1);
+    let mut ix = env.take_offer_ix(1);
+    ix.data = EscrowInstruction::TakeOffer { id: 2 }.pack();
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(EscrowError::InvalidOffer, AnchorError::ConstraintSeeds),
+    );
+}
+
+fn take_from_foreign_ata(env: &mut EscrowEnv) {
+    make(env, 1);
+    let (taker_ata_b, stranger_ata_b) = (
+        env.ata(&env.taker, &env.mint_b),
+        env.ata(&env.stranger, &env.mint_b),
+    );
+    let ix = swap(env.take_offer_ix(1), &taker_ata_b, stranger_ata_b);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenAccount,
+            AnchorError::ConstraintTokenOwner,
+        ),
+    );
+}
+
+fn take_into_foreign_ata(env: &mut EscrowEnv) {
+    make(env, 1);
+    let (taker_ata_a, stranger_ata_a) = (
+        env.ata(&env.taker, &env.mint_a),
+        env.ata(&env.stranger, &env.mint_a),
+    );
+    let ix = swap(env.take_offer_ix(1), &taker_ata_a, stranger_ata_a);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenAccount,
+            AnchorError::ConstraintTokenOwner,
+        ),
+    );
+}
+
+fn take_wrong_vault(env: &mut EscrowEnv) {
+    make(env, 1);
+    let other_vault = env.ata(&env.offer(1), &env.mint_b);
+    let ix = swap(env.take_offer_ix(1), &env.vault(1), other_vault);
+    env.step(
+        "take",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenAccount,
+            AnchorError::AccountNotInitialized,
+        ),
+    );
+}
+
+// Refunding an offer
+
+fn refund_unsigned(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = unsigned(env.refund_offer_ix(1), &env.maker);
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(EscrowError::NotSigner, AnchorError::AccountNotSigner),
+    );
+}
+
+fn refund_readonly_maker(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.refund_offer_ix(1), &env.maker);
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(EscrowError::AccountNotMutable, AnchorError::ConstraintMut),
+    );
+}
+
+fn refund_readonly_maker_ata(env: &mut EscrowEnv) {
+    make(env, 1);
+    let maker_ata = env.ata(&env.maker, &env.mint_a);
+    let ix = readonly(env.refund_offer_ix(1), &maker_ata);
+    env.step("refund", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn refund_readonly_offer(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.refund_offer_ix(1), &env.offer(1));
+    env.step("refund", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn refund_readonly_vault(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = readonly(env.refund_offer_ix(1), &env.vault(1));
+    env.step("refund", &[ix], fails(EscrowError::AccountNotMutable));
+}
+
+fn refund_invalid_token_program(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = swap(
+        env.refund_offer_ix(1),
+        &env.token_program,
+        system_program::ID,
+    );
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenProgram,
+            AnchorError::InvalidProgramId,
+        ),
+    );
+}
+
+fn refund_by_stranger(env: &mut EscrowEnv) {
+    make(env, 1);
+    let (maker, stranger, mint_a) = (env.maker, env.stranger, env.mint_a);
+    let mut ix = env.refund_offer_ix(1);
+    ix = swap(ix, &env.ata(&maker, &mint_a), env.ata(&stranger, &mint_a));
+    ix = swap(ix, &maker, stranger);
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(EscrowError::InvalidOffer, AnchorError::ConstraintSeeds),
+    );
+}
+
+fn refund_twice(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.refund_offer_ix(1);
+    env.step("refund", std::slice::from_ref(&ix), Expect::Success);
+    env.step(
+        "refund again",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidOffer,
+            AnchorError::AccountNotInitialized,
+        ),
+    );
+}
+
+fn refund_taken(env: &mut EscrowEnv) {
+    make(env, 1);
+    let ix = env.take_offer_ix(1);
+    env.step("take", &[ix], Expect::Success);
+    let ix = env.refund_offer_ix(1);
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidOffer,
+            AnchorError::AccountNotInitialized,
+        ),
+    );
+}
+
+fn refund_wrong_id(env: &mut EscrowEnv) {
+    make(env, 1);
+    let mut ix = env.refund_offer_ix(1);
+    ix.data = EscrowInstruction::RefundOffer { id: 2 }.pack();
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(EscrowError::InvalidOffer, AnchorError::ConstraintSeeds),
+    );
+}
+
+fn refund_into_foreign_ata(env: &mut EscrowEnv) {
+    make(env, 1);
+    let (maker_ata, stranger_ata) = (
+        env.ata(&env.maker, &env.mint_a),
+        env.ata(&env.stranger, &env.mint_a),
+    );
+    let ix = swap(env.refund_offer_ix(1), &maker_ata, stranger_ata);
+    env.step(
+        "refund",
+        &[ix],
+        anchor_fails(
+            EscrowError::InvalidTokenAccount,
+            AnchorError::ConstraintTokenOwner,
+        ),
+    );
+}
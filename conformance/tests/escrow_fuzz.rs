@@ -0,0 +1,425 @@
+//! Random sequences of make, take and refund against every build of the
+//! escrow, with random wallets, amounts, ids, mints of both token programs
+//! and signatures. Whatever the sequence, no transaction may create or lose
+//! tokens or lamports, only the maker may refund an offer, and an offer
+//! which was taken or refunded stays closed until its maker makes it again.
+//!
+//! Runs a fixed 64 cases, about a minute, set `PROPTEST_CASES` for longer runs.
+
+use conformance_tests::{
+    Implementation,
+    escrow::{ESCROW_BUILDS, EscrowEnv},
+    swap, unsigned,
+};
+use escrow_client::{
+    instruction::{make_offer, refund_offer, take_offer},
+    offer_address, vault_address,
+};
+use proptest::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::collections::BTreeMap;
+
+/// Cases of a run when `PROPTEST_CASES` isn't set
+const CASES: u32 = 64;
+
+/// Ids the wallets make offers with, few enough for the operations to collide
+const IDS: u64 = 3;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Make {
+        maker: usize,
+        id: u64,
+        mint_a: usize,
+        mint_b: usize,
+        offered: u64,
+        wanted: u64,
+        // Pass the token program of the other mints
+        other_program: bool,
+    },
+    Take {
+        taker: usize,
+        maker: usize,
+        id: u64,
+        // Other mints than the offer's
+        mints: Option<(usize, usize)>,
+        maker_signs: bool,
+    },
+    // `signer` refunds the offer of `maker`, passing its own token account
+    Refund {
+        signer: usize,
+        maker: usize,
+        id: u64,
+        mints: Option<(usize, usize)>,
+    },
+}
+
+/// What the model knows of an open offer
+#[derive(Clone, Copy, Debug)]
+struct Open {
+    mint_a: usize,
+    mint_b: usize,
+    offered: u64,
+    wanted: u64,
+}
+
+/// The maker, taker and stranger of the escrow table, and two mints of each
+/// token program. Some wallets have no token account for some mints.
+struct Fuzz {
+    env: EscrowEnv,
+    wallets: [Pubkey; 3],
+    mints: [Pubkey; 4],
+    programs: [Pubkey; 4],
+    supplies: [u64; 4],
+    // Open offers by maker and id
+    open: BTreeMap<(usize, u64), Open>,
+}
+
+impl Fuzz {
+    fn new(build: &'static Implementation) -> Self {
+        let mut env = EscrowEnv::new(build, spl_token::id());
+        let mint_d = env.create_mint("mint d", &spl_token_2022::id());
+        let (maker, taker, foreign_mint) = (env.maker, env.taker, env.foreign_mint);
+        env.mint_to(&maker, &foreign_mint, 1_000_000);
+        env.mint_to(&taker, &mint_d, 1_000_000);
+
+        let wallets = [env.maker, env.taker, env.stranger];
+        let mints = [env.mint_a, env.mint_b, env.foreign_mint, mint_d];
+        let programs = mints.map(|mint| env.svm.get_account(&mint).unwrap().owner);
+        let mut fuzz = Fuzz {
+            env,
+            wallets,
+            mints,
+            programs,
+            supplies: [0; 4],
+            open: BTreeMap::new(),
+        };
+        fuzz.supplies = std::array::from_fn(|mint| fuzz.supply(mint));
+        fuzz
+    }
+
+    fn ata(&self, wallet: usize, mint: usize) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            &self.wallets[wallet],
+            &self.mints[mint],
+            &self.programs[mint],
+        )
+    }
+
+    fn balance(&self, wallet: usize, mint: usize) -> u64 {
+        self.env.balance(&self.ata(wallet, mint))
+    }
+
+    fn supply(&self, mint: usize) -> u64 {
+        let account = self.env.svm.get_account(&self.mints[mint]).unwrap();
+        spl_token_2022::extension::StateWithExtensions::<spl_token_2022::state::Mint>::unpack(
+            &account.data,
+        )
+        .unwrap()
+        .base
+        .supply
+    }
+
+    fn program(&self, mint: usize, other: bool) -> Pubkey {
+        match (self.programs[mint] == spl_token::id()) != other {
+            true => spl_token::id(),
+            false => spl_token_2022::id(),
+        }
+    }
+
+    // Everything which holds tokens: the wallets and every offer they can make
+    fn owners(&self) -> Vec<Pubkey> {
+        let mut owners = self.wallets.to_vec();
+        for maker in &self.wallets {
+            owners.extend((0..IDS).map(|id| offer_address(maker, id)));
+        }
+        owners
+    }
+
+    // Token accounts of the mint for every owner, at the addresses of both token programs
+    fn token_accounts(&self, mint: usize) -> Vec<Pubkey> {
+        let programs = [spl_token::id(), spl_token_2022::id()];
+        self.owners()
+            .iter()
+            .flat_map(|owner| {
+                programs.map(|program| {
+                    get_associated_token_address_with_program_id(owner, &self.mints[mint], &program)
+                })
+            })
+            .collect()
+    }
+
+    // Every account the operations can create or pay to. The fee payer only pays the fees.
+    fn lamports(&self) -> u64 {
+        let mut accounts = self.owners();
+        accounts.extend(self.mints);
+        accounts.extend((0..4).flat_map(|mint| self.token_accounts(mint)));
+        accounts
+            .iter()
+            .map(|account| self.env.lamports(account))
+            .sum()
+    }
+
+    // Tokens of a mint held by the wallets and the offers
+    fn held(&self, mint: usize) -> u64 {
+        self.token_accounts(mint)
+            .iter()
+            .map(|account| self.env.balance(account))
+            .sum()
+    }
+
+    fn run(&mut self, op: &Op) -> Result<(), TestCaseError> {
+        let lamports = self.lamports();
+        let balances: BTreeMap<_, _> = (0..3)
+            .flat_map(|wallet| (0..4).map(move |mint| (wallet, mint)))
+            .map(|(wallet, mint)| ((wallet, mint), self.balance(wallet, mint)))
+            .collect();
+
+        match *op {
+            Op::Make {
+                maker,
+                id,
+                mint_a,
+                mint_b,
+                offered,
+                wanted,
+                other_program,
+            } => {
+                let ix = make_offer(
+                    &self.wallets[maker],
+                    &self.mints[mint_a],
+                    &self.mints[mint_b],
+                    &self.program(mint_a, other_program),
+                    id,
+                    offered,
+                    wanted,
+                );
+                let made = self.env.try_send(&[ix]).is_ok();
+                if made {
+                    prop_assert!(
+                        !self.open.contains_key(&(maker, id)),
+                        "made an open offer again"
+                    );
+                    prop_assert_eq!(
+                        self.balance(maker, mint_a),
+                        balances[&(maker, mint_a)] - offered,
+                        "maker's offered tokens"
+                    );
+                    self.open.insert(
+                        (maker, id),
+                        Open {
+                            mint_a,
+                            mint_b,
+                            offered,
+                            wanted,
+                        },
+                    );
+                }
+            }
+            Op::Take {
+                taker,
+                maker,
+                id,
+                mints,
+                maker_signs,
+            } => {
+                let offer = self.open.get(&(maker, id)).copied();
+                let (mint_a, mint_b) = mints
+                    .or(offer.map(|offer| (offer.mint_a, offer.mint_b)))
+                    .unwrap_or((0, 1));
+                let mut ix = take_offer(
+                    &self.wallets[taker],
+                    &self.wallets[maker],
+                    &self.mints[mint_a],
+                    &self.mints[mint_b],
+                    &self.programs[mint_a],
+                    id,
+                );
+                if !maker_signs {
+                    ix = unsigned(ix, &self.wallets[maker]);
+                }
+                if self.env.try_send(&[ix]).is_ok() {
+                    let offer = offer.ok_or_else(|| TestCaseError::fail("took a closed offer"))?;
+                    prop_assert!(maker_signs, "took without the maker's signature");
+                    prop_assert_eq!(
+                        (mint_a, mint_b),
+                        (offer.mint_a, offer.mint_b),
+                        "took other mints"
+                    );
+                    // Else the transfers of the same account net out
+                    if taker != maker && mint_a != mint_b {
+                        prop_assert_eq!(
+                            self.balance(taker, mint_a),
+                            balances[&(taker, mint_a)] + offer.offered,
+                            "taker's offered tokens"
+                        );
+                        prop_assert_eq!(
+                            self.balance(maker, mint_b),
+                            balances[&(maker, mint_b)] + offer.wanted,
+                            "maker's wanted tokens"
+                        );
+                    }
+                    self.open.remove(&(maker, id));
+                }
+            }
+            Op::Refund {
+                signer,
+                maker,
+                id,
+                mints,
+            } => {
+                let offer = self.open.get(&(maker, id)).copied();
+                let (mint_a, mint_b) = mints
+                    .or(offer.map(|offer| (offer.mint_a, offer.mint_b)))
+                    .unwrap_or((0, 1));
+                let (program, attacker) = (self.programs[mint_a], self.wallets[signer]);
+                let victim = self.wallets[maker];
+                let mut ix = refund_offer(
+                    &attacker,
+                    &self.mints[mint_a],
+                    &self.mints[mint_b],
+                    &program,
+                    id,
+                );
+                // Point the signer's refund at the maker's offer
+                ix = swap(
+                    ix,
+                    &offer_address(&attacker, id),
+                    offer_address(&victim, id),
+                );
+                ix = swap(
+                    ix,
+                    &vault_address(&attacker, id, &self.mints[mint_a], &program),
+                    vault_address(&victim, id, &self.mints[mint_a], &program),
+                );
+                let refunded = self.env.try_send(&[ix]);
+                let own = |offer: &Open| {
+                    signer == maker && (mint_a, mint_b) == (offer.mint_a, offer.mint_b)
+                };
+                match offer {
+                    Some(offer) if own(&offer) => {
+                        prop_assert!(refunded.is_ok(), "maker can't refund: {:?}", refunded);
+                        prop_assert_eq!(
+                            self.balance(maker, mint_a),
+                            balances[&(maker, mint_a)] + offer.offered,
+                            "maker's refunded tokens"
+                        );
+                        self.open.remove(&(maker, id));
+                    }
+                    _ => prop_assert!(
+                        refunded.is_err(),
+                        "refunded by {signer} of {maker}'s offer {id}"
+                    ),
+                }
+            }
+        }
+
+        prop_assert_eq!(self.lamports(), lamports, "lamports after {:?}", op);
+        for mint in 0..4 {
+            prop_assert_eq!(
+                self.supply(mint),
+                self.supplies[mint],
+                "supply of mint {}",
+                mint
+            );
+            prop_assert_eq!(
+                self.held(mint),
+                self.supplies[mint],
+                "tokens of mint {}",
+                mint
+            );
+        }
+        for (maker, wallet) in self.wallets.iter().enumerate() {
+            for id in 0..IDS {
+                let offer = offer_address(wallet, id);
+                match self.open.get(&(maker, id)) {
+                    Some(open) => prop_assert_eq!(
+                        self.env.balance(&vault_address(
+                            wallet,
+                            id,
+                            &self.mints[open.mint_a],
+                            &self.programs[open.mint_a],
+                        )),
+                        open.offered,
+                        "vault of open offer {} of {}",
+                        id,
+                        maker
+                    ),
+                    None => prop_assert!(
+                        !self.env.exists(&offer),
+                        "closed offer {id} of {maker} exists"
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn amount() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        Just(0),
+        1..=1_000u64,
+        1..=1_000_000u64,
+        Just(1_000_001),
+        Just(u64::MAX)
+    ]
+}
+
+fn mints() -> impl Strategy<Value = (usize, usize)> {
+    (0..4usize, 0..4usize)
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..3usize, 0..IDS, mints(), amount(), amount(), prop::bool::weighted(0.1)).prop_map(
+            |(maker, id, (mint_a, mint_b), offered, wanted, other_program)| Op::Make {
+                maker,
+                id,
+                mint_a,
+                mint_b,
+                offered,
+                wanted,
+                other_program,
+            }
+        ),
+        2 => (
+            0..3usize,
+            0..3usize,
+            0..IDS,
+            prop::option::weighted(0.2, mints()),
+            prop::bool::weighted(0.9),
+        )
+            .prop_map(|(taker, maker, id, mints, maker_signs)| Op::Take {
+                taker,
+                maker,
+                id,
+                mints,
+                maker_signs,
+            }),
+        2 => (0..3usize, 0..3usize, 0..IDS, prop::option::weighted(0.2, mints()))
+            .prop_map(|(signer, maker, id, mints)| Op::Refund { signer, maker, id, mints }),
+    ]
+}
+
+fn config() -> ProptestConfig {
+    let cases = std::env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok());
+    ProptestConfig::with_cases(cases.unwrap_or(CASES))
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn escrow_conserves_value(ops in prop::collection::vec(op(), 1..24)) {
+        for build in ESCROW_BUILDS {
+            let mut fuzz = Fuzz::new(build);
+            for op in &ops {
+                fuzz.run(op).map_err(|error| {
+                    TestCaseError::fail(format!("[{}] {error}", build.name))
+                })?;
+            }
+        }
+    }
+}
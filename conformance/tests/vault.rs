@@ -0,0 +1,1074 @@
+use conformance_tests::{
+    Expect, Scenario, WALLET_LAMPORTS, check_conformance, custom, readonly, swap, unsigned,
+    vault::{BALANCE, VAULT_BUILDS, VaultEnv, open_config},
+};
+use solana_sdk::instruction::InstructionError;
+use solana_system_interface::program as system_program;
+use spl_token::error::TokenError;
+use spl_token_vault::{error::VaultError, escrow::ESCROW_PROGRAM_ID, state::LockupTier};
+
+const DEPOSIT: u64 = 300_000;
+const DAY: i64 = 24 * 60 * 60;
+
+fn fails(error: VaultError) -> Expect {
+    Expect::Failure(custom(error as u32))
+}
+
+// Vault with a 5% early exit penalty and no cooldown, Alice and Bob have a user state
+fn open(env: &mut VaultEnv) {
+    env.setup_vault(500, 0);
+    let instructions = [
+        env.initialize_user_ix(&env.alice),
+        env.initialize_user_ix(&env.bob),
+    ];
+    env.send(&instructions);
+}
+
+// Same with Alice's deposit in it
+fn deposited(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None);
+    env.step("deposit", &[ix], Expect::Success);
+}
+
+static SCENARIOS: &[Scenario<VaultEnv>] = &[
+    Scenario {
+        name: "deposit and withdraw",
+        run: deposit_and_withdraw,
+    },
+    Scenario {
+        name: "withdraw everything and close",
+        run: withdraw_and_close,
+    },
+    Scenario {
+        name: "close an empty user state",
+        run: close_user,
+    },
+    Scenario {
+        name: "deposit for another user",
+        run: deposit_for,
+    },
+    Scenario {
+        name: "withdraw a locked position early",
+        run: withdraw_early,
+    },
+    Scenario {
+        name: "withdraw the treasury",
+        run: withdraw_treasury,
+    },
+    Scenario {
+        name: "withdraw through a ticket",
+        run: withdraw_through_ticket,
+    },
+    Scenario {
+        name: "withdraw as a delegate",
+        run: withdraw_as_delegate,
+    },
+    Scenario {
+        name: "flash loan",
+        run: flash_loan,
+    },
+    Scenario {
+        name: "config limits",
+        run: config_limits,
+    },
+    Scenario {
+        name: "rate limit",
+        run: rate_limit,
+    },
+    Scenario {
+        name: "admin transfer",
+        run: admin_transfer,
+    },
+    Scenario {
+        name: "unregister and register again",
+        run: register_again,
+    },
+    Scenario {
+        name: "reconcile",
+        run: reconcile,
+    },
+    Scenario {
+        name: "take an escrow offer",
+        run: take_escrow_offer,
+    },
+    Scenario {
+        name: "refund an escrow offer",
+        run: refund_escrow_offer,
+    },
+    Scenario {
+        name: "migrate a legacy position",
+        run: migrate_legacy_position,
+    },
+    Scenario {
+        name: "initialize a vault twice",
+        run: initialize_vault_twice,
+    },
+    Scenario {
+        name: "initialize a vault with invalid parameters",
+        run: initialize_vault_invalid,
+    },
+    Scenario {
+        name: "initialize a vault as a stranger",
+        run: initialize_vault_by_stranger,
+    },
+    Scenario {
+        name: "initialize the registry without the upgrade authority",
+        run: initialize_registry_by_stranger,
+    },
+    Scenario {
+        name: "initialize a user twice",
+        run: initialize_user_twice,
+    },
+    Scenario {
+        name: "initialize another user's state",
+        run: initialize_foreign_user,
+    },
+    Scenario {
+        name: "deposit without a signature",
+        run: deposit_unsigned,
+    },
+    Scenario {
+        name: "deposit with the user state read only",
+        run: deposit_readonly,
+    },
+    Scenario {
+        name: "deposit nothing",
+        run: deposit_nothing,
+    },
+    Scenario {
+        name: "deposit without a user state",
+        run: deposit_without_user_state,
+    },
+    Scenario {
+        name: "deposit into another user's state",
+        run: deposit_into_foreign_state,
+    },
+    Scenario {
+        name: "deposit with another token program",
+        run: deposit_invalid_token_program,
+    },
+    Scenario {
+        name: "deposit into another token account",
+        run: deposit_into_foreign_ata,
+    },
+    Scenario {
+        name: "deposit with a fake registry",
+        run: deposit_fake_registry,
+    },
+    Scenario {
+        name: "deposit with a fake vault state",
+        run: deposit_fake_vault_state,
+    },
+    Scenario {
+        name: "deposit with a fake vault config",
+        run: deposit_fake_vault_config,
+    },
+    Scenario {
+        name: "withdraw more than the position",
+        run: withdraw_too_much,
+    },
+    Scenario {
+        name: "withdraw from another user's position",
+        run: withdraw_foreign_position,
+    },
+    Scenario {
+        name: "withdraw from another token account",
+        run: withdraw_from_foreign_ata,
+    },
+    Scenario {
+        name: "claim another user's ticket",
+        run: claim_foreign_ticket,
+    },
+    Scenario {
+        name: "admin instructions by a stranger",
+        run: admin_by_stranger,
+    },
+    Scenario {
+        name: "flash loan without a repay",
+        run: flash_loan_without_repay,
+    },
+    Scenario {
+        name: "make an escrow offer for another user",
+        run: escrow_offer_by_stranger,
+    },
+    Scenario {
+        name: "truncated and unknown instructions",
+        run: invalid_instructions,
+    },
+];
+
+#[test]
+fn vault_builds_conform_with_spl_token() {
+    check_conformance(VAULT_BUILDS, SCENARIOS, |build| {
+        VaultEnv::new(build, spl_token::id())
+    });
+}
+
+#[test]
+fn vault_builds_conform_with_token_2022() {
+    check_conformance(VAULT_BUILDS, SCENARIOS, |build| {
+        VaultEnv::new(build, spl_token_2022::id())
+    });
+}
+
+// Happy paths
+
+fn deposit_and_withdraw(env: &mut VaultEnv) {
+    deposited(env);
+    let alice_ata = env.ata(&env.alice, &env.mint);
+    env.check("vault balance", env.balance(&env.vault_ata()), DEPOSIT);
+    env.check("alice balance", env.balance(&alice_ata), BALANCE - DEPOSIT);
+
+    let ix = env.withdraw_ix(&env.alice, &env.alice, 100_000, false);
+    env.step("withdraw", &[ix], Expect::Success);
+    env.check(
+        "vault balance",
+        env.balance(&env.vault_ata()),
+        DEPOSIT - 100_000,
+    );
+    env.check(
+        "alice balance",
+        env.balance(&alice_ata),
+        BALANCE - DEPOSIT + 100_000,
+    );
+}
+
+fn withdraw_and_close(env: &mut VaultEnv) {
+    deposited(env);
+    let ix = env.withdraw_ix(&env.alice, &env.alice, DEPOSIT, true);
+    env.step("withdraw", &[ix], Expect::Success);
+    env.check(
+        "user state lamports",
+        env.lamports(&env.user_state(&env.alice)),
+        0,
+    );
+    // The rent of the user state goes back to Alice
+    env.check("alice lamports", env.lamports(&env.alice), WALLET_LAMPORTS);
+}
+
+fn close_user(env: &mut VaultEnv) {
+    deposited(env);
+    let ix = env.close_user_ix(&env.alice);
+    env.step(
+        "close with a balance",
+        std::slice::from_ref(&ix),
+        fails(VaultError::UserStateNotEmpty),
+    );
+    let withdraw = env.withdraw_ix(&env.alice, &env.alice, DEPOSIT, false);
+    env.step("withdraw", &[withdraw], Expect::Success);
+    env.step("close", &[ix], Expect::Success);
+    env.check("alice lamports", env.lamports(&env.alice), WALLET_LAMPORTS);
+}
+
+fn deposit_for(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.deposit_for_ix(&env.bob, &env.alice, DEPOSIT);
+    env.step("deposit for alice", &[ix], Expect::Success);
+    let ix = env.withdraw_ix(&env.bob, &env.alice, DEPOSIT, false);
+    env.step("bob withdraws", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.withdraw_ix(&env.alice, &env.alice, DEPOSIT, false);
+    env.step("alice withdraws", &[ix], Expect::Success);
+    env.check(
+        "alice balance",
+        env.balance(&env.ata(&env.alice, &env.mint)),
+        BALANCE + DEPOSIT,
+    );
+}
+
+fn withdraw_early(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.deposit_ix(&env.alice, DEPOSIT, LockupTier::Days30);
+    env.step("deposit", &[ix], Expect::Success);
+    let ix = env.withdraw_ix(&env.alice, &env.alice, 100_000, false);
+    env.step("withdraw", &[ix], fails(VaultError::PositionLocked));
+
+    // 5% of the early withdrawal stays in the vault
+    let ix = env.withdraw_early_ix(&env.alice, 100_000);
+    env.step("withdraw early", &[ix], Expect::Success);
+    let alice_ata = env.ata(&env.alice, &env.mint);
+    env.check(
+        "alice balance",
+        env.balance(&alice_ata),
+        BALANCE - DEPOSIT + 95_000,
+    );
+    env.check(
+        "vault balance",
+        env.balance(&env.vault_ata()),
+        DEPOSIT - 95_000,
+    );
+
+    // The penalty is rounded up, 5% of 9 tokens is still 1 token
+    let ix = env.withdraw_early_ix(&env.alice, 9);
+    env.step("withdraw 9 tokens early", &[ix], Expect::Success);
+    env.check(
+        "alice balance",
+        env.balance(&alice_ata),
+        BALANCE - DEPOSIT + 95_008,
+    );
+
+    env.warp(30 * DAY);
+    let ix = env.withdraw_ix(&env.alice, &env.alice, 199_991, false);
+    env.step("withdraw after the lockup", &[ix], Expect::Success);
+    env.check("alice balance", env.balance(&alice_ata), BALANCE - 5_001);
+}
+
+fn withdraw_treasury(env: &mut VaultEnv) {
+    open(env);
+    let (admin, alice, bob) = (env.admin, env.alice, env.bob);
+    let mut params = open_config();
+    params.early_exit_penalty_bps = 1_000;
+    let ix = env.update_config_ix(&admin, params);
+    env.step("raise the penalty", &[ix], Expect::Success);
+    let ix = env.deposit_ix(&alice, DEPOSIT, LockupTier::Days30);
+    env.step("deposit", &[ix], Expect::Success);
+    let ix = env.withdraw_early_ix(&alice, 100_000);
+    env.step("withdraw early", &[ix], Expect::Success);
+
+    // The penalties go wherever the admin wants them, here to Bob
+    let bob_ata = env.ata(&bob, &env.mint);
+    let ix = env.withdraw_treasury_ix(&bob, &bob_ata, 10_000);
+    env.step("withdraw as a stranger", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.withdraw_treasury_ix(&admin, &bob_ata, 10_001);
+    env.step(
+        "withdraw more than the treasury",
+        &[ix],
+        fails(VaultError::InsufficientFunds),
+    );
+    let ix = env.withdraw_treasury_ix(&admin, &bob_ata, 10_000);
+    env.step("withdraw", &[ix], Expect::Success);
+    env.check("bob balance", env.balance(&bob_ata), BALANCE + 10_000);
+    env.check(
+        "vault balance",
+        env.balance(&env.vault_ata()),
+        DEPOSIT - 100_000,
+    );
+    let ix = env.withdraw_treasury_ix(&admin, &bob_ata, 1);
+    env.step(
+        "withdraw from an empty treasury",
+        &[ix],
+        fails(VaultError::InsufficientFunds),
+    );
+}
+
+fn withdraw_through_ticket(env: &mut VaultEnv) {
+    env.setup_vault(0, DAY);
+    let instructions = [
+        env.initialize_user_ix(&env.alice),
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+    ];
+    env.send(&instructions);
+
+    let ix = env.withdraw_ix(&env.alice, &env.alice, 100_000, false);
+    env.step(
+        "withdraw",
+        &[ix],
+        fails(VaultError::InstantWithdrawDisabled),
+    );
+    let ix = env.request_withdraw_ix(&env.alice, 1, 100_000);
+    env.step(
+        "request with the wrong ticket",
+        &[ix],
+        fails(VaultError::InvalidTicket),
+    );
+    let ix = env.request_withdraw_ix(&env.alice, 0, DEPOSIT + 1);
+    env.step(
+        "request too much",
+        &[ix],
+        fails(VaultError::InsufficientFunds),
+    );
+    let ix = env.request_withdraw_ix(&env.alice, 0, 100_000);
+    env.step("request", &[ix], Expect::Success);
+
+    let ix = env.claim_withdraw_ix(&env.alice, 0);
+    env.step(
+        "claim during the cooldown",
+        std::slice::from_ref(&ix),
+        fails(VaultError::CooldownActive),
+    );
+    env.warp(DAY);
+    env.step("claim", std::slice::from_ref(&ix), Expect::Success);
+    env.check(
+        "alice balance",
+        env.balance(&env.ata(&env.alice, &env.mint)),
+        BALANCE - DEPOSIT + 100_000,
+    );
+    env.check("ticket lamports", env.lamports(&env.ticket(0)), 0);
+    env.step("claim again", &[ix], fails(VaultError::InvalidAccountOwner));
+}
+
+fn withdraw_as_delegate(env: &mut VaultEnv) {
+    deposited(env);
+    let (alice, bob) = (env.alice, env.bob);
+    let ix = env.set_delegate_ix(&alice, &alice, None);
+    env.step(
+        "delegate to herself",
+        &[ix],
+        fails(VaultError::InvalidDelegate),
+    );
+    let ix = env.set_delegate_ix(&alice, &bob, Some(50_000));
+    env.step("delegate", &[ix], Expect::Success);
+
+    let ix = env.withdraw_ix(&bob, &alice, 30_000, false);
+    env.step("bob withdraws", std::slice::from_ref(&ix), Expect::Success);
+    env.step(
+        "bob withdraws again",
+        std::slice::from_ref(&ix),
+        fails(VaultError::DelegateAllowanceExceeded),
+    );
+    let close = env.withdraw_ix(&bob, &alice, 10_000, true);
+    env.step("bob closes", &[close], fails(VaultError::Unauthorized));
+    env.check(
+        "bob balance",
+        env.balance(&env.ata(&bob, &env.mint)),
+        BALANCE + 30_000,
+    );
+
+    let revoke = env.revoke_delegate_ix(&alice);
+    env.step("revoke", &[revoke], Expect::Success);
+    env.step(
+        "bob withdraws after the revoke",
+        &[ix],
+        fails(VaultError::Unauthorized),
+    );
+}
+
+fn flash_loan(env: &mut VaultEnv) {
+    deposited(env);
+    let (admin, bob) = (env.admin, env.bob);
+    let instructions = [
+        env.flash_loan_ix(&bob, 100_000),
+        env.flash_repay_ix(&bob, 100_000),
+    ];
+    env.step(
+        "borrow while disabled",
+        &instructions,
+        fails(VaultError::FlashLoansDisabled),
+    );
+
+    let ix = env.set_flash_loan_fee_ix(&admin, 10_001);
+    env.step("set an invalid fee", &[ix], fails(VaultError::InvalidFee));
+    let ix = env.set_flash_loan_fee_ix(&admin, 30);
+    env.step("set the fee", &[ix], Expect::Success);
+
+    env.step("borrow", &instructions, Expect::Success);
+    // 0.3% of 100_000 goes to the vault
+    env.check(
+        "vault balance",
+        env.balance(&env.vault_ata()),
+        DEPOSIT + 300,
+    );
+    env.check(
+        "bob balance",
+        env.balance(&env.ata(&bob, &env.mint)),
+        BALANCE - 300,
+    );
+
+    let instructions = [
+        env.flash_loan_ix(&bob, 100_000),
+        env.flash_repay_ix(&bob, 99_999),
+    ];
+    env.step(
+        "repay less",
+        &instructions,
+        fails(VaultError::InvalidFlashRepay),
+    );
+}
+
+fn config_limits(env: &mut VaultEnv) {
+    deposited(env);
+    let (admin, alice) = (env.admin, env.alice);
+    let mut params = open_config();
+    params.user_deposit_cap = DEPOSIT + 100_000;
+    params.tvl_cap = DEPOSIT + 200_000;
+    params.min_deposit = 1_000;
+    params.min_withdraw = 1_000;
+    let ix = env.update_config_ix(&admin, params);
+    env.step("update config", &[ix], Expect::Success);
+
+    let ix = env.deposit_ix(&alice, 999, LockupTier::None);
+    env.step(
+        "deposit below the minimum",
+        &[ix],
+        fails(VaultError::BelowMinimum),
+    );
+    let ix = env.deposit_ix(&alice, 100_001, LockupTier::None);
+    env.step(
+        "deposit over the user cap",
+        &[ix],
+        fails(VaultError::UserCapExceeded),
+    );
+    let ix = env.deposit_for_ix(&env.bob, &env.bob, 200_001);
+    env.step(
+        "deposit over the tvl cap",
+        &[ix],
+        fails(VaultError::TvlCapExceeded),
+    );
+    let ix = env.withdraw_ix(&alice, &alice, 999, false);
+    env.step(
+        "withdraw below the minimum",
+        &[ix],
+        fails(VaultError::BelowMinimum),
+    );
+
+    params.deposit_paused = true;
+    params.withdraw_paused = true;
+    let ix = env.update_config_ix(&admin, params);
+    env.step("pause", &[ix], Expect::Success);
+    let ix = env.deposit_ix(&alice, 1_000, LockupTier::None);
+    env.step(
+        "deposit while paused",
+        &[ix],
+        fails(VaultError::DepositPaused),
+    );
+    let ix = env.withdraw_ix(&alice, &alice, 1_000, false);
+    env.step(
+        "withdraw while paused",
+        &[ix],
+        fails(VaultError::WithdrawPaused),
+    );
+
+    params.rate_limit_window = -1;
+    let ix = env.update_config_ix(&admin, params);
+    env.step(
+        "negative rate limit window",
+        &[ix],
+        fails(VaultError::InvalidRateLimitWindow),
+    );
+
+    params.rate_limit_window = 0;
+    params.early_exit_penalty_bps = 10_001;
+    let ix = env.update_config_ix(&admin, params);
+    env.step(
+        "penalty over 100%",
+        &[ix],
+        fails(VaultError::InvalidPenalty),
+    );
+}
+
+fn rate_limit(env: &mut VaultEnv) {
+    deposited(env);
+    let (admin, alice) = (env.admin, env.alice);
+    let mut params = open_config();
+    params.rate_limit_window = DAY;
+    params.user_window_limit = 100_000;
+    let ix = env.update_config_ix(&admin, params);
+    env.step("update config", &[ix], Expect::Success);
+
+    let ix = env.withdraw_ix(&alice, &alice, 60_000, false);
+    env.step("withdraw", std::slice::from_ref(&ix), Expect::Success);
+    env.step(
+        "withdraw over the limit",
+        std::slice::from_ref(&ix),
+        fails(VaultError::UserRateLimitExceeded),
+    );
+    env.warp(DAY);
+    env.step("withdraw in the next window", &[ix], Expect::Success);
+}
+
+fn admin_transfer(env: &mut VaultEnv) {
+    open(env);
+    let (admin, alice, bob) = (env.admin, env.alice, env.bob);
+    let ix = env.accept_admin_ix(&bob);
+    env.step(
+        "accept without a transfer",
+        &[ix],
+        fails(VaultError::NoPendingAdmin),
+    );
+    let ix = env.transfer_admin_ix(&admin, &bob);
+    env.step("transfer", &[ix], Expect::Success);
+    let ix = env.accept_admin_ix(&alice);
+    env.step(
+        "accept by another wallet",
+        &[ix],
+        fails(VaultError::NoPendingAdmin),
+    );
+    let ix = env.accept_admin_ix(&bob);
+    env.step("accept", &[ix], Expect::Success);
+
+    let ix = env.update_config_ix(&admin, open_config());
+    env.step("old admin updates", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.update_config_ix(&bob, open_config());
+    env.step("new admin updates", &[ix], Expect::Success);
+}
+
+fn register_again(env: &mut VaultEnv) {
+    deposited(env);
+    let admin = env.admin;
+    let ix = env.register_vault_ix(&admin);
+    env.step(
+        "register twice",
+        &[ix],
+        fails(VaultError::MintAlreadyRegistered),
+    );
+    let ix = env.unregister_vault_ix(&admin);
+    env.step("unregister", std::slice::from_ref(&ix), Expect::Success);
+    env.step(
+        "unregister twice",
+        &[ix],
+        fails(VaultError::MintNotRegistered),
+    );
+
+    let ix = env.deposit_ix(&env.alice, 1_000, LockupTier::None);
+    env.step(
+        "deposit",
+        std::slice::from_ref(&ix),
+        fails(VaultError::MintNotRegistered),
+    );
+    // Withdrawals keep working so users can exit
+    let withdraw = env.withdraw_ix(&env.alice, &env.alice, 1_000, false);
+    env.step("withdraw", &[withdraw], Expect::Success);
+
+    let register = env.register_vault_ix(&admin);
+    env.step("register", &[register], Expect::Success);
+    env.step("deposit again", &[ix], Expect::Success);
+}
+
+fn reconcile(env: &mut VaultEnv) {
+    deposited(env);
+    let ix = env.reconcile_ix(false);
+    env.step("reconcile", &[ix], Expect::Success);
+    let ix = env.reconcile_ix(true);
+    env.step("reconcile and pause if insolvent", &[ix], Expect::Success);
+    let ix = swap(
+        env.reconcile_ix(false),
+        &env.vault_ata(),
+        env.ata(&env.alice, &env.mint),
+    );
+    env.step(
+        "reconcile another token account",
+        &[ix],
+        fails(VaultError::InvalidVaultTokenAccount),
+    );
+}
+
+fn take_escrow_offer(env: &mut VaultEnv) {
+    deposited(env);
+    let (alice, bob) = (env.alice, env.bob);
+    let ix = env.make_escrow_offer_ix(&alice, 1, DEPOSIT + 1, 50_000);
+    env.step(
+        "offer more than the position",
+        &[ix],
+        fails(VaultError::InsufficientFunds),
+    );
+    let ix = env.make_escrow_offer_ix(&alice, 1, 100_000, 50_000);
+    env.step("make", &[ix], Expect::Success);
+    env.check(
+        "vault balance",
+        env.balance(&env.vault_ata()),
+        DEPOSIT - 100_000,
+    );
+    env.check(
+        "escrow maker lamports",
+        env.lamports(&env.escrow_maker(&alice)),
+        0,
+    );
+
+    // Earmarked tokens can't be withdrawn
+    let ix = env.withdraw_ix(&alice, &alice, DEPOSIT, false);
+    env.step(
+        "withdraw the earmarked tokens",
+        &[ix],
+        fails(VaultError::InsufficientFunds),
+    );
+
+    // The escrow maker forwards mint B to alice's account, the vault doesn't create it
+    let mint_b = env.mint_b;
+    env.mint_to(&alice, &mint_b, 0);
+    let ix = env.take_escrow_offer_ix(&bob, &alice, 1);
+    env.step("take", std::slice::from_ref(&ix), Expect::Success);
+    env.check(
+        "bob balance",
+        env.balance(&env.ata(&bob, &env.mint)),
+        BALANCE + 100_000,
+    );
+    env.check(
+        "alice balance of B",
+        env.balance(&env.ata(&alice, &env.mint_b)),
+        50_000,
+    );
+    env.step("take again", &[ix], fails(VaultError::InvalidOffer));
+}
+
+fn refund_escrow_offer(env: &mut VaultEnv) {
+    deposited(env);
+    let alice = env.alice;
+    let ix = env.make_escrow_offer_ix(&alice, 7, 250_000, 10);
+    env.step("make", &[ix], Expect::Success);
+    let ix = env.refund_escrow_offer_ix(&alice, 7);
+    env.step("refund", std::slice::from_ref(&ix), Expect::Success);
+    env.check("vault balance", env.balance(&env.vault_ata()), DEPOSIT);
+    env.step("refund again", &[ix], fails(VaultError::InvalidOffer));
+
+    let ix = env.withdraw_ix(&alice, &alice, DEPOSIT, true);
+    env.step("withdraw everything", &[ix], Expect::Success);
+    // The escrow accounts were paid by Alice and closed again
+    env.check("alice lamports", env.lamports(&alice), WALLET_LAMPORTS);
+}
+
+fn migrate_legacy_position(env: &mut VaultEnv) {
+    env.setup_vault(0, 0);
+    let (alice, admin) = (env.alice, env.admin);
+    env.set_legacy_balance(&alice, DEPOSIT);
+    let ix = env.migrate_user_state_ix(&alice, &alice);
+    env.step(
+        "migrate as alice",
+        &[ix],
+        fails(VaultError::NotUpgradeAuthority),
+    );
+    let ix = env.migrate_user_state_ix(&admin, &alice);
+    env.step(
+        "migrate before the tokens are in the vault",
+        std::slice::from_ref(&ix),
+        fails(VaultError::MigrationNotBacked),
+    );
+
+    let (vault_state, mint) = (env.vault_state(), env.mint);
+    env.mint_to(&vault_state, &mint, DEPOSIT);
+    env.step("migrate", &[ix], Expect::Success);
+    env.check("legacy lamports", env.lamports(&env.legacy_state(&alice)), 0);
+    let ix = env.withdraw_ix(&alice, &alice, DEPOSIT, true);
+    env.step("withdraw the migrated balance", &[ix], Expect::Success);
+    env.check(
+        "alice balance",
+        env.balance(&env.ata(&alice, &mint)),
+        BALANCE + DEPOSIT,
+    );
+}
+
+// Error paths
+
+fn initialize_vault_twice(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.initialize_vault_ix(&env.admin, 0, 0);
+    env.step(
+        "initialize the vault again",
+        &[ix],
+        fails(VaultError::AlreadyInitialized),
+    );
+    let ix = env.initialize_registry_ix(&env.admin);
+    env.step(
+        "initialize the registry again",
+        &[ix],
+        fails(VaultError::AlreadyInitialized),
+    );
+}
+
+fn initialize_vault_invalid(env: &mut VaultEnv) {
+    let admin = env.admin;
+    let ix = env.initialize_vault_ix(&admin, 10_001, 0);
+    env.step(
+        "penalty over 100%",
+        &[ix],
+        fails(VaultError::InvalidPenalty),
+    );
+    let ix = env.initialize_vault_ix(&admin, 0, -1);
+    env.step(
+        "negative cooldown",
+        &[ix],
+        fails(VaultError::InvalidCooldown),
+    );
+    let ix = swap(
+        env.initialize_vault_ix(&admin, 0, 0),
+        &env.token_program,
+        system_program::ID,
+    );
+    env.step(
+        "another token program",
+        &[ix],
+        fails(VaultError::InvalidTokenProgram),
+    );
+    let ix = swap(
+        env.initialize_vault_ix(&admin, 0, 0),
+        &env.vault_config(),
+        env.registry(),
+    );
+    env.step(
+        "another config address",
+        &[ix],
+        fails(VaultError::InvalidVaultConfig),
+    );
+    let ix = unsigned(env.initialize_vault_ix(&admin, 0, 0), &admin);
+    env.step("without a signature", &[ix], fails(VaultError::NotSigner));
+}
+
+fn initialize_vault_by_stranger(env: &mut VaultEnv) {
+    let alice = env.alice;
+    let ix = env.initialize_vault_ix(&alice, 0, 0);
+    env.step(
+        "before the registry",
+        &[ix],
+        fails(VaultError::Unauthorized),
+    );
+    let ix = env.initialize_registry_ix(&env.admin);
+    env.step("initialize the registry", &[ix], Expect::Success);
+    let ix = env.initialize_vault_ix(&alice, 0, 0);
+    env.step(
+        "after the registry",
+        &[ix],
+        fails(VaultError::Unauthorized),
+    );
+    // The vault of the mint is still free for the registry admin
+    let ix = env.initialize_vault_ix(&env.admin, 0, 0);
+    env.step("initialize as the admin", &[ix], Expect::Success);
+}
+
+fn initialize_registry_by_stranger(env: &mut VaultEnv) {
+    let alice = env.alice;
+    let ix = env.initialize_registry_ix(&alice);
+    env.step(
+        "initialize as alice",
+        &[ix],
+        fails(VaultError::NotUpgradeAuthority),
+    );
+    let ix = swap(
+        env.initialize_registry_ix(&alice),
+        &env.program_data(),
+        env.vault_state(),
+    );
+    env.step(
+        "with another program data account",
+        &[ix],
+        fails(VaultError::InvalidProgramData),
+    );
+    let ix = unsigned(env.initialize_registry_ix(&env.admin), &env.admin);
+    env.step("without a signature", &[ix], fails(VaultError::NotSigner));
+    // Alice didn't take the registry, the admin still can
+    let ix = env.initialize_registry_ix(&env.admin);
+    env.step("initialize as the admin", &[ix], Expect::Success);
+}
+
+fn initialize_user_twice(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.initialize_user_ix(&env.alice);
+    env.step(
+        "initialize again",
+        &[ix],
+        fails(VaultError::AlreadyInitialized),
+    );
+}
+
+fn initialize_foreign_user(env: &mut VaultEnv) {
+    open(env);
+    let admin = env.admin;
+    let ix = swap(
+        env.initialize_user_ix(&admin),
+        &env.user_state(&admin),
+        env.user_state(&env.alice),
+    );
+    env.step(
+        "initialize alice's state",
+        &[ix],
+        fails(VaultError::InvalidUserAccount),
+    );
+}
+
+fn deposit_unsigned(env: &mut VaultEnv) {
+    open(env);
+    let ix = unsigned(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.alice,
+    );
+    env.step("deposit", &[ix], fails(VaultError::NotSigner));
+}
+
+fn deposit_readonly(env: &mut VaultEnv) {
+    open(env);
+    let ix = readonly(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.user_state(&env.alice),
+    );
+    env.step("deposit", &[ix], fails(VaultError::NotWritable));
+}
+
+fn deposit_nothing(env: &mut VaultEnv) {
+    open(env);
+    let ix = env.deposit_ix(&env.alice, 0, LockupTier::None);
+    env.step("deposit", &[ix], fails(VaultError::InvalidAmount));
+}
+
+fn deposit_without_user_state(env: &mut VaultEnv) {
+    env.setup_vault(0, 0);
+    let ix = env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None);
+    env.step("deposit", &[ix], fails(VaultError::InvalidAccountOwner));
+}
+
+fn deposit_into_foreign_state(env: &mut VaultEnv) {
+    open(env);
+    let (alice, bob) = (env.alice, env.bob);
+    let ix = swap(
+        env.deposit_ix(&alice, DEPOSIT, LockupTier::None),
+        &env.user_state(&alice),
+        env.user_state(&bob),
+    );
+    env.step("deposit", &[ix], fails(VaultError::InvalidUserAccount));
+}
+
+fn deposit_invalid_token_program(env: &mut VaultEnv) {
+    open(env);
+    let ix = swap(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.token_program,
+        system_program::ID,
+    );
+    env.step("deposit", &[ix], fails(VaultError::InvalidTokenProgram));
+}
+
+fn deposit_into_foreign_ata(env: &mut VaultEnv) {
+    open(env);
+    let bob_ata = env.ata(&env.bob, &env.mint);
+    let ix = swap(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.vault_ata(),
+        bob_ata,
+    );
+    env.step(
+        "deposit",
+        &[ix],
+        fails(VaultError::InvalidVaultTokenAccount),
+    );
+}
+
+fn deposit_fake_registry(env: &mut VaultEnv) {
+    // The config is owned by the vault program too, only its address gives it away
+    open(env);
+    let ix = swap(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.registry(),
+        env.vault_config(),
+    );
+    env.step("deposit", &[ix], fails(VaultError::InvalidRegistry));
+}
+
+fn deposit_fake_vault_state(env: &mut VaultEnv) {
+    open(env);
+    let ix = swap(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.vault_state(),
+        env.registry(),
+    );
+    env.step("deposit", &[ix], fails(VaultError::InvalidVaultAuthority));
+}
+
+fn deposit_fake_vault_config(env: &mut VaultEnv) {
+    open(env);
+    let bob_state = env.user_state(&env.bob);
+    let ix = swap(
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        &env.vault_config(),
+        bob_state,
+    );
+    env.step("deposit", &[ix], fails(VaultError::InvalidVaultConfig));
+}
+
+fn withdraw_too_much(env: &mut VaultEnv) {
+    deposited(env);
+    let ix = env.withdraw_ix(&env.alice, &env.alice, DEPOSIT + 1, false);
+    env.step("withdraw", &[ix], fails(VaultError::InsufficientFunds));
+}
+
+fn withdraw_foreign_position(env: &mut VaultEnv) {
+    deposited(env);
+    let ix = env.withdraw_ix(&env.bob, &env.alice, 1, false);
+    env.step("withdraw", &[ix], fails(VaultError::Unauthorized));
+}
+
+fn withdraw_from_foreign_ata(env: &mut VaultEnv) {
+    // The vault state can't sign for Bob's token account, the token program refuses the transfer
+    deposited(env);
+    let bob_ata = env.ata(&env.bob, &env.mint);
+    let ix = swap(
+        env.withdraw_ix(&env.alice, &env.alice, 1_000, false),
+        &env.vault_ata(),
+        bob_ata,
+    );
+    env.step(
+        "withdraw",
+        &[ix],
+        Expect::Failure(custom(TokenError::OwnerMismatch as u32)),
+    );
+}
+
+fn claim_foreign_ticket(env: &mut VaultEnv) {
+    env.setup_vault(0, DAY);
+    let instructions = [
+        env.initialize_user_ix(&env.alice),
+        env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None),
+        env.request_withdraw_ix(&env.alice, 0, 100_000),
+    ];
+    env.send(&instructions);
+    env.warp(DAY);
+    let ix = env.claim_withdraw_ix(&env.bob, 0);
+    env.step("claim", &[ix], fails(VaultError::InvalidOwner));
+}
+
+fn admin_by_stranger(env: &mut VaultEnv) {
+    deposited(env);
+    let alice = env.alice;
+    let ix = env.update_config_ix(&alice, open_config());
+    env.step("update config", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.transfer_admin_ix(&alice, &alice);
+    env.step("transfer admin", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.set_flash_loan_fee_ix(&alice, 30);
+    env.step("set flash loan fee", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.unregister_vault_ix(&alice);
+    env.step("unregister", &[ix], fails(VaultError::Unauthorized));
+    let ix = env.register_vault_ix(&alice);
+    env.step("register", &[ix], fails(VaultError::Unauthorized));
+}
+
+fn flash_loan_without_repay(env: &mut VaultEnv) {
+    deposited(env);
+    let (admin, bob) = (env.admin, env.bob);
+    let ix = env.set_flash_loan_fee_ix(&admin, 30);
+    env.step("set the fee", &[ix], Expect::Success);
+    let ix = env.flash_loan_ix(&bob, 100_000);
+    env.step("borrow", &[ix], fails(VaultError::MissingFlashRepay));
+    let ix = env.flash_repay_ix(&bob, 100_000);
+    env.step(
+        "repay without a loan",
+        &[ix],
+        fails(VaultError::InvalidFlashRepay),
+    );
+}
+
+fn escrow_offer_by_stranger(env: &mut VaultEnv) {
+    deposited(env);
+    let (alice, bob) = (env.alice, env.bob);
+    let ix = swap(env.make_escrow_offer_ix(&alice, 1, 1_000, 1), &alice, bob);
+    env.step("make", &[ix], fails(VaultError::InvalidUserAccount));
+    let ix = swap(
+        env.make_escrow_offer_ix(&alice, 1, 1_000, 1),
+        &ESCROW_PROGRAM_ID,
+        system_program::ID,
+    );
+    env.step(
+        "make with another escrow",
+        &[ix],
+        fails(VaultError::InvalidEscrowProgram),
+    );
+}
+
+fn invalid_instructions(env: &mut VaultEnv) {
+    deposited(env);
+    let mut ix = env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None);
+    ix.data.truncate(5);
+    env.step(
+        "truncated",
+        std::slice::from_ref(&ix),
+        fails(VaultError::InvalidInstruction),
+    );
+    ix.data = vec![u8::MAX];
+    env.step(
+        "unknown",
+        std::slice::from_ref(&ix),
+        fails(VaultError::InvalidInstruction),
+    );
+    ix.data = env.deposit_ix(&env.alice, DEPOSIT, LockupTier::None).data;
+    ix.accounts.truncate(4);
+    env.step(
+        "missing accounts",
+        &[ix],
+        Expect::Failure(InstructionError::NotEnoughAccountKeys),
+    );
+}
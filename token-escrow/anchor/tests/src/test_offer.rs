@@ -0,0 +1,82 @@
+use anchor_client::{
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        program_pack::Pack,
+        signature::{read_keypair_file, Keypair, Signer},
+        system_instruction,
+    },
+    Client, Cluster,
+};
+use spl_associated_token_account::instruction::create_associated_token_account;
+
+#[test]
+fn test_make_and_refund_offer() {
+    let anchor_wallet = std::env::var("ANCHOR_WALLET").unwrap();
+    let payer = read_keypair_file(&anchor_wallet).unwrap();
+
+    let client = Client::new_with_options(Cluster::Localnet, &payer, CommitmentConfig::confirmed());
+    let program = client.program(escrow_client::ID).unwrap();
+    let rpc = program.rpc();
+    let maker = payer.pubkey();
+    let token_program = spl_token::id();
+
+    // Two mints of the payer, and 1000 tokens of mint A for the maker
+    let (mint_a, mint_b) = (Keypair::new(), Keypair::new());
+    let rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .unwrap();
+    let mut setup = program.request().signer(&mint_a).signer(&mint_b);
+    for mint in [&mint_a, &mint_b] {
+        setup = setup
+            .instruction(system_instruction::create_account(
+                &maker,
+                &mint.pubkey(),
+                rent,
+                spl_token::state::Mint::LEN as u64,
+                &token_program,
+            ))
+            .instruction(
+                spl_token::instruction::initialize_mint2(&token_program, &mint.pubkey(), &maker, None, 6)
+                    .unwrap(),
+            );
+    }
+    let maker_ata = escrow_client::ata(&maker, &mint_a.pubkey(), &token_program);
+    setup
+        .instruction(create_associated_token_account(&maker, &maker, &mint_a.pubkey(), &token_program))
+        .instruction(
+            spl_token::instruction::mint_to(&token_program, &mint_a.pubkey(), &maker_ata, &maker, &[], 1_000)
+                .unwrap(),
+        )
+        .send()
+        .unwrap();
+
+    let id = 1;
+    program
+        .request()
+        .instruction(escrow_client::instruction::make_offer(
+            &maker,
+            &mint_a.pubkey(),
+            &mint_b.pubkey(),
+            &token_program,
+            id,
+            400,
+            100,
+        ))
+        .send()
+        .unwrap();
+
+    let offer = escrow_client::state::fetch_offer(|address| rpc.get_account(address).ok(), &maker, id)
+        .unwrap()
+        .expect("the offer is open");
+    assert_eq!(offer.token_a_offered_amount, 400);
+    assert_eq!(offer.token_b_wanted_amount, 100);
+
+    program
+        .request()
+        .instruction(escrow_client::instruction::refund_fetched_offer(&offer, &token_program))
+        .send()
+        .unwrap();
+
+    let refunded = escrow_client::state::fetch_offer(|address| rpc.get_account(address).ok(), &maker, id).unwrap();
+    assert_eq!(refunded, None);
+}
@@ -0,0 +1,53 @@
+//! Typed errors of failed escrow instructions.
+//!
+//! The first codes of `EscrowError`, from 6000, are the Anchor program's
+//! `EscrowErrorCode`, the others are raised by the native and pinocchio ports
+//! only. Anchor's own constraint errors, below 6000, have no typed equivalent.
+
+use crate::EscrowError;
+use num_traits::FromPrimitive;
+use solana_program::{instruction::InstructionError, program_error::ProgramError};
+
+/// Escrow error of a custom error code
+pub fn escrow_error(code: u32) -> Option<EscrowError> {
+    EscrowError::from_u32(code)
+}
+
+/// Escrow error of a `ProgramError::Custom`, e.g. from a CPI into the escrow
+pub fn from_program_error(error: &ProgramError) -> Option<EscrowError> {
+    match error {
+        ProgramError::Custom(code) => escrow_error(*code),
+        _ => None,
+    }
+}
+
+/// Escrow error of the `InstructionError` of a failed transaction
+pub fn from_instruction_error(error: &InstructionError) -> Option<EscrowError> {
+    match error {
+        InstructionError::Custom(code) => escrow_error(*code),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        for error in [
+            EscrowError::AccountNotMutable,
+            EscrowError::FailedRefundClosure,
+            EscrowError::InvalidInstruction,
+            EscrowError::Overflow,
+        ] {
+            assert_eq!(from_program_error(&error.into()), Some(error));
+            assert_eq!(from_instruction_error(&InstructionError::Custom(error as u32)), Some(error));
+        }
+        assert_eq!(escrow_error(6000), Some(EscrowError::AccountNotMutable));
+        assert_eq!(escrow_error(6009), Some(EscrowError::FailedRefundClosure));
+        // Anchor's ConstraintSeeds
+        assert_eq!(escrow_error(2006), None);
+        assert_eq!(from_program_error(&ProgramError::InvalidArgument), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Instruction builders. `token_program` is the owner of both mints, SPL Token
+//! or Token-2022, and decides the address of every token account.
+
+use crate::{ID, Offer, ata, offer_address};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_system_interface::program as system_program;
+use spl_token_escrow::processor::EscrowInstruction;
+
+/// The maker offers `token_a_offered_amount` tokens of mint A for
+/// `token_b_wanted_amount` tokens of mint B. The offer holds the tokens of
+/// mint A until it's taken or refunded.
+pub fn make_offer(
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    token_program: &Pubkey,
+    id: u64,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+) -> Instruction {
+    // The vault is the offer's ATA, derived here to search for the offer's bump once
+    let offer = offer_address(maker, id);
+    Instruction {
+        program_id: ID,
+        accounts: vec![
+            AccountMeta::new(*maker, true),
+            AccountMeta::new_readonly(*token_mint_a, false),
+            AccountMeta::new_readonly(*token_mint_b, false),
+            AccountMeta::new(ata(maker, token_mint_a, token_program), false),
+            AccountMeta::new(offer, false),
+            AccountMeta::new(ata(&offer, token_mint_a, token_program), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::MakeOffer { id, token_a_offered_amount, token_b_wanted_amount }
+            .pack(),
+    }
+}
+
+/// The taker pays the wanted tokens to the maker and receives the offered
+/// ones. The maker signs too, and gets the rent of the offer back.
+pub fn take_offer(
+    taker: &Pubkey,
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    token_program: &Pubkey,
+    id: u64,
+) -> Instruction {
+    let offer = offer_address(maker, id);
+    Instruction {
+        program_id: ID,
+        accounts: vec![
+            AccountMeta::new(*taker, true),
+            AccountMeta::new(*maker, true),
+            AccountMeta::new_readonly(*token_mint_a, false),
+            AccountMeta::new_readonly(*token_mint_b, false),
+            AccountMeta::new(ata(taker, token_mint_a, token_program), false),
+            AccountMeta::new(ata(taker, token_mint_b, token_program), false),
+            AccountMeta::new(ata(maker, token_mint_b, token_program), false),
+            AccountMeta::new(offer, false),
+            AccountMeta::new(ata(&offer, token_mint_a, token_program), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::TakeOffer { id }.pack(),
+    }
+}
+
+/// The maker closes the offer and gets the offered tokens back
+pub fn refund_offer(
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    token_program: &Pubkey,
+    id: u64,
+) -> Instruction {
+    let offer = offer_address(maker, id);
+    Instruction {
+        program_id: ID,
+        accounts: vec![
+            AccountMeta::new(*maker, true),
+            AccountMeta::new_readonly(*token_mint_a, false),
+            AccountMeta::new_readonly(*token_mint_b, false),
+            AccountMeta::new(ata(maker, token_mint_a, token_program), false),
+            AccountMeta::new(offer, false),
+            AccountMeta::new(ata(&offer, token_mint_a, token_program), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: EscrowInstruction::RefundOffer { id }.pack(),
+    }
+}
+
+/// Take a fetched offer, its maker and mints come from the account
+pub fn take_fetched_offer(taker: &Pubkey, offer: &Offer, token_program: &Pubkey) -> Instruction {
+    take_offer(taker, &offer.maker, &offer.token_mint_a, &offer.token_mint_b, token_program, offer.id)
+}
+
+/// Refund a fetched offer, its maker and mints come from the account
+pub fn refund_fetched_offer(offer: &Offer, token_program: &Pubkey) -> Instruction {
+    refund_offer(&offer.maker, &offer.token_mint_a, &offer.token_mint_b, token_program, offer.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_address;
+
+    #[test]
+    fn take_and_refund_reuse_the_make_accounts() {
+        let (maker, taker) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token_program = Pubkey::new_unique();
+        let make = make_offer(&maker, &mint_a, &mint_b, &token_program, 7, 100, 25);
+        let take = take_offer(&taker, &maker, &mint_a, &mint_b, &token_program, 7);
+        let refund = refund_offer(&maker, &mint_a, &mint_b, &token_program, 7);
+
+        assert_eq!(refund.accounts, make.accounts);
+        // The offer and its vault
+        assert_eq!(take.accounts[7..9], make.accounts[4..6]);
+        assert_eq!(make.accounts[5].pubkey, vault_address(&maker, 7, &mint_a, &token_program));
+        assert_eq!(
+            EscrowInstruction::unpack(&take.data).unwrap(),
+            EscrowInstruction::TakeOffer { id: 7 }
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! Client of the token escrow.
+//!
+//! The Anchor program and its native and pinocchio ports share instructions,
+//! accounts and error codes, so this crate talks to whichever one is deployed.
+//! The builders derive the offer PDA, its vault and the associated token
+//! accounts of the maker and taker for either token program.
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+
+use solana_program::{pubkey, pubkey::Pubkey};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+pub use spl_token_escrow::{error::EscrowError, state::Offer};
+
+/// Program id of the escrow, the one every build is deployed at
+pub const ID: Pubkey = pubkey!("6qDkztsEJ4r73Sqk1pDdL1degbzjyccqAX2uPDCsJJWf");
+
+/// Address of the maker's offer with this id
+pub fn offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Offer::find_address(&ID, maker, id).0
+}
+
+/// Token account of the offer holding the offered tokens of mint A
+pub fn vault_address(maker: &Pubkey, id: u64, token_mint_a: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(&offer_address(maker, id), token_mint_a, token_program)
+}
+
+/// Associated token account of the owner, `token_program` is the owner of the mint
+pub fn ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
@@ -0,0 +1,85 @@
+//! Fetching and decoding offers. Fetching goes through a closure returning the
+//! account at an address, so the same helpers work over RPC, e.g.
+//! `|address| rpc.get_account(address).ok()`, or against an in-process SVM.
+
+use crate::{EscrowError, ID, Offer, offer_address};
+use borsh::BorshDeserialize;
+use solana_account::Account;
+use solana_program::pubkey::Pubkey;
+pub use spl_token_escrow::state::OFFER_DISCRIMINATOR;
+
+/// Offset of `Offer::maker` in the account data, to filter the offers of one
+/// maker with a memcmp in `getProgramAccounts`
+pub const MAKER_OFFSET: usize = OFFER_DISCRIMINATOR.len() + 8;
+
+/// Decode an offer account, checking its owner and discriminator
+pub fn decode_offer(account: &Account) -> Result<Offer, EscrowError> {
+    if account.owner != ID {
+        return Err(EscrowError::InvalidOffer);
+    }
+    decode_offer_data(&account.data)
+}
+
+/// Decode the data of an offer account, discriminator included
+pub fn decode_offer_data(data: &[u8]) -> Result<Offer, EscrowError> {
+    let body = data
+        .strip_prefix(&OFFER_DISCRIMINATOR[..])
+        .ok_or(EscrowError::InvalidOffer)?;
+    Offer::deserialize(&mut &body[..]).map_err(|_| EscrowError::InvalidOffer)
+}
+
+/// Fetch and decode the maker's offer with this id, `None` once it's taken or
+/// refunded
+pub fn fetch_offer(
+    get_account: impl FnOnce(&Pubkey) -> Option<Account>,
+    maker: &Pubkey,
+    id: u64,
+) -> Result<Option<Offer>, EscrowError> {
+    match get_account(&offer_address(maker, id)) {
+        // A closed offer goes back to the system program with no data
+        Some(account) if account.owner == ID => decode_offer(&account).map(Some),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::to_vec;
+
+    fn offer_account(offer: &Offer) -> Account {
+        let mut data = OFFER_DISCRIMINATOR.to_vec();
+        data.extend(to_vec(offer).unwrap());
+        Account { lamports: 1, data, owner: ID, executable: false, rent_epoch: 0 }
+    }
+
+    #[test]
+    fn fetch_decodes_open_offers_only() {
+        let maker = Pubkey::new_unique();
+        let offer = Offer {
+            id: 3,
+            maker,
+            token_mint_a: Pubkey::new_unique(),
+            token_mint_b: Pubkey::new_unique(),
+            token_a_offered_amount: 1_000,
+            token_b_wanted_amount: 500,
+            bump: Offer::find_address(&ID, &maker, 3).1,
+        };
+        let account = offer_account(&offer);
+        assert_eq!(account.data.len(), Offer::SPACE);
+        assert_eq!(account.data[MAKER_OFFSET..][..32], maker.to_bytes());
+
+        let fetched = fetch_offer(|address| (*address == offer_address(&maker, 3)).then(|| account.clone()), &maker, 3);
+        assert_eq!(fetched, Ok(Some(offer)));
+        assert_eq!(fetch_offer(|_| None, &maker, 3), Ok(None));
+
+        let mut closed = account.clone();
+        closed.owner = Pubkey::default();
+        closed.data.clear();
+        assert_eq!(fetch_offer(|_| Some(closed), &maker, 3), Ok(None));
+
+        let mut garbage = account;
+        garbage.data[0] ^= 1;
+        assert_eq!(decode_offer(&garbage), Err(EscrowError::InvalidOffer));
+    }
+}
@@ -0,0 +1,52 @@
+use num_derive::FromPrimitive;
+use solana_program::program_error::ProgramError;
+use thiserror::Error;
+
+// The first errors keep the codes of the Anchor escrow's `EscrowErrorCode`,
+// which start at 6000 like every Anchor error code. Anchor's own account
+// constraint errors have no equivalent here and map to the native ones below.
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum EscrowError {
+    #[error("Account Not Mutable")]
+    AccountNotMutable = 6000,
+    #[error("Insufficient token balance in maker's account")]
+    InsufficientMakerBalance,
+    #[error("Insufficient token balance in taker's account")]
+    InsufficientTakerBalance,
+    #[error("Invalid token mint - must be different from offered token")]
+    InvalidTokenMint,
+    #[error("Amount must be greater than zero")]
+    InvalidAmount,
+    #[error("Token transfer failed")]
+    TokenTransferFailed,
+    #[error("Failed to withdraw tokens from vault")]
+    FailedVaultWithdrawal,
+    #[error("Failed to close vault account")]
+    FailedVaultClosure,
+    #[error("Failed to refund tokens from vault")]
+    FailedRefundTransfer,
+    #[error("Failed to close vault during refund")]
+    FailedRefundClosure,
+    #[error("Invalid instruction")]
+    InvalidInstruction,
+    #[error("Missing required signer")]
+    NotSigner,
+    #[error("Invalid offer account")]
+    InvalidOffer,
+    #[error("Offer account already exists")]
+    OfferAlreadyExists,
+    #[error("Token program must be SPL Token or Token-2022")]
+    InvalidTokenProgram,
+    #[error("Mint doesn't belong to the token program")]
+    InvalidMint,
+    #[error("Token account is not the expected associated token account")]
+    InvalidTokenAccount,
+    #[error("Overflow occurred")]
+    Overflow,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(e: EscrowError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
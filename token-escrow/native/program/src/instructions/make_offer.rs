@@ -0,0 +1,128 @@
+use super::shared::{
+    check_ata, check_token_program, create_pda_account, mint_decimals, token_account_amount,
+    transfer_tokens,
+};
+use crate::error::EscrowError;
+use crate::state::Offer;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::instruction::create_associated_token_account;
+
+
+/// Create the offer PDA and its token account, then move the offered tokens
+/// of mint A into it.
+///
+/// Accounts:
+/// [signer writable maker]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [writable maker_ata]
+/// [writable offer]
+/// [writable vault_ata_a]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+pub fn make_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    id: u64,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let maker = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let maker_ata = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let vault_ata_a = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let _ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !maker.is_signer {
+        return Err(EscrowError::NotSigner.into());
+    }
+    if !maker.is_writable || !maker_ata.is_writable || !offer.is_writable || !vault_ata_a.is_writable
+    {
+        return Err(EscrowError::AccountNotMutable.into());
+    }
+    check_token_program(token_prog)?;
+    mint_decimals(token_mint_a, token_prog)?;
+    mint_decimals(token_mint_b, token_prog)?;
+    check_ata(maker_ata, maker.key, token_mint_a.key, token_prog.key)?;
+
+    // Derive the offer PDA, its token account is the offer's ATA
+    let id_bytes = id.to_le_bytes();
+    let (offer_pda, offer_bump) = Offer::find_address(program_id, maker.key, id);
+    if offer_pda != *offer.key {
+        return Err(EscrowError::InvalidOffer.into());
+    }
+    check_ata(vault_ata_a, offer.key, token_mint_a.key, token_prog.key)?;
+
+    if token_a_offered_amount == 0 || token_b_wanted_amount == 0 {
+        return Err(EscrowError::InvalidAmount.into());
+    }
+    if token_account_amount(maker_ata)? < token_a_offered_amount {
+        return Err(EscrowError::InsufficientMakerBalance.into());
+    }
+    if token_mint_a.key == token_mint_b.key {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+
+    // Create the offer and its token account, the maker pays the rent of both
+    msg!("Creating offer {}", id);
+    let [prefix, maker_seed, id_seed] = Offer::seeds(maker.key, &id_bytes);
+    create_pda_account(
+        program_id,
+        maker,
+        offer,
+        system_prog,
+        Offer::SPACE,
+        &[prefix, maker_seed, id_seed, &[offer_bump]],
+    )?;
+    invoke(
+        &create_associated_token_account(maker.key, offer.key, token_mint_a.key, token_prog.key),
+        &[
+            maker.clone(),
+            vault_ata_a.clone(),
+            offer.clone(),
+            token_mint_a.clone(),
+            system_prog.clone(),
+            token_prog.clone(),
+        ],
+    )?;
+
+    msg!("Send the offered tokens to vault");
+    transfer_tokens(
+        maker_ata,
+        vault_ata_a,
+        token_a_offered_amount,
+        token_mint_a,
+        maker,
+        token_prog,
+        None,
+    )
+    .map_err(|_| EscrowError::TokenTransferFailed)?;
+
+    msg!("Save the offer details in the Offer state account");
+    Offer {
+        id,
+        maker: *maker.key,
+        token_mint_a: *token_mint_a.key,
+        token_mint_b: *token_mint_b.key,
+        token_a_offered_amount,
+        token_b_wanted_amount,
+        bump: offer_bump,
+    }
+    .store(offer)?;
+
+    Ok(())
+}
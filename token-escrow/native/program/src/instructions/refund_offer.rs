@@ -0,0 +1,75 @@
+use super::shared::{
+    check_ata, check_token_program, close_account, close_token_account, load_offer,
+    token_account_amount, transfer_tokens,
+};
+use crate::error::EscrowError;
+use crate::state::Offer;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Return everything in the offer's token account to the maker and close the
+/// offer and its token account, the rent of both goes to the maker.
+///
+/// Accounts:
+/// [signer writable maker]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [writable maker_ata_a]
+/// [writable offer]
+/// [writable vault_ata_a]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+pub fn refund_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let maker = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let maker_ata_a = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let vault_ata_a = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !maker.is_signer {
+        return Err(EscrowError::NotSigner.into());
+    }
+    if !maker.is_writable || !maker_ata_a.is_writable || !offer.is_writable || !vault_ata_a.is_writable
+    {
+        return Err(EscrowError::AccountNotMutable.into());
+    }
+    check_token_program(token_prog)?;
+    check_ata(maker_ata_a, maker.key, token_mint_a.key, token_prog.key)?;
+    check_ata(vault_ata_a, offer.key, token_mint_a.key, token_prog.key)?;
+
+    // Verify and load the offer
+    let offer_data = load_offer(program_id, offer, maker.key, token_mint_a.key, token_mint_b.key, id)?;
+
+    msg!("Refunding offer {}", id);
+    let id_bytes = id.to_le_bytes();
+    let [prefix, maker_seed, id_seed] = Offer::seeds(maker.key, &id_bytes);
+    let offer_seeds: &[&[u8]] = &[prefix, maker_seed, id_seed, &[offer_data.bump]];
+    transfer_tokens(
+        vault_ata_a,
+        maker_ata_a,
+        token_account_amount(vault_ata_a)?,
+        token_mint_a,
+        offer,
+        token_prog,
+        Some(offer_seeds),
+    )
+    .map_err(|_| EscrowError::FailedRefundTransfer)?;
+    close_token_account(vault_ata_a, maker, offer, token_prog, offer_seeds)
+        .map_err(|_| EscrowError::FailedRefundClosure)?;
+
+    close_account(offer, maker)?;
+    msg!("Offer {} refunded", id);
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::error::EscrowError;
+use crate::state::Offer;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{Sysvar, rent::Rent},
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    instruction as token_instruction,
+    state::{Account as TokenAccount, Mint},
+};
+
+// The escrow works with both the SPL Token and the Token-2022 program
+pub fn check_token_program(token_prog: &AccountInfo) -> Result<(), EscrowError> {
+    if *token_prog.key != spl_token::id() && *token_prog.key != spl_token_2022::id() {
+        return Err(EscrowError::InvalidTokenProgram);
+    }
+    Ok(())
+}
+
+// Check the mint belongs to the token program and return its decimals
+pub fn mint_decimals(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<u8, ProgramError> {
+    check_token_program(token_prog)?;
+    if token_mint.owner != token_prog.key {
+        return Err(EscrowError::InvalidMint.into());
+    }
+    let mint_data = token_mint.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)
+        .map_err(|_| EscrowError::InvalidMint)?;
+    Ok(mint.base.decimals)
+}
+
+// The token account is the associated token account of the owner for the mint
+pub fn check_ata(
+    token_account: &AccountInfo,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_prog: &Pubkey,
+) -> Result<(), EscrowError> {
+    if *token_account.key != get_associated_token_address_with_program_id(owner, mint, token_prog) {
+        return Err(EscrowError::InvalidTokenAccount);
+    }
+    Ok(())
+}
+
+// Token balance of a token account of either token program
+pub fn token_account_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let account_data = token_account.data.borrow();
+    let account = StateWithExtensions::<TokenAccount>::unpack(&account_data)
+        .map_err(|_| EscrowError::InvalidTokenAccount)?;
+    Ok(account.base.amount)
+}
+
+// Verify and load the offer of the maker, the same checks as the `has_one` and
+// `seeds` constraints of the Anchor accounts
+pub fn load_offer(
+    program_id: &Pubkey,
+    offer: &AccountInfo,
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    id: u64,
+) -> Result<Offer, EscrowError> {
+    let offer_data = Offer::load(program_id, offer)?;
+    if offer_data.maker != *maker
+        || offer_data.token_mint_a != *token_mint_a
+        || offer_data.token_mint_b != *token_mint_b
+    {
+        return Err(EscrowError::InvalidOffer);
+    }
+    let id_bytes = id.to_le_bytes();
+    let [prefix, maker_seed, id_seed] = Offer::seeds(maker, &id_bytes);
+    let pda = Pubkey::create_program_address(
+        &[prefix, maker_seed, id_seed, &[offer_data.bump]],
+        program_id,
+    )
+    .map_err(|_| EscrowError::InvalidOffer)?;
+    if pda != *offer.key {
+        return Err(EscrowError::InvalidOffer);
+    }
+    Ok(offer_data)
+}
+
+// Create a PDA owned by the program. An address which already holds lamports
+// can't go through create_account, it's topped up, allocated and assigned instead.
+pub fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_prog: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if !account.data_is_empty() || *account.owner != system_program::ID {
+        return Err(EscrowError::OfferAlreadyExists.into());
+    }
+    let lamports = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_prog.clone()],
+            &[seeds],
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, shortfall),
+            &[payer.clone(), account.clone(), system_prog.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_prog.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_prog.clone()],
+        &[seeds],
+    )
+}
+
+// Transfer tokens, `signer_seeds` are needed when the authority is a PDA
+pub fn transfer_tokens<'a>(
+    from: &AccountInfo<'a>,
+    to: &AccountInfo<'a>,
+    amount: u64,
+    token_mint: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    token_prog: &AccountInfo<'a>,
+    signer_seeds: Option<&[&[u8]]>,
+) -> ProgramResult {
+    let transfer_ix = token_instruction::transfer_checked(
+        token_prog.key,
+        from.key,
+        token_mint.key,
+        to.key,
+        authority.key,
+        &[],
+        amount,
+        mint_decimals(token_mint, token_prog)?,
+    )?;
+    let account_infos = [
+        from.clone(),
+        token_mint.clone(),
+        to.clone(),
+        authority.clone(),
+        token_prog.clone(),
+    ];
+    match signer_seeds {
+        Some(seeds) => invoke_signed(&transfer_ix, &account_infos, &[seeds]),
+        None => invoke(&transfer_ix, &account_infos),
+    }
+}
+
+// Close a token account owned by a PDA and send its rent to the destination
+pub fn close_token_account<'a>(
+    token_account: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    token_prog: &AccountInfo<'a>,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    invoke_signed(
+        &token_instruction::close_account(
+            token_prog.key,
+            token_account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[token_account.clone(), destination.clone(), authority.clone(), token_prog.clone()],
+        &[signer_seeds],
+    )
+}
+
+// Close a program owned account and send its rent to the destination
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **account.try_borrow_mut_lamports()? = 0;
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(EscrowError::Overflow)?;
+
+    account.data.borrow_mut().fill(0);
+    account.resize(0)?;
+    account.assign(&system_program::ID);
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use super::shared::{
+    check_ata, check_token_program, close_account, close_token_account, load_offer,
+    token_account_amount, transfer_tokens,
+};
+use crate::error::EscrowError;
+use crate::state::Offer;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+
+/// The taker pays the wanted tokens of mint B to the maker and receives the
+/// offered tokens of mint A. The offer's token account is closed to the taker
+/// and the offer to the maker, as the Anchor escrow does.
+///
+/// Accounts:
+/// [signer writable taker]
+/// [signer writable maker]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [writable taker_ata_a]
+/// [writable taker_ata_b]
+/// [writable maker_ata_b]
+/// [writable offer]
+/// [writable vault_ata_a]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+pub fn take_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let taker = next_account_info(account_info_iter)?;
+    let maker = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let taker_ata_a = next_account_info(account_info_iter)?;
+    let taker_ata_b = next_account_info(account_info_iter)?;
+    let maker_ata_b = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let vault_ata_a = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let _ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !taker.is_signer || !maker.is_signer {
+        return Err(EscrowError::NotSigner.into());
+    }
+    if !taker.is_writable
+        || !maker.is_writable
+        || !taker_ata_a.is_writable
+        || !taker_ata_b.is_writable
+        || !maker_ata_b.is_writable
+        || !offer.is_writable
+        || !vault_ata_a.is_writable
+    {
+        return Err(EscrowError::AccountNotMutable.into());
+    }
+    check_token_program(token_prog)?;
+    check_ata(taker_ata_a, taker.key, token_mint_a.key, token_prog.key)?;
+    check_ata(taker_ata_b, taker.key, token_mint_b.key, token_prog.key)?;
+    check_ata(maker_ata_b, maker.key, token_mint_b.key, token_prog.key)?;
+    check_ata(vault_ata_a, offer.key, token_mint_a.key, token_prog.key)?;
+
+    // Verify and load the offer
+    let offer_data = load_offer(program_id, offer, maker.key, token_mint_a.key, token_mint_b.key, id)?;
+
+    // The taker pays for the ATAs which don't exist yet. Existing ones are
+    // left alone without a CPI, as Anchor's `init_if_needed` does
+    for (owner, mint, ata) in [(taker, token_mint_a, taker_ata_a), (maker, token_mint_b, maker_ata_b)] {
+        if !ata.data_is_empty() && ata.owner == token_prog.key {
+            continue;
+        }
+        invoke(
+            &create_associated_token_account_idempotent(taker.key, owner.key, mint.key, token_prog.key),
+            &[
+                taker.clone(),
+                ata.clone(),
+                owner.clone(),
+                mint.clone(),
+                system_prog.clone(),
+                token_prog.clone(),
+            ],
+        )?;
+    }
+
+    // Send the wanted tokens from the taker to the maker
+    if token_account_amount(taker_ata_b)? < offer_data.token_b_wanted_amount {
+        return Err(EscrowError::InsufficientTakerBalance.into());
+    }
+    if token_mint_a.key == token_mint_b.key {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    transfer_tokens(
+        taker_ata_b,
+        maker_ata_b,
+        offer_data.token_b_wanted_amount,
+        token_mint_b,
+        taker,
+        token_prog,
+        None,
+    )
+    .map_err(|_| EscrowError::TokenTransferFailed)?;
+
+    // The offer owns the vault and signs for it
+    msg!("Withdraw the offered tokens to the taker");
+    let id_bytes = id.to_le_bytes();
+    let [prefix, maker_seed, id_seed] = Offer::seeds(maker.key, &id_bytes);
+    let offer_seeds: &[&[u8]] = &[prefix, maker_seed, id_seed, &[offer_data.bump]];
+    transfer_tokens(
+        vault_ata_a,
+        taker_ata_a,
+        offer_data.token_a_offered_amount,
+        token_mint_a,
+        offer,
+        token_prog,
+        Some(offer_seeds),
+    )
+    .map_err(|_| EscrowError::TokenTransferFailed)?;
+    close_token_account(vault_ata_a, taker, offer, token_prog, offer_seeds)
+        .map_err(|_| EscrowError::FailedVaultClosure)?;
+
+    close_account(offer, maker)?;
+    msg!("Offer {} taken", id);
+
+    Ok(())
+}
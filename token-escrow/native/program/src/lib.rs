@@ -1,14 +1,15 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Native port of the Anchor escrow in `token-escrow/anchor`.
+//!
+//! Instructions, accounts and the `Offer` layout are the same as the Anchor
+//! program's, discriminators included, so clients can talk to either one.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod processor;
+pub mod instructions;
+pub mod error;
+pub mod state;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+#[cfg(not(feature = "no-entrypoint"))]
+use {crate::processor::process_instruction, solana_program::entrypoint};
+
+#[cfg(not(feature = "no-entrypoint"))]
+entrypoint!(process_instruction);
@@ -0,0 +1,139 @@
+use borsh::BorshDeserialize;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+use crate::error::EscrowError;
+use crate::instructions::{make_offer, refund_offer, take_offer};
+
+// First 8 bytes of sha256("global:<instruction name>"), the discriminators Anchor uses
+pub const MAKE_OFFER: [u8; 8] = [214, 98, 97, 35, 59, 12, 44, 178];
+pub const TAKE_OFFER: [u8; 8] = [128, 156, 242, 207, 237, 192, 103, 240];
+pub const REFUND_OFFER: [u8; 8] = [171, 18, 70, 32, 244, 121, 60, 75];
+
+/// Instructions of the escrow, encoded the way Anchor encodes them: the
+/// discriminator followed by the borsh encoded arguments. Take and refund
+/// carry the offer id, the Anchor accounts read it through `#[instruction(id: u64)]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscrowInstruction {
+    MakeOffer { id: u64, token_a_offered_amount: u64, token_b_wanted_amount: u64 },
+    TakeOffer { id: u64 },
+    RefundOffer { id: u64 },
+}
+
+impl EscrowInstruction {
+    pub fn unpack(data: &[u8]) -> Result<Self, EscrowError> {
+        let (discriminator, mut args) =
+            data.split_at_checked(8).ok_or(EscrowError::InvalidInstruction)?;
+        // Like Anchor, trailing bytes after the arguments are ignored
+        let mut arg = || u64::deserialize(&mut args).map_err(|_| EscrowError::InvalidInstruction);
+        match <[u8; 8]>::try_from(discriminator).unwrap() {
+            MAKE_OFFER => Ok(Self::MakeOffer {
+                id: arg()?,
+                token_a_offered_amount: arg()?,
+                token_b_wanted_amount: arg()?,
+            }),
+            TAKE_OFFER => Ok(Self::TakeOffer { id: arg()? }),
+            REFUND_OFFER => Ok(Self::RefundOffer { id: arg()? }),
+            _ => Err(EscrowError::InvalidInstruction),
+        }
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let (discriminator, args) = match *self {
+            Self::MakeOffer { id, token_a_offered_amount, token_b_wanted_amount } => {
+                (MAKE_OFFER, vec![id, token_a_offered_amount, token_b_wanted_amount])
+            }
+            Self::TakeOffer { id } => (TAKE_OFFER, vec![id]),
+            Self::RefundOffer { id } => (REFUND_OFFER, vec![id]),
+        };
+        let mut data = discriminator.to_vec();
+        for arg in args {
+            data.extend_from_slice(&arg.to_le_bytes());
+        }
+        data
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match EscrowInstruction::unpack(instruction_data)? {
+        EscrowInstruction::MakeOffer { id, token_a_offered_amount, token_b_wanted_amount } => {
+            make_offer(program_id, accounts, id, token_a_offered_amount, token_b_wanted_amount)
+        }
+        EscrowInstruction::TakeOffer { id } => take_offer(program_id, accounts, id),
+        EscrowInstruction::RefundOffer { id } => refund_offer(program_id, accounts, id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{OFFER_DISCRIMINATOR, Offer};
+    use anchor_lang::{AccountSerialize, Discriminator, InstructionData, Space};
+
+    #[test]
+    fn discriminators_match_anchor() {
+        assert_eq!(MAKE_OFFER, escrow::instruction::MakeOffer::DISCRIMINATOR);
+        assert_eq!(TAKE_OFFER, escrow::instruction::TakeOffer::DISCRIMINATOR);
+        assert_eq!(REFUND_OFFER, escrow::instruction::RefundOffer::DISCRIMINATOR);
+        assert_eq!(OFFER_DISCRIMINATOR, escrow::Offer::DISCRIMINATOR);
+    }
+
+    #[test]
+    fn instruction_data_matches_anchor() {
+        let anchor = escrow::instruction::MakeOffer {
+            id: 7,
+            token_a_offered_amount: 100,
+            token_b_offered_amount: 25,
+        }
+        .data();
+        let native = EscrowInstruction::MakeOffer {
+            id: 7,
+            token_a_offered_amount: 100,
+            token_b_wanted_amount: 25,
+        };
+        assert_eq!(native.pack(), anchor);
+        assert_eq!(EscrowInstruction::unpack(&anchor).unwrap(), native);
+
+        // Anchor reads the id of take and refund right behind the discriminator
+        let mut take = escrow::instruction::TakeOffer {}.data();
+        take.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(EscrowInstruction::TakeOffer { id: 7 }.pack(), take);
+    }
+
+    #[test]
+    fn offer_layout_matches_anchor() {
+        let maker = Pubkey::new_unique();
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut anchor = Vec::new();
+        escrow::Offer {
+            id: 3,
+            maker,
+            token_mint_a: mint_a,
+            token_mint_b: mint_b,
+            token_a_offered_amount: 1_000,
+            token_b_wanted_amount: 500,
+            bump: 254,
+        }
+        .try_serialize(&mut anchor)
+        .unwrap();
+        assert_eq!(anchor.len(), Offer::SPACE);
+        assert_eq!(Offer::LEN, escrow::Offer::INIT_SPACE);
+
+        let offer = Offer::deserialize(&mut &anchor[OFFER_DISCRIMINATOR.len()..]).unwrap();
+        assert_eq!(
+            offer,
+            Offer {
+                id: 3,
+                maker,
+                token_mint_a: mint_a,
+                token_mint_b: mint_b,
+                token_a_offered_amount: 1_000,
+                token_b_wanted_amount: 500,
+                bump: 254,
+            }
+        );
+    }
+}
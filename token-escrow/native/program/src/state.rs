@@ -0,0 +1,57 @@
+use crate::error::EscrowError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+
+// First 8 bytes of sha256("account:Offer"), the discriminator Anchor writes
+pub const OFFER_DISCRIMINATOR: [u8; 8] = [215, 88, 60, 71, 170, 162, 73, 229];
+
+/// Offer account, byte for byte the Anchor escrow's `Offer` behind its discriminator
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub id: u64,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_a_offered_amount: u64,
+    pub token_b_wanted_amount: u64,
+    pub bump: u8,
+}
+
+impl Offer {
+    // Size of the body
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1;
+    // Size of the account, discriminator included
+    pub const SPACE: usize = OFFER_DISCRIMINATOR.len() + Self::LEN;
+
+    pub fn seeds<'a>(maker: &'a Pubkey, id: &'a [u8; 8]) -> [&'a [u8]; 3] {
+        [b"offer", maker.as_ref(), id]
+    }
+
+    pub fn find_address(program_id: &Pubkey, maker: &Pubkey, id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&Self::seeds(maker, &id.to_le_bytes()), program_id)
+    }
+
+    // Check the owner and discriminator of an offer account and decode it
+    pub fn load(program_id: &Pubkey, offer: &AccountInfo) -> Result<Self, EscrowError> {
+        if offer.owner != program_id {
+            return Err(EscrowError::InvalidOffer);
+        }
+        let data = offer.data.borrow();
+        if data.get(..OFFER_DISCRIMINATOR.len()) != Some(&OFFER_DISCRIMINATOR[..]) {
+            return Err(EscrowError::InvalidOffer);
+        }
+        Self::deserialize(&mut &data[OFFER_DISCRIMINATOR.len()..])
+            .map_err(|_| EscrowError::InvalidOffer)
+    }
+
+    // Write the discriminator and the offer into a freshly created account
+    pub fn store(&self, offer: &AccountInfo) -> Result<(), EscrowError> {
+        let mut data = offer.data.borrow_mut();
+        if data.len() != Self::SPACE {
+            return Err(EscrowError::InvalidOffer);
+        }
+        data[..OFFER_DISCRIMINATOR.len()].copy_from_slice(&OFFER_DISCRIMINATOR);
+        self.serialize(&mut &mut data[OFFER_DISCRIMINATOR.len()..])
+            .map_err(|_| EscrowError::InvalidOffer)
+    }
+}
@@ -0,0 +1,49 @@
+use pinocchio::program_error::ProgramError;
+
+// Same codes as the native escrow, the first ones are the Anchor escrow's
+// `EscrowErrorCode`, which start at 6000 like every Anchor error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowError {
+    // Account Not Mutable
+    AccountNotMutable = 6000,
+    // Insufficient token balance in maker's account
+    InsufficientMakerBalance,
+    // Insufficient token balance in taker's account
+    InsufficientTakerBalance,
+    // Invalid token mint - must be different from offered token
+    InvalidTokenMint,
+    // Amount must be greater than zero
+    InvalidAmount,
+    // Token transfer failed
+    TokenTransferFailed,
+    // Failed to withdraw tokens from vault
+    FailedVaultWithdrawal,
+    // Failed to close vault account
+    FailedVaultClosure,
+    // Failed to refund tokens from vault
+    FailedRefundTransfer,
+    // Failed to close vault during refund
+    FailedRefundClosure,
+    // Invalid instruction
+    InvalidInstruction,
+    // Missing required signer
+    NotSigner,
+    // Invalid offer account
+    InvalidOffer,
+    // Offer account already exists
+    OfferAlreadyExists,
+    // Token program must be SPL Token or Token-2022
+    InvalidTokenProgram,
+    // Mint doesn't belong to the token program
+    InvalidMint,
+    // Token account is not the expected associated token account
+    InvalidTokenAccount,
+    // Overflow occurred
+    Overflow,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(e: EscrowError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,96 @@
+use super::shared::{
+    check_ata, check_token_program, create_ata, create_pda_account, mint_decimals,
+    token_account_amount, transfer_tokens,
+};
+use crate::error::EscrowError;
+use crate::state::Offer;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+
+/// Create the offer PDA and its token account, then move the offered tokens
+/// of mint A into it.
+///
+/// Accounts:
+/// [signer writable maker]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [writable maker_ata]
+/// [writable offer]
+/// [writable vault_ata_a]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+pub fn make_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    id: u64,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+) -> ProgramResult {
+    let [maker, token_mint_a, token_mint_b, maker_ata, offer, vault_ata_a, token_prog, _ata_prog, system_prog, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !maker.is_signer() {
+        return Err(EscrowError::NotSigner.into());
+    }
+    if !maker.is_writable() || !maker_ata.is_writable() || !offer.is_writable() || !vault_ata_a.is_writable()
+    {
+        return Err(EscrowError::AccountNotMutable.into());
+    }
+    check_token_program(token_prog)?;
+    mint_decimals(token_mint_a, token_prog)?;
+    mint_decimals(token_mint_b, token_prog)?;
+    check_ata(maker_ata, maker.key(), token_mint_a.key(), token_prog.key())?;
+
+    // Derive the offer PDA, its token account is the offer's ATA
+    let (offer_pda, offer_bump) = Offer::find_address(program_id, maker.key(), id);
+    if offer_pda != *offer.key() {
+        return Err(EscrowError::InvalidOffer.into());
+    }
+    check_ata(vault_ata_a, offer.key(), token_mint_a.key(), token_prog.key())?;
+
+    if token_a_offered_amount == 0 || token_b_wanted_amount == 0 {
+        return Err(EscrowError::InvalidAmount.into());
+    }
+    if token_account_amount(maker_ata)? < token_a_offered_amount {
+        return Err(EscrowError::InsufficientMakerBalance.into());
+    }
+    if token_mint_a.key() == token_mint_b.key() {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+
+    // Create the offer and its token account, the maker pays the rent of both
+    msg!("Creating offer");
+    let id_bytes = id.to_le_bytes();
+    let bump = [offer_bump];
+    let offer_seeds = [Seed::from(b"offer"), Seed::from(maker.key()), Seed::from(&id_bytes), Seed::from(&bump)];
+    create_pda_account(program_id, maker, offer, Offer::SPACE, Signer::from(&offer_seeds))?;
+    create_ata(maker, vault_ata_a, offer, token_mint_a, system_prog, token_prog, false)?;
+
+    msg!("Send the offered tokens to vault");
+    transfer_tokens(maker_ata, vault_ata_a, token_a_offered_amount, token_mint_a, maker, token_prog, &[])
+        .map_err(|_| EscrowError::TokenTransferFailed)?;
+
+    msg!("Save the offer details in the Offer state account");
+    let mut offer_data = Offer::init(offer)?;
+    offer_data.set_id(id);
+    offer_data.maker = *maker.key();
+    offer_data.token_mint_a = *token_mint_a.key();
+    offer_data.token_mint_b = *token_mint_b.key();
+    offer_data.set_token_a_offered_amount(token_a_offered_amount);
+    offer_data.set_token_b_wanted_amount(token_b_wanted_amount);
+    offer_data.bump = offer_bump;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use super::shared::{
+    check_ata, check_token_program, close_account, close_token_account, load_offer,
+    token_account_amount, transfer_tokens,
+};
+use crate::error::EscrowError;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+
+/// Return everything in the offer's token account to the maker and close the
+/// offer and its token account, the rent of both goes to the maker.
+///
+/// Accounts:
+/// [signer writable maker]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [writable maker_ata_a]
+/// [writable offer]
+/// [writable vault_ata_a]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+pub fn refund_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let [maker, token_mint_a, token_mint_b, maker_ata_a, offer, vault_ata_a, token_prog, ..] = accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !maker.is_signer() {
+        return Err(EscrowError::NotSigner.into());
+    }
+    if !maker.is_writable() || !maker_ata_a.is_writable() || !offer.is_writable() || !vault_ata_a.is_writable()
+    {
+        return Err(EscrowError::AccountNotMutable.into());
+    }
+    check_token_program(token_prog)?;
+    check_ata(maker_ata_a, maker.key(), token_mint_a.key(), token_prog.key())?;
+    check_ata(vault_ata_a, offer.key(), token_mint_a.key(), token_prog.key())?;
+
+    // Verify and load the offer
+    let offer_data = load_offer(program_id, offer, maker.key(), token_mint_a.key(), token_mint_b.key(), id)?;
+
+    msg!("Refunding offer");
+    let id_bytes = id.to_le_bytes();
+    let bump = [offer_data.bump];
+    let offer_seeds = [Seed::from(b"offer"), Seed::from(maker.key()), Seed::from(&id_bytes), Seed::from(&bump)];
+    transfer_tokens(
+        vault_ata_a,
+        maker_ata_a,
+        token_account_amount(vault_ata_a)?,
+        token_mint_a,
+        offer,
+        token_prog,
+        &[Signer::from(&offer_seeds)],
+    )
+    .map_err(|_| EscrowError::FailedRefundTransfer)?;
+    close_token_account(vault_ata_a, maker, offer, token_prog, Signer::from(&offer_seeds))
+        .map_err(|_| EscrowError::FailedRefundClosure)?;
+
+    close_account(offer, maker)?;
+    msg!("Offer refunded");
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+//! Account checks and CPIs shared by the instructions. `pinocchio-token`
+//! only targets the SPL Token program, so the token, associated token and
+//! system instructions are built here and work with Token-2022 as well.
+
+use crate::error::EscrowError;
+use crate::state::Offer;
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::{invoke, invoke_signed},
+    instruction::{AccountMeta, Instruction, Signer},
+    program_error::ProgramError,
+    pubkey::{Pubkey, create_program_address, find_program_address},
+    sysvars::{Sysvar, rent::Rent},
+    ProgramResult,
+};
+use pinocchio_pubkey::pubkey;
+
+pub const SYSTEM_PROGRAM_ID: Pubkey = [0; 32];
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ATA_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+// Base layouts shared by both token programs
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const MINT_INITIALIZED_OFFSET: usize = 45;
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+
+// The escrow works with both the SPL Token and the Token-2022 program
+pub fn check_token_program(token_prog: &AccountInfo) -> Result<(), EscrowError> {
+    if *token_prog.key() != TOKEN_PROGRAM_ID && *token_prog.key() != TOKEN_2022_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenProgram);
+    }
+    Ok(())
+}
+
+// Check the mint belongs to the token program and return its decimals
+pub fn mint_decimals(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<u8, ProgramError> {
+    check_token_program(token_prog)?;
+    if !token_mint.is_owned_by(token_prog.key()) {
+        return Err(EscrowError::InvalidMint.into());
+    }
+    let mint_data = token_mint.try_borrow_data()?;
+    if mint_data.len() < MINT_LEN || mint_data[MINT_INITIALIZED_OFFSET] != 1 {
+        return Err(EscrowError::InvalidMint.into());
+    }
+    Ok(mint_data[MINT_DECIMALS_OFFSET])
+}
+
+// Address of the associated token account of the owner for the mint
+pub fn ata_address(owner: &Pubkey, mint: &Pubkey, token_prog: &Pubkey) -> Pubkey {
+    find_program_address(&[owner, token_prog, mint], &ATA_PROGRAM_ID).0
+}
+
+// The token account is the associated token account of the owner for the mint
+pub fn check_ata(
+    token_account: &AccountInfo,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_prog: &Pubkey,
+) -> Result<(), EscrowError> {
+    if *token_account.key() != ata_address(owner, mint, token_prog) {
+        return Err(EscrowError::InvalidTokenAccount);
+    }
+    Ok(())
+}
+
+// Token balance of an initialized token account of either token program
+pub fn token_account_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let account_data = token_account.try_borrow_data()?;
+    if account_data.len() < TOKEN_ACCOUNT_LEN || account_data[TOKEN_ACCOUNT_STATE_OFFSET] == 0 {
+        return Err(EscrowError::InvalidTokenAccount.into());
+    }
+    let amount = &account_data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8];
+    Ok(u64::from_le_bytes(amount.try_into().unwrap()))
+}
+
+// Verify and copy out the offer of the maker, the same checks as the `has_one`
+// and `seeds` constraints of the Anchor accounts. The copy leaves the account
+// unborrowed for the CPIs it signs and for closing it.
+pub fn load_offer(
+    program_id: &Pubkey,
+    offer: &AccountInfo,
+    maker: &Pubkey,
+    token_mint_a: &Pubkey,
+    token_mint_b: &Pubkey,
+    id: u64,
+) -> Result<Offer, EscrowError> {
+    let offer_data = *Offer::load(program_id, offer)?;
+    if offer_data.maker != *maker
+        || offer_data.token_mint_a != *token_mint_a
+        || offer_data.token_mint_b != *token_mint_b
+    {
+        return Err(EscrowError::InvalidOffer);
+    }
+    let pda = create_program_address(
+        &[b"offer", maker, &id.to_le_bytes(), &[offer_data.bump]],
+        program_id,
+    )
+    .map_err(|_| EscrowError::InvalidOffer)?;
+    if pda != *offer.key() {
+        return Err(EscrowError::InvalidOffer);
+    }
+    Ok(offer_data)
+}
+
+// Create a PDA owned by the program. An address which already holds lamports
+// can't go through create_account, it's topped up, allocated and assigned instead.
+pub fn create_pda_account(
+    program_id: &Pubkey,
+    payer: &AccountInfo,
+    account: &AccountInfo,
+    space: usize,
+    signer: Signer,
+) -> ProgramResult {
+    if !account.data_is_empty() || !account.is_owned_by(&SYSTEM_PROGRAM_ID) {
+        return Err(EscrowError::OfferAlreadyExists.into());
+    }
+    let lamports = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        let mut data = [0; 52];
+        data[4..12].copy_from_slice(&lamports.to_le_bytes());
+        data[12..20].copy_from_slice(&(space as u64).to_le_bytes());
+        data[20..52].copy_from_slice(program_id);
+        return invoke_signed(
+            &Instruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                accounts: &[
+                    AccountMeta::writable_signer(payer.key()),
+                    AccountMeta::writable_signer(account.key()),
+                ],
+                data: &data,
+            },
+            &[payer, account],
+            &[signer],
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_transfer(payer, account, shortfall, &[])?;
+    }
+    let mut allocate = [0; 12];
+    allocate[..4].copy_from_slice(&8u32.to_le_bytes());
+    allocate[4..].copy_from_slice(&(space as u64).to_le_bytes());
+    let mut assign = [0; 36];
+    assign[..4].copy_from_slice(&1u32.to_le_bytes());
+    assign[4..].copy_from_slice(program_id);
+    for data in [&allocate[..], &assign[..]] {
+        invoke_signed(
+            &Instruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                accounts: &[AccountMeta::writable_signer(account.key())],
+                data,
+            },
+            &[account],
+            core::slice::from_ref(&signer),
+        )?;
+    }
+    Ok(())
+}
+
+// Move lamports out of a system account, `signers` when it's a PDA
+pub fn system_transfer(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    lamports: u64,
+    signers: &[Signer],
+) -> ProgramResult {
+    let mut data = [0; 12];
+    data[..4].copy_from_slice(&2u32.to_le_bytes());
+    data[4..].copy_from_slice(&lamports.to_le_bytes());
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            accounts: &[AccountMeta::writable_signer(from.key()), AccountMeta::writable(to.key())],
+            data: &data,
+        },
+        &[from, to],
+        signers,
+    )
+}
+
+// Create the associated token account of the owner, the payer pays its rent.
+// The idempotent variant leaves an existing account alone.
+pub fn create_ata(
+    payer: &AccountInfo,
+    ata: &AccountInfo,
+    owner: &AccountInfo,
+    token_mint: &AccountInfo,
+    system_prog: &AccountInfo,
+    token_prog: &AccountInfo,
+    idempotent: bool,
+) -> ProgramResult {
+    invoke(
+        &Instruction {
+            program_id: &ATA_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable_signer(payer.key()),
+                AccountMeta::writable(ata.key()),
+                AccountMeta::readonly(owner.key()),
+                AccountMeta::readonly(token_mint.key()),
+                AccountMeta::readonly(system_prog.key()),
+                AccountMeta::readonly(token_prog.key()),
+            ],
+            data: &[u8::from(idempotent)],
+        },
+        &[payer, ata, owner, token_mint, system_prog, token_prog],
+    )
+}
+
+// Transfer tokens with TransferChecked, `signers` are needed when the authority is a PDA
+pub fn transfer_tokens(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    amount: u64,
+    token_mint: &AccountInfo,
+    authority: &AccountInfo,
+    token_prog: &AccountInfo,
+    signers: &[Signer],
+) -> ProgramResult {
+    let mut data = [0; 10];
+    data[0] = 12;
+    data[1..9].copy_from_slice(&amount.to_le_bytes());
+    data[9] = mint_decimals(token_mint, token_prog)?;
+    invoke_signed(
+        &Instruction {
+            program_id: token_prog.key(),
+            accounts: &[
+                AccountMeta::writable(from.key()),
+                AccountMeta::readonly(token_mint.key()),
+                AccountMeta::writable(to.key()),
+                AccountMeta::readonly_signer(authority.key()),
+            ],
+            data: &data,
+        },
+        &[from, token_mint, to, authority],
+        signers,
+    )
+}
+
+// Close a token account owned by a PDA and send its rent to the destination
+pub fn close_token_account(
+    token_account: &AccountInfo,
+    destination: &AccountInfo,
+    authority: &AccountInfo,
+    token_prog: &AccountInfo,
+    signer: Signer,
+) -> ProgramResult {
+    invoke_signed(
+        &Instruction {
+            program_id: token_prog.key(),
+            accounts: &[
+                AccountMeta::writable(token_account.key()),
+                AccountMeta::writable(destination.key()),
+                AccountMeta::readonly_signer(authority.key()),
+            ],
+            data: &[9],
+        },
+        &[token_account, destination, authority],
+        &[signer],
+    )
+}
+
+// Close a program owned account and send its rent to the destination
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    let mut destination_lamports = destination.try_borrow_mut_lamports()?;
+    *destination_lamports = destination_lamports
+        .checked_add(lamports)
+        .ok_or(EscrowError::Overflow)?;
+    drop(destination_lamports);
+    // Zeroes the lamports, data length and owner, the owner becomes the system program
+    account.close()
+}
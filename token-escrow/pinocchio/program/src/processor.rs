@@ -0,0 +1,56 @@
+use pinocchio::{account_info::AccountInfo, pubkey::Pubkey, ProgramResult};
+
+use crate::error::EscrowError;
+use crate::instructions::{make_offer, refund_offer, take_offer};
+
+// First 8 bytes of sha256("global:<instruction name>"), the discriminators Anchor uses
+pub const MAKE_OFFER: [u8; 8] = [214, 98, 97, 35, 59, 12, 44, 178];
+pub const TAKE_OFFER: [u8; 8] = [128, 156, 242, 207, 237, 192, 103, 240];
+pub const REFUND_OFFER: [u8; 8] = [171, 18, 70, 32, 244, 121, 60, 75];
+
+/// Instructions of the escrow, encoded like the Anchor escrow's: the
+/// discriminator followed by the little endian arguments. Take and refund
+/// carry the offer id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowInstruction {
+    MakeOffer { id: u64, token_a_offered_amount: u64, token_b_wanted_amount: u64 },
+    TakeOffer { id: u64 },
+    RefundOffer { id: u64 },
+}
+
+impl EscrowInstruction {
+    pub fn unpack(data: &[u8]) -> Result<Self, EscrowError> {
+        let (discriminator, args) =
+            data.split_at_checked(8).ok_or(EscrowError::InvalidInstruction)?;
+        // Like Anchor, trailing bytes after the arguments are ignored
+        let arg = |index: usize| {
+            args.get(index * 8..index * 8 + 8)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or(EscrowError::InvalidInstruction)
+        };
+        match <[u8; 8]>::try_from(discriminator).unwrap() {
+            MAKE_OFFER => Ok(Self::MakeOffer {
+                id: arg(0)?,
+                token_a_offered_amount: arg(1)?,
+                token_b_wanted_amount: arg(2)?,
+            }),
+            TAKE_OFFER => Ok(Self::TakeOffer { id: arg(0)? }),
+            REFUND_OFFER => Ok(Self::RefundOffer { id: arg(0)? }),
+            _ => Err(EscrowError::InvalidInstruction),
+        }
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match EscrowInstruction::unpack(instruction_data)? {
+        EscrowInstruction::MakeOffer { id, token_a_offered_amount, token_b_wanted_amount } => {
+            make_offer(program_id, accounts, id, token_a_offered_amount, token_b_wanted_amount)
+        }
+        EscrowInstruction::TakeOffer { id } => take_offer(program_id, accounts, id),
+        EscrowInstruction::RefundOffer { id } => refund_offer(program_id, accounts, id),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::error::EscrowError;
+use pinocchio::{
+    account_info::{AccountInfo, Ref, RefMut},
+    pubkey::{Pubkey, find_program_address},
+};
+
+// First 8 bytes of sha256("account:Offer"), the discriminator Anchor writes
+pub const OFFER_DISCRIMINATOR: [u8; 8] = [215, 88, 60, 71, 170, 162, 73, 229];
+
+/// Offer account, byte for byte the Anchor escrow's `Offer` behind its
+/// discriminator. Integers are stored little endian with an alignment of 1,
+/// so the body is read in place.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Offer {
+    id: [u8; 8],
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    token_a_offered_amount: [u8; 8],
+    token_b_wanted_amount: [u8; 8],
+    pub bump: u8,
+}
+
+impl Offer {
+    // Size of the body
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1;
+    // Size of the account, discriminator included
+    pub const SPACE: usize = OFFER_DISCRIMINATOR.len() + Self::LEN;
+
+    pub fn find_address(program_id: &Pubkey, maker: &Pubkey, id: u64) -> (Pubkey, u8) {
+        find_program_address(&[b"offer", maker, &id.to_le_bytes()], program_id)
+    }
+
+    pub fn id(&self) -> u64 {
+        u64::from_le_bytes(self.id)
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id.to_le_bytes();
+    }
+
+    pub fn token_a_offered_amount(&self) -> u64 {
+        u64::from_le_bytes(self.token_a_offered_amount)
+    }
+
+    pub fn set_token_a_offered_amount(&mut self, amount: u64) {
+        self.token_a_offered_amount = amount.to_le_bytes();
+    }
+
+    pub fn token_b_wanted_amount(&self) -> u64 {
+        u64::from_le_bytes(self.token_b_wanted_amount)
+    }
+
+    pub fn set_token_b_wanted_amount(&mut self, amount: u64) {
+        self.token_b_wanted_amount = amount.to_le_bytes();
+    }
+
+    // Check the owner, size and discriminator of an offer account and view it in place
+    pub fn load<'a>(program_id: &Pubkey, offer: &'a AccountInfo) -> Result<Ref<'a, Self>, EscrowError> {
+        if !offer.is_owned_by(program_id) {
+            return Err(EscrowError::InvalidOffer);
+        }
+        let data = offer.try_borrow_data().map_err(|_| EscrowError::InvalidOffer)?;
+        if data.len() != Self::SPACE || data[..OFFER_DISCRIMINATOR.len()] != OFFER_DISCRIMINATOR {
+            return Err(EscrowError::InvalidOffer);
+        }
+        // SAFETY: the length was checked and `Offer` has an alignment of 1
+        Ok(Ref::map(data, |data| unsafe {
+            &*(data[OFFER_DISCRIMINATOR.len()..].as_ptr() as *const Self)
+        }))
+    }
+
+    // Write the discriminator of a freshly created offer account and view its body
+    pub fn init<'a>(offer: &'a AccountInfo) -> Result<RefMut<'a, Self>, EscrowError> {
+        let mut data = offer.try_borrow_mut_data().map_err(|_| EscrowError::InvalidOffer)?;
+        if data.len() != Self::SPACE {
+            return Err(EscrowError::InvalidOffer);
+        }
+        data[..OFFER_DISCRIMINATOR.len()].copy_from_slice(&OFFER_DISCRIMINATOR);
+        // SAFETY: the length was checked and `Offer` has an alignment of 1
+        Ok(RefMut::map(data, |data| unsafe {
+            &mut *(data[OFFER_DISCRIMINATOR.len()..].as_mut_ptr() as *mut Self)
+        }))
+    }
+}
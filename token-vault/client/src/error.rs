@@ -0,0 +1,46 @@
+//! Typed errors of failed vault instructions. Every `VaultError` is returned
+//! as `ProgramError::Custom` with the variant's index as code.
+
+use crate::VaultError;
+use num_traits::FromPrimitive;
+use solana_program::{instruction::InstructionError, program_error::ProgramError};
+
+/// Vault error of a custom error code
+pub fn vault_error(code: u32) -> Option<VaultError> {
+    VaultError::from_u32(code)
+}
+
+/// Vault error of a `ProgramError::Custom`, e.g. from a CPI into the vault
+pub fn from_program_error(error: &ProgramError) -> Option<VaultError> {
+    match error {
+        ProgramError::Custom(code) => vault_error(*code),
+        _ => None,
+    }
+}
+
+/// Vault error of the `InstructionError` of a failed transaction
+pub fn from_instruction_error(error: &InstructionError) -> Option<VaultError> {
+    match error {
+        InstructionError::Custom(code) => vault_error(*code),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        for error in [
+            VaultError::AlreadyInitialized,
+            VaultError::InsufficientFunds,
+            VaultError::MigrationNotBacked,
+        ] {
+            assert_eq!(from_program_error(&error.into()), Some(error));
+            assert_eq!(from_instruction_error(&InstructionError::Custom(error as u32)), Some(error));
+        }
+        assert_eq!(vault_error(VaultError::MigrationNotBacked as u32 + 1), None);
+        assert_eq!(from_program_error(&ProgramError::InvalidArgument), None);
+    }
+}
@@ -0,0 +1,543 @@
+//! Instruction builders, one per `VaultInstruction` variant. The accounts are
+//! listed in the order of the `Accounts:` docs of the handlers.
+
+use crate::{
+    ata, config_address, program_data_address, registry_address, ticket_address, user_address, vault_address,
+};
+use borsh::to_vec;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_system_interface::program as system_program;
+use spl_token_vault::{
+    escrow::{ESCROW_PROGRAM_ID, escrow_maker_address, offer_address},
+    header::AccountKind,
+    legacy::LegacyLayout,
+    processor::VaultInstruction,
+    state::{ConfigParams, LockupTier},
+};
+
+/// Vault of one mint. Every PDA and token account of the vault and its users
+/// is derived from the program id, the mint and the mint's token program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Vault {
+    pub program_id: Pubkey,
+    pub mint: Pubkey,
+    /// SPL Token or Token-2022, the owner of the mint
+    pub token_program: Pubkey,
+}
+
+impl Vault {
+    pub fn new(program_id: Pubkey, mint: Pubkey, token_program: Pubkey) -> Self {
+        Vault { program_id, mint, token_program }
+    }
+
+    pub fn state(&self) -> Pubkey {
+        vault_address(&self.program_id, &self.mint)
+    }
+
+    pub fn config(&self) -> Pubkey {
+        config_address(&self.program_id, &self.state())
+    }
+
+    pub fn registry(&self) -> Pubkey {
+        registry_address(&self.program_id)
+    }
+
+    /// Holds the upgrade authority of the program
+    pub fn program_data(&self) -> Pubkey {
+        program_data_address(&self.program_id)
+    }
+
+    /// Token account holding the deposits, owned by the vault state
+    pub fn ata(&self) -> Pubkey {
+        ata(&self.state(), &self.mint, &self.token_program)
+    }
+
+    pub fn user_state(&self, user: &Pubkey) -> Pubkey {
+        user_address(&self.program_id, user, &self.mint)
+    }
+
+    /// Token account of the user for the vault's mint
+    pub fn user_ata(&self, user: &Pubkey) -> Pubkey {
+        ata(user, &self.mint, &self.token_program)
+    }
+
+    pub fn ticket(&self, id: u64) -> Pubkey {
+        ticket_address(&self.program_id, &self.state(), id)
+    }
+
+    /// PDA making the escrow offers of the user's position
+    pub fn escrow_maker(&self, user: &Pubkey) -> Pubkey {
+        escrow_maker_address(&self.program_id, &self.user_state(user)).0
+    }
+
+    /// Escrow offer `id` made from the user's position
+    pub fn escrow_offer(&self, user: &Pubkey, id: u64) -> Pubkey {
+        offer_address(&self.escrow_maker(user), id)
+    }
+
+    fn instruction(&self, instruction: VaultInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: to_vec(&instruction).unwrap(),
+        }
+    }
+
+    /// Create the vault state, config and ATA, the payer becomes the admin
+    /// The payer must be the registry admin, or the upgrade authority before there is a registry
+    pub fn initialize_vault(
+        &self,
+        payer: &Pubkey,
+        early_exit_penalty_bps: u16,
+        withdraw_cooldown: i64,
+    ) -> Instruction {
+        self.instruction(
+            VaultInstruction::InitializeVault { early_exit_penalty_bps, withdraw_cooldown },
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new(self.config(), false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.registry(), false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn initialize_user(&self, user: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::InitializeUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        )
+    }
+
+    pub fn deposit(&self, user: &Pubkey, amount: u64, lockup: LockupTier) -> Instruction {
+        self.instruction(VaultInstruction::Deposit { amount, lockup }, self.deposit_accounts(user, user))
+    }
+
+    /// Deposit from the payer's ATA into the position of `owner`
+    pub fn deposit_for(&self, payer: &Pubkey, owner: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::DepositFor { owner: *owner, amount },
+            self.deposit_accounts(payer, owner),
+        )
+    }
+
+    fn deposit_accounts(&self, payer: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(self.user_ata(payer), false),
+            AccountMeta::new(self.ata(), false),
+            AccountMeta::new(self.user_state(owner), false),
+            AccountMeta::new(self.state(), false),
+            AccountMeta::new_readonly(self.config(), false),
+            AccountMeta::new_readonly(self.registry(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ]
+    }
+
+    /// Withdraw from the position of `owner` into the signer's ATA. The signer
+    /// is the owner or its delegate.
+    pub fn withdraw(
+        &self,
+        signer: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+        close_if_empty: bool,
+    ) -> Instruction {
+        self.instruction(
+            VaultInstruction::Withdraw { amount, close_if_empty },
+            self.withdraw_accounts(signer, owner),
+        )
+    }
+
+    pub fn withdraw_early(&self, user: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(VaultInstruction::WithdrawEarly { amount }, self.withdraw_accounts(user, user))
+    }
+
+    fn withdraw_accounts(&self, signer: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*signer, true),
+            AccountMeta::new(self.user_ata(signer), false),
+            AccountMeta::new(self.ata(), false),
+            AccountMeta::new(self.user_state(owner), false),
+            AccountMeta::new(self.state(), false),
+            AccountMeta::new_readonly(self.config(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ]
+    }
+
+    /// Queue a withdrawal. `ticket_id` is the `next_ticket_id` of the vault state.
+    pub fn request_withdraw(&self, user: &Pubkey, ticket_id: u64, amount: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::RequestWithdraw { amount },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    pub fn claim_withdraw(&self, user: &Pubkey, ticket_id: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::ClaimWithdraw,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_ata(user), false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn update_config(&self, admin: &Pubkey, params: ConfigParams) -> Instruction {
+        let mut accounts = self.admin_accounts(admin);
+        // The early exit penalty is kept in the vault state
+        accounts.push(AccountMeta::new(self.state(), false));
+        self.instruction(VaultInstruction::UpdateConfig { params }, accounts)
+    }
+
+    pub fn transfer_admin(&self, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::TransferAdmin { new_admin: *new_admin },
+            self.admin_accounts(admin),
+        )
+    }
+
+    pub fn accept_admin(&self, pending_admin: &Pubkey) -> Instruction {
+        self.instruction(VaultInstruction::AcceptAdmin, self.admin_accounts(pending_admin))
+    }
+
+    fn admin_accounts(&self, admin: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(self.config(), false),
+            AccountMeta::new_readonly(self.mint, false),
+        ]
+    }
+
+    /// Close an empty position, its rent goes back to the user
+    pub fn close_user(&self, user: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::CloseUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    /// Move the user's position out of a legacy account into its user state
+    /// The authority must be the upgrade authority of the program, and the
+    /// vault ATA must already hold the legacy tokens
+    pub fn migrate_user_state(
+        &self,
+        authority: &Pubkey,
+        user: &Pubkey,
+        layout: LegacyLayout,
+    ) -> Instruction {
+        let legacy_state = Pubkey::find_program_address(&[b"state", user.as_ref()], &self.program_id).0;
+        self.instruction(
+            VaultInstruction::MigrateUserState { layout },
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new(*user, false),
+                AccountMeta::new(legacy_state, false),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    /// Rewrite an account of an older program version with the current layout
+    pub fn upgrade_account(&self, payer: &Pubkey, account: &Pubkey, kind: AccountKind) -> Instruction {
+        self.instruction(
+            VaultInstruction::UpgradeAccount { kind },
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(*account, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    /// The payer must be the upgrade authority of the program
+    pub fn initialize_registry(&self, payer: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::InitializeRegistry,
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn register_vault(&self, admin: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::RegisterVault,
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(self.state(), false),
+                AccountMeta::new_readonly(self.ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn unregister_vault(&self, admin: &Pubkey) -> Instruction {
+        self.instruction(
+            VaultInstruction::UnregisterVault,
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    /// Lend to the borrower's ATA. A `flash_repay` must follow in the same transaction.
+    pub fn flash_loan(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::FlashLoan { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(self.user_ata(borrower), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ],
+        )
+    }
+
+    /// Repay the loan, `amount` is the principal plus the fee
+    pub fn flash_repay(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::FlashRepay { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.user_ata(borrower), false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn set_flash_loan_fee(&self, admin: &Pubkey, fee_bps: u16) -> Instruction {
+        self.instruction(
+            VaultInstruction::SetFlashLoanFee { fee_bps },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    /// Allow `delegate` to withdraw from the user's position, `None` for no cap
+    pub fn set_delegate(&self, user: &Pubkey, delegate: &Pubkey, allowance: Option<u64>) -> Instruction {
+        self.instruction(
+            VaultInstruction::SetDelegate { delegate: *delegate, allowance },
+            self.delegate_accounts(user),
+        )
+    }
+
+    pub fn revoke_delegate(&self, user: &Pubkey) -> Instruction {
+        self.instruction(VaultInstruction::RevokeDelegate, self.delegate_accounts(user))
+    }
+
+    fn delegate_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new(self.user_state(user), false),
+            AccountMeta::new_readonly(self.mint, false),
+        ]
+    }
+
+    /// Check the vault ATA covers the deposits, anyone can send it
+    pub fn reconcile(&self, pause_if_insolvent: bool) -> Instruction {
+        let vault_config = if pause_if_insolvent {
+            AccountMeta::new(self.config(), false)
+        } else {
+            AccountMeta::new_readonly(self.config(), false)
+        };
+        self.instruction(
+            VaultInstruction::Reconcile { pause_if_insolvent },
+            vec![
+                AccountMeta::new_readonly(self.state(), false),
+                AccountMeta::new_readonly(self.ata(), false),
+                vault_config,
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    /// Offer `amount` of the user's position on the escrow for `wanted_amount`
+    /// tokens of `mint_b`
+    pub fn make_escrow_offer(
+        &self,
+        user: &Pubkey,
+        mint_b: &Pubkey,
+        id: u64,
+        amount: u64,
+        wanted_amount: u64,
+    ) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = self.escrow_offer(user, id);
+        self.instruction(
+            VaultInstruction::MakeEscrowOffer { id, amount, wanted_amount },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(self.user_ata(&escrow_maker), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.user_ata(&offer), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    /// Take the whole escrow offer `id` of `owner`'s position, the wanted
+    /// tokens go to the owner
+    pub fn take_escrow_offer(&self, taker: &Pubkey, owner: &Pubkey, mint_b: &Pubkey, id: u64) -> Instruction {
+        let escrow_maker = self.escrow_maker(owner);
+        let offer = self.escrow_offer(owner, id);
+        self.instruction(
+            VaultInstruction::TakeEscrowOffer { id },
+            vec![
+                AccountMeta::new(*taker, true),
+                AccountMeta::new(self.user_state(owner), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(*owner, false),
+                AccountMeta::new(self.user_ata(taker), false),
+                AccountMeta::new(ata(taker, mint_b, &self.token_program), false),
+                AccountMeta::new(ata(&escrow_maker, mint_b, &self.token_program), false),
+                AccountMeta::new(ata(owner, mint_b, &self.token_program), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.user_ata(&offer), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    /// Refund the escrow offer `id` back into the user's position
+    pub fn refund_escrow_offer(&self, user: &Pubkey, mint_b: &Pubkey, id: u64) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = self.escrow_offer(user, id);
+        self.instruction(
+            VaultInstruction::RefundEscrowOffer { id },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(self.user_ata(&escrow_maker), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(self.user_ata(&offer), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    /// Pay out `amount` of the collected early exit penalties to the
+    /// `destination` token account
+    pub fn withdraw_treasury(&self, admin: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(
+            VaultInstruction::WithdrawTreasury { amount },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new(self.ata(), false),
+                AccountMeta::new(self.state(), false),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshDeserialize;
+
+    #[test]
+    fn deposit_and_withdraw_share_the_position() {
+        let vault = Vault::new(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (user, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let deposit = vault.deposit(&user, 100, LockupTier::Days30);
+        let withdraw = vault.withdraw(&delegate, &user, 40, false);
+
+        assert_eq!(deposit.accounts[3].pubkey, user_address(&vault.program_id, &user, &vault.mint));
+        assert_eq!(withdraw.accounts[3], deposit.accounts[3]);
+        // The delegate withdraws into its own ATA
+        assert_eq!(withdraw.accounts[1].pubkey, vault.user_ata(&delegate));
+        assert!(matches!(
+            VaultInstruction::try_from_slice(&withdraw.data).unwrap(),
+            VaultInstruction::Withdraw { amount: 40, close_if_empty: false }
+        ));
+    }
+}
@@ -0,0 +1,60 @@
+//! Client of the native token vault, and of its pinocchio port which shares
+//! instructions, accounts and error codes.
+//!
+//! `Vault` builds every `VaultInstruction` with its accounts in the order the
+//! handlers expect. The vault has no fixed address, every helper takes the
+//! program id it's deployed at.
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk_ids::bpf_loader_upgradeable;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+pub use instruction::Vault;
+pub use spl_token_vault::{
+    error::VaultError,
+    header::AccountKind,
+    legacy::LegacyLayout,
+    processor::VaultInstruction,
+    state::{ConfigParams, LockupTier, UserState, VaultConfig, VaultRegistry, VaultState, WithdrawTicket},
+};
+
+/// Vault state of the mint, `[b"vault", mint]`. It also owns the vault ATA.
+pub fn vault_address(program_id: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", mint.as_ref()], program_id).0
+}
+
+/// Position of the user in the vault of the mint, `[b"user", user, mint]`
+pub fn user_address(program_id: &Pubkey, user: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"user", user.as_ref(), mint.as_ref()], program_id).0
+}
+
+/// Admin settings of a vault, `[b"config", vault_state]`
+pub fn config_address(program_id: &Pubkey, vault_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"config", vault_state.as_ref()], program_id).0
+}
+
+/// The registry listing every vault, `[b"registry"]`
+pub fn registry_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"registry"], program_id).0
+}
+
+/// ProgramData account of the program, where the upgradeable loader keeps its
+/// upgrade authority, `[program_id]` of the loader
+pub fn program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Withdrawal ticket of a vault, `[b"ticket", vault_state, id]`
+pub fn ticket_address(program_id: &Pubkey, vault_state: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"ticket", vault_state.as_ref(), &id.to_le_bytes()], program_id)
+        .0
+}
+
+/// Associated token account of the owner, `token_program` is the owner of the mint
+pub fn ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
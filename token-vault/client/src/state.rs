@@ -0,0 +1,97 @@
+//! Fetching and decoding vault accounts. Fetching goes through a closure
+//! returning the account at an address, e.g. `|address| rpc.get_account(address).ok()`
+//! over RPC or the `get_account` of an in-process SVM.
+
+use crate::{UserState, VaultError, user_address};
+use solana_account::Account;
+use solana_program::pubkey::Pubkey;
+use solana_sdk_ids::bpf_loader_upgradeable;
+use spl_token_vault::header::{VaultAccount, decode};
+
+/// Decode an account of the vault program, checking its owner, header and size.
+/// Accounts of an older version fail with `OutdatedAccount`, send an
+/// `UpgradeAccount` first.
+pub fn decode_account<T: VaultAccount>(program_id: &Pubkey, account: &Account) -> Result<T, VaultError> {
+    if account.owner != *program_id {
+        return Err(VaultError::InvalidAccountOwner);
+    }
+    decode(&account.data)
+}
+
+/// Fetch and decode the account at `address`, `None` when it doesn't exist
+pub fn fetch_account<T: VaultAccount>(
+    get_account: impl FnOnce(&Pubkey) -> Option<Account>,
+    program_id: &Pubkey,
+    address: &Pubkey,
+) -> Result<Option<T>, VaultError> {
+    get_account(address)
+        .map(|account| decode_account(program_id, &account))
+        .transpose()
+}
+
+pub fn decode_user_state(program_id: &Pubkey, account: &Account) -> Result<UserState, VaultError> {
+    decode_account(program_id, account)
+}
+
+/// Fetch the position of the user in the vault of the mint, `None` before
+/// `InitializeUser` or after `CloseUser`
+pub fn fetch_user_state(
+    get_account: impl FnOnce(&Pubkey) -> Option<Account>,
+    program_id: &Pubkey,
+    user: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Option<UserState>, VaultError> {
+    fetch_account(get_account, program_id, &user_address(program_id, user, mint))
+}
+
+/// Upgrade authority of a program from its ProgramData account, `None` when
+/// the program is immutable or the account isn't a ProgramData account
+pub fn upgrade_authority(program_data: &Account) -> Option<Pubkey> {
+    // `UpgradeableLoaderState::ProgramData`: the tag 3u32, the slot, then the authority as an Option
+    let data = program_data.data.get(..45)?;
+    if program_data.owner != bpf_loader_upgradeable::id() || data[..4] != 3u32.to_le_bytes() || data[12] != 1 {
+        return None;
+    }
+    Pubkey::try_from(&data[13..]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token_vault::header::{AccountHeader, HEADER_LEN};
+
+    fn user_state_account(program_id: &Pubkey, user: &UserState, version: u8) -> Account {
+        let mut data = vec![0; UserState::SPACE];
+        let header = AccountHeader { discriminator: UserState::DISCRIMINATOR, version, bump: 255 };
+        data[..HEADER_LEN].copy_from_slice(&borsh::to_vec(&header).unwrap());
+        user.pack(&mut data[HEADER_LEN..]).unwrap();
+        Account { lamports: 1, data, owner: *program_id, executable: false, rent_epoch: 0 }
+    }
+
+    #[test]
+    fn user_state_is_decoded_after_checks() {
+        let program_id = Pubkey::new_unique();
+        let (user, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut state = UserState::unpack(&[0; UserState::LEN]).unwrap();
+        state.user = user;
+        state.mint = mint;
+        state.amount = 500.into();
+        let account = user_state_account(&program_id, &state, UserState::VERSION);
+
+        let fetched = fetch_user_state(|_| Some(account.clone()), &program_id, &user, &mint)
+            .unwrap()
+            .unwrap();
+        assert_eq!((fetched.user, u64::from(fetched.amount)), (user, 500));
+        assert!(fetch_user_state(|_| None, &program_id, &user, &mint).unwrap().is_none());
+
+        let outdated = user_state_account(&program_id, &state, UserState::VERSION - 1);
+        assert_eq!(
+            decode_user_state(&program_id, &outdated).unwrap_err(),
+            VaultError::OutdatedAccount
+        );
+        assert_eq!(
+            decode_user_state(&Pubkey::new_unique(), &account).unwrap_err(),
+            VaultError::InvalidAccountOwner
+        );
+    }
+}
@@ -0,0 +1,354 @@
+//! IDL of the native token vault, generated from the shank annotations in its
+//! sources: the `#[account]` attributes of `VaultInstruction`, the structs
+//! deriving `ShankAccount` and the `#[error]` messages of `VaultError`.
+//!
+//! The output has the layout `shank idl` writes, which Solita and Codama read
+//! to generate clients. On top of it every account lists the fields of the
+//! vault's account header before its body, and the discriminator the header
+//! starts with, the way Anchor IDLs carry it.
+//!
+//! Regenerate `spl_token_vault.json` after changing the program with
+//!
+//! ```text
+//! cargo run -p spl-token-vault-idl
+//! ```
+//!
+//! The tests fail while it is out of date.
+
+use anyhow::{Context, Result, bail};
+use heck::ToLowerCamelCase;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use shank_macro_impl::{
+    account::extract_account_structs,
+    error::extract_this_errors,
+    instruction::{InstructionVariantFields, extract_instruction_enums},
+    krate::CrateContext,
+    parsed_enum::ParsedEnum,
+    parsed_struct::ParsedStruct,
+    types::{Composite, Primitive, RustType, TypeKind, Value as RustValue},
+};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use syn::{Attribute, Lit, Meta};
+
+/// Crate of the native vault program
+pub const PROGRAM_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../program");
+
+/// Where the generated IDL is committed
+pub const IDL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/spl_token_vault.json");
+
+/// Fields of the header every vault account starts with, see `header.rs`
+const HEADER_FIELDS: [(&str, &str); 3] = [("discriminator", "[u8; 8]"), ("version", "u8"), ("bump", "u8")];
+
+#[derive(Debug, Serialize)]
+pub struct Idl {
+    pub version: String,
+    pub name: String,
+    pub instructions: Vec<IdlInstruction>,
+    pub accounts: Vec<IdlAccount>,
+    pub types: Vec<IdlTypeDefinition>,
+    pub errors: Vec<IdlError>,
+    pub metadata: IdlMetadata,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    pub accounts: Vec<IdlInstructionAccount>,
+    pub args: Vec<IdlField>,
+    pub discriminant: IdlDiscriminant,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdlInstructionAccount {
+    pub name: String,
+    pub is_mut: bool,
+    pub is_signer: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlDiscriminant {
+    #[serde(rename = "type")]
+    pub ty: Value,
+    pub value: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlAccount {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    pub discriminator: [u8; 8],
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlTypeDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlError {
+    pub code: u32,
+    pub name: String,
+    pub msg: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlMetadata {
+    pub origin: String,
+}
+
+/// IDL of the vault program, named and versioned after its crate
+pub fn vault_idl() -> Result<Idl> {
+    let manifest = std::fs::read_to_string(Path::new(PROGRAM_DIR).join("Cargo.toml"))?;
+    let version = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("version = "))
+        .context("the program's manifest has no version")?
+        .trim_matches('"');
+    generate(Path::new(PROGRAM_DIR), "spl_token_vault", version)
+}
+
+/// Build the IDL of the program crate at `program_root`, the directory of its
+/// `Cargo.toml`
+pub fn generate(program_root: &Path, name: &str, version: &str) -> Result<Idl> {
+    let krate = CrateContext::parse(program_root.join("src/lib.rs"))
+        .with_context(|| format!("can't parse the crate at {}", program_root.display()))?;
+    let mut types = Types::default();
+
+    let [instruction] = &extract_instruction_enums(krate.enums())?[..] else {
+        bail!("expected exactly one enum deriving ShankInstruction");
+    };
+    let instruction_enum = krate
+        .enums()
+        .find(|item| item.ident == instruction.ident)
+        .expect("the instruction enum is one of the crate's enums");
+    let mut instructions = Vec::new();
+    for (variant, item) in instruction.variants.iter().zip(&instruction_enum.variants) {
+        let args = match &variant.field_tys {
+            InstructionVariantFields::Named(fields) => fields
+                .iter()
+                .map(|(name, ty)| Ok(IdlField { name: name.to_lower_camel_case(), ty: types.resolve(ty)? }))
+                .collect::<Result<_>>()?,
+            InstructionVariantFields::Unnamed(fields) if fields.is_empty() => Vec::new(),
+            InstructionVariantFields::Unnamed(_) => {
+                bail!("{}: only unit and struct variants are supported", variant.ident)
+            }
+        };
+        instructions.push(IdlInstruction {
+            name: variant.ident.to_string(),
+            docs: docs(&item.attrs),
+            accounts: variant
+                .accounts
+                .iter()
+                .map(|account| IdlInstructionAccount {
+                    name: account.name.to_lower_camel_case(),
+                    is_mut: account.writable,
+                    is_signer: account.signer,
+                    desc: account.desc.clone(),
+                    optional: account.optional,
+                })
+                .collect(),
+            args,
+            discriminant: IdlDiscriminant { ty: json!("u8"), value: variant.discriminant },
+        });
+    }
+
+    let accounts = extract_account_structs(krate.structs())?
+        .iter()
+        .map(|account| {
+            let name = account.ident.to_string();
+            let mut fields = HEADER_FIELDS
+                .iter()
+                .map(|(field, ty)| {
+                    Ok(IdlField { name: field.to_string(), ty: types.resolve(&parse_type(ty)?)? })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            fields.extend(types.fields(account)?);
+            Ok(IdlAccount {
+                docs: docs(&account.attrs),
+                discriminator: discriminator(&name),
+                ty: json!({ "kind": "struct", "fields": fields }),
+                name,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let errors = extract_this_errors(krate.enums())?
+        .into_iter()
+        .map(|error| IdlError { code: error.code, name: error.name, msg: error.desc })
+        .collect();
+
+    Ok(Idl {
+        version: version.to_string(),
+        name: name.to_string(),
+        instructions,
+        accounts,
+        types: types.define(&krate)?,
+        errors,
+        metadata: IdlMetadata { origin: "shank".to_string() },
+    })
+}
+
+/// The IDL as it's written to disk
+pub fn to_json(idl: &Idl) -> String {
+    serde_json::to_string_pretty(idl).unwrap() + "\n"
+}
+
+/// First 8 bytes of sha256("account:<Name>"), what the header of the account starts with
+pub fn discriminator(account: &str) -> [u8; 8] {
+    Sha256::digest(format!("account:{account}"))[..8].try_into().unwrap()
+}
+
+// Custom types referenced so far, defined once everything is resolved
+#[derive(Default)]
+struct Types {
+    referenced: BTreeMap<String, Option<IdlTypeDefinition>>,
+}
+
+impl Types {
+    // IDL type of a Rust type. spl-pod's little endian integers are encoded
+    // like the integers they wrap.
+    fn resolve(&mut self, ty: &RustType) -> Result<Value> {
+        Ok(match &ty.kind {
+            TypeKind::Primitive(primitive) => json!(primitive_name(primitive)?),
+            TypeKind::Value(RustValue::String | RustValue::Str | RustValue::CString) => json!("string"),
+            TypeKind::Value(RustValue::Custom(name)) => match name.as_str() {
+                "Pubkey" => json!("publicKey"),
+                "PodBool" => json!("bool"),
+                "PodU16" => json!("u16"),
+                "PodU32" => json!("u32"),
+                "PodU64" => json!("u64"),
+                "PodI64" => json!("i64"),
+                "PodU128" => json!("u128"),
+                name => {
+                    self.referenced.entry(name.to_string()).or_default();
+                    json!({ "defined": name })
+                }
+            },
+            TypeKind::Composite(Composite::Vec, inner) => json!({ "vec": self.resolve(&inner[0])? }),
+            TypeKind::Composite(Composite::Option, inner) => json!({ "option": self.resolve(&inner[0])? }),
+            TypeKind::Composite(Composite::Array(len), inner) => {
+                json!({ "array": [self.resolve(&inner[0])?, len] })
+            }
+            TypeKind::Composite(Composite::Tuple, inners) => {
+                json!({ "tuple": inners.iter().map(|inner| self.resolve(inner)).collect::<Result<Vec<_>>>()? })
+            }
+            kind => bail!("{}: unsupported type {kind:?}", ty.ident),
+        })
+    }
+
+    fn fields(&mut self, strct: &ParsedStruct) -> Result<Vec<IdlField>> {
+        strct
+            .fields
+            .iter()
+            .map(|field| {
+                Ok(IdlField {
+                    name: field.ident.to_string().to_lower_camel_case(),
+                    ty: self.resolve(&field.rust_type)?,
+                })
+            })
+            .collect()
+    }
+
+    fn variants(&mut self, enm: &ParsedEnum) -> Result<Vec<Value>> {
+        enm.variants
+            .iter()
+            .map(|variant| {
+                let name = variant.ident.to_string();
+                if variant.fields.is_empty() {
+                    return Ok(json!({ "name": name }));
+                }
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let ty = self.resolve(&field.rust_type)?;
+                        Ok(match &field.ident {
+                            Some(ident) => {
+                                json!({ "name": ident.to_string().to_lower_camel_case(), "type": ty })
+                            }
+                            None => ty,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(json!({ "name": name, "fields": fields }))
+            })
+            .collect()
+    }
+
+    // Define every referenced type from the crate's structs and enums,
+    // including the types their fields reference in turn
+    fn define(mut self, krate: &CrateContext) -> Result<Vec<IdlTypeDefinition>> {
+        while let Some(name) =
+            self.referenced.iter().find(|(_, defined)| defined.is_none()).map(|(name, _)| name.clone())
+        {
+            let ty = if let Some(item) = krate.structs().find(|item| item.ident == name) {
+                json!({ "kind": "struct", "fields": self.fields(&ParsedStruct::try_from(item)?)? })
+            } else if let Some(item) = krate.enums().find(|item| item.ident == name) {
+                json!({ "kind": "enum", "variants": self.variants(&ParsedEnum::try_from(item)?)? })
+            } else {
+                bail!("type {name} is not defined in the crate");
+            };
+            self.referenced.insert(name.clone(), Some(IdlTypeDefinition { name, ty }));
+        }
+        Ok(self.referenced.into_values().flatten().collect())
+    }
+}
+
+fn primitive_name(primitive: &Primitive) -> Result<&'static str> {
+    Ok(match primitive {
+        Primitive::U8 => "u8",
+        Primitive::I8 => "i8",
+        Primitive::U16 => "u16",
+        Primitive::I16 => "i16",
+        Primitive::U32 => "u32",
+        Primitive::I32 => "i32",
+        Primitive::U64 => "u64",
+        Primitive::I64 => "i64",
+        Primitive::U128 => "u128",
+        Primitive::I128 => "i128",
+        Primitive::Bool => "bool",
+        Primitive::USize => bail!("usize has no fixed size on the wire"),
+    })
+}
+
+fn parse_type(ty: &str) -> Result<RustType> {
+    Ok(RustType::try_from(&syn::parse_str::<syn::Type>(ty)?)?)
+}
+
+// Lines of the `///` comments
+fn docs(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(meta)) => match meta.lit {
+                Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,14 @@
+use spl_token_vault_idl::{IDL_PATH, to_json, vault_idl};
+
+fn main() -> anyhow::Result<()> {
+    let idl = vault_idl()?;
+    std::fs::write(IDL_PATH, to_json(&idl))?;
+    println!(
+        "wrote {} instructions, {} accounts, {} types and {} errors to {IDL_PATH}",
+        idl.instructions.len(),
+        idl.accounts.len(),
+        idl.types.len(),
+        idl.errors.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use solana_program::pubkey::Pubkey;
+use spl_token_vault::{
+    header::{AccountKind, VaultAccount},
+    legacy::LegacyLayout,
+    state::{ConfigParams, LockupTier, UserState, VaultConfig, VaultRegistry, VaultState, WithdrawTicket},
+};
+use spl_token_vault_idl::{IDL_PATH, to_json, vault_idl};
+use vault_client::{Vault, error::vault_error};
+
+#[test]
+fn committed_idl_is_up_to_date() {
+    let generated = to_json(&vault_idl().unwrap());
+    let committed = std::fs::read_to_string(IDL_PATH).unwrap();
+    assert!(
+        generated == committed,
+        "{IDL_PATH} is out of date, regenerate it with `cargo run -p spl-token-vault-idl`"
+    );
+}
+
+// The builders of the client pass the accounts the IDL lists, in its order.
+// They may pass more of them writable, e.g. the fee payer.
+#[test]
+fn accounts_match_the_client() {
+    let idl = vault_idl().unwrap();
+    let vault = Vault::new(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (user, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let params = ConfigParams {
+        user_deposit_cap: 0,
+        tvl_cap: 0,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: 0,
+        user_window_limit: 0,
+        vault_window_limit: 0,
+        early_exit_penalty_bps: 0,
+    };
+    let instructions = [
+        vault.initialize_vault(&user, 0, 0),
+        vault.initialize_user(&user),
+        vault.deposit(&user, 1, LockupTier::None),
+        vault.withdraw(&user, &user, 1, false),
+        vault.withdraw_early(&user, 1),
+        vault.request_withdraw(&user, 0, 1),
+        vault.claim_withdraw(&user, 0),
+        vault.update_config(&user, params),
+        vault.transfer_admin(&user, &other),
+        vault.accept_admin(&user),
+        vault.close_user(&user),
+        vault.migrate_user_state(&user, &other, LegacyLayout::ProgramState),
+        vault.upgrade_account(&user, &other, AccountKind::UserState),
+        vault.initialize_registry(&user),
+        vault.register_vault(&user),
+        vault.unregister_vault(&user),
+        vault.flash_loan(&user, 1),
+        vault.flash_repay(&user, 1),
+        vault.set_flash_loan_fee(&user, 1),
+        vault.deposit_for(&user, &other, 1),
+        vault.set_delegate(&user, &other, None),
+        vault.revoke_delegate(&user),
+        vault.reconcile(true),
+        vault.make_escrow_offer(&user, &other, 0, 1, 1),
+        vault.take_escrow_offer(&other, &user, &other, 0),
+        vault.refund_escrow_offer(&user, &other, 0),
+        vault.withdraw_treasury(&user, &other, 1),
+    ];
+    assert_eq!(instructions.len(), idl.instructions.len());
+
+    for (instruction, described) in instructions.iter().zip(&idl.instructions) {
+        let name = &described.name;
+        assert_eq!(usize::from(instruction.data[0]), described.discriminant.value, "{name}");
+        assert_eq!(instruction.accounts.len(), described.accounts.len(), "{name}");
+        for (meta, account) in instruction.accounts.iter().zip(&described.accounts) {
+            assert_eq!(meta.is_signer, account.is_signer, "{name} {}", account.name);
+            assert!(meta.is_writable || !account.is_mut, "{name} {} must be writable", account.name);
+        }
+    }
+}
+
+#[test]
+fn accounts_and_errors_match_the_program() {
+    let idl = vault_idl().unwrap();
+    let discriminators = [
+        ("VaultState", VaultState::DISCRIMINATOR),
+        ("VaultConfig", VaultConfig::DISCRIMINATOR),
+        ("VaultRegistry", VaultRegistry::DISCRIMINATOR),
+        ("UserState", UserState::DISCRIMINATOR),
+        ("WithdrawTicket", WithdrawTicket::DISCRIMINATOR),
+    ];
+    let described: Vec<_> =
+        idl.accounts.iter().map(|account| (account.name.as_str(), account.discriminator)).collect();
+    assert_eq!(described, discriminators);
+
+    for error in &idl.errors {
+        let named = vault_error(error.code).map(|error| (format!("{error:?}"), error.to_string()));
+        assert_eq!(named, Some((error.name.clone(), error.msg.clone())), "code {}", error.code);
+    }
+    assert_eq!(vault_error(idl.errors.len() as u32), None, "every error is in the IDL");
+}
@@ -1,7 +1,8 @@
+use num_derive::FromPrimitive;
 use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
 pub enum VaultError {
     #[error("Vault is already initialized")]
     AlreadyInitialized,
@@ -27,6 +28,112 @@ pub enum VaultError {
     NotSigner,
     #[error("General failure")]
     GenericError,
+    #[error("Account is not writable")]
+    NotWritable,
+    #[error("Invalid user state account data")]
+    InvalidUserState,
+    #[error("Failed to serialize account data")]
+    SerializationError,
+    #[error("Invalid Vault state account")]
+    InvalidVaultState,
+    #[error("Invalid Vault token account")]
+    InvalidVaultTokenAccount,
+    #[error("Amount must be greater than zero")]
+    InvalidAmount,
+    #[error("Position is still locked")]
+    PositionLocked,
+    #[error("Penalty must not exceed 10000 basis points")]
+    InvalidPenalty,
+    #[error("Vault only allows withdrawals through a withdrawal request")]
+    InstantWithdrawDisabled,
+    #[error("Invalid withdrawal ticket")]
+    InvalidTicket,
+    #[error("Withdrawal ticket is still cooling down")]
+    CooldownActive,
+    #[error("Not enough liquidity in the vault for this ticket")]
+    InsufficientLiquidity,
+    #[error("Cooldown must not be negative")]
+    InvalidCooldown,
+    #[error("Invalid Vault config account")]
+    InvalidVaultConfig,
+    #[error("Signer is not the vault admin")]
+    Unauthorized,
+    #[error("No admin transfer is pending for this signer")]
+    NoPendingAdmin,
+    #[error("Deposits are paused")]
+    DepositPaused,
+    #[error("Withdrawals are paused")]
+    WithdrawPaused,
+    #[error("Amount is below the vault minimum")]
+    BelowMinimum,
+    #[error("Deposit exceeds the per user cap")]
+    UserCapExceeded,
+    #[error("Deposit exceeds the vault TVL cap")]
+    TvlCapExceeded,
+    #[error("User state still holds a balance")]
+    UserStateNotEmpty,
+    #[error("Token program must be SPL Token or Token-2022")]
+    InvalidTokenProgram,
+    #[error("Mint has an extension the vault can't custody")]
+    UnsupportedMint,
+    #[error("Invalid legacy user account")]
+    InvalidLegacyAccount,
+    #[error("Account is not owned by the vault program")]
+    InvalidAccountOwner,
+    #[error("Account data has the wrong size")]
+    InvalidAccountSize,
+    #[error("Account discriminator does not match the expected type")]
+    InvalidDiscriminator,
+    #[error("Account was written by an older version, upgrade it first")]
+    OutdatedAccount,
+    #[error("Account version is newer than this program supports")]
+    UnsupportedAccountVersion,
+    #[error("Account already has the current version")]
+    AccountUpToDate,
+    #[error("Invalid vault registry account")]
+    InvalidRegistry,
+    #[error("Mint has no registered vault")]
+    MintNotRegistered,
+    #[error("Mint already has a registered vault")]
+    MintAlreadyRegistered,
+    #[error("Vault registry is full")]
+    RegistryFull,
+    #[error("Flash loans are disabled for this vault")]
+    FlashLoansDisabled,
+    #[error("A flash loan is open on this vault")]
+    FlashLoanInProgress,
+    #[error("Flash loan is not followed by a matching flash repay")]
+    MissingFlashRepay,
+    #[error("Flash repay does not match the open flash loan")]
+    InvalidFlashRepay,
+    #[error("Flash loans can't be taken through a CPI")]
+    FlashLoanViaCpi,
+    #[error("Fee exceeds 100%")]
+    InvalidFee,
+    #[error("Vault has no deposits to share the fee")]
+    NoDepositors,
+    #[error("Delegate can't be the default key or the user")]
+    InvalidDelegate,
+    #[error("Withdrawal exceeds the delegate's allowance")]
+    DelegateAllowanceExceeded,
+    #[error("Rate limit window must not be negative")]
+    InvalidRateLimitWindow,
+    #[error("Withdrawal exceeds the user's limit for the current window")]
+    UserRateLimitExceeded,
+    #[error("Withdrawal exceeds the vault's limit for the current window")]
+    VaultRateLimitExceeded,
+    #[error("Invalid escrow program")]
+    InvalidEscrowProgram,
+    #[error("Invalid escrow offer")]
+    InvalidOffer,
+    #[error("Invalid escrow maker account")]
+    InvalidEscrowMaker,
+    #[error("Invalid program data account of the vault program")]
+    InvalidProgramData,
+    #[error("Signer is not the upgrade authority of the vault program")]
+    NotUpgradeAuthority,
+    #[error("Vault token account doesn't hold the migrated balance")]
+    MigrationNotBacked,
 }
 
 impl From<VaultError> for ProgramError {
@@ -0,0 +1,30 @@
+//! Interface of the token escrow program in `token-escrow`, which vault users
+//! can post offers to straight from their position.
+//!
+//! The escrow needs the maker to sign its instructions, so offers are made by
+//! an escrow maker PDA of the user state. It holds no data and is only funded
+//! for the duration of an instruction.
+//!
+//! The escrow has no partial fills, `take_offer` pays out the whole offered
+//! amount and closes the offer. A take therefore settles everything the offer
+//! earmarks and a refund returns all of it to the position, nothing is left
+//! over to flow back. To sell part of an offer, refund it and post a smaller
+//! one.
+
+use crate::error::VaultError;
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+
+pub use escrow_client::{
+    ID as ESCROW_PROGRAM_ID, Offer, offer_address,
+    instruction::{make_offer, refund_offer, take_offer},
+};
+
+// Read an offer account of the escrow program
+pub fn load_offer(offer: &AccountInfo) -> Result<Offer, VaultError> {
+    Offer::load(&ESCROW_PROGRAM_ID, offer).map_err(|_| VaultError::InvalidOffer)
+}
+
+// PDA making the escrow offers of a user state
+pub fn escrow_maker_address(program_id: &Pubkey, user_state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"escrow_maker", user_state.as_ref()], program_id)
+}
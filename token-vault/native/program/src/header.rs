@@ -0,0 +1,235 @@
+//! Header every account of the vault program starts with, followed by the
+//! body of the account type.
+//!
+//! Layout: `[discriminator: 8][version: 1][bump: 1][body]`
+//!
+//! Bodies are either borsh encoded or, for the accounts touched by every
+//! deposit and withdrawal, `#[repr(C)]` Pod structs which are read in place.
+
+use crate::error::VaultError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::Pod;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+    sysvar::{Sysvar, rent::Rent},
+};
+use solana_system_interface::instruction;
+use std::cell::{Ref, RefMut};
+
+// Size of the header
+pub const HEADER_LEN: usize = 8 + 1 + 1;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountHeader {
+    // First 8 bytes of sha256("account:<TypeName>")
+    pub discriminator: [u8; 8],
+    // Layout version of the body
+    pub version: u8,
+    // Bump of the PDA holding the account
+    pub bump: u8,
+}
+
+/// Account types which can be passed to `UpgradeAccount`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKind {
+    VaultState,
+    VaultConfig,
+    UserState,
+    WithdrawTicket,
+}
+
+/// Implemented by every account type stored behind an `AccountHeader`
+pub trait VaultAccount: Sized {
+    const DISCRIMINATOR: [u8; 8];
+    // Version written by this build of the program
+    const VERSION: u8;
+    // Size of the body of the current version
+    const LEN: usize;
+    // Size of the account, header included
+    const SPACE: usize = HEADER_LEN + Self::LEN;
+
+    /// Decode the body of the current version
+    fn unpack(body: &[u8]) -> Result<Self, VaultError>;
+
+    /// Encode into the body of the current version
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError>;
+
+    /// Decode the data of an account written by an older version. `None` is
+    /// for accounts created before headers existed, `data` is then the whole
+    /// account, otherwise only the body behind the header.
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError>;
+
+    /// PDA the account is stored under and its bump
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8);
+
+    /// Find the bumps of other PDAs the account stores, for accounts decoded
+    /// from a version which didn't store them yet
+    fn find_bumps(&mut self, _program_id: &Pubkey, _address: &Pubkey) {}
+}
+
+// Check the owner and read the header of a program account
+pub fn read_header(program_id: &Pubkey, account: &AccountInfo) -> Result<AccountHeader, VaultError> {
+    if account.owner != program_id {
+        return Err(VaultError::InvalidAccountOwner);
+    }
+    parse_header(&account.data.borrow())
+}
+
+// Read the header at the start of account data
+pub fn parse_header(data: &[u8]) -> Result<AccountHeader, VaultError> {
+    if data.len() < HEADER_LEN {
+        return Err(VaultError::InvalidAccountSize);
+    }
+    AccountHeader::try_from_slice(&data[..HEADER_LEN]).map_err(|_| VaultError::InvalidAccountSize)
+}
+
+// Check the owner, size, discriminator and version of an account
+pub fn check<T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<AccountHeader, VaultError> {
+    if account.owner != program_id {
+        return Err(VaultError::InvalidAccountOwner);
+    }
+    check_data::<T>(&account.data.borrow())
+}
+
+// Check the size, discriminator and version of account data, the owner is up
+// to the caller. Clients use it on fetched accounts.
+pub fn check_data<T: VaultAccount>(data: &[u8]) -> Result<AccountHeader, VaultError> {
+    let header = parse_header(data)?;
+    if header.discriminator != T::DISCRIMINATOR {
+        return Err(VaultError::InvalidDiscriminator);
+    }
+    if header.version < T::VERSION {
+        return Err(VaultError::OutdatedAccount);
+    }
+    if header.version > T::VERSION {
+        return Err(VaultError::UnsupportedAccountVersion);
+    }
+    if data.len() != T::SPACE {
+        return Err(VaultError::InvalidAccountSize);
+    }
+    Ok(header)
+}
+
+// Decode the body of checked account data
+pub fn decode<T: VaultAccount>(data: &[u8]) -> Result<T, VaultError> {
+    check_data::<T>(data)?;
+    T::unpack(&data[HEADER_LEN..])
+}
+
+// Load a copy of an account after checking it
+pub fn load<T: VaultAccount>(program_id: &Pubkey, account: &AccountInfo) -> Result<T, VaultError> {
+    check::<T>(program_id, account)?;
+    T::unpack(&account.data.borrow()[HEADER_LEN..])
+}
+
+// Write the body of a loaded account back, the header stays untouched
+pub fn store<T: VaultAccount>(account: &AccountInfo, body: &T) -> Result<(), VaultError> {
+    if account.data_len() != T::SPACE {
+        return Err(VaultError::InvalidAccountSize);
+    }
+    body.pack(&mut account.data.borrow_mut()[HEADER_LEN..])
+}
+
+// Read only view into the body of a checked Pod account, nothing is copied.
+// The view holds a borrow of the account data, drop it before a CPI which
+// writes to the account.
+pub fn load_ref<'a, T: VaultAccount + Pod>(
+    program_id: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<Ref<'a, T>, VaultError> {
+    check::<T>(program_id, account)?;
+    Ok(Ref::map(account.data.borrow(), |data| bytemuck::from_bytes(&data[HEADER_LEN..])))
+}
+
+// Mutable view into the body of a checked Pod account, writes go straight
+// to the account data. Drop it before any CPI the account is passed to.
+pub fn load_mut<'a, T: VaultAccount + Pod>(
+    program_id: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<RefMut<'a, T>, VaultError> {
+    check::<T>(program_id, account)?;
+    Ok(RefMut::map(account.data.borrow_mut(), |data| {
+        bytemuck::from_bytes_mut(&mut data[HEADER_LEN..])
+    }))
+}
+
+// Write the header and body of a freshly created account
+pub fn init<T: VaultAccount>(account: &AccountInfo, bump: u8, body: &T) -> Result<(), VaultError> {
+    init_header::<T>(account, bump)?;
+    body.pack(&mut account.data.borrow_mut()[HEADER_LEN..])
+}
+
+// Write only the header of a freshly created account, the body stays zeroed.
+// Used for Pod accounts too large to build on the stack.
+pub fn init_header<T: VaultAccount>(account: &AccountInfo, bump: u8) -> Result<(), VaultError> {
+    if account.data_len() != T::SPACE {
+        return Err(VaultError::InvalidAccountSize);
+    }
+    let header = AccountHeader { discriminator: T::DISCRIMINATOR, version: T::VERSION, bump };
+    header
+        .serialize(&mut &mut account.data.borrow_mut()[..HEADER_LEN])
+        .map_err(|_| VaultError::SerializationError)
+}
+
+// Rewrite an account of an older version with the current layout. The account
+// is resized in place and the payer tops up the rent when it grows.
+pub fn upgrade<'a, T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_prog: &AccountInfo<'a>,
+) -> ProgramResult {
+    if account.owner != program_id {
+        return Err(VaultError::InvalidAccountOwner.into());
+    }
+
+    // Accounts without our discriminator are from before headers existed
+    let mut body = {
+        let data = account.data.borrow();
+        let header = match data.get(..HEADER_LEN).map(AccountHeader::try_from_slice) {
+            Some(Ok(header)) if header.discriminator == T::DISCRIMINATOR => Some(header),
+            _ => None,
+        };
+        match header {
+            Some(header) if header.version == T::VERSION => {
+                return Err(VaultError::AccountUpToDate.into());
+            }
+            Some(header) if header.version > T::VERSION => {
+                return Err(VaultError::UnsupportedAccountVersion.into());
+            }
+            Some(header) => {
+                msg!("Upgrading account from version {} to {}", header.version, T::VERSION);
+                T::from_version(Some(header.version), &data[HEADER_LEN..])?
+            }
+            None => {
+                msg!("Upgrading unversioned account to version {}", T::VERSION);
+                T::from_version(None, &data)?
+            }
+        }
+    };
+
+    // The address ties the decoded data to the account type
+    let (address, bump) = body.find_address(program_id);
+    if address != *account.key {
+        return Err(VaultError::InvalidDiscriminator.into());
+    }
+    body.find_bumps(program_id, account.key);
+
+    let lamports = Rent::get()?.minimum_balance(T::SPACE);
+    if account.lamports() < lamports {
+        invoke(
+            &instruction::transfer(payer.key, account.key, lamports - account.lamports()),
+            &[payer.clone(), account.clone(), system_prog.clone()],
+        )?;
+    }
+    account.resize(T::SPACE)?;
+    init(account, bump, &body)?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use super::shared::load_vault_config;
+use crate::error::VaultError;
+use crate::header::store;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Second step of an admin transfer, the proposed admin accepts the role.
+///
+/// Accounts:
+/// [signer pending_admin]
+/// [writable vault_config]
+/// [readonly token_mint]
+pub fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pending_admin = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !pending_admin.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_config.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let (vault_pda, _vault_bump) =
+        Pubkey::find_program_address(&[b"vault", token_mint.key.as_ref()], program_id);
+    let mut vault_config_data = load_vault_config(program_id, vault_config, &vault_pda)?;
+    if vault_config_data.pending_admin == Pubkey::default()
+        || vault_config_data.pending_admin != *pending_admin.key
+    {
+        return Err(VaultError::NoPendingAdmin.into());
+    }
+
+    msg!("{} is now the vault admin", pending_admin.key);
+    vault_config_data.admin = *pending_admin.key;
+    vault_config_data.pending_admin = Pubkey::default();
+    store(vault_config, &vault_config_data)?;
+
+    Ok(())
+}
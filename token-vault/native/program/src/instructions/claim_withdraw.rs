@@ -0,0 +1,124 @@
+use super::shared::{
+    check_vault_ata, close_account, is_pda, load_vault_config, load_vault_state,
+    load_vault_state_mut, token_account_amount, transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::header::{load, read_header};
+use crate::state::WithdrawTicket;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+
+/// Second step of a withdrawal, pays out a ticket once its cooldown has passed
+/// and closes it. When the vault is short on liquidity tickets are served in
+/// the order they were requested.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_ata]
+/// [writable vault_ata]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [writable ticket]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn claim_withdraw(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_ata = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let ticket = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_ata.is_writable
+        || !vault_ata.is_writable
+        || !vault_state.is_writable
+        || !ticket.is_writable
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    load_vault_state(program_id, vault_state, token_mint.key)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    if load_vault_config(program_id, vault_config, vault_state.key)?.withdraw_paused {
+        return Err(VaultError::WithdrawPaused.into());
+    }
+
+    // Verify and load the ticket
+    let ticket_bump = read_header(program_id, ticket)?.bump;
+    let ticket_data = load::<WithdrawTicket>(program_id, ticket)?;
+    if !is_pda(
+        program_id,
+        ticket.key,
+        &[b"ticket", vault_state.key.as_ref(), &ticket_data.id.to_le_bytes()],
+        ticket_bump,
+    ) {
+        return Err(VaultError::InvalidTicket.into());
+    }
+    if ticket_data.owner != *payer.key {
+        return Err(VaultError::InvalidOwner.into());
+    }
+
+    if Clock::get()?.unix_timestamp < ticket_data.claimable_after {
+        msg!("Ticket can be claimed after {}", ticket_data.claimable_after);
+        return Err(VaultError::CooldownActive.into());
+    }
+
+    // Tickets are funded in the order they were requested, from the liquidity
+    // which isn't set aside for earlier tickets yet, so a later ticket can't
+    // jump the queue while the vault is short on liquidity. Funded tickets
+    // can be claimed in any order.
+    let vault_balance = token_account_amount(vault_ata)?;
+    {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+        // Lent out tokens are not in the vault ATA until the loan is repaid
+        if u64::from(vault_state_data.flash_loan_amount) != 0 {
+            return Err(VaultError::FlashLoanInProgress.into());
+        }
+        let liquidity = vault_balance.saturating_sub(vault_state_data.treasury.into());
+        let funded_total = vault_state_data.fund_tickets(liquidity);
+        // Tokens set aside can have left the vault since, the treasury is never paid out
+        if ticket_data.queue_end > funded_total || ticket_data.amount > liquidity {
+            return Err(VaultError::InsufficientLiquidity.into());
+        }
+    }
+
+    // Pay out the ticket
+    msg!("Claiming withdrawal ticket {}", ticket_data.id);
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        user_ata,
+        vault_state,
+        token_mint,
+        token_prog,
+        ticket_data.amount,
+    )?;
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    vault_state_data.claimed_total = u64::from(vault_state_data.claimed_total)
+        .checked_add(ticket_data.amount)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    drop(vault_state_data);
+
+    // Close the ticket and return the rent to the user
+    close_account(ticket, payer)?;
+    msg!("Withdrawal ticket claimed");
+
+    Ok(())
+}
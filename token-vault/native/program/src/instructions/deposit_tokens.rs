@@ -1,26 +1,62 @@
+use super::shared::{
+    check_vault_ata, load_registry, load_user_state, load_vault_config, load_vault_state_mut,
+    mint_decimals, token_account_amount,
+};
 use crate::error::VaultError;
-use crate::state::UserState;
+use crate::state::LockupTier;
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::{invoke},
-    pubkey::Pubkey};
-use borsh::{BorshDeserialize, BorshSerialize};
-use spl_token::{instruction as token_instruction};
+    program::invoke,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token_2022::instruction as token_instruction;
 
 
+/// Deposit into the payer's own position and lock it for the chosen tier.
+///
 /// Accounts:
 /// [signer payer]
 /// [writable user_ata]
 /// [writable vault_ata]
 /// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly registry]
 /// [readonly token_mint]
 /// [readonly token program]
 pub fn deposit_tokens(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     deposit_amount: u64,
+    lockup: LockupTier,
+) -> ProgramResult {
+    deposit(program_id, accounts, None, deposit_amount, Some(lockup))
+}
+
+/// Deposit from the payer's tokens into the position of `owner`, e.g. from a
+/// hot key into the position of a cold key. The lock of the position is left
+/// as it is. Same accounts as `deposit_tokens`, with the user state of `owner`.
+pub fn deposit_for(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    owner: Pubkey,
+    deposit_amount: u64,
+) -> ProgramResult {
+    deposit(program_id, accounts, Some(&owner), deposit_amount, None)
+}
+
+// Tokens come from the payer's ATA and are credited to the position of `owner`,
+// the payer's own position when not set
+fn deposit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    owner: Option<&Pubkey>,
+    deposit_amount: u64,
+    lockup: Option<LockupTier>,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
@@ -28,6 +64,9 @@ pub fn deposit_tokens(
     let user_ata = next_account_info(account_info_iter)?;
     let vault_ata = next_account_info(account_info_iter)?;
     let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let registry = next_account_info(account_info_iter)?;
     let token_mint = next_account_info(account_info_iter)?;
     let token_prog = next_account_info(account_info_iter)?;
 
@@ -35,49 +74,115 @@ pub fn deposit_tokens(
     if !payer.is_signer {
         return Err(VaultError::NotSigner.into());
     }
-    if !user_state.is_writable || !user_ata.is_writable || !vault_ata.is_writable {
+    if !user_state.is_writable
+        || !user_ata.is_writable
+        || !vault_ata.is_writable
+        || !vault_state.is_writable
+    {
         return Err(VaultError::NotWritable.into());
     }
+    if deposit_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // Verify and load the user's state PDA
+    let owner = owner.unwrap_or(payer.key);
+    let mut user_state_data = load_user_state(program_id, user_state, owner, token_mint.key)?;
+
+    // Tokens must land in the vault ATA, otherwise the user would be credited for nothing
+    let decimals = mint_decimals(token_mint, token_prog)?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+
+    // Only mints the registry admin listed can be deposited
+    {
+        let registry_data = load_registry(program_id, registry)?;
+        let entry = registry_data
+            .find(token_mint.key)
+            .ok_or(VaultError::MintNotRegistered)?;
+        if entry.vault_state != *vault_state.key || entry.vault_ata != *vault_ata.key {
+            return Err(VaultError::InvalidRegistry.into());
+        }
+    }
+    let vault_balance = token_account_amount(vault_ata)?;
 
-    // Derive a PDA for user's state
-    let (state_pda, state_bump) = Pubkey::find_program_address(
-        &[b"user", payer.key.as_ref(), token_mint.key.as_ref()],
-        program_id,
-    );
+    // Fees earned so far count towards the user's cap
+    vault_state_data.settle_fees(&mut user_state_data)?;
+
+    // Enforce the limits set by the vault admin
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key)?;
+    if vault_config_data.deposit_paused {
+        return Err(VaultError::DepositPaused.into());
+    }
+    if deposit_amount < vault_config_data.min_deposit {
+        return Err(VaultError::BelowMinimum.into());
+    }
+    let user_total = u64::from(user_state_data.amount)
+        .checked_add(deposit_amount)
+        .ok_or(VaultError::Overflow)?;
+    if user_total > vault_config_data.user_deposit_cap {
+        return Err(VaultError::UserCapExceeded.into());
+    }
+    let vault_total = vault_balance
+        .checked_add(deposit_amount)
+        .ok_or(VaultError::Overflow)?;
+    if vault_total > vault_config_data.tvl_cap {
+        return Err(VaultError::TvlCapExceeded.into());
+    }
 
     // Transfer tokens from user ATA to vault ATA
     msg!("Transferring tokens from user ATA to vault ATA");
     // Create the transfer instruction
-    let transfer_ix = token_instruction::transfer(
+    let transfer_ix = token_instruction::transfer_checked(
         token_prog.key,
         user_ata.key,
+        token_mint.key,
         vault_ata.key,
         payer.key,
-        &[payer.key],
-        deposit_amount, // Amount to transfer, this should be parsed from instruction_data
+        &[],
+        deposit_amount,
+        decimals,
     )?;
 
     invoke(
         &transfer_ix,
         &[
             user_ata.clone(),
+            token_mint.clone(),
             vault_ata.clone(),
             payer.clone(),
-            token_mint.clone(),
             token_prog.clone(),
         ],
     )?;
 
+    // Mints with a transfer fee deliver less than what was sent, only credit what the vault received
+    let received = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+
     // Update user state
     msg!("Updating user state");
-    // Deserialize the user state
-    let mut user_state_data = UserState::try_from_slice(&user_state.data.borrow())
-        .map_err(|_| VaultError::InvalidUserState)?;
     // Update the user state with the new deposit amount
-    user_state_data.amount += deposit_amount;
-    // Serialize the updated user state back to the account
-    user_state_data.serialize(&mut &mut user_state.data.borrow_mut()[..])
-        .map_err(|_| VaultError::SerializationError)?;
+    user_state_data.amount = u64::from(user_state_data.amount)
+        .checked_add(received)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    vault_state_data.add_deposits(received)?;
+
+    // A deposit can only extend the lock of the position, never shorten it
+    if let Some(lockup) = lockup {
+        let now = Clock::get()?.unix_timestamp;
+        let was_locked = user_state_data.is_locked(now);
+        let unlock_ts = now.checked_add(lockup.duration()).ok_or(VaultError::Overflow)?;
+        if unlock_ts > i64::from(user_state_data.unlock_ts) {
+            user_state_data.unlock_ts = unlock_ts.into();
+        }
+        if !was_locked || lockup.boost_bps() > u16::from(user_state_data.boost_bps) {
+            user_state_data.boost_bps = lockup.boost_bps().into();
+        }
+    }
+
+    // The user state is updated in place, nothing to serialize
     msg!("Deposit successful!");
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,128 @@
+use super::shared::{
+    check_vault_ata, load_vault_config, load_vault_state_mut, transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::processor::VaultInstruction;
+use borsh::BorshDeserialize;
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    instruction::{TRANSACTION_LEVEL_STACK_HEIGHT, get_stack_height},
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Lend `amount` from the vault ATA for the rest of the transaction. A
+/// `FlashRepay` of the same amount for this vault must follow later in the
+/// transaction, it pays back the principal and the fee, which goes to the
+/// depositors.
+///
+/// Accounts:
+/// [signer borrower]
+/// [writable vault_ata]
+/// [writable borrower_ata]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly token_mint]
+/// [readonly token program]
+/// [readonly instructions sysvar]
+pub fn flash_loan(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let borrower = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let borrower_ata = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let instructions_sysvar = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !borrower.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_ata.is_writable || !borrower_ata.is_writable || !vault_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    if amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // The repayment is looked up among the instructions of the transaction, a
+    // program calling in through a CPI could skip it once it has the tokens
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Err(VaultError::FlashLoanViaCpi.into());
+    }
+
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    if load_vault_config(program_id, vault_config, vault_state.key)?.withdraw_paused {
+        return Err(VaultError::WithdrawPaused.into());
+    }
+
+    // The vault state signs the transfer, so its view is dropped before it
+    {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+        if u16::from(vault_state_data.flash_fee_bps) == 0 {
+            return Err(VaultError::FlashLoansDisabled.into());
+        }
+        if u64::from(vault_state_data.flash_loan_amount) != 0 {
+            return Err(VaultError::FlashLoanInProgress.into());
+        }
+        if u64::from(vault_state_data.total_deposits) == 0 {
+            return Err(VaultError::NoDepositors.into());
+        }
+        find_flash_repay(program_id, instructions_sysvar, vault_ata.key, vault_state.key, amount)?;
+        vault_state_data.flash_loan_amount = amount.into();
+    }
+
+    msg!("Lending {} tokens", amount);
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        borrower_ata,
+        vault_state,
+        token_mint,
+        token_prog,
+        amount,
+    )
+}
+
+// Find the first FlashRepay for this vault after the current instruction, it
+// must repay exactly the amount lent
+fn find_flash_repay(
+    program_id: &Pubkey,
+    instructions_sysvar: &AccountInfo,
+    vault_ata: &Pubkey,
+    vault_state: &Pubkey,
+    amount: u64,
+) -> ProgramResult {
+    let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+
+    // Loading past the last instruction fails, which ends the search
+    let mut index = current_index + 1;
+    while let Ok(instruction) = load_instruction_at_checked(index, instructions_sysvar) {
+        index += 1;
+        if instruction.program_id != *program_id {
+            continue;
+        }
+        let Ok(VaultInstruction::FlashRepay { amount: repaid }) =
+            VaultInstruction::try_from_slice(&instruction.data)
+        else {
+            continue;
+        };
+        // FlashRepay accounts: borrower, borrower_ata, vault_ata, vault_state, ...
+        let for_this_vault = instruction.accounts.get(2).is_some_and(|meta| meta.pubkey == *vault_ata)
+            && instruction.accounts.get(3).is_some_and(|meta| meta.pubkey == *vault_state);
+        if !for_this_vault {
+            continue;
+        }
+        if repaid != amount {
+            return Err(VaultError::InvalidFlashRepay.into());
+        }
+        return Ok(());
+    }
+    Err(VaultError::MissingFlashRepay.into())
+}
@@ -0,0 +1,92 @@
+use super::shared::{
+    check_vault_ata, load_vault_state, load_vault_state_mut, mint_decimals, token_account_amount,
+};
+use crate::error::VaultError;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+};
+use spl_token_2022::instruction as token_instruction;
+
+
+/// Pay back the open flash loan of the vault plus its fee. The fee is shared
+/// between the depositors in proportion to their positions.
+///
+/// Accounts:
+/// [signer borrower]
+/// [writable borrower_ata]
+/// [writable vault_ata]
+/// [writable vault_state]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn flash_repay(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let borrower = next_account_info(account_info_iter)?;
+    let borrower_ata = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !borrower.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !borrower_ata.is_writable || !vault_ata.is_writable || !vault_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let decimals = mint_decimals(token_mint, token_prog)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    let fee = {
+        let vault_state_data = load_vault_state(program_id, vault_state, token_mint.key)?;
+        if amount == 0 || u64::from(vault_state_data.flash_loan_amount) != amount {
+            return Err(VaultError::InvalidFlashRepay.into());
+        }
+        vault_state_data.flash_fee(amount)?
+    };
+    let repayment = amount.checked_add(fee).ok_or(VaultError::Overflow)?;
+
+    // Transfer the principal and the fee from the borrower ATA to the vault ATA
+    msg!("Repaying {} tokens with a fee of {}", amount, fee);
+    let vault_balance = token_account_amount(vault_ata)?;
+    let transfer_ix = token_instruction::transfer_checked(
+        token_prog.key,
+        borrower_ata.key,
+        token_mint.key,
+        vault_ata.key,
+        borrower.key,
+        &[],
+        repayment,
+        decimals,
+    )?;
+    invoke(
+        &transfer_ix,
+        &[
+            borrower_ata.clone(),
+            token_mint.clone(),
+            vault_ata.clone(),
+            borrower.clone(),
+            token_prog.clone(),
+        ],
+    )?;
+
+    // Mints with a transfer fee deliver less than what was sent
+    let received = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+    if received < repayment {
+        return Err(VaultError::InvalidFlashRepay.into());
+    }
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    vault_state_data.accrue_fee(fee)?;
+    vault_state_data.flash_loan_amount = 0.into();
+    msg!("Flash loan repaid");
+
+    Ok(())
+}
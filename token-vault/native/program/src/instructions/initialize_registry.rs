@@ -0,0 +1,63 @@
+use super::shared::{check_upgrade_authority, create_pda_account, load_registry_mut};
+use crate::error::VaultError;
+use crate::header::{VaultAccount, init_header};
+use crate::state::VaultRegistry;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Create the vault registry, the payer becomes its admin. Only the upgrade
+/// authority of the program may, whoever creates the registry decides which
+/// vaults take deposits.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable registry]
+/// [readonly system program]
+/// [readonly program_data]
+pub fn initialize_registry(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let registry = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let program_data = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable || !registry.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    check_upgrade_authority(program_id, program_data, payer)?;
+
+    let (registry_pda, registry_bump) = Pubkey::find_program_address(&[b"registry"], program_id);
+    if registry_pda != *registry.key {
+        return Err(VaultError::InvalidRegistry.into());
+    }
+    if !registry.data_is_empty() {
+        return Err(VaultError::AlreadyInitialized.into());
+    }
+
+    msg!("Creating vault registry account");
+    create_pda_account(
+        program_id,
+        payer,
+        registry,
+        system_prog,
+        VaultRegistry::SPACE,
+        &[b"registry", &[registry_bump]],
+    )?;
+
+    // The registry is too large to build on the stack, the new account is
+    // already zeroed so only the header and the admin are written
+    init_header::<VaultRegistry>(registry, registry_bump)?;
+    load_registry_mut(program_id, registry)?.admin = *payer.key;
+
+    Ok(())
+}
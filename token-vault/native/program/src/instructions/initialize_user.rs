@@ -1,15 +1,16 @@
+use super::shared::create_pda_account;
 use crate::error::VaultError;
-use crate::state::UserState;
+use crate::escrow::escrow_maker_address;
+use crate::header::{VaultAccount, init};
+use crate::state::{LockupTier, RateWindow, UNSYNCED_FEE_INDEX, UserState};
 
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
     entrypoint::ProgramResult,
     msg,
-    program::{invoke_signed},
     pubkey::Pubkey,
-    sysvar::{Sysvar, rent::Rent},
 };
-use solana_system_interface::{instruction, program};
+use spl_pod::primitives::{PodI64, PodU64};
 
 /// Accounts:
 /// [signer payer]
@@ -17,26 +18,23 @@ use solana_system_interface::{instruction, program};
 /// [readonly token_mint]
 /// [readonly token program]
 /// [readonly system program]
-pub fn initialize_user(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
+/// [readonly rent sysvar]
+pub fn initialize_user(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
     let payer = next_account_info(account_info_iter)?;
     let user_state = next_account_info(account_info_iter)?;
     let token_mint = next_account_info(account_info_iter)?;
-    let token_prog = next_account_info(account_info_iter)?;
+    let _token_prog = next_account_info(account_info_iter)?;
     let system_prog = next_account_info(account_info_iter)?;
-    let rent_sysvar = next_account_info(account_info_iter)?;
+    let _rent_sysvar = next_account_info(account_info_iter)?;
 
     // Basic checks
     if !payer.is_signer {
         return Err(VaultError::NotSigner.into());
     }
     if !user_state.is_writable {
-        return Err(VaultError::NotSigner.into());
+        return Err(VaultError::NotWritable.into());
     }
 
     // Derive a PDA for user's state
@@ -44,6 +42,9 @@ pub fn initialize_user(
         &[b"user", payer.key.as_ref(), token_mint.key.as_ref()],
         program_id,
     );
+    if state_pda != *user_state.key {
+        return Err(VaultError::InvalidUserAccount.into());
+    }
 
     if !user_state.data_is_empty() {
         return Err(VaultError::AlreadyInitialized.into());
@@ -51,35 +52,32 @@ pub fn initialize_user(
 
     // Create user state
     msg!("Creating state account for the user and mint");
-    // Calculate rent-exempt lamports for the state account
-    let space = UserState::LEN;
-    let rent = &Rent::from_account_info(rent_sysvar)?;
-    let lamports = rent.minimum_balance(space);
-
-    // Create the instruction
-    let create_state_ix = &instruction::create_account(
-        payer.key,      // Payer
-        user_state.key, // New account address
-        lamports,       // Lamports
-        space as u64,   // Space
-        &program::ID,     // Owner program
-    );
-    invoke_signed(
-        &create_state_ix,
-        &[payer.clone(), user_state.clone()],
-        &[&[b"user", payer.key.as_ref(), token_mint.key.as_ref(), &[state_bump]]],
+    create_pda_account(
+        program_id,
+        payer,
+        user_state,
+        system_prog,
+        UserState::SPACE,
+        &[b"user", payer.key.as_ref(), token_mint.key.as_ref(), &[state_bump]],
     )?;
 
     // Initialize default state for this user
     let initial_state = UserState {
         user: *payer.key,
         mint: *token_mint.key,
-        amount: 0,
-        is_initialized: true,
+        amount: PodU64::from(0),
+        unlock_ts: PodI64::from(0),
+        boost_bps: LockupTier::None.boost_bps().into(),
+        fee_index: UNSYNCED_FEE_INDEX.into(),
+        delegate: Pubkey::default(),
+        delegate_allowance: PodU64::from(0),
+        outflow: RateWindow::default(),
+        earmarked: PodU64::from(0),
+        escrow_maker_bump: escrow_maker_address(program_id, user_state.key).1,
     };
 
     // Now serialize and save it
-    initial_state.serialize(&mut &mut user_state.data.borrow_mut()[..])?;
+    init(user_state, state_bump, &initial_state)?;
 
     Ok(())
 }
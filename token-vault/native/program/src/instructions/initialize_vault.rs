@@ -1,4 +1,7 @@
+use super::shared::{check_mint_extensions, check_vault_creator, create_pda_account, mint_decimals};
 use crate::error::VaultError;
+use crate::header::{VaultAccount, init};
+use crate::state::{BPS_DENOMINATOR, RateWindow, VaultConfig, VaultState};
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
     entrypoint::ProgramResult,
@@ -6,41 +9,137 @@ use solana_program::{
     program::invoke,
     pubkey::Pubkey,
 };
-use spl_associated_token_account::instruction::create_associated_token_account;
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+};
+use spl_pod::primitives::{PodU16, PodU64, PodU128};
 
+/// Create the vault of a mint, the payer becomes its admin. The payer must be
+/// the registry admin, or the upgrade authority of the program.
+///
 /// Accounts:
 /// [signer payer]
+/// [writable vault_state]
+/// [writable vault_config]
 /// [writable vault_ATA]
 /// [readonly token_mint]
 /// [readonly token program]
+/// [readonly associated token program]
 /// [readonly system program]
+/// [readonly registry]
+/// [readonly program_data]
 pub fn initialize_vault(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    early_exit_penalty_bps: u16,
+    withdraw_cooldown: i64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
     let payer = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
     let vault_ata = next_account_info(account_info_iter)?;
     let token_mint = next_account_info(account_info_iter)?;
     let token_prog = next_account_info(account_info_iter)?;
+    let ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let registry = next_account_info(account_info_iter)?;
+    let program_data = next_account_info(account_info_iter)?;
 
     // Basic checks
     if !payer.is_signer {
         return Err(VaultError::NotSigner.into());
     }
-    if !vault_ata.is_writable {
-        return Err(VaultError::NotSigner.into());
+    if !vault_state.is_writable || !vault_config.is_writable || !vault_ata.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    check_vault_creator(program_id, registry, program_data, payer)?;
+    if u64::from(early_exit_penalty_bps) > BPS_DENOMINATOR {
+        return Err(VaultError::InvalidPenalty.into());
     }
+    if withdraw_cooldown < 0 {
+        return Err(VaultError::InvalidCooldown.into());
+    }
+    mint_decimals(token_mint, token_prog)?;
+    check_mint_extensions(token_mint)?;
 
-    // Derive a PDA which acts as owner for the vault(And vault is basically ATA for this program)
+    // Derive a PDA which holds the vault state and acts as owner for the vault(And vault is basically ATA for this program)
     let (vault_pda, vault_bump) =
         Pubkey::find_program_address(&[b"vault", token_mint.key.as_ref()], program_id);
+    if vault_pda != *vault_state.key {
+        return Err(VaultError::InvalidVaultAuthority.into());
+    }
+    if *vault_ata.key
+        != get_associated_token_address_with_program_id(&vault_pda, token_mint.key, token_prog.key)
+    {
+        return Err(VaultError::InvalidVaultTokenAccount.into());
+    }
+    let (config_pda, config_bump) =
+        Pubkey::find_program_address(&[b"config", vault_pda.as_ref()], program_id);
+    if config_pda != *vault_config.key {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
 
-    if !vault_ata.data_is_empty() {
+    if !vault_state.data_is_empty() || !vault_config.data_is_empty() || !vault_ata.data_is_empty() {
         return Err(VaultError::AlreadyInitialized.into());
     }
 
+    // Create the vault state
+    msg!("Creating vault state account");
+    create_pda_account(
+        program_id,
+        payer,
+        vault_state,
+        system_prog,
+        VaultState::SPACE,
+        &[b"vault", token_mint.key.as_ref(), &[vault_bump]],
+    )?;
+
+    let initial_state = VaultState {
+        mint: *token_mint.key,
+        early_exit_penalty_bps: early_exit_penalty_bps.into(),
+        treasury: PodU64::from(0),
+        withdraw_cooldown: withdraw_cooldown.into(),
+        next_ticket_id: PodU64::from(0),
+        queued_total: PodU64::from(0),
+        claimed_total: PodU64::from(0),
+        funded_total: PodU64::from(0),
+        flash_fee_bps: PodU16::from(0),
+        flash_loan_amount: PodU64::from(0),
+        total_deposits: PodU64::from(0),
+        fee_index: PodU128::from(0),
+        outflow: RateWindow::default(),
+    };
+    init(vault_state, vault_bump, &initial_state)?;
+
+    // Create the vault config, the payer becomes the admin of the vault
+    msg!("Creating vault config account");
+    create_pda_account(
+        program_id,
+        payer,
+        vault_config,
+        system_prog,
+        VaultConfig::SPACE,
+        &[b"config", vault_pda.as_ref(), &[config_bump]],
+    )?;
+
+    let initial_config = VaultConfig {
+        vault: vault_pda,
+        admin: *payer.key,
+        pending_admin: Pubkey::default(),
+        user_deposit_cap: u64::MAX,
+        tvl_cap: u64::MAX,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: 0,
+        user_window_limit: u64::MAX,
+        vault_window_limit: u64::MAX,
+    };
+    init(vault_config, config_bump, &initial_config)?;
+
     // Create Vault or in simpler terms it's the ATA for this program
     msg!("Creating program token vault account");
 
@@ -48,7 +147,18 @@ pub fn initialize_vault(
     let create_ata_ix =
         create_associated_token_account(payer.key, &vault_pda, token_mint.key, token_prog.key);
 
-    invoke(&create_ata_ix, &[vault_ata.clone(), token_mint.clone()])?;
+    invoke(
+        &create_ata_ix,
+        &[
+            payer.clone(),
+            vault_ata.clone(),
+            vault_state.clone(),
+            token_mint.clone(),
+            system_prog.clone(),
+            token_prog.clone(),
+            ata_prog.clone(),
+        ],
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,196 @@
+use super::shared::{
+    check_escrow_maker, check_vault_ata, check_withdraw_allowed, create_maker_ata,
+    empty_maker_ata, load_user_state, load_vault_config, load_vault_state_mut, record_outflow,
+    release_escrow_maker, token_account_space, transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::escrow::{self, Offer};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    pubkey::Pubkey,
+    sysvar::{Sysvar, rent::Rent},
+};
+use solana_system_interface::instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+
+/// Post an escrow offer of `amount` tokens from the user's position, asking
+/// `wanted_amount` of mint B in return. The tokens go from the vault ATA to the
+/// offer's vault without passing through the user's wallet, and are tracked as
+/// earmarked until the offer is taken or refunded. The user pays the rent of
+/// the offer accounts.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [writable vault_ata]
+/// [writable escrow_maker]
+/// [writable maker_ata_a]
+/// [writable offer]
+/// [writable offer_vault_ata]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly escrow program]
+pub fn make_escrow_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    id: u64,
+    amount: u64,
+    wanted_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let escrow_maker = next_account_info(account_info_iter)?;
+    let maker_ata_a = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let offer_vault_ata = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let escrow_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable
+        || !user_state.is_writable
+        || !vault_state.is_writable
+        || !vault_ata.is_writable
+        || !escrow_maker.is_writable
+        || !maker_ata_a.is_writable
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+    if amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // The offered tokens pass through the escrow maker's ATA, it must be the real one
+    if *maker_ata_a.key
+        != get_associated_token_address_with_program_id(
+            escrow_maker.key,
+            token_mint_a.key,
+            token_prog.key,
+        )
+    {
+        return Err(VaultError::InvalidEscrowMaker.into());
+    }
+    check_vault_ata(vault_ata, vault_state.key, token_mint_a.key, token_prog.key)?;
+
+    // An offer is an outflow of the vault, the same rules as for a withdrawal apply
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key)?;
+    check_withdraw_allowed(&vault_config_data, amount)?;
+    let maker_bump = {
+        let mut user_state_data =
+            load_user_state(program_id, user_state, payer.key, token_mint_a.key)?;
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint_a.key)?;
+        if i64::from(vault_state_data.withdraw_cooldown) > 0 {
+            return Err(VaultError::InstantWithdrawDisabled.into());
+        }
+        vault_state_data.settle_fees(&mut user_state_data)?;
+
+        let position = u64::from(user_state_data.amount);
+        if position < amount {
+            return Err(VaultError::InsufficientFunds.into());
+        }
+        let now = Clock::get()?.unix_timestamp;
+        if user_state_data.is_locked(now) {
+            msg!("Position is locked until {}", i64::from(user_state_data.unlock_ts));
+            return Err(VaultError::PositionLocked.into());
+        }
+        record_outflow(&vault_config_data, &mut vault_state_data, &mut user_state_data, amount, now)?;
+
+        user_state_data.amount = (position - amount).into();
+        user_state_data.earmarked = u64::from(user_state_data.earmarked)
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?
+            .into();
+        vault_state_data.sub_deposits(amount)?;
+        user_state_data.escrow_maker_bump
+    };
+    check_escrow_maker(program_id, escrow_maker, user_state.key, maker_bump, escrow_prog)?;
+    let maker_seeds: &[&[u8]] = &[b"escrow_maker", user_state.key.as_ref(), &[maker_bump]];
+
+    // Move the tokens to the escrow maker, which hands them to the escrow
+    msg!("Earmarking {} tokens for escrow offer {}", amount, id);
+    create_maker_ata(payer, maker_ata_a, escrow_maker, token_mint_a, system_prog, token_prog)?;
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        maker_ata_a,
+        vault_state,
+        token_mint_a,
+        token_prog,
+        amount,
+    )?;
+
+    // The escrow maker pays the offer accounts, the user funds it for that
+    let rent = Rent::get()?;
+    let needed = rent
+        .minimum_balance(Offer::SPACE)
+        .checked_add(rent.minimum_balance(token_account_space(token_mint_a, token_prog)?))
+        .ok_or(VaultError::Overflow)?;
+    if escrow_maker.lamports() < needed {
+        invoke(
+            &instruction::transfer(payer.key, escrow_maker.key, needed - escrow_maker.lamports()),
+            &[payer.clone(), escrow_maker.clone(), system_prog.clone()],
+        )?;
+    }
+
+    invoke_signed(
+        &escrow::make_offer(
+            escrow_maker.key,
+            token_mint_a.key,
+            token_mint_b.key,
+            token_prog.key,
+            id,
+            amount,
+            wanted_amount,
+        ),
+        &[
+            escrow_maker.clone(),
+            token_mint_a.clone(),
+            token_mint_b.clone(),
+            maker_ata_a.clone(),
+            offer.clone(),
+            offer_vault_ata.clone(),
+            token_prog.clone(),
+            ata_prog.clone(),
+            system_prog.clone(),
+            escrow_prog.clone(),
+        ],
+        &[maker_seeds],
+    )?;
+
+    // Nothing stays with the escrow maker between instructions
+    empty_maker_ata(
+        maker_ata_a,
+        vault_ata,
+        payer,
+        escrow_maker,
+        token_mint_a,
+        token_prog,
+        maker_seeds,
+    )?;
+    release_escrow_maker(escrow_maker, payer, system_prog, maker_seeds)?;
+    msg!("Escrow offer {} posted", id);
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use super::shared::{
+    check_token_program, check_upgrade_authority, check_vault_ata, close_account,
+    create_pda_account, load_vault_state, load_vault_state_mut, token_account_amount,
+};
+use crate::error::VaultError;
+use crate::escrow::escrow_maker_address;
+use crate::header::{VaultAccount, init, load_mut};
+use crate::legacy::{LegacyLayout, ProgramState, UserBalances};
+use crate::state::{LockupTier, RateWindow, UNSYNCED_FEE_INDEX, UserState};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+use borsh::BorshDeserialize;
+use spl_pod::primitives::{PodI64, PodU64};
+
+
+/// Move a position from a legacy vault account into the user's `UserState`.
+/// The balance is carried over and the legacy account is closed, its rent goes
+/// back to the user. See `legacy` for which program owns the legacy accounts.
+///
+/// Only the upgrade authority of the program can migrate. The legacy
+/// `[b"state", user]` accounts don't say which mint they hold, the authority
+/// picks the vault. The vault ATA must hold the migrated balance on top of
+/// everything else the vault owes, move the legacy tokens in beforehand.
+///
+/// Accounts:
+/// [signer writable authority]
+/// [writable user]
+/// [writable legacy_state]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_ATA]
+/// [readonly token_mint]
+/// [readonly token program]
+/// [readonly system program]
+/// [readonly program_data]
+pub fn migrate_user_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    layout: LegacyLayout,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let authority = next_account_info(account_info_iter)?;
+    let user = next_account_info(account_info_iter)?;
+    let legacy_state = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let program_data = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !authority.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !authority.is_writable
+        || !user.is_writable
+        || !legacy_state.is_writable
+        || !user_state.is_writable
+        || !vault_state.is_writable
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+    check_upgrade_authority(program_id, program_data, authority)?;
+
+    load_vault_state(program_id, vault_state, token_mint.key)?;
+    check_token_program(token_prog)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    if vault_ata.owner != token_prog.key {
+        return Err(VaultError::InvalidVaultTokenAccount.into());
+    }
+
+    // Verify the legacy PDA and read the balance it holds
+    let (legacy_pda, _legacy_bump) = Pubkey::find_program_address(&[b"state", user.key.as_ref()], program_id);
+    if legacy_pda != *legacy_state.key || legacy_state.owner != program_id {
+        return Err(VaultError::InvalidLegacyAccount.into());
+    }
+
+    let balance = {
+        let legacy_data = legacy_state.data.borrow();
+        match layout {
+            LegacyLayout::UserBalances => {
+                let legacy = UserBalances::try_from_slice(&legacy_data)
+                    .map_err(|_| VaultError::InvalidLegacyAccount)?;
+                if legacy.wallet != *user.key {
+                    return Err(VaultError::InvalidLegacyAccount.into());
+                }
+                legacy.balance
+            }
+            LegacyLayout::ProgramState => {
+                let legacy = ProgramState::try_from_slice(&legacy_data)
+                    .map_err(|_| VaultError::InvalidLegacyAccount)?;
+                if legacy.owner != *user.key {
+                    return Err(VaultError::InvalidLegacyAccount.into());
+                }
+                legacy.balance
+            }
+        }
+    };
+
+    // Derive a PDA for user's state
+    let (state_pda, state_bump) = Pubkey::find_program_address(
+        &[b"user", user.key.as_ref(), token_mint.key.as_ref()],
+        program_id,
+    );
+    if state_pda != *user_state.key {
+        return Err(VaultError::InvalidUserAccount.into());
+    }
+
+    // Create the user state when the user has none yet, otherwise top it up
+    if user_state.data_is_empty() {
+        msg!("Creating state account for the user and mint");
+        create_pda_account(
+            program_id,
+            authority,
+            user_state,
+            system_prog,
+            UserState::SPACE,
+            &[b"user", user.key.as_ref(), token_mint.key.as_ref(), &[state_bump]],
+        )?;
+
+        let new_state = UserState {
+            user: *user.key,
+            mint: *token_mint.key,
+            amount: PodU64::from(0),
+            unlock_ts: PodI64::from(0),
+            boost_bps: LockupTier::None.boost_bps().into(),
+            fee_index: UNSYNCED_FEE_INDEX.into(),
+            delegate: Pubkey::default(),
+            delegate_allowance: PodU64::from(0),
+            outflow: RateWindow::default(),
+            earmarked: PodU64::from(0),
+            escrow_maker_bump: escrow_maker_address(program_id, user_state.key).1,
+        };
+        init(user_state, state_bump, &new_state)?;
+    }
+
+    {
+        let mut user_state_data = load_mut::<UserState>(program_id, user_state)?;
+        if user_state_data.user != *user.key || user_state_data.mint != *token_mint.key {
+            return Err(VaultError::InvalidUserState.into());
+        }
+
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+        vault_state_data.settle_fees(&mut user_state_data)?;
+
+        msg!("Migrating legacy balance of {}", balance);
+        user_state_data.amount = u64::from(user_state_data.amount)
+            .checked_add(balance)
+            .ok_or(VaultError::Overflow)?
+            .into();
+        vault_state_data.add_deposits(balance)?;
+
+        // Every migrated balance has to be backed by tokens in the vault ATA
+        if token_account_amount(vault_ata)? < vault_state_data.liabilities()? {
+            return Err(VaultError::MigrationNotBacked.into());
+        }
+    }
+
+    // The legacy account is no longer needed
+    close_account(legacy_state, user)
+}
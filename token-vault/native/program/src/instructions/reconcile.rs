@@ -0,0 +1,114 @@
+use super::shared::{check_vault_ata, load_vault_config, load_vault_state, token_account_amount};
+use crate::error::VaultError;
+use crate::header::store;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    pubkey::Pubkey,
+};
+
+
+/// Outcome of `Reconcile`, set as the return data of the instruction
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileReport {
+    // Tokens held by the vault ATA
+    pub vault_balance: u64,
+    // Sum of the synced user positions
+    pub total_deposits: u64,
+    // Early exit penalties kept by the vault
+    pub treasury: u64,
+    // Requested withdrawals which have not been claimed yet
+    pub pending_tickets: u64,
+    // What the vault owes, the vault is solvent while its balance covers it
+    pub liabilities: u64,
+    pub solvent: bool,
+    // Whether this call paused the vault
+    pub paused: bool,
+}
+
+/// Compare what the vault owes with the balance of its ATA. Anyone can call
+/// it, the report is logged and set as return data. With `pause_if_insolvent`
+/// an insolvent vault has its deposits and withdrawals paused until the admin
+/// resumes them.
+///
+/// Unsettled flash loan fees and positions not synced since an upgrade are
+/// not in `total_deposits`, they only ever show up as a surplus.
+///
+/// Accounts:
+/// [readonly vault_state]
+/// [readonly vault_ata]
+/// [readonly vault_config], writable with `pause_if_insolvent`
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn reconcile(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pause_if_insolvent: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if pause_if_insolvent && !vault_config.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let vault_state_data = load_vault_state(program_id, vault_state, token_mint.key)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    if vault_ata.owner != token_prog.key {
+        return Err(VaultError::InvalidVaultTokenAccount.into());
+    }
+    // Lent out tokens are not in the vault ATA until the loan is repaid
+    if u64::from(vault_state_data.flash_loan_amount) != 0 {
+        return Err(VaultError::FlashLoanInProgress.into());
+    }
+
+    let vault_balance = token_account_amount(vault_ata)?;
+    let total_deposits = u64::from(vault_state_data.total_deposits);
+    let treasury = u64::from(vault_state_data.treasury);
+    let pending_tickets = vault_state_data.pending_tickets()?;
+    let liabilities = vault_state_data.liabilities()?;
+    let solvent = vault_balance >= liabilities;
+
+    let mut paused = false;
+    if !solvent && pause_if_insolvent {
+        let mut vault_config_data = load_vault_config(program_id, vault_config, vault_state.key)?;
+        if !vault_config_data.deposit_paused || !vault_config_data.withdraw_paused {
+            msg!("Vault is insolvent, pausing deposits and withdrawals");
+            vault_config_data.deposit_paused = true;
+            vault_config_data.withdraw_paused = true;
+            store(vault_config, &vault_config_data)?;
+            paused = true;
+        }
+    }
+
+    let report = ReconcileReport {
+        vault_balance,
+        total_deposits,
+        treasury,
+        pending_tickets,
+        liabilities,
+        solvent,
+        paused,
+    };
+    msg!(
+        "Reconcile: balance {} deposits {} treasury {} tickets {} liabilities {} solvent {}",
+        vault_balance,
+        total_deposits,
+        treasury,
+        pending_tickets,
+        liabilities,
+        solvent
+    );
+    set_return_data(&borsh::to_vec(&report).map_err(|_| VaultError::SerializationError)?);
+
+    Ok(())
+}
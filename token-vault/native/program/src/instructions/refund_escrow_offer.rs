@@ -0,0 +1,151 @@
+use super::shared::{
+    check_escrow_maker, check_vault_ata, create_maker_ata, empty_maker_ata, load_user_state,
+    load_vault_state_mut, release_escrow_maker, token_account_amount,
+};
+use crate::error::VaultError;
+use crate::escrow::{self, load_offer};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+
+/// Cancel an escrow offer posted from the user's position. The offered tokens
+/// go back into the vault and are credited to the position again, the rent of
+/// the offer accounts is returned to the user.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable user_state]
+/// [writable vault_state]
+/// [writable vault_ata]
+/// [writable escrow_maker]
+/// [writable maker_ata_a]
+/// [writable offer]
+/// [writable offer_vault_ata]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly escrow program]
+pub fn refund_escrow_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let escrow_maker = next_account_info(account_info_iter)?;
+    let maker_ata_a = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let offer_vault_ata = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let escrow_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable
+        || !user_state.is_writable
+        || !vault_state.is_writable
+        || !vault_ata.is_writable
+        || !escrow_maker.is_writable
+        || !maker_ata_a.is_writable
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // Only the owner of the position cancels its offers
+    let maker_bump =
+        load_user_state(program_id, user_state, payer.key, token_mint_a.key)?.escrow_maker_bump;
+    check_escrow_maker(program_id, escrow_maker, user_state.key, maker_bump, escrow_prog)?;
+    let maker_seeds: &[&[u8]] = &[b"escrow_maker", user_state.key.as_ref(), &[maker_bump]];
+    if *maker_ata_a.key
+        != get_associated_token_address_with_program_id(
+            escrow_maker.key,
+            token_mint_a.key,
+            token_prog.key,
+        )
+    {
+        return Err(VaultError::InvalidEscrowMaker.into());
+    }
+    load_vault_state_mut(program_id, vault_state, token_mint_a.key)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint_a.key, token_prog.key)?;
+
+    let offer_data = load_offer(offer)?;
+    if offer_data.maker != *escrow_maker.key
+        || offer_data.id != id
+        || offer_data.token_mint_a != *token_mint_a.key
+        || offer_data.token_mint_b != *token_mint_b.key
+    {
+        return Err(VaultError::InvalidOffer.into());
+    }
+
+    // The escrow refunds into the escrow maker's ATA, which is emptied into the vault
+    msg!("Refunding escrow offer {}", id);
+    create_maker_ata(payer, maker_ata_a, escrow_maker, token_mint_a, system_prog, token_prog)?;
+    invoke_signed(
+        &escrow::refund_offer(
+            escrow_maker.key,
+            token_mint_a.key,
+            token_mint_b.key,
+            token_prog.key,
+            id,
+        ),
+        &[
+            escrow_maker.clone(),
+            token_mint_a.clone(),
+            token_mint_b.clone(),
+            maker_ata_a.clone(),
+            offer.clone(),
+            offer_vault_ata.clone(),
+            token_prog.clone(),
+            ata_prog.clone(),
+            system_prog.clone(),
+            escrow_prog.clone(),
+        ],
+        &[maker_seeds],
+    )?;
+
+    // Credit what actually reached the vault, transfer fees included
+    let vault_balance = token_account_amount(vault_ata)?;
+    empty_maker_ata(
+        maker_ata_a,
+        vault_ata,
+        payer,
+        escrow_maker,
+        token_mint_a,
+        token_prog,
+        maker_seeds,
+    )?;
+    release_escrow_maker(escrow_maker, payer, system_prog, maker_seeds)?;
+    let credited = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key, token_mint_a.key)?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint_a.key)?;
+    vault_state_data.settle_fees(&mut user_state_data)?;
+    user_state_data.amount = u64::from(user_state_data.amount)
+        .checked_add(credited)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    user_state_data.earmarked = u64::from(user_state_data.earmarked)
+        .checked_sub(offer_data.token_a_offered_amount)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    vault_state_data.add_deposits(credited)?;
+    msg!("Escrow offer {} refunded, {} tokens back in the position", id, credited);
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use super::shared::{
+    check_withdraw_allowed, create_pda_account, load_user_state, load_vault_config,
+    load_vault_state_mut, record_outflow,
+};
+use crate::error::VaultError;
+use crate::header::{VaultAccount, init};
+use crate::state::WithdrawTicket;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+/// First step of a withdrawal, the amount is taken out of the user's position
+/// and moved into a withdrawal ticket which can be claimed after the vault's cooldown.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [writable ticket]
+/// [readonly token_mint]
+/// [readonly system program]
+pub fn request_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    withdraw_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let ticket = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable || !vault_state.is_writable || !ticket.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    if withdraw_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // Verify and load the user and vault state PDAs
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key, token_mint.key)?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    vault_state_data.settle_fees(&mut user_state_data)?;
+
+    // Enforce the limits set by the vault admin
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key)?;
+    check_withdraw_allowed(&vault_config_data, withdraw_amount)?;
+
+    let amount = u64::from(user_state_data.amount);
+    if amount < withdraw_amount {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if user_state_data.is_locked(now) {
+        msg!("Position is locked until {}", i64::from(user_state_data.unlock_ts));
+        return Err(VaultError::PositionLocked.into());
+    }
+    // The ticket is the only way out of a vault with a cooldown, and a zero
+    // cooldown ticket can be claimed right away, so the amount counts now
+    record_outflow(&vault_config_data, &mut vault_state_data, &mut user_state_data, withdraw_amount, now)?;
+
+    // Derive the PDA of the next ticket
+    let ticket_id = u64::from(vault_state_data.next_ticket_id);
+    let (ticket_pda, ticket_bump) = Pubkey::find_program_address(
+        &[b"ticket", vault_state.key.as_ref(), &ticket_id.to_le_bytes()],
+        program_id,
+    );
+    if ticket_pda != *ticket.key {
+        return Err(VaultError::InvalidTicket.into());
+    }
+
+    // Create the ticket
+    msg!("Creating withdrawal ticket {}", ticket_id);
+    create_pda_account(
+        program_id,
+        payer,
+        ticket,
+        system_prog,
+        WithdrawTicket::SPACE,
+        &[b"ticket", vault_state.key.as_ref(), &ticket_id.to_le_bytes(), &[ticket_bump]],
+    )?;
+
+    // Queue the amount behind every earlier ticket
+    let queued_total = u64::from(vault_state_data.queued_total)
+        .checked_add(withdraw_amount)
+        .ok_or(VaultError::Overflow)?;
+    vault_state_data.next_ticket_id = ticket_id.checked_add(1).ok_or(VaultError::Overflow)?.into();
+    vault_state_data.queued_total = queued_total.into();
+    vault_state_data.sub_deposits(withdraw_amount)?;
+
+    let ticket_data = WithdrawTicket {
+        owner: *payer.key,
+        mint: *token_mint.key,
+        id: ticket_id,
+        amount: withdraw_amount,
+        queue_end: queued_total,
+        claimable_after: now
+            .checked_add(vault_state_data.withdraw_cooldown.into())
+            .ok_or(VaultError::Overflow)?,
+    };
+    init(ticket, ticket_bump, &ticket_data)?;
+
+    // The requested amount leaves the user's position
+    user_state_data.amount = (amount - withdraw_amount).into();
+    msg!("Withdrawal of {} requested, claimable after {}", withdraw_amount, ticket_data.claimable_after);
+
+    Ok(())
+}
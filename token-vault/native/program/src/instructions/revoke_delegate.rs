@@ -0,0 +1,38 @@
+use super::shared::load_user_state;
+use crate::error::VaultError;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Remove the withdraw delegate of the position
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [readonly token_mint]
+pub fn revoke_delegate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key, token_mint.key)?;
+    msg!("Revoking withdraw delegate {}", user_state_data.delegate);
+    user_state_data.delegate = Pubkey::default();
+    user_state_data.delegate_allowance = 0.into();
+
+    Ok(())
+}
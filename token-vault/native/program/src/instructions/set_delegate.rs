@@ -0,0 +1,50 @@
+use super::shared::load_user_state;
+use crate::error::VaultError;
+use crate::state::UNLIMITED_ALLOWANCE;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Let another key withdraw from the position, up to `allowance` when set.
+/// Replaces any earlier delegate and its remaining allowance.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [readonly token_mint]
+pub fn set_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    allowance: Option<u64>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    // The default key marks a position without delegate
+    if delegate == Pubkey::default() || delegate == *payer.key {
+        return Err(VaultError::InvalidDelegate.into());
+    }
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key, token_mint.key)?;
+    let allowance = allowance.unwrap_or(UNLIMITED_ALLOWANCE);
+    msg!("Setting withdraw delegate {} with an allowance of {}", delegate, allowance);
+    user_state_data.delegate = delegate;
+    user_state_data.delegate_allowance = allowance.into();
+
+    Ok(())
+}
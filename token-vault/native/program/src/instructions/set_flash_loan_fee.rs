@@ -0,0 +1,51 @@
+use super::shared::{load_vault_config, load_vault_state_mut};
+use crate::error::VaultError;
+use crate::state::BPS_DENOMINATOR;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Set the fee charged on flash loans, zero disables them
+///
+/// Accounts:
+/// [signer admin]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly token_mint]
+pub fn set_flash_loan_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_bps: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let admin = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !admin.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    if u64::from(fee_bps) > BPS_DENOMINATOR {
+        return Err(VaultError::InvalidFee.into());
+    }
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    if load_vault_config(program_id, vault_config, vault_state.key)?.admin != *admin.key {
+        return Err(VaultError::Unauthorized.into());
+    }
+
+    msg!("Setting flash loan fee to {} bps", fee_bps);
+    vault_state_data.flash_fee_bps = fee_bps.into();
+
+    Ok(())
+}
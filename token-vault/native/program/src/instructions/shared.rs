@@ -0,0 +1,515 @@
+use crate::error::VaultError;
+use crate::escrow::ESCROW_PROGRAM_ID;
+use crate::header::{load, load_mut, load_ref, read_header};
+use crate::state::{UserState, VaultConfig, VaultRegistry, VaultState};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{Sysvar, rent::Rent},
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use std::cell::{Ref, RefMut};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    instruction as token_instruction,
+    state::{Account as TokenAccount, Mint},
+};
+
+// Tag, slot and upgrade authority in front of the program in a ProgramData account
+const PROGRAM_DATA_METADATA_LEN: usize = 45;
+
+// Mint extensions which make a mint unusable for the vault:
+// - NonTransferable tokens can't be moved into or out of the vault
+// - TransferHook needs extra accounts the vault doesn't pass along
+// - PermanentDelegate lets a third party move tokens out of the vault ATA
+const UNSUPPORTED_MINT_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::NonTransferable,
+    ExtensionType::TransferHook,
+    ExtensionType::PermanentDelegate,
+];
+
+// Whether the key is the PDA of the seeds and a bump stored at init time. A single
+// create_program_address is much cheaper than find_program_address searching for the bump.
+pub fn is_pda(program_id: &Pubkey, key: &Pubkey, seeds: &[&[u8]], bump: u8) -> bool {
+    let bump = [bump];
+    let mut seeds = seeds.to_vec();
+    seeds.push(&bump);
+    Pubkey::create_program_address(&seeds, program_id).is_ok_and(|pda| pda == *key)
+}
+
+// Check the user state PDA of the signer for the given mint and borrow it in place
+pub fn load_user_state<'a>(
+    program_id: &Pubkey,
+    user_state: &'a AccountInfo,
+    user: &Pubkey,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, UserState>, VaultError> {
+    let state_bump = read_header(program_id, user_state)?.bump;
+    if !is_pda(program_id, user_state.key, &[b"user", user.as_ref(), mint.as_ref()], state_bump) {
+        return Err(VaultError::InvalidUserAccount);
+    }
+
+    let user_state_data = load_mut::<UserState>(program_id, user_state)?;
+    if user_state_data.user != *user {
+        return Err(VaultError::InvalidUserState);
+    }
+    Ok(user_state_data)
+}
+
+// Check a user state PDA against the user stored in it and borrow it in place,
+// for handlers the owner or its delegate can sign
+pub fn load_position<'a>(
+    program_id: &Pubkey,
+    user_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, UserState>, VaultError> {
+    let state_bump = read_header(program_id, user_state)?.bump;
+    let user_state_data = load_mut::<UserState>(program_id, user_state)?;
+    let seeds: &[&[u8]] = &[b"user", user_state_data.user.as_ref(), mint.as_ref()];
+    if !is_pda(program_id, user_state.key, seeds, state_bump) {
+        return Err(VaultError::InvalidUserAccount);
+    }
+    Ok(user_state_data)
+}
+
+// Check the vault state PDA for the given mint and borrow it read only
+pub fn load_vault_state<'a>(
+    program_id: &Pubkey,
+    vault_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<Ref<'a, VaultState>, VaultError> {
+    vault_bump(program_id, vault_state, mint)?;
+    load_ref::<VaultState>(program_id, vault_state)
+}
+
+// Same as `load_vault_state` for handlers which update the vault state
+pub fn load_vault_state_mut<'a>(
+    program_id: &Pubkey,
+    vault_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, VaultState>, VaultError> {
+    vault_bump(program_id, vault_state, mint)?;
+    load_mut::<VaultState>(program_id, vault_state)
+}
+
+// Check the vault state PDA for the given mint with the bump stored in its header
+pub fn vault_bump(
+    program_id: &Pubkey,
+    vault_state: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<u8, VaultError> {
+    if vault_state.owner != program_id {
+        return Err(VaultError::InvalidVaultState);
+    }
+    let vault_bump = read_header(program_id, vault_state)?.bump;
+    if !is_pda(program_id, vault_state.key, &[b"vault", mint.as_ref()], vault_bump) {
+        return Err(VaultError::InvalidVaultAuthority);
+    }
+    Ok(vault_bump)
+}
+
+// The vault ATA is the associated token account of the already checked vault PDA for the mint
+pub fn check_vault_ata(
+    vault_ata: &AccountInfo,
+    vault_state: &Pubkey,
+    mint: &Pubkey,
+    token_prog: &Pubkey,
+) -> Result<(), VaultError> {
+    if *vault_ata.key != get_associated_token_address_with_program_id(vault_state, mint, token_prog)
+    {
+        return Err(VaultError::InvalidVaultTokenAccount);
+    }
+    Ok(())
+}
+
+// The vault works with both the SPL Token and the Token-2022 program
+pub fn check_token_program(token_prog: &AccountInfo) -> Result<(), VaultError> {
+    if *token_prog.key != spl_token::id() && *token_prog.key != spl_token_2022::id() {
+        return Err(VaultError::InvalidTokenProgram);
+    }
+    Ok(())
+}
+
+// Check the mint belongs to the token program and return its decimals
+pub fn mint_decimals(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<u8, ProgramError> {
+    check_token_program(token_prog)?;
+    if token_mint.owner != token_prog.key {
+        return Err(VaultError::InvalidMint.into());
+    }
+    let mint_data = token_mint.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)
+        .map_err(|_| VaultError::InvalidMint)?;
+    Ok(mint.base.decimals)
+}
+
+// Reject mints with extensions the vault can't custody
+pub fn check_mint_extensions(token_mint: &AccountInfo) -> ProgramResult {
+    let mint_data = token_mint.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)
+        .map_err(|_| VaultError::InvalidMint)?;
+    if mint
+        .get_extension_types()?
+        .iter()
+        .any(|extension| UNSUPPORTED_MINT_EXTENSIONS.contains(extension))
+    {
+        return Err(VaultError::UnsupportedMint.into());
+    }
+    Ok(())
+}
+
+// Token balance of a token account of either token program
+pub fn token_account_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let account_data = token_account.data.borrow();
+    let account = StateWithExtensions::<TokenAccount>::unpack(&account_data)?;
+    Ok(account.base.amount)
+}
+
+// Transfer tokens out of the vault ATA, the vault state PDA signs as the owner of the vault
+pub fn transfer_from_vault<'a>(
+    program_id: &Pubkey,
+    vault_ata: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    vault_state: &AccountInfo<'a>,
+    token_mint: &AccountInfo<'a>,
+    token_prog: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let vault_bump = vault_bump(program_id, vault_state, token_mint.key)?;
+
+    let transfer_ix = token_instruction::transfer_checked(
+        token_prog.key,
+        vault_ata.key,
+        token_mint.key,
+        destination.key,
+        vault_state.key,
+        &[],
+        amount,
+        mint_decimals(token_mint, token_prog)?,
+    )?;
+
+    invoke_signed(
+        &transfer_ix,
+        &[
+            vault_ata.clone(),
+            token_mint.clone(),
+            destination.clone(),
+            vault_state.clone(),
+            token_prog.clone(),
+        ],
+        &[&[b"vault", token_mint.key.as_ref(), &[vault_bump]]],
+    )
+}
+
+// Create a PDA owned by the program. An address which already holds lamports
+// can't go through create_account, it's topped up, allocated and assigned instead.
+pub fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_prog: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if !account.data_is_empty() || *account.owner != system_program::ID {
+        return Err(VaultError::AlreadyInitialized.into());
+    }
+    let lamports = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_prog.clone()],
+            &[seeds],
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, shortfall),
+            &[payer.clone(), account.clone(), system_prog.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_prog.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_prog.clone()],
+        &[seeds],
+    )
+}
+
+// Close a program owned account and send its rent to the destination
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **account.try_borrow_mut_lamports()? = 0;
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(VaultError::Overflow)?;
+
+    account.data.borrow_mut().fill(0);
+    account.resize(0)?;
+    account.assign(&system_program::ID);
+    Ok(())
+}
+
+// Check the config PDA of the vault and load it
+pub fn load_vault_config(
+    program_id: &Pubkey,
+    vault_config: &AccountInfo,
+    vault_state: &Pubkey,
+) -> Result<VaultConfig, VaultError> {
+    let config_bump = read_header(program_id, vault_config)?.bump;
+    if !is_pda(program_id, vault_config.key, &[b"config", vault_state.as_ref()], config_bump) {
+        return Err(VaultError::InvalidVaultConfig);
+    }
+
+    let vault_config_data = load::<VaultConfig>(program_id, vault_config)?;
+    if vault_config_data.vault != *vault_state {
+        return Err(VaultError::InvalidVaultConfig);
+    }
+    Ok(vault_config_data)
+}
+
+// Check the registry PDA and borrow it read only
+pub fn load_registry<'a>(
+    program_id: &Pubkey,
+    registry: &'a AccountInfo,
+) -> Result<Ref<'a, VaultRegistry>, VaultError> {
+    let registry_bump = read_header(program_id, registry)?.bump;
+    if !is_pda(program_id, registry.key, &[b"registry"], registry_bump) {
+        return Err(VaultError::InvalidRegistry);
+    }
+    load_ref::<VaultRegistry>(program_id, registry)
+}
+
+// Same as `load_registry` for the admin instructions which change it
+pub fn load_registry_mut<'a>(
+    program_id: &Pubkey,
+    registry: &'a AccountInfo,
+) -> Result<RefMut<'a, VaultRegistry>, VaultError> {
+    let registry_bump = read_header(program_id, registry)?.bump;
+    if !is_pda(program_id, registry.key, &[b"registry"], registry_bump) {
+        return Err(VaultError::InvalidRegistry);
+    }
+    load_mut::<VaultRegistry>(program_id, registry)
+}
+
+// Check the signer is the upgrade authority of this program. The upgradeable
+// loader keeps it in the ProgramData account at the PDA of the program id,
+// behind the `ProgramData` tag (3u32) and the deployment slot, as an Option.
+pub fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+    authority: &AccountInfo,
+) -> Result<(), VaultError> {
+    let (program_data_pda, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if *program_data.key != program_data_pda || *program_data.owner != bpf_loader_upgradeable::id() {
+        return Err(VaultError::InvalidProgramData);
+    }
+    let data = program_data.try_borrow_data().map_err(|_| VaultError::InvalidProgramData)?;
+    if data.len() < PROGRAM_DATA_METADATA_LEN || data[..4] != 3u32.to_le_bytes() {
+        return Err(VaultError::InvalidProgramData);
+    }
+    // An immutable program has no authority, nobody can sign for it
+    if data[12] != 1 || data[13..PROGRAM_DATA_METADATA_LEN] != *authority.key.as_ref() {
+        return Err(VaultError::NotUpgradeAuthority);
+    }
+    if !authority.is_signer {
+        return Err(VaultError::NotSigner);
+    }
+    Ok(())
+}
+
+// Vaults are created by the registry admin, or by the upgrade authority before
+// there is a registry. The payer becomes the admin of the vault, anyone else
+// could take the vault of a mint before its real admin.
+pub fn check_vault_creator(
+    program_id: &Pubkey,
+    registry: &AccountInfo,
+    program_data: &AccountInfo,
+    payer: &AccountInfo,
+) -> Result<(), VaultError> {
+    if !registry.data_is_empty() && load_registry(program_id, registry)?.admin == *payer.key {
+        return Ok(());
+    }
+    check_upgrade_authority(program_id, program_data, payer).map_err(|_| VaultError::Unauthorized)
+}
+
+// Outflows share the pause flag and the minimum amount of the vault config
+pub fn check_withdraw_allowed(vault_config: &VaultConfig, amount: u64) -> Result<(), VaultError> {
+    if vault_config.withdraw_paused {
+        return Err(VaultError::WithdrawPaused);
+    }
+    if amount < vault_config.min_withdraw {
+        return Err(VaultError::BelowMinimum);
+    }
+    Ok(())
+}
+
+// Count a withdrawal against the user's and the vault's rate limit windows. The
+// error tells which limit was hit, the logs tell when its window resets.
+pub fn record_outflow(
+    vault_config: &VaultConfig,
+    vault_state: &mut VaultState,
+    user_state: &mut UserState,
+    amount: u64,
+    now: i64,
+) -> Result<(), VaultError> {
+    let window = vault_config.rate_limit_window;
+    let user_limit = vault_config.user_window_limit;
+    if let Err(reset_at) = user_state.outflow.record(amount, window, user_limit, now) {
+        msg!("User withdrawal limit reached, the window resets at {}", reset_at);
+        return Err(VaultError::UserRateLimitExceeded);
+    }
+    let vault_limit = vault_config.vault_window_limit;
+    if let Err(reset_at) = vault_state.outflow.record(amount, window, vault_limit, now) {
+        msg!("Vault withdrawal limit reached, the window resets at {}", reset_at);
+        return Err(VaultError::VaultRateLimitExceeded);
+    }
+    Ok(())
+}
+
+// Size of a new associated token account for the mint
+pub fn token_account_space(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<usize, ProgramError> {
+    let mint_data = token_mint.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    let mut extensions = ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+    // Token-2022 ATAs are created with an immutable owner
+    if *token_prog.key == spl_token_2022::id() {
+        extensions.push(ExtensionType::ImmutableOwner);
+    }
+    ExtensionType::try_calculate_account_len::<TokenAccount>(&extensions)
+}
+
+// Check the escrow program and the escrow maker PDA of the user state, with
+// the bump stored in the user state
+pub fn check_escrow_maker(
+    program_id: &Pubkey,
+    escrow_maker: &AccountInfo,
+    user_state: &Pubkey,
+    maker_bump: u8,
+    escrow_prog: &AccountInfo,
+) -> Result<(), VaultError> {
+    if *escrow_prog.key != ESCROW_PROGRAM_ID {
+        return Err(VaultError::InvalidEscrowProgram);
+    }
+    let seeds: &[&[u8]] = &[b"escrow_maker", user_state.as_ref()];
+    if !is_pda(program_id, escrow_maker.key, seeds, maker_bump)
+        || *escrow_maker.owner != system_program::ID
+    {
+        return Err(VaultError::InvalidEscrowMaker);
+    }
+    Ok(())
+}
+
+// Create the escrow maker's ATA for the mint if it doesn't exist, the payer pays its rent
+pub fn create_maker_ata<'a>(
+    payer: &AccountInfo<'a>,
+    maker_ata: &AccountInfo<'a>,
+    escrow_maker: &AccountInfo<'a>,
+    token_mint: &AccountInfo<'a>,
+    system_prog: &AccountInfo<'a>,
+    token_prog: &AccountInfo<'a>,
+) -> ProgramResult {
+    invoke(
+        &create_associated_token_account_idempotent(
+            payer.key,
+            escrow_maker.key,
+            token_mint.key,
+            token_prog.key,
+        ),
+        &[
+            payer.clone(),
+            maker_ata.clone(),
+            escrow_maker.clone(),
+            token_mint.clone(),
+            system_prog.clone(),
+            token_prog.clone(),
+        ],
+    )
+}
+
+// Move tokens out of the escrow maker's ATA and close it, the rent goes to `rent_destination`
+pub fn empty_maker_ata<'a>(
+    maker_ata: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    rent_destination: &AccountInfo<'a>,
+    escrow_maker: &AccountInfo<'a>,
+    token_mint: &AccountInfo<'a>,
+    token_prog: &AccountInfo<'a>,
+    maker_seeds: &[&[u8]],
+) -> ProgramResult {
+    let amount = token_account_amount(maker_ata)?;
+    if amount > 0 {
+        invoke_signed(
+            &token_instruction::transfer_checked(
+                token_prog.key,
+                maker_ata.key,
+                token_mint.key,
+                destination.key,
+                escrow_maker.key,
+                &[],
+                amount,
+                mint_decimals(token_mint, token_prog)?,
+            )?,
+            &[
+                maker_ata.clone(),
+                token_mint.clone(),
+                destination.clone(),
+                escrow_maker.clone(),
+                token_prog.clone(),
+            ],
+            &[maker_seeds],
+        )?;
+    }
+    invoke_signed(
+        &token_instruction::close_account(
+            token_prog.key,
+            maker_ata.key,
+            rent_destination.key,
+            escrow_maker.key,
+            &[],
+        )?,
+        &[maker_ata.clone(), rent_destination.clone(), escrow_maker.clone(), token_prog.clone()],
+        &[maker_seeds],
+    )
+}
+
+// Send every lamport left on the escrow maker to the destination, so it
+// never stays behind below the rent exempt minimum
+pub fn release_escrow_maker<'a>(
+    escrow_maker: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    system_prog: &AccountInfo<'a>,
+    maker_seeds: &[&[u8]],
+) -> ProgramResult {
+    let lamports = escrow_maker.lamports();
+    if lamports == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(escrow_maker.key, destination.key, lamports),
+        &[escrow_maker.clone(), destination.clone(), system_prog.clone()],
+        &[maker_seeds],
+    )
+}
@@ -0,0 +1,154 @@
+use super::shared::{check_escrow_maker, empty_maker_ata, load_position, release_escrow_maker};
+use crate::error::VaultError;
+use crate::escrow::{self, load_offer};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+
+/// Fill an escrow offer posted from a vault position. The taker pays mint B to
+/// the escrow maker, which forwards it to the owner's mint B ATA. The rent of
+/// the offer account goes back to the owner, the escrow gives the rent of its
+/// token account to the taker.
+/// Offers are only filled whole, see the `escrow` module.
+///
+/// Accounts:
+/// [signer writable taker]
+/// [writable user_state]
+/// [writable escrow_maker]
+/// [writable owner]
+/// [writable taker_ata_a]
+/// [writable taker_ata_b]
+/// [writable maker_ata_b]
+/// [writable owner_ata_b]
+/// [writable offer]
+/// [writable offer_vault_ata]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly escrow program]
+pub fn take_escrow_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let taker = next_account_info(account_info_iter)?;
+    let user_state = next_account_info(account_info_iter)?;
+    let escrow_maker = next_account_info(account_info_iter)?;
+    let owner = next_account_info(account_info_iter)?;
+    let taker_ata_a = next_account_info(account_info_iter)?;
+    let taker_ata_b = next_account_info(account_info_iter)?;
+    let maker_ata_b = next_account_info(account_info_iter)?;
+    let owner_ata_b = next_account_info(account_info_iter)?;
+    let offer = next_account_info(account_info_iter)?;
+    let offer_vault_ata = next_account_info(account_info_iter)?;
+    let token_mint_a = next_account_info(account_info_iter)?;
+    let token_mint_b = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+    let ata_prog = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+    let escrow_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !taker.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !taker.is_writable
+        || !user_state.is_writable
+        || !escrow_maker.is_writable
+        || !owner.is_writable
+        || !maker_ata_b.is_writable
+        || !owner_ata_b.is_writable
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // The proceeds and the rent go to the owner of the position
+    let maker_bump = {
+        let position = load_position(program_id, user_state, token_mint_a.key)?;
+        if position.user != *owner.key {
+            return Err(VaultError::InvalidOwner.into());
+        }
+        position.escrow_maker_bump
+    };
+    check_escrow_maker(program_id, escrow_maker, user_state.key, maker_bump, escrow_prog)?;
+    let maker_seeds: &[&[u8]] = &[b"escrow_maker", user_state.key.as_ref(), &[maker_bump]];
+    if *maker_ata_b.key
+        != get_associated_token_address_with_program_id(
+            escrow_maker.key,
+            token_mint_b.key,
+            token_prog.key,
+        )
+    {
+        return Err(VaultError::InvalidEscrowMaker.into());
+    }
+    if *owner_ata_b.key
+        != get_associated_token_address_with_program_id(owner.key, token_mint_b.key, token_prog.key)
+    {
+        return Err(VaultError::InvalidOwner.into());
+    }
+
+    // The offer is closed by the escrow, read what it earmarks first
+    let offer_data = load_offer(offer)?;
+    if offer_data.maker != *escrow_maker.key
+        || offer_data.id != id
+        || offer_data.token_mint_a != *token_mint_a.key
+        || offer_data.token_mint_b != *token_mint_b.key
+    {
+        return Err(VaultError::InvalidOffer.into());
+    }
+
+    msg!("Taking escrow offer {}", id);
+    invoke_signed(
+        &escrow::take_offer(
+            taker.key,
+            escrow_maker.key,
+            token_mint_a.key,
+            token_mint_b.key,
+            token_prog.key,
+            id,
+        ),
+        &[
+            taker.clone(),
+            escrow_maker.clone(),
+            token_mint_a.clone(),
+            token_mint_b.clone(),
+            taker_ata_a.clone(),
+            taker_ata_b.clone(),
+            maker_ata_b.clone(),
+            offer.clone(),
+            offer_vault_ata.clone(),
+            token_prog.clone(),
+            ata_prog.clone(),
+            system_prog.clone(),
+            escrow_prog.clone(),
+        ],
+        &[maker_seeds],
+    )?;
+
+    // Forward the proceeds to the owner, the taker paid the maker ATA and gets its rent back
+    empty_maker_ata(
+        maker_ata_b,
+        owner_ata_b,
+        taker,
+        escrow_maker,
+        token_mint_b,
+        token_prog,
+        maker_seeds,
+    )?;
+    release_escrow_maker(escrow_maker, owner, system_prog, maker_seeds)?;
+
+    let mut user_state_data = load_position(program_id, user_state, token_mint_a.key)?;
+    user_state_data.earmarked = u64::from(user_state_data.earmarked)
+        .checked_sub(offer_data.token_a_offered_amount)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    msg!("Escrow offer {} taken", id);
+
+    Ok(())
+}
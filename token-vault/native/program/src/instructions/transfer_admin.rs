@@ -0,0 +1,50 @@
+use super::shared::load_vault_config;
+use crate::error::VaultError;
+use crate::header::store;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// First step of an admin transfer, the current admin proposes the new admin.
+/// Proposing `Pubkey::default()` cancels a pending transfer.
+///
+/// Accounts:
+/// [signer admin]
+/// [writable vault_config]
+/// [readonly token_mint]
+pub fn transfer_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let admin = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !admin.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_config.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let (vault_pda, _vault_bump) =
+        Pubkey::find_program_address(&[b"vault", token_mint.key.as_ref()], program_id);
+    let mut vault_config_data = load_vault_config(program_id, vault_config, &vault_pda)?;
+    if vault_config_data.admin != *admin.key {
+        return Err(VaultError::Unauthorized.into());
+    }
+
+    msg!("Proposing {} as new vault admin", new_admin);
+    vault_config_data.pending_admin = new_admin;
+    store(vault_config, &vault_config_data)?;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use super::shared::load_registry_mut;
+use crate::error::VaultError;
+use crate::state::RegistryEntry;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Remove a vault from the registry. Deposits into it are refused afterwards,
+/// withdrawals keep working so users can still exit.
+///
+/// Accounts:
+/// [signer admin]
+/// [writable registry]
+/// [readonly token_mint]
+pub fn unregister_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let admin = next_account_info(account_info_iter)?;
+    let registry = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !admin.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !registry.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let mut registry_data = load_registry_mut(program_id, registry)?;
+    if registry_data.admin != *admin.key {
+        return Err(VaultError::Unauthorized.into());
+    }
+    let index = registry_data
+        .vaults()
+        .iter()
+        .position(|entry| entry.mint == *token_mint.key)
+        .ok_or(VaultError::MintNotRegistered)?;
+
+    // Move the last entry into the freed slot so the used entries stay contiguous
+    msg!("Unregistering the vault of mint {}", token_mint.key);
+    let last = u32::from(registry_data.count) as usize - 1;
+    registry_data.entries[index] = registry_data.entries[last];
+    registry_data.entries[last] = RegistryEntry::default();
+    registry_data.count = (last as u32).into();
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::error::VaultError;
+use crate::header::{AccountKind, upgrade};
+use crate::state::{UserState, VaultConfig, VaultState, WithdrawTicket};
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+
+/// Rewrite an account created by an older version of the program with the
+/// current header and layout. The account keeps its address and balance, the
+/// payer covers the extra rent when it grows.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable account]
+/// [readonly system program]
+pub fn upgrade_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    kind: AccountKind,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let account = next_account_info(account_info_iter)?;
+    let system_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !payer.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable || !account.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    match kind {
+        AccountKind::VaultState => upgrade::<VaultState>(program_id, account, payer, system_prog),
+        AccountKind::VaultConfig => upgrade::<VaultConfig>(program_id, account, payer, system_prog),
+        AccountKind::UserState => upgrade::<UserState>(program_id, account, payer, system_prog),
+        AccountKind::WithdrawTicket => {
+            upgrade::<WithdrawTicket>(program_id, account, payer, system_prog)
+        }
+    }
+}
@@ -1,27 +1,38 @@
+use super::shared::{
+    check_withdraw_allowed, close_account, load_position, load_vault_config, load_vault_state, load_vault_state_mut,
+    record_outflow, transfer_from_vault,
+};
 use crate::error::VaultError;
-use crate::state::UserState;
+use crate::state::UNLIMITED_ALLOWANCE;
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::{invoke_signed},
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
-use spl_token::{instruction as token_instruction};
 
 
+/// The payer is either the owner of the position or its withdraw delegate,
+/// a delegate with a capped allowance uses it up. When `close_if_empty` is
+/// set and the withdrawal drains the position, the user state is closed and
+/// its rent returned to the owner, which only the owner can do.
+///
 /// Accounts:
-/// [signer payer]
+/// [signer writable payer]
 /// [writable user_ata]
 /// [writable vault_ata]
 /// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
 /// [readonly token_mint]
 /// [readonly token program]
 pub fn withdraw_tokens(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     withdraw_amount: u64,
+    close_if_empty: bool,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
@@ -29,6 +40,8 @@ pub fn withdraw_tokens(
     let user_ata = next_account_info(account_info_iter)?;
     let vault_ata = next_account_info(account_info_iter)?;
     let user_state = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
     let token_mint = next_account_info(account_info_iter)?;
     let token_prog = next_account_info(account_info_iter)?;
 
@@ -36,49 +49,95 @@ pub fn withdraw_tokens(
     if !payer.is_signer {
         return Err(VaultError::NotSigner.into());
     }
-    if !user_state.is_writable || !user_ata.is_writable || !vault_ata.is_writable {
+    if !user_state.is_writable
+        || !user_ata.is_writable
+        || !vault_ata.is_writable
+        || !vault_state.is_writable
+    {
         return Err(VaultError::NotWritable.into());
     }
+    if withdraw_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
 
-    // Derive a PDA for user's state
-    let (state_pda, state_bump) = Pubkey::find_program_address(
-        &[b"user", payer.key.as_ref(), token_mint.key.as_ref()],
-        program_id,
-    );
+    // Enforce the limits set by the vault admin
+    let withdraw_cooldown =
+        i64::from(load_vault_state(program_id, vault_state, token_mint.key)?.withdraw_cooldown);
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key)?;
+    check_withdraw_allowed(&vault_config_data, withdraw_amount)?;
 
-    // Transfer tokens from vault ATA to user ATA
-    msg!("Transferring tokens from vault ATA to user ATA");
-    // Create the transfer instruction
-    let transfer_ix = token_instruction::transfer(
-        token_prog.key,
-        vault_ata.key,
-        user_ata.key,
-        payer.key,
-        &[payer.key],
-        withdraw_amount, // Amount to transfer, this should be parsed from instruction_data
-    )?;
+    // Vaults with a cooldown only pay out through withdrawal tickets
+    if withdraw_cooldown > 0 {
+        return Err(VaultError::InstantWithdrawDisabled.into());
+    }
+
+    // Verify and load the user's state PDA, then check the payer may withdraw from it
+    let mut user_state_data = load_position(program_id, user_state, token_mint.key)?;
+    if user_state_data.user != *payer.key {
+        if !user_state_data.has_delegate() || user_state_data.delegate != *payer.key {
+            return Err(VaultError::Unauthorized.into());
+        }
+        if close_if_empty {
+            return Err(VaultError::Unauthorized.into());
+        }
+        let allowance = u64::from(user_state_data.delegate_allowance);
+        if allowance != UNLIMITED_ALLOWANCE {
+            let allowance = allowance
+                .checked_sub(withdraw_amount)
+                .ok_or(VaultError::DelegateAllowanceExceeded)?;
+            user_state_data.delegate_allowance = allowance.into();
+        }
+        msg!("Withdrawing as delegate of {}", user_state_data.user);
+    }
 
-    // Invoke the transfer instruction
-    invoke_signed(&transfer_ix,
-    &[
-        user_ata.clone(),
-        vault_ata.clone(),
-        payer.clone(),
-        token_mint.clone(),
-        token_prog.clone(),
-    ],
+    // Credit the fees earned so far, take the amount out of the vault's deposits
+    // and count it against the rate limits. The vault state signs the transfer,
+    // so its view is dropped before it.
+    let now = Clock::get()?.unix_timestamp;
+    {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+        vault_state_data.settle_fees(&mut user_state_data)?;
+        if u64::from(user_state_data.amount) >= withdraw_amount {
+            vault_state_data.sub_deposits(withdraw_amount)?;
+        }
+        record_outflow(
+            &vault_config_data,
+            &mut vault_state_data,
+            &mut user_state_data,
+            withdraw_amount,
+            now,
+        )?;
+    }
+    let amount = u64::from(user_state_data.amount);
+    if amount < withdraw_amount {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    if user_state_data.is_locked(now) {
+        msg!("Position is locked until {}", i64::from(user_state_data.unlock_ts));
+        return Err(VaultError::PositionLocked.into());
+    }
 
-    &[&[state_pda, payer.key.as_ref(), token_mint.key.as_ref(), &state_bump]]
+    // Transfer tokens from vault ATA to user ATA
+    msg!("Transferring tokens from vault ATA to user ATA");
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        user_ata,
+        vault_state,
+        token_mint,
+        token_prog,
+        withdraw_amount,
     )?;
 
     // Update user state
     msg!("Updating user state after withdrawal");
-    let mut user_state_data = UserState::try_from_slice(&user_state.data.borrow())
-        .map_err(|_| VaultError::InvalidUserState)?;
-    user_state_data -= withdraw_amount;
-    user_state_data.serialize(&mut &mut user_state.data.borrow_mut()[..])
-        .map_err(|_| VaultError::SerializationError)?;
+    user_state_data.amount = (amount - withdraw_amount).into();
+    if close_if_empty && amount == withdraw_amount && u64::from(user_state_data.earmarked) == 0 {
+        msg!("Position drained, closing user state");
+        drop(user_state_data);
+        return close_account(user_state, payer);
+    }
     msg!("Withdrawal successful, user state updated");
 
     Ok(())
-}
\ No newline at end of file
+}
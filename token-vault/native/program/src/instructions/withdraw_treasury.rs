@@ -0,0 +1,80 @@
+use super::shared::{
+    check_vault_ata, load_vault_config, load_vault_state, load_vault_state_mut,
+    transfer_from_vault,
+};
+use crate::error::VaultError;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+
+/// Pay out early exit penalties collected in the treasury to a token account
+/// of the admin's choosing
+///
+/// Accounts:
+/// [signer admin]
+/// [writable destination]
+/// [writable vault_ata]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn withdraw_treasury(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let admin = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
+    let vault_ata = next_account_info(account_info_iter)?;
+    let vault_state = next_account_info(account_info_iter)?;
+    let vault_config = next_account_info(account_info_iter)?;
+    let token_mint = next_account_info(account_info_iter)?;
+    let token_prog = next_account_info(account_info_iter)?;
+
+    // Basic checks
+    if !admin.is_signer {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !destination.is_writable || !vault_ata.is_writable || !vault_state.is_writable {
+        return Err(VaultError::NotWritable.into());
+    }
+    if amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    let vault_state_data = load_vault_state(program_id, vault_state, token_mint.key)?;
+    check_vault_ata(vault_ata, vault_state.key, token_mint.key, token_prog.key)?;
+    if load_vault_config(program_id, vault_config, vault_state.key)?.admin != *admin.key {
+        return Err(VaultError::Unauthorized.into());
+    }
+    // Lent out tokens are not in the vault ATA until the loan is repaid
+    if u64::from(vault_state_data.flash_loan_amount) != 0 {
+        return Err(VaultError::FlashLoanInProgress.into());
+    }
+    if u64::from(vault_state_data.treasury) < amount {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    drop(vault_state_data);
+
+    msg!("Withdrawing {} tokens from the treasury", amount);
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        destination,
+        vault_state,
+        token_mint,
+        token_prog,
+        amount,
+    )?;
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key)?;
+    vault_state_data.treasury = (u64::from(vault_state_data.treasury) - amount).into();
+
+    Ok(())
+}
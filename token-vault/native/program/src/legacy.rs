@@ -0,0 +1,550 @@
+//! Account layouts of the earlier token vault programs in `token-vault/src`,
+//! only used to migrate their user positions into `UserState`, and the
+//! older layouts of this program's own accounts, upgraded with `UpgradeAccount`.
+//!
+//! The legacy accounts belong to the program they were written by, the
+//! `src/lib.rs` and `src/lib2.rs` programs declare [`LEGACY_PROGRAM_ID`].
+//! `MigrateUserState` can only close an account this program owns, so the
+//! migration runs with this program deployed as an upgrade of the legacy
+//! program at its address. Deployed anywhere else no legacy account passes
+//! the checks. The legacy `[b"vault", mint]` PDA is the vault state PDA of
+//! this program as well, the legacy tokens are in the vault ATA already.
+//!
+//! Positions of the `src/new/lib.rs` program are not migrated. Its
+//! `[b"vault", mint]` account is a 40 byte `{vault_mint, total_deposits}`
+//! which no version of `VaultState` decodes, and its tokens sit in the ATA of
+//! a `[b"vault_auth", vault_state]` PDA this program never signs for, so none
+//! of its balances could be backed by the vault ATA.
+
+use crate::error::VaultError;
+use crate::state::{RateWindow, UNSYNCED_FEE_INDEX};
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use solana_program::{pubkey, pubkey::Pubkey};
+use spl_pod::primitives::{PodI64, PodU16, PodU64, PodU128};
+
+/// Program id the legacy programs declare, the owner of their accounts
+pub const LEGACY_PROGRAM_ID: Pubkey = pubkey!("CDbgE8B3ZRfoKKUhCjePByGHQG2LSBd2JcTNz3eeBdMt");
+
+// Read a Pod layout, `data` may be unaligned
+fn read_pod<T: Pod>(data: &[u8], error: VaultError) -> Result<T, VaultError> {
+    bytemuck::try_pod_read_unaligned(data).map_err(|_| error)
+}
+
+/// Layout a legacy user account was written with
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyLayout {
+    /// `token-vault/src/lib.rs`, stored under `[b"state", user]`
+    UserBalances,
+    /// `token-vault/src/lib2.rs`, stored under `[b"state", user]`
+    ProgramState,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UserBalances {
+    pub wallet: Pubkey,
+    pub balance: u64,
+}
+impl UserBalances {
+    // Size of this struct
+    pub const LEN: usize = 32 + 8;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ProgramState {
+    pub owner: Pubkey,
+    pub balance: u64,
+}
+impl ProgramState {
+    // Size of this struct
+    pub const LEN: usize = 32 + 8;
+}
+
+/// `VaultState` before account headers, upgraded with `UpgradeAccount`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct VaultStateV0 {
+    pub mint: Pubkey,
+    pub early_exit_penalty_bps: u16,
+    pub treasury: u64,
+    pub is_initialized: bool,
+    pub withdraw_cooldown: i64,
+    pub next_ticket_id: u64,
+    pub queued_total: u64,
+    pub claimed_total: u64,
+}
+
+/// `VaultConfig` before account headers, upgraded with `UpgradeAccount`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct VaultConfigV0 {
+    pub vault: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub user_deposit_cap: u64,
+    pub tvl_cap: u64,
+    pub min_deposit: u64,
+    pub min_withdraw: u64,
+    pub deposit_paused: bool,
+    pub withdraw_paused: bool,
+    pub is_initialized: bool,
+}
+
+/// `VaultConfig` version 1, before withdrawal rate limits
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct VaultConfigV1 {
+    pub vault: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub user_deposit_cap: u64,
+    pub tvl_cap: u64,
+    pub min_deposit: u64,
+    pub min_withdraw: u64,
+    pub deposit_paused: bool,
+    pub withdraw_paused: bool,
+}
+
+impl TryFrom<VaultConfigV0> for VaultConfigV1 {
+    type Error = VaultError;
+
+    fn try_from(v0: VaultConfigV0) -> Result<Self, VaultError> {
+        if !v0.is_initialized {
+            return Err(VaultError::InvalidVaultConfig);
+        }
+        Ok(VaultConfigV1 {
+            vault: v0.vault,
+            admin: v0.admin,
+            pending_admin: v0.pending_admin,
+            user_deposit_cap: v0.user_deposit_cap,
+            tvl_cap: v0.tvl_cap,
+            min_deposit: v0.min_deposit,
+            min_withdraw: v0.min_withdraw,
+            deposit_paused: v0.deposit_paused,
+            withdraw_paused: v0.withdraw_paused,
+        })
+    }
+}
+
+/// `VaultState` version 1, borsh encoded behind the account header
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct VaultStateV1 {
+    pub mint: Pubkey,
+    pub early_exit_penalty_bps: u16,
+    pub treasury: u64,
+    pub withdraw_cooldown: i64,
+    pub next_ticket_id: u64,
+    pub queued_total: u64,
+    pub claimed_total: u64,
+}
+
+impl TryFrom<VaultStateV0> for VaultStateV1 {
+    type Error = VaultError;
+
+    fn try_from(v0: VaultStateV0) -> Result<Self, VaultError> {
+        if !v0.is_initialized {
+            return Err(VaultError::InvalidVaultState);
+        }
+        Ok(VaultStateV1 {
+            mint: v0.mint,
+            early_exit_penalty_bps: v0.early_exit_penalty_bps,
+            treasury: v0.treasury,
+            withdraw_cooldown: v0.withdraw_cooldown,
+            next_ticket_id: v0.next_ticket_id,
+            queued_total: v0.queued_total,
+            claimed_total: v0.claimed_total,
+        })
+    }
+}
+
+/// `VaultState` version 2, the first Pod layout
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct VaultStateV2 {
+    pub mint: Pubkey,
+    pub early_exit_penalty_bps: PodU16,
+    pub treasury: PodU64,
+    pub withdraw_cooldown: PodI64,
+    pub next_ticket_id: PodU64,
+    pub queued_total: PodU64,
+    pub claimed_total: PodU64,
+}
+
+impl VaultStateV1 {
+    /// Decode version 1 or the unversioned layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        let invalid = |_| VaultError::InvalidVaultState;
+        match version {
+            None => VaultStateV0::try_from_slice(data).map_err(invalid)?.try_into(),
+            Some(1) => VaultStateV1::try_from_slice(data).map_err(invalid),
+            Some(_) => Err(VaultError::UnsupportedAccountVersion),
+        }
+    }
+}
+
+impl From<VaultStateV1> for VaultStateV2 {
+    fn from(v1: VaultStateV1) -> Self {
+        VaultStateV2 {
+            mint: v1.mint,
+            early_exit_penalty_bps: v1.early_exit_penalty_bps.into(),
+            treasury: v1.treasury.into(),
+            withdraw_cooldown: v1.withdraw_cooldown.into(),
+            next_ticket_id: v1.next_ticket_id.into(),
+            queued_total: v1.queued_total.into(),
+            claimed_total: v1.claimed_total.into(),
+        }
+    }
+}
+
+/// `VaultState` version 3, before withdrawal rate limits
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct VaultStateV3 {
+    pub mint: Pubkey,
+    pub early_exit_penalty_bps: PodU16,
+    pub treasury: PodU64,
+    pub withdraw_cooldown: PodI64,
+    pub next_ticket_id: PodU64,
+    pub queued_total: PodU64,
+    pub claimed_total: PodU64,
+    pub flash_fee_bps: PodU16,
+    pub flash_loan_amount: PodU64,
+    pub total_deposits: PodU64,
+    pub fee_index: PodU128,
+}
+
+impl VaultStateV2 {
+    /// Decode version 2 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(2) => read_pod(data, VaultError::InvalidVaultState),
+            _ => Ok(VaultStateV1::from_version(version, data)?.into()),
+        }
+    }
+}
+
+// Positions are counted into total_deposits as their users sync them
+impl From<VaultStateV2> for VaultStateV3 {
+    fn from(v2: VaultStateV2) -> Self {
+        VaultStateV3 {
+            mint: v2.mint,
+            early_exit_penalty_bps: v2.early_exit_penalty_bps,
+            treasury: v2.treasury,
+            withdraw_cooldown: v2.withdraw_cooldown,
+            next_ticket_id: v2.next_ticket_id,
+            queued_total: v2.queued_total,
+            claimed_total: v2.claimed_total,
+            flash_fee_bps: PodU16::from(0),
+            flash_loan_amount: PodU64::from(0),
+            total_deposits: PodU64::from(0),
+            fee_index: PodU128::from(0),
+        }
+    }
+}
+
+impl VaultStateV3 {
+    /// Decode version 3 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(3) => read_pod(data, VaultError::InvalidVaultState),
+            _ => Ok(VaultStateV2::from_version(version, data)?.into()),
+        }
+    }
+}
+
+/// `VaultState` version 4, before tickets were funded in queue order
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct VaultStateV4 {
+    pub mint: Pubkey,
+    pub early_exit_penalty_bps: PodU16,
+    pub treasury: PodU64,
+    pub withdraw_cooldown: PodI64,
+    pub next_ticket_id: PodU64,
+    pub queued_total: PodU64,
+    pub claimed_total: PodU64,
+    pub flash_fee_bps: PodU16,
+    pub flash_loan_amount: PodU64,
+    pub total_deposits: PodU64,
+    pub fee_index: PodU128,
+    pub outflow: RateWindow,
+}
+
+impl From<VaultStateV3> for VaultStateV4 {
+    fn from(v3: VaultStateV3) -> Self {
+        VaultStateV4 {
+            mint: v3.mint,
+            early_exit_penalty_bps: v3.early_exit_penalty_bps,
+            treasury: v3.treasury,
+            withdraw_cooldown: v3.withdraw_cooldown,
+            next_ticket_id: v3.next_ticket_id,
+            queued_total: v3.queued_total,
+            claimed_total: v3.claimed_total,
+            flash_fee_bps: v3.flash_fee_bps,
+            flash_loan_amount: v3.flash_loan_amount,
+            total_deposits: v3.total_deposits,
+            fee_index: v3.fee_index,
+            outflow: RateWindow::default(),
+        }
+    }
+}
+
+impl VaultStateV4 {
+    /// Decode version 4 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(4) => read_pod(data, VaultError::InvalidVaultState),
+            _ => Ok(VaultStateV3::from_version(version, data)?.into()),
+        }
+    }
+}
+
+/// `UserState` before account headers, upgraded with `UpgradeAccount`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UserStateV0 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub is_initialized: bool,
+    pub unlock_ts: i64,
+    pub boost_bps: u16,
+}
+
+/// `UserState` version 1, borsh encoded behind the account header
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UserStateV1 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub unlock_ts: i64,
+    pub boost_bps: u16,
+}
+
+impl TryFrom<UserStateV0> for UserStateV1 {
+    type Error = VaultError;
+
+    fn try_from(v0: UserStateV0) -> Result<Self, VaultError> {
+        if !v0.is_initialized {
+            return Err(VaultError::InvalidUserState);
+        }
+        Ok(UserStateV1 {
+            user: v0.user,
+            mint: v0.mint,
+            amount: v0.amount,
+            unlock_ts: v0.unlock_ts,
+            boost_bps: v0.boost_bps,
+        })
+    }
+}
+
+/// `UserState` version 2, the first Pod layout
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UserStateV2 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: PodU64,
+    pub unlock_ts: PodI64,
+    pub boost_bps: PodU16,
+}
+
+impl UserStateV1 {
+    /// Decode version 1 or the unversioned layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        let invalid = |_| VaultError::InvalidUserState;
+        match version {
+            None => UserStateV0::try_from_slice(data).map_err(invalid)?.try_into(),
+            Some(1) => UserStateV1::try_from_slice(data).map_err(invalid),
+            Some(_) => Err(VaultError::UnsupportedAccountVersion),
+        }
+    }
+}
+
+impl From<UserStateV1> for UserStateV2 {
+    fn from(v1: UserStateV1) -> Self {
+        UserStateV2 {
+            user: v1.user,
+            mint: v1.mint,
+            amount: v1.amount.into(),
+            unlock_ts: v1.unlock_ts.into(),
+            boost_bps: v1.boost_bps.into(),
+        }
+    }
+}
+
+/// `UserState` version 3, before withdraw delegates
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UserStateV3 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: PodU64,
+    pub unlock_ts: PodI64,
+    pub boost_bps: PodU16,
+    pub fee_index: PodU128,
+}
+
+impl UserStateV2 {
+    /// Decode version 2 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(2) => read_pod(data, VaultError::InvalidUserState),
+            _ => Ok(UserStateV1::from_version(version, data)?.into()),
+        }
+    }
+}
+
+impl From<UserStateV2> for UserStateV3 {
+    fn from(v2: UserStateV2) -> Self {
+        UserStateV3 {
+            user: v2.user,
+            mint: v2.mint,
+            amount: v2.amount,
+            unlock_ts: v2.unlock_ts,
+            boost_bps: v2.boost_bps,
+            fee_index: UNSYNCED_FEE_INDEX.into(),
+        }
+    }
+}
+
+/// `UserState` version 4, before withdrawal rate limits
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UserStateV4 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: PodU64,
+    pub unlock_ts: PodI64,
+    pub boost_bps: PodU16,
+    pub fee_index: PodU128,
+    pub delegate: Pubkey,
+    pub delegate_allowance: PodU64,
+}
+
+impl UserStateV3 {
+    /// Decode version 3 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(3) => read_pod(data, VaultError::InvalidUserState),
+            _ => Ok(UserStateV2::from_version(version, data)?.into()),
+        }
+    }
+}
+
+impl From<UserStateV3> for UserStateV4 {
+    fn from(v3: UserStateV3) -> Self {
+        UserStateV4 {
+            user: v3.user,
+            mint: v3.mint,
+            amount: v3.amount,
+            unlock_ts: v3.unlock_ts,
+            boost_bps: v3.boost_bps,
+            fee_index: v3.fee_index,
+            delegate: Pubkey::default(),
+            delegate_allowance: PodU64::from(0),
+        }
+    }
+}
+
+impl UserStateV4 {
+    /// Decode version 4 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(4) => read_pod(data, VaultError::InvalidUserState),
+            _ => Ok(UserStateV3::from_version(version, data)?.into()),
+        }
+    }
+}
+
+/// `UserState` version 5, before escrow offers
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UserStateV5 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: PodU64,
+    pub unlock_ts: PodI64,
+    pub boost_bps: PodU16,
+    pub fee_index: PodU128,
+    pub delegate: Pubkey,
+    pub delegate_allowance: PodU64,
+    pub outflow: RateWindow,
+}
+
+impl From<UserStateV4> for UserStateV5 {
+    fn from(v4: UserStateV4) -> Self {
+        UserStateV5 {
+            user: v4.user,
+            mint: v4.mint,
+            amount: v4.amount,
+            unlock_ts: v4.unlock_ts,
+            boost_bps: v4.boost_bps,
+            fee_index: v4.fee_index,
+            delegate: v4.delegate,
+            delegate_allowance: v4.delegate_allowance,
+            outflow: RateWindow::default(),
+        }
+    }
+}
+
+impl UserStateV5 {
+    /// Decode version 5 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(5) => read_pod(data, VaultError::InvalidUserState),
+            _ => Ok(UserStateV4::from_version(version, data)?.into()),
+        }
+    }
+}
+
+/// `UserState` version 6, before the escrow maker bump was stored
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UserStateV6 {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: PodU64,
+    pub unlock_ts: PodI64,
+    pub boost_bps: PodU16,
+    pub fee_index: PodU128,
+    pub delegate: Pubkey,
+    pub delegate_allowance: PodU64,
+    pub outflow: RateWindow,
+    pub earmarked: PodU64,
+}
+
+impl From<UserStateV5> for UserStateV6 {
+    fn from(v5: UserStateV5) -> Self {
+        UserStateV6 {
+            user: v5.user,
+            mint: v5.mint,
+            amount: v5.amount,
+            unlock_ts: v5.unlock_ts,
+            boost_bps: v5.boost_bps,
+            fee_index: v5.fee_index,
+            delegate: v5.delegate,
+            delegate_allowance: v5.delegate_allowance,
+            outflow: v5.outflow,
+            earmarked: PodU64::from(0),
+        }
+    }
+}
+
+impl UserStateV6 {
+    /// Decode version 6 or any older layout
+    pub fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            Some(6) => read_pod(data, VaultError::InvalidUserState),
+            _ => Ok(UserStateV5::from_version(version, data)?.into()),
+        }
+    }
+}
+
+/// `WithdrawTicket` before account headers, upgraded with `UpgradeAccount`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct WithdrawTicketV0 {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub id: u64,
+    pub amount: u64,
+    pub queue_end: u64,
+    pub claimable_after: i64,
+    pub is_initialized: bool,
+}
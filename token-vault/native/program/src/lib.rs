@@ -1,10 +1,13 @@
-use solana_program::entrypoint;
-
 pub mod processor;
 pub mod instructions;
 pub mod error;
 pub mod state;
+pub mod legacy;
+pub mod header;
+pub mod escrow;
 
-use crate::processor::process_instruction;
+#[cfg(not(feature = "no-entrypoint"))]
+use {crate::processor::process_instruction, solana_program::entrypoint};
 
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
@@ -1,4 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use shank::ShankInstruction;
 use solana_program::{
     account_info::{AccountInfo},
     entrypoint::ProgramResult,
@@ -9,17 +10,307 @@ use crate::error;
 use crate::instructions::{
     initialize_vault::initialize_vault,
     initialize_user::initialize_user,
-    deposit_tokens::deposit_tokens,
+    deposit_tokens::{deposit_for, deposit_tokens},
     withdraw_tokens::withdraw_tokens,
-
+    withdraw_early::withdraw_early,
+    request_withdraw::request_withdraw,
+    claim_withdraw::claim_withdraw,
+    update_config::update_config,
+    transfer_admin::transfer_admin,
+    accept_admin::accept_admin,
+    close_user::close_user,
+    migrate_user_state::migrate_user_state,
+    upgrade_account::upgrade_account,
+    initialize_registry::initialize_registry,
+    register_vault::register_vault,
+    unregister_vault::unregister_vault,
+    flash_loan::flash_loan,
+    flash_repay::flash_repay,
+    set_flash_loan_fee::set_flash_loan_fee,
+    set_delegate::set_delegate,
+    revoke_delegate::revoke_delegate,
+    reconcile::reconcile,
+    make_escrow_offer::make_escrow_offer,
+    take_escrow_offer::take_escrow_offer,
+    refund_escrow_offer::refund_escrow_offer,
+    withdraw_treasury::withdraw_treasury,
 };
+use crate::header::AccountKind;
+use crate::legacy::LegacyLayout;
+use crate::state::{ConfigParams, LockupTier};
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// Instructions of the vault, borsh encoded with the variant index as the
+/// first byte. The `#[account]` attributes list the accounts of each
+/// instruction in order, the IDL in `token-vault/native/idl` is generated from
+/// them and from the account structs deriving `ShankAccount`.
+#[rustfmt::skip]
+#[derive(Debug, BorshSerialize, BorshDeserialize, ShankInstruction)]
 pub enum VaultInstruction {
-    InitializeVault,
+    /// Create the vault state, config and ATA of a mint, the payer becomes the admin
+    #[account(0, writable, signer, name = "payer", desc = "Registry admin, or upgrade authority of the program")]
+    #[account(1, writable, name = "vault_state")]
+    #[account(2, writable, name = "vault_config")]
+    #[account(3, writable, name = "vault_ata", desc = "ATA of the vault state, holds the deposits")]
+    #[account(4, name = "token_mint")]
+    #[account(5, name = "token_program")]
+    #[account(6, name = "ata_program")]
+    #[account(7, name = "system_program")]
+    #[account(8, name = "registry", desc = "May not exist yet")]
+    #[account(9, name = "program_data", desc = "ProgramData account of the program, holds the upgrade authority")]
+    InitializeVault { early_exit_penalty_bps: u16, withdraw_cooldown: i64 },
+
+    /// Create the payer's user state in the vault of the mint
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, name = "token_mint")]
+    #[account(3, name = "token_program")]
+    #[account(4, name = "system_program")]
+    #[account(5, name = "rent")]
     InitializeUser,
-    Deposit { amount: u64 },
-    Withdraw { amount: u64 },
+
+    /// Deposit into the payer's own position and lock it for the chosen tier
+    #[account(0, signer, name = "payer")]
+    #[account(1, writable, name = "user_ata")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "user_state")]
+    #[account(4, writable, name = "vault_state")]
+    #[account(5, name = "vault_config")]
+    #[account(6, name = "registry")]
+    #[account(7, name = "token_mint")]
+    #[account(8, name = "token_program")]
+    Deposit { amount: u64, lockup: LockupTier },
+
+    /// Withdraw from a position as its owner or its delegate
+    #[account(0, writable, signer, name = "payer", desc = "Owner or delegate of the position, gets the rent back when it closes")]
+    #[account(1, writable, name = "user_ata", desc = "ATA of the payer")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "user_state")]
+    #[account(4, writable, name = "vault_state")]
+    #[account(5, name = "vault_config")]
+    #[account(6, name = "token_mint")]
+    #[account(7, name = "token_program")]
+    Withdraw { amount: u64, close_if_empty: bool },
+
+    /// Withdraw from a locked position, paying the early exit penalty
+    #[account(0, signer, name = "payer")]
+    #[account(1, writable, name = "user_ata")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "user_state")]
+    #[account(4, writable, name = "vault_state")]
+    #[account(5, name = "vault_config")]
+    #[account(6, name = "token_mint")]
+    #[account(7, name = "token_program")]
+    WithdrawEarly { amount: u64 },
+
+    /// Move an amount of the position into a withdrawal ticket
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, writable, name = "vault_state")]
+    #[account(3, name = "vault_config")]
+    #[account(4, writable, name = "ticket", desc = "Ticket PDA with the vault's next ticket id")]
+    #[account(5, name = "token_mint")]
+    #[account(6, name = "system_program")]
+    RequestWithdraw { amount: u64 },
+
+    /// Pay out a withdrawal ticket once its cooldown has passed and close it
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_ata")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "vault_state")]
+    #[account(4, name = "vault_config")]
+    #[account(5, writable, name = "ticket")]
+    #[account(6, name = "token_mint")]
+    #[account(7, name = "token_program")]
+    ClaimWithdraw,
+
+    /// Change the caps, limits and pauses of the vault and its early exit penalty
+    #[account(0, signer, name = "admin")]
+    #[account(1, writable, name = "vault_config")]
+    #[account(2, name = "token_mint")]
+    #[account(3, writable, name = "vault_state")]
+    UpdateConfig { params: ConfigParams },
+
+    /// Propose a new admin, `Pubkey::default()` cancels a pending transfer
+    #[account(0, signer, name = "admin")]
+    #[account(1, writable, name = "vault_config")]
+    #[account(2, name = "token_mint")]
+    TransferAdmin { new_admin: Pubkey },
+
+    /// Accept the admin role proposed by the current admin
+    #[account(0, signer, name = "pending_admin")]
+    #[account(1, writable, name = "vault_config")]
+    #[account(2, name = "token_mint")]
+    AcceptAdmin,
+
+    /// Close an empty user state and return its rent to the user
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, name = "token_mint")]
+    CloseUser,
+
+    /// Move a position from a legacy vault account into the user's user state
+    #[account(0, writable, signer, name = "authority", desc = "Upgrade authority of the program")]
+    #[account(1, writable, name = "user")]
+    #[account(2, writable, name = "legacy_state", desc = "Owned by this program, deployed at the legacy program's address")]
+    #[account(3, writable, name = "user_state")]
+    #[account(4, writable, name = "vault_state")]
+    #[account(5, name = "vault_ata", desc = "Must hold the migrated balance")]
+    #[account(6, name = "token_mint")]
+    #[account(7, name = "token_program")]
+    #[account(8, name = "system_program")]
+    #[account(9, name = "program_data", desc = "ProgramData account of the program, holds the upgrade authority")]
+    MigrateUserState { layout: LegacyLayout },
+
+    /// Rewrite an account of an older program version with the current layout
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "account")]
+    #[account(2, name = "system_program")]
+    UpgradeAccount { kind: AccountKind },
+
+    /// Create the vault registry, the payer becomes its admin
+    #[account(0, writable, signer, name = "payer", desc = "Upgrade authority of the program")]
+    #[account(1, writable, name = "registry")]
+    #[account(2, name = "system_program")]
+    #[account(3, name = "program_data", desc = "ProgramData account of the program, holds the upgrade authority")]
+    InitializeRegistry,
+
+    /// Add a vault to the registry, deposits into it are accepted from then on
+    #[account(0, signer, name = "admin", desc = "Admin of the registry")]
+    #[account(1, writable, name = "registry")]
+    #[account(2, name = "vault_state")]
+    #[account(3, name = "vault_ata")]
+    #[account(4, name = "token_mint")]
+    #[account(5, name = "token_program")]
+    RegisterVault,
+
+    /// Remove a vault from the registry, withdrawals keep working
+    #[account(0, signer, name = "admin", desc = "Admin of the registry")]
+    #[account(1, writable, name = "registry")]
+    #[account(2, name = "token_mint")]
+    UnregisterVault,
+
+    /// Lend from the vault ATA, a `FlashRepay` must follow in the same transaction
+    #[account(0, signer, name = "borrower")]
+    #[account(1, writable, name = "vault_ata")]
+    #[account(2, writable, name = "borrower_ata")]
+    #[account(3, writable, name = "vault_state")]
+    #[account(4, name = "vault_config")]
+    #[account(5, name = "token_mint")]
+    #[account(6, name = "token_program")]
+    #[account(7, name = "instructions_sysvar")]
+    FlashLoan { amount: u64 },
+
+    /// Pay back the open flash loan plus its fee
+    #[account(0, signer, name = "borrower")]
+    #[account(1, writable, name = "borrower_ata")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "vault_state")]
+    #[account(4, name = "token_mint")]
+    #[account(5, name = "token_program")]
+    FlashRepay { amount: u64 },
+
+    /// Set the fee charged on flash loans, zero disables them
+    #[account(0, signer, name = "admin")]
+    #[account(1, writable, name = "vault_state")]
+    #[account(2, name = "vault_config")]
+    #[account(3, name = "token_mint")]
+    SetFlashLoanFee { fee_bps: u16 },
+
+    /// Deposit from the payer's ATA into the position of `owner`
+    #[account(0, signer, name = "payer")]
+    #[account(1, writable, name = "user_ata", desc = "ATA of the payer")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "user_state", desc = "User state of the owner")]
+    #[account(4, writable, name = "vault_state")]
+    #[account(5, name = "vault_config")]
+    #[account(6, name = "registry")]
+    #[account(7, name = "token_mint")]
+    #[account(8, name = "token_program")]
+    DepositFor { owner: Pubkey, amount: u64 },
+
+    /// Let another key withdraw from the position, up to `allowance` when set
+    #[account(0, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, name = "token_mint")]
+    SetDelegate { delegate: Pubkey, allowance: Option<u64> },
+
+    /// Remove the withdraw delegate of the position
+    #[account(0, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, name = "token_mint")]
+    RevokeDelegate,
+
+    /// Compare what the vault owes with its ATA balance, anyone can call it
+    #[account(0, name = "vault_state")]
+    #[account(1, name = "vault_ata")]
+    #[account(2, writable, name = "vault_config", desc = "Only written with `pause_if_insolvent`, can be readonly otherwise")]
+    #[account(3, name = "token_mint")]
+    #[account(4, name = "token_program")]
+    Reconcile { pause_if_insolvent: bool },
+
+    /// Post an escrow offer from the payer's position
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, writable, name = "vault_state")]
+    #[account(3, name = "vault_config")]
+    #[account(4, writable, name = "vault_ata")]
+    #[account(5, writable, name = "escrow_maker", desc = "PDA making the offers of the position")]
+    #[account(6, writable, name = "maker_ata_a", desc = "ATA of the escrow maker for mint A")]
+    #[account(7, writable, name = "offer")]
+    #[account(8, writable, name = "offer_vault_ata")]
+    #[account(9, name = "token_mint_a", desc = "Mint of the vault")]
+    #[account(10, name = "token_mint_b")]
+    #[account(11, name = "token_program")]
+    #[account(12, name = "ata_program")]
+    #[account(13, name = "system_program")]
+    #[account(14, name = "escrow_program")]
+    MakeEscrowOffer { id: u64, amount: u64, wanted_amount: u64 },
+
+    /// Take a whole escrow offer posted from a position, the owner gets mint B
+    #[account(0, writable, signer, name = "taker")]
+    #[account(1, writable, name = "user_state", desc = "User state of the owner")]
+    #[account(2, writable, name = "escrow_maker")]
+    #[account(3, writable, name = "owner")]
+    #[account(4, writable, name = "taker_ata_a")]
+    #[account(5, writable, name = "taker_ata_b")]
+    #[account(6, writable, name = "maker_ata_b", desc = "ATA of the escrow maker for mint B")]
+    #[account(7, writable, name = "owner_ata_b")]
+    #[account(8, writable, name = "offer")]
+    #[account(9, writable, name = "offer_vault_ata")]
+    #[account(10, name = "token_mint_a")]
+    #[account(11, name = "token_mint_b")]
+    #[account(12, name = "token_program")]
+    #[account(13, name = "ata_program")]
+    #[account(14, name = "system_program")]
+    #[account(15, name = "escrow_program")]
+    TakeEscrowOffer { id: u64 },
+
+    /// Cancel an escrow offer, the tokens go back into the position
+    #[account(0, writable, signer, name = "payer")]
+    #[account(1, writable, name = "user_state")]
+    #[account(2, writable, name = "vault_state")]
+    #[account(3, writable, name = "vault_ata")]
+    #[account(4, writable, name = "escrow_maker")]
+    #[account(5, writable, name = "maker_ata_a")]
+    #[account(6, writable, name = "offer")]
+    #[account(7, writable, name = "offer_vault_ata")]
+    #[account(8, name = "token_mint_a")]
+    #[account(9, name = "token_mint_b")]
+    #[account(10, name = "token_program")]
+    #[account(11, name = "ata_program")]
+    #[account(12, name = "system_program")]
+    #[account(13, name = "escrow_program")]
+    RefundEscrowOffer { id: u64 },
+
+    /// Pay out early exit penalties from the treasury
+    #[account(0, signer, name = "admin")]
+    #[account(1, writable, name = "destination", desc = "Token account receiving the tokens")]
+    #[account(2, writable, name = "vault_ata")]
+    #[account(3, writable, name = "vault_state")]
+    #[account(4, name = "vault_config")]
+    #[account(5, name = "token_mint")]
+    #[account(6, name = "token_program")]
+    WithdrawTreasury { amount: u64 },
 }
 
 pub fn process_instruction(
@@ -31,9 +322,58 @@ pub fn process_instruction(
         .map_err(|_| error::VaultError::InvalidInstruction)?;
 
     match instr {
-        VaultInstruction::InitializeVault => initialize_vault(program_id, accounts, instruction_data),
-        VaultInstruction::InitializeUser => initialize_user(program_id, accounts, instruction_data),
-        VaultInstruction::Deposit { amount } => deposit_tokens(program_id, accounts, amount),
-        VaultInstruction::Withdraw { amount } => withdraw_tokens(program_id, accounts, amount),
+        VaultInstruction::InitializeVault { early_exit_penalty_bps, withdraw_cooldown } => {
+            initialize_vault(program_id, accounts, early_exit_penalty_bps, withdraw_cooldown)
+        }
+        VaultInstruction::InitializeUser => initialize_user(program_id, accounts),
+        VaultInstruction::Deposit { amount, lockup } => {
+            deposit_tokens(program_id, accounts, amount, lockup)
+        }
+        VaultInstruction::Withdraw { amount, close_if_empty } => {
+            withdraw_tokens(program_id, accounts, amount, close_if_empty)
+        }
+        VaultInstruction::WithdrawEarly { amount } => withdraw_early(program_id, accounts, amount),
+        VaultInstruction::RequestWithdraw { amount } => {
+            request_withdraw(program_id, accounts, amount)
+        }
+        VaultInstruction::ClaimWithdraw => claim_withdraw(program_id, accounts),
+        VaultInstruction::UpdateConfig { params } => update_config(program_id, accounts, params),
+        VaultInstruction::TransferAdmin { new_admin } => {
+            transfer_admin(program_id, accounts, new_admin)
+        }
+        VaultInstruction::AcceptAdmin => accept_admin(program_id, accounts),
+        VaultInstruction::CloseUser => close_user(program_id, accounts),
+        VaultInstruction::MigrateUserState { layout } => {
+            migrate_user_state(program_id, accounts, layout)
+        }
+        VaultInstruction::UpgradeAccount { kind } => upgrade_account(program_id, accounts, kind),
+        VaultInstruction::InitializeRegistry => initialize_registry(program_id, accounts),
+        VaultInstruction::RegisterVault => register_vault(program_id, accounts),
+        VaultInstruction::UnregisterVault => unregister_vault(program_id, accounts),
+        VaultInstruction::FlashLoan { amount } => flash_loan(program_id, accounts, amount),
+        VaultInstruction::FlashRepay { amount } => flash_repay(program_id, accounts, amount),
+        VaultInstruction::SetFlashLoanFee { fee_bps } => {
+            set_flash_loan_fee(program_id, accounts, fee_bps)
+        }
+        VaultInstruction::DepositFor { owner, amount } => {
+            deposit_for(program_id, accounts, owner, amount)
+        }
+        VaultInstruction::SetDelegate { delegate, allowance } => {
+            set_delegate(program_id, accounts, delegate, allowance)
+        }
+        VaultInstruction::RevokeDelegate => revoke_delegate(program_id, accounts),
+        VaultInstruction::Reconcile { pause_if_insolvent } => {
+            reconcile(program_id, accounts, pause_if_insolvent)
+        }
+        VaultInstruction::MakeEscrowOffer { id, amount, wanted_amount } => {
+            make_escrow_offer(program_id, accounts, id, amount, wanted_amount)
+        }
+        VaultInstruction::TakeEscrowOffer { id } => take_escrow_offer(program_id, accounts, id),
+        VaultInstruction::RefundEscrowOffer { id } => {
+            refund_escrow_offer(program_id, accounts, id)
+        }
+        VaultInstruction::WithdrawTreasury { amount } => {
+            withdraw_treasury(program_id, accounts, amount)
+        }
     }
 }
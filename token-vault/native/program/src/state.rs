@@ -1,14 +1,534 @@
+use crate::error::VaultError;
+use crate::escrow::escrow_maker_address;
+use crate::header::VaultAccount;
+use crate::legacy::{
+    UserStateV6, VaultConfigV0, VaultConfigV1, VaultStateV4, WithdrawTicketV0,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use shank::ShankAccount;
 use solana_program::pubkey::Pubkey;
+use spl_pod::primitives::{PodI64, PodU16, PodU32, PodU64, PodU128};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Basis points denominator used for boosts and penalties
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Scale of `VaultState::fee_index`, the fee earned per deposited token
+pub const FEE_INDEX_SCALE: u128 = 1_000_000_000_000;
+
+/// Fee index snapshot of a position which is not counted in the vault's
+/// `total_deposits` yet, set on new and upgraded user states
+pub const UNSYNCED_FEE_INDEX: u128 = u128::MAX;
+
+/// Allowance of a withdraw delegate without a cap
+pub const UNLIMITED_ALLOWANCE: u64 = u64::MAX;
+
+/// Read in place on every withdrawal, so it is a fixed size Pod layout.
+/// Integers are stored little endian with an alignment of 1.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable, ShankAccount)]
+pub struct VaultState {
+    pub mint: Pubkey,
+    // Penalty charged on early exits from a locked position, in basis points
+    pub early_exit_penalty_bps: PodU16,
+    // Penalties collected so far, these tokens stay in the vault ATA
+    pub treasury: PodU64,
+    // Seconds a withdrawal ticket waits before it can be claimed, instant withdrawals are disabled when non zero
+    pub withdraw_cooldown: PodI64,
+    // Id the next withdrawal ticket will get
+    pub next_ticket_id: PodU64,
+    // Running total of all amounts ever queued, tickets are ordered by it
+    pub queued_total: PodU64,
+    // Running total of all amounts ever paid out to tickets
+    pub claimed_total: PodU64,
+    // Running total of the queue the vault has set tokens aside for, in the
+    // order tickets were requested. Tickets ending at or before it can be
+    // claimed in any order.
+    pub funded_total: PodU64,
+    // Fee charged on flash loans, in basis points, flash loans are disabled when zero
+    pub flash_fee_bps: PodU16,
+    // Principal of the flash loan being repaid in this transaction, zero when none is open
+    pub flash_loan_amount: PodU64,
+    // Sum of the synced user positions, flash loan fees are shared pro rata over it
+    pub total_deposits: PodU64,
+    // Flash loan fees earned per deposited token so far, scaled by FEE_INDEX_SCALE
+    pub fee_index: PodU128,
+    // Withdrawals of all users in the current rate limit window
+    pub outflow: RateWindow,
+}
+impl VaultAccount for VaultState {
+    const DISCRIMINATOR: [u8; 8] = [228, 196, 82, 165, 98, 210, 235, 152];
+    const VERSION: u8 = 5;
+    // Size of this struct
+    const LEN: usize = 32 + 2 + 8 + 8 + 8 + 8 + 8 + 8 + 2 + 8 + 8 + 16 + RateWindow::LEN;
+
+    fn unpack(body: &[u8]) -> Result<Self, VaultError> {
+        bytemuck::try_pod_read_unaligned(body).map_err(|_| VaultError::InvalidAccountSize)
+    }
+
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError> {
+        *bytemuck::try_from_bytes_mut(body).map_err(|_| VaultError::InvalidAccountSize)? = *self;
+        Ok(())
+    }
+
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        let v4 = VaultStateV4::from_version(version, data)?;
+        Ok(VaultState {
+            mint: v4.mint,
+            early_exit_penalty_bps: v4.early_exit_penalty_bps,
+            treasury: v4.treasury,
+            withdraw_cooldown: v4.withdraw_cooldown,
+            next_ticket_id: v4.next_ticket_id,
+            queued_total: v4.queued_total,
+            claimed_total: v4.claimed_total,
+            // Nothing is set aside yet, the next claim funds the queue
+            funded_total: v4.claimed_total,
+            flash_fee_bps: v4.flash_fee_bps,
+            flash_loan_amount: v4.flash_loan_amount,
+            total_deposits: v4.total_deposits,
+            fee_index: v4.fee_index,
+            outflow: v4.outflow,
+        })
+    }
+
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"vault", self.mint.as_ref()], program_id)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct VaultConfig {
+    pub vault: Pubkey,
+    pub admin: Pubkey,
+    // Admin proposed by the current admin, `Pubkey::default()` when there is none
+    pub pending_admin: Pubkey,
+    // Maximum amount a single user can hold in the vault
+    pub user_deposit_cap: u64,
+    // Maximum amount of tokens the vault ATA can hold
+    pub tvl_cap: u64,
+    pub min_deposit: u64,
+    pub min_withdraw: u64,
+    pub deposit_paused: bool,
+    pub withdraw_paused: bool,
+    // Length of a withdrawal rate limit window in seconds, rate limits are off when zero
+    pub rate_limit_window: i64,
+    // Maximum a single user can withdraw per window
+    pub user_window_limit: u64,
+    // Maximum all users together can withdraw per window
+    pub vault_window_limit: u64,
+}
+impl VaultAccount for VaultConfig {
+    const DISCRIMINATOR: [u8; 8] = [99, 86, 43, 216, 184, 102, 119, 77];
+    const VERSION: u8 = 2;
+    // Size of this struct
+    const LEN: usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 8 + 8;
+
+    fn unpack(body: &[u8]) -> Result<Self, VaultError> {
+        Self::try_from_slice(body).map_err(|_| VaultError::InvalidAccountSize)
+    }
+
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError> {
+        self.serialize(&mut &mut body[..]).map_err(|_| VaultError::SerializationError)
+    }
+
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        let invalid = |_| VaultError::InvalidVaultConfig;
+        let v1 = match version {
+            None => VaultConfigV1::try_from(VaultConfigV0::try_from_slice(data).map_err(invalid)?)?,
+            Some(1) => VaultConfigV1::try_from_slice(data).map_err(invalid)?,
+            Some(_) => return Err(VaultError::UnsupportedAccountVersion),
+        };
+        Ok(VaultConfig {
+            vault: v1.vault,
+            admin: v1.admin,
+            pending_admin: v1.pending_admin,
+            user_deposit_cap: v1.user_deposit_cap,
+            tvl_cap: v1.tvl_cap,
+            min_deposit: v1.min_deposit,
+            min_withdraw: v1.min_withdraw,
+            deposit_paused: v1.deposit_paused,
+            withdraw_paused: v1.withdraw_paused,
+            rate_limit_window: 0,
+            user_window_limit: u64::MAX,
+            vault_window_limit: u64::MAX,
+        })
+    }
+
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"config", self.vault.as_ref()], program_id)
+    }
+}
+
+/// Maximum number of vaults the registry can hold
+pub const MAX_VAULTS: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct RegistryEntry {
+    pub mint: Pubkey,
+    pub vault_state: Pubkey,
+    pub vault_ata: Pubkey,
+}
+
+/// Single account listing every vault, stored under `[b"registry"]`.
+/// Clients enumerate the vaults from one fetch of it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable, ShankAccount)]
+pub struct VaultRegistry {
+    pub admin: Pubkey,
+    // Number of used entries, the rest is zeroed
+    pub count: PodU32,
+    // MAX_VAULTS entries, spelled out for the IDL generator which only reads literal lengths
+    pub entries: [RegistryEntry; 64],
+}
+impl VaultAccount for VaultRegistry {
+    const DISCRIMINATOR: [u8; 8] = [15, 54, 133, 46, 80, 169, 250, 79];
+    const VERSION: u8 = 1;
+    // Size of this struct
+    const LEN: usize = 32 + 4 + 96 * MAX_VAULTS;
+
+    fn unpack(body: &[u8]) -> Result<Self, VaultError> {
+        bytemuck::try_pod_read_unaligned(body).map_err(|_| VaultError::InvalidAccountSize)
+    }
+
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError> {
+        *bytemuck::try_from_bytes_mut(body).map_err(|_| VaultError::InvalidAccountSize)? = *self;
+        Ok(())
+    }
+
+    fn from_version(_version: Option<u8>, _data: &[u8]) -> Result<Self, VaultError> {
+        Err(VaultError::UnsupportedAccountVersion)
+    }
+
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"registry"], program_id)
+    }
+}
+
+// The entries literal must follow MAX_VAULTS, and LEN the struct
+const _: () = assert!(MAX_VAULTS == 64 && core::mem::size_of::<VaultRegistry>() == VaultRegistry::LEN);
+
+impl VaultRegistry {
+    pub fn vaults(&self) -> &[RegistryEntry] {
+        &self.entries[..u32::from(self.count) as usize]
+    }
+
+    pub fn find(&self, mint: &Pubkey) -> Option<&RegistryEntry> {
+        self.vaults().iter().find(|entry| entry.mint == *mint)
+    }
+}
+
+/// Values an admin can change with `UpdateConfig`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy)]
+pub struct ConfigParams {
+    pub user_deposit_cap: u64,
+    pub tvl_cap: u64,
+    pub min_deposit: u64,
+    pub min_withdraw: u64,
+    pub deposit_paused: bool,
+    pub withdraw_paused: bool,
+    pub rate_limit_window: i64,
+    pub user_window_limit: u64,
+    pub vault_window_limit: u64,
+    // Stored in the vault state, in basis points
+    pub early_exit_penalty_bps: u16,
+}
+
+/// Amount withdrawn in the current window of a withdrawal rate limit
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub struct RateWindow {
+    // Unix timestamp the window started at
+    pub start: PodI64,
+    pub withdrawn: PodU64,
+}
+impl RateWindow {
+    pub const LEN: usize = 8 + 8;
+
+    /// Add `amount` to the window, a new window starts once the previous one
+    /// has run out. Fails with the time the window resets when `limit` would
+    /// be exceeded.
+    pub fn record(&mut self, amount: u64, window: i64, limit: u64, now: i64) -> Result<(), i64> {
+        if window <= 0 {
+            return Ok(());
+        }
+        let mut reset_at = i64::from(self.start).saturating_add(window);
+        if now >= reset_at {
+            self.start = now.into();
+            self.withdrawn = PodU64::from(0);
+            reset_at = now.saturating_add(window);
+        }
+        let withdrawn = u64::from(self.withdrawn).saturating_add(amount);
+        if withdrawn > limit {
+            return Err(reset_at);
+        }
+        self.withdrawn = withdrawn.into();
+        Ok(())
+    }
+}
+
+/// Read and written in place by every deposit and withdrawal, same Pod
+/// layout rules as `VaultState`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable, ShankAccount)]
 pub struct UserState {
     pub user: Pubkey,
     pub mint: Pubkey,
-    pub amount: u64,
-    pub is_initialized: bool,
+    pub amount: PodU64,
+    // Unix timestamp before which the position can't be withdrawn normally
+    pub unlock_ts: PodI64,
+    // Reward weight multiplier of the position, in basis points
+    pub boost_bps: PodU16,
+    // Vault fee index the amount was last settled at, UNSYNCED_FEE_INDEX before the first sync
+    pub fee_index: PodU128,
+    // Key allowed to withdraw on behalf of the user, the default key when there is none
+    pub delegate: Pubkey,
+    // Amount the delegate can still withdraw, UNLIMITED_ALLOWANCE when uncapped
+    pub delegate_allowance: PodU64,
+    // Withdrawals of the position in the current rate limit window
+    pub outflow: RateWindow,
+    // Amount taken out of the position into open escrow offers
+    pub earmarked: PodU64,
+    // Bump of the escrow maker PDA of the position
+    pub escrow_maker_bump: u8,
+}
+impl VaultAccount for UserState {
+    const DISCRIMINATOR: [u8; 8] = [72, 177, 85, 249, 76, 167, 186, 126];
+    const VERSION: u8 = 7;
+    // Size of this struct
+    const LEN: usize = 32 + 32 + 8 + 8 + 2 + 16 + 32 + 8 + RateWindow::LEN + 8 + 1;
+
+    fn unpack(body: &[u8]) -> Result<Self, VaultError> {
+        bytemuck::try_pod_read_unaligned(body).map_err(|_| VaultError::InvalidAccountSize)
+    }
+
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError> {
+        *bytemuck::try_from_bytes_mut(body).map_err(|_| VaultError::InvalidAccountSize)? = *self;
+        Ok(())
+    }
+
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        let v6 = UserStateV6::from_version(version, data)?;
+        Ok(UserState {
+            user: v6.user,
+            mint: v6.mint,
+            amount: v6.amount,
+            unlock_ts: v6.unlock_ts,
+            boost_bps: v6.boost_bps,
+            fee_index: v6.fee_index,
+            delegate: v6.delegate,
+            delegate_allowance: v6.delegate_allowance,
+            outflow: v6.outflow,
+            earmarked: v6.earmarked,
+            // Found by find_bumps once the address is known
+            escrow_maker_bump: 0,
+        })
+    }
+
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"user", self.user.as_ref(), self.mint.as_ref()],
+            program_id,
+        )
+    }
+
+    fn find_bumps(&mut self, program_id: &Pubkey, address: &Pubkey) {
+        self.escrow_maker_bump = escrow_maker_address(program_id, address).1;
+    }
+}
+impl VaultState {
+    /// Credit the user the flash loan fees earned since the last settlement
+    /// and count the position in `total_deposits` if it isn't yet. Called
+    /// before every change of the position, which must also be applied to
+    /// `total_deposits`. Positions can't change while a flash loan is open,
+    /// a deposit made before the repayment would take a share of its fee.
+    pub fn settle_fees(&mut self, user: &mut UserState) -> Result<(), VaultError> {
+        if u64::from(self.flash_loan_amount) != 0 {
+            return Err(VaultError::FlashLoanInProgress);
+        }
+        let amount = u64::from(user.amount);
+        let index = u128::from(self.fee_index);
+        let snapshot = u128::from(user.fee_index);
+        let added = if snapshot == UNSYNCED_FEE_INDEX {
+            amount
+        } else {
+            let earned = u128::from(amount)
+                .checked_mul(index - snapshot)
+                .ok_or(VaultError::Overflow)?
+                / FEE_INDEX_SCALE;
+            let earned = u64::try_from(earned).map_err(|_| VaultError::Overflow)?;
+            user.amount = amount.checked_add(earned).ok_or(VaultError::Overflow)?.into();
+            earned
+        };
+        self.add_deposits(added)?;
+        user.fee_index = index.into();
+        Ok(())
+    }
+
+    /// Share a flash loan fee between the synced positions
+    pub fn accrue_fee(&mut self, fee: u64) -> Result<(), VaultError> {
+        let total_deposits = u64::from(self.total_deposits);
+        if total_deposits == 0 {
+            return Err(VaultError::NoDepositors);
+        }
+        let increase = u128::from(fee)
+            .checked_mul(FEE_INDEX_SCALE)
+            .ok_or(VaultError::Overflow)?
+            / u128::from(total_deposits);
+        self.fee_index = u128::from(self.fee_index)
+            .checked_add(increase)
+            .ok_or(VaultError::Overflow)?
+            .into();
+        Ok(())
+    }
+
+    /// Fee owed on a flash loan of `amount`, rounded up
+    pub fn flash_fee(&self, amount: u64) -> Result<u64, VaultError> {
+        let fee = u128::from(amount)
+            .checked_mul(u128::from(u16::from(self.flash_fee_bps)))
+            .ok_or(VaultError::Overflow)?
+            .div_ceil(u128::from(BPS_DENOMINATOR));
+        u64::try_from(fee).map_err(|_| VaultError::Overflow)
+    }
+
+    /// Requested withdrawals which have not been claimed yet
+    pub fn pending_tickets(&self) -> Result<u64, VaultError> {
+        u64::from(self.queued_total)
+            .checked_sub(self.claimed_total.into())
+            .ok_or(VaultError::Overflow)
+    }
+
+    /// Set the liquidity which isn't set aside for funded tickets yet aside
+    /// for the next tickets of the queue. Returns the new `funded_total`.
+    pub fn fund_tickets(&mut self, liquidity: u64) -> u64 {
+        let funded = u64::from(self.funded_total);
+        let set_aside = funded.saturating_sub(self.claimed_total.into());
+        let funded = funded
+            .saturating_add(liquidity.saturating_sub(set_aside))
+            .min(self.queued_total.into());
+        self.funded_total = funded.into();
+        funded
+    }
+
+    /// What the vault owes: the synced positions, the treasury and the
+    /// pending tickets. The vault ATA must hold at least this much.
+    pub fn liabilities(&self) -> Result<u64, VaultError> {
+        u64::from(self.total_deposits)
+            .checked_add(self.treasury.into())
+            .and_then(|owed| owed.checked_add(self.pending_tickets().ok()?))
+            .ok_or(VaultError::Overflow)
+    }
+
+    // Take a settled change of a position into account
+    pub fn add_deposits(&mut self, amount: u64) -> Result<(), VaultError> {
+        self.total_deposits = u64::from(self.total_deposits)
+            .checked_add(amount)
+            .ok_or(VaultError::Overflow)?
+            .into();
+        Ok(())
+    }
+
+    pub fn sub_deposits(&mut self, amount: u64) -> Result<(), VaultError> {
+        self.total_deposits = u64::from(self.total_deposits)
+            .checked_sub(amount)
+            .ok_or(VaultError::Overflow)?
+            .into();
+        Ok(())
+    }
 }
 impl UserState {
+    pub fn is_locked(&self, now: i64) -> bool {
+        now < i64::from(self.unlock_ts)
+    }
+
+    pub fn has_delegate(&self) -> bool {
+        self.delegate != Pubkey::default()
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct WithdrawTicket {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub id: u64,
+    pub amount: u64,
+    // Value of the vault's queued_total once this ticket was added, used to serve tickets FIFO
+    pub queue_end: u64,
+    // Unix timestamp after which the ticket can be claimed
+    pub claimable_after: i64,
+}
+impl VaultAccount for WithdrawTicket {
+    const DISCRIMINATOR: [u8; 8] = [237, 23, 164, 58, 53, 248, 240, 94];
+    const VERSION: u8 = 1;
     // Size of this struct
-    pub const LEN: usize = 32 + 32 + 8 + 1;
+    const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8;
+
+    fn unpack(body: &[u8]) -> Result<Self, VaultError> {
+        Self::try_from_slice(body).map_err(|_| VaultError::InvalidAccountSize)
+    }
+
+    fn pack(&self, body: &mut [u8]) -> Result<(), VaultError> {
+        self.serialize(&mut &mut body[..]).map_err(|_| VaultError::SerializationError)
+    }
+
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError> {
+        match version {
+            None => {
+                let v0 = WithdrawTicketV0::try_from_slice(data)
+                    .map_err(|_| VaultError::InvalidTicket)?;
+                if !v0.is_initialized {
+                    return Err(VaultError::InvalidTicket);
+                }
+                Ok(WithdrawTicket {
+                    owner: v0.owner,
+                    mint: v0.mint,
+                    id: v0.id,
+                    amount: v0.amount,
+                    queue_end: v0.queue_end,
+                    claimable_after: v0.claimable_after,
+                })
+            }
+            Some(_) => Err(VaultError::UnsupportedAccountVersion),
+        }
+    }
+
+    // Tickets live under the vault state PDA of their mint
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        let (vault_pda, _vault_bump) =
+            Pubkey::find_program_address(&[b"vault", self.mint.as_ref()], program_id);
+        Pubkey::find_program_address(
+            &[b"ticket", vault_pda.as_ref(), &self.id.to_le_bytes()],
+            program_id,
+        )
+    }
+}
+
+/// Lockup period a depositor can choose when depositing
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockupTier {
+    None,
+    Days30,
+    Days90,
+    Days180,
+}
+impl LockupTier {
+    pub fn duration(&self) -> i64 {
+        match self {
+            LockupTier::None => 0,
+            LockupTier::Days30 => 30 * SECONDS_PER_DAY,
+            LockupTier::Days90 => 90 * SECONDS_PER_DAY,
+            LockupTier::Days180 => 180 * SECONDS_PER_DAY,
+        }
+    }
+
+    pub fn boost_bps(&self) -> u16 {
+        match self {
+            LockupTier::None => 10_000,
+            LockupTier::Days30 => 11_000,
+            LockupTier::Days90 => 12_500,
+            LockupTier::Days180 => 15_000,
+        }
+    }
 }
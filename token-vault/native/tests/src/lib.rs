@@ -0,0 +1,824 @@
+//! Harness for the native token vault.
+//!
+//! The tests load the SBF builds of the program and of the Anchor escrow into
+//! LiteSVM. Build them as described in the docs of `benchmark/`, then run
+//! `cargo test` from this directory. The compute units are measured in
+//! `benchmark` as well.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use litesvm::{LiteSVM, types::TransactionMetadata};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::Transaction,
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::{create_associated_token_account, create_associated_token_account_idempotent},
+};
+use spl_token_2022::{
+    extension::{ExtensionType, StateWithExtensions, StateWithExtensionsMut},
+    state::{Account as TokenAccount, Mint},
+};
+use spl_token_vault::{
+    escrow::{self, ESCROW_PROGRAM_ID},
+    header::{AccountKind, HEADER_LEN, VaultAccount},
+    instructions::ReconcileReport,
+    legacy::LegacyLayout,
+    processor::VaultInstruction,
+    state::{ConfigParams, LockupTier, UserState, VaultConfig, VaultState},
+};
+
+const PROGRAM_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../target/deploy/spl_token_vault.so");
+
+const ESCROW_SO: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../../token-escrow/anchor/target/deploy/escrow.so"
+);
+
+pub const DECIMALS: u8 = 6;
+
+pub struct Harness {
+    pub svm: LiteSVM,
+    pub program_id: Pubkey,
+    pub payer: Keypair,
+    pub mint: Pubkey,
+    // Token program of the mint, SPL Token unless `use_token_2022_mint` replaced it
+    pub token_program: Pubkey,
+}
+
+impl Harness {
+    // Fresh SVM with the vault program loaded, a funded payer and an SPL Token
+    // mint. The payer deployed the program, it's the upgrade authority.
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        let program_id = Pubkey::new_unique();
+        svm.add_program_from_file(program_id, PROGRAM_SO)
+            .expect("build the program with cargo build-sbf first");
+
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 100_000_000_000).unwrap();
+
+        let mut harness = Harness {
+            svm,
+            program_id,
+            payer,
+            mint: Pubkey::default(),
+            token_program: spl_token::id(),
+        };
+        let payer = harness.payer.pubkey();
+        harness.set_upgrade_authority(Some(&payer));
+        harness.mint = harness.create_mint();
+        harness
+    }
+
+    // Load the escrow program for the escrow bridge tests
+    pub fn load_escrow(&mut self) {
+        self.svm
+            .add_program_from_file(ESCROW_PROGRAM_ID, ESCROW_SO)
+            .expect("build the escrow with anchor build first");
+    }
+
+    // LiteSVM loads programs without a ProgramData account, write the one
+    // `solana program deploy` leaves behind
+    pub fn set_upgrade_authority(&mut self, authority: Option<&Pubkey>) {
+        // `UpgradeableLoaderState::ProgramData` at slot 0, then the Option of the authority
+        let mut data = vec![0; 45];
+        data[..4].copy_from_slice(&3u32.to_le_bytes());
+        if let Some(authority) = authority {
+            data[12] = 1;
+            data[13..].copy_from_slice(authority.as_ref());
+        }
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(self.program_data(), account).unwrap();
+    }
+
+    pub fn create_mint(&mut self) -> Pubkey {
+        let mint = Keypair::new();
+        let lamports = self.svm.minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN);
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint.pubkey(),
+                lamports,
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &self.payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions, &[&mint]);
+        mint.pubkey()
+    }
+
+    // Replace the mint with a Token-2022 mint with these extensions. `init_extensions`
+    // returns the instructions initializing them, they run before InitializeMint2.
+    pub fn use_token_2022_mint(
+        &mut self,
+        extensions: &[ExtensionType],
+        init_extensions: impl FnOnce(&Pubkey) -> Vec<Instruction>,
+    ) {
+        let mint = Keypair::new();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap();
+        let lamports = self.svm.minimum_balance_for_rent_exemption(space);
+        let mut instructions = vec![system_instruction::create_account(
+            &self.payer.pubkey(),
+            &mint.pubkey(),
+            lamports,
+            space as u64,
+            &spl_token_2022::id(),
+        )];
+        instructions.extend(init_extensions(&mint.pubkey()));
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(
+                &spl_token_2022::id(),
+                &mint.pubkey(),
+                &self.payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        );
+        self.send(&instructions, &[&mint]);
+        self.mint = mint.pubkey();
+        self.token_program = spl_token_2022::id();
+    }
+
+    // Send the instructions signed by the payer and the extra signers
+    pub fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) {
+        self.try_send(instructions, signers).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    // Same as `send`, returns the error and logs of a failed transaction instead of panicking
+    pub fn try_send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), String> {
+        self.execute(instructions, signers).map(|_| ())
+    }
+
+    // Metadata of the transaction, or its error and logs
+    fn execute(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<TransactionMetadata, String> {
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            self.svm.latest_blockhash(),
+        );
+        let result = self.svm.send_transaction(tx);
+        // Identical transactions would otherwise be rejected as already processed
+        self.svm.expire_blockhash();
+        result.map_err(|failed| format!("{:?}\n{}", failed.err, failed.meta.logs.join("\n")))
+    }
+
+    pub fn vault_state(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault", self.mint.as_ref()], &self.program_id).0
+    }
+
+    pub fn vault_config(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"config", self.vault_state().as_ref()], &self.program_id).0
+    }
+
+    pub fn registry(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"registry"], &self.program_id).0
+    }
+
+    pub fn vault_ata(&self) -> Pubkey {
+        get_associated_token_address_with_program_id(&self.vault_state(), &self.mint, &self.token_program)
+    }
+
+    pub fn program_data(&self) -> Pubkey {
+        Pubkey::find_program_address(&[self.program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+    }
+
+    // New wallet with enough SOL to pay for its own accounts
+    pub fn new_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        self.svm.airdrop(&user.pubkey(), 10_000_000_000).unwrap();
+        user
+    }
+
+    pub fn user_state(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"user", user.as_ref(), self.mint.as_ref()],
+            &self.program_id,
+        )
+        .0
+    }
+
+    pub fn user_ata(&self, user: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(user, &self.mint, &self.token_program)
+    }
+
+    // Create the user's ATA and mint tokens into it
+    pub fn fund_user(&mut self, user: &Pubkey, amount: u64) {
+        let instructions = [
+            create_associated_token_account(
+                &self.payer.pubkey(),
+                user,
+                &self.mint,
+                &self.token_program,
+            ),
+            spl_token_2022::instruction::mint_to(
+                &self.token_program,
+                &self.mint,
+                &self.user_ata(user),
+                &self.payer.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions, &[]);
+    }
+
+    // Mint tokens of any mint to the owner's ATA, creating it when needed
+    pub fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let instructions = [
+            create_associated_token_account_idempotent(
+                &self.payer.pubkey(),
+                owner,
+                mint,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                &get_associated_token_address(owner, mint),
+                &self.payer.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
+        ];
+        self.send(&instructions, &[]);
+    }
+
+    pub fn escrow_maker(&self, user: &Pubkey) -> Pubkey {
+        escrow::escrow_maker_address(&self.program_id, &self.user_state(user)).0
+    }
+
+    pub fn make_escrow_offer_ix(
+        &self,
+        user: &Pubkey,
+        mint_b: &Pubkey,
+        id: u64,
+        amount: u64,
+        wanted_amount: u64,
+    ) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = escrow::offer_address(&escrow_maker, id);
+        self.vault_instruction(
+            VaultInstruction::MakeEscrowOffer { id, amount, wanted_amount },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(get_associated_token_address(&escrow_maker, &self.mint), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(get_associated_token_address(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    pub fn take_escrow_offer_ix(
+        &self,
+        taker: &Pubkey,
+        owner: &Pubkey,
+        mint_b: &Pubkey,
+        id: u64,
+    ) -> Instruction {
+        let escrow_maker = self.escrow_maker(owner);
+        let offer = escrow::offer_address(&escrow_maker, id);
+        self.vault_instruction(
+            VaultInstruction::TakeEscrowOffer { id },
+            vec![
+                AccountMeta::new(*taker, true),
+                AccountMeta::new(self.user_state(owner), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(*owner, false),
+                AccountMeta::new(get_associated_token_address(taker, &self.mint), false),
+                AccountMeta::new(get_associated_token_address(taker, mint_b), false),
+                AccountMeta::new(get_associated_token_address(&escrow_maker, mint_b), false),
+                AccountMeta::new(get_associated_token_address(owner, mint_b), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(get_associated_token_address(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    pub fn refund_escrow_offer_ix(&self, user: &Pubkey, mint_b: &Pubkey, id: u64) -> Instruction {
+        let escrow_maker = self.escrow_maker(user);
+        let offer = escrow::offer_address(&escrow_maker, id);
+        self.vault_instruction(
+            VaultInstruction::RefundEscrowOffer { id },
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(escrow_maker, false),
+                AccountMeta::new(get_associated_token_address(&escrow_maker, &self.mint), false),
+                AccountMeta::new(offer, false),
+                AccountMeta::new(get_associated_token_address(&offer, &self.mint), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(ESCROW_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    fn vault_instruction(&self, instruction: VaultInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+        let mut data = Vec::new();
+        instruction.serialize(&mut data).unwrap();
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    pub fn initialize_vault(&mut self, early_exit_penalty_bps: u16, withdraw_cooldown: i64) {
+        let ix = self.initialize_vault_ix(early_exit_penalty_bps, withdraw_cooldown);
+        self.send(&[ix], &[]);
+    }
+
+    pub fn initialize_vault_ix(&self, early_exit_penalty_bps: u16, withdraw_cooldown: i64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::InitializeVault { early_exit_penalty_bps, withdraw_cooldown },
+            vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new(self.vault_config(), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(self.registry(), false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    pub fn initialize_registry(&mut self) {
+        let ix = self.vault_instruction(
+            VaultInstruction::InitializeRegistry,
+            vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        );
+        self.send(&[ix], &[]);
+    }
+
+    pub fn register_vault(&mut self) {
+        let ix = self.vault_instruction(
+            VaultInstruction::RegisterVault,
+            vec![
+                AccountMeta::new_readonly(self.payer.pubkey(), true),
+                AccountMeta::new(self.registry(), false),
+                AccountMeta::new_readonly(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        );
+        self.send(&[ix], &[]);
+    }
+
+    pub fn update_config(&mut self, params: ConfigParams) {
+        let ix = self.update_config_ix(&self.payer.pubkey(), params);
+        self.send(&[ix], &[]);
+    }
+
+    pub fn update_config_ix(&self, admin: &Pubkey, params: ConfigParams) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::UpdateConfig { params },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new(self.vault_state(), false),
+            ],
+        )
+    }
+
+    pub fn transfer_admin_ix(&self, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::TransferAdmin { new_admin: *new_admin },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    pub fn accept_admin_ix(&self, pending_admin: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::AcceptAdmin,
+            vec![
+                AccountMeta::new_readonly(*pending_admin, true),
+                AccountMeta::new(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    // Registry, vault and config for the mint, the payer is the admin of all of them
+    pub fn setup_vault(&mut self, early_exit_penalty_bps: u16, withdraw_cooldown: i64) {
+        self.initialize_registry();
+        self.initialize_vault(early_exit_penalty_bps, withdraw_cooldown);
+        self.register_vault();
+    }
+
+    pub fn initialize_user(&mut self, user: &Keypair) {
+        let ix = self.initialize_user_ix(&user.pubkey());
+        self.send(&[ix], &[user]);
+    }
+
+    pub fn initialize_user_ix(&self, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::InitializeUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        )
+    }
+
+    pub fn deposit(&mut self, user: &Keypair, amount: u64, lockup: LockupTier) {
+        let ix = self.deposit_ix(user, amount, lockup);
+        self.send(&[ix], &[user]);
+    }
+
+    pub fn deposit_ix(&self, user: &Keypair, amount: u64, lockup: LockupTier) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::Deposit { amount, lockup },
+            self.deposit_accounts(&user.pubkey(), &user.pubkey()),
+        )
+    }
+
+    // Deposit from the payer's ATA into the position of `owner`
+    pub fn deposit_for(&mut self, payer: &Keypair, owner: &Pubkey, amount: u64) {
+        let ix = self.vault_instruction(
+            VaultInstruction::DepositFor { owner: *owner, amount },
+            self.deposit_accounts(&payer.pubkey(), owner),
+        );
+        self.send(&[ix], &[payer]);
+    }
+
+    fn deposit_accounts(&self, payer: &Pubkey, owner: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(self.user_ata(payer), false),
+            AccountMeta::new(self.vault_ata(), false),
+            AccountMeta::new(self.user_state(owner), false),
+            AccountMeta::new(self.vault_state(), false),
+            AccountMeta::new_readonly(self.vault_config(), false),
+            AccountMeta::new_readonly(self.registry(), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ]
+    }
+
+    pub fn withdraw(&mut self, user: &Keypair, amount: u64) {
+        let ix = self.withdraw_ix(user, &user.pubkey(), amount);
+        self.send(&[ix], &[user]);
+    }
+
+    // Withdraw from the position of `owner` into the signer's ATA, the signer is the owner or its delegate
+    pub fn withdraw_ix(&self, signer: &Keypair, owner: &Pubkey, amount: u64) -> Instruction {
+        self.withdraw_and_close_ix(signer, owner, amount, false)
+    }
+
+    // Same as `withdraw_ix`, closes the user state when `close_if_empty` is set and the position is drained
+    pub fn withdraw_and_close_ix(
+        &self,
+        signer: &Keypair,
+        owner: &Pubkey,
+        amount: u64,
+        close_if_empty: bool,
+    ) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::Withdraw { amount, close_if_empty },
+            vec![
+                AccountMeta::new(signer.pubkey(), true),
+                AccountMeta::new(self.user_ata(&signer.pubkey()), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.user_state(owner), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn withdraw_early_ix(&self, user: &Keypair, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::WithdrawEarly { amount },
+            vec![
+                AccountMeta::new_readonly(user.pubkey(), true),
+                AccountMeta::new(self.user_ata(&user.pubkey()), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.user_state(&user.pubkey()), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    // Pay out the treasury into `destination`, signed by `admin`
+    pub fn withdraw_treasury_ix(&self, admin: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::WithdrawTreasury { amount },
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn ticket(&self, id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"ticket", self.vault_state().as_ref(), &id.to_le_bytes()],
+            &self.program_id,
+        )
+        .0
+    }
+
+    // Queue a withdrawal into the ticket with the vault's next ticket id
+    pub fn request_withdraw_ix(&self, user: &Keypair, ticket_id: u64, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::RequestWithdraw { amount },
+            vec![
+                AccountMeta::new(user.pubkey(), true),
+                AccountMeta::new(self.user_state(&user.pubkey()), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        )
+    }
+
+    pub fn claim_withdraw_ix(&self, user: &Keypair, ticket_id: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::ClaimWithdraw,
+            vec![
+                AccountMeta::new(user.pubkey(), true),
+                AccountMeta::new(self.user_ata(&user.pubkey()), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new(self.ticket(ticket_id), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    pub fn close_user_ix(&self, user: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::CloseUser,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        )
+    }
+
+    // PDA a legacy program stored the user's position under
+    pub fn legacy_state(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"state", user.as_ref()], &self.program_id).0
+    }
+
+    // Migrate a legacy position of the user into its user state, signed by `authority`
+    pub fn migrate_user_state_ix(&self, authority: &Pubkey, user: &Pubkey, layout: LegacyLayout) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::MigrateUserState { layout },
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new(*user, false),
+                AccountMeta::new(self.legacy_state(user), false),
+                AccountMeta::new(self.user_state(user), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(self.program_data(), false),
+            ],
+        )
+    }
+
+    // Rewrite an account of an older program version with the current layout
+    pub fn upgrade_account_ix(&self, kind: AccountKind, account: &Pubkey) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::UpgradeAccount { kind },
+            vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(*account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    pub fn set_delegate(&mut self, user: &Keypair, delegate: &Pubkey, allowance: Option<u64>) {
+        let ix = self.vault_instruction(
+            VaultInstruction::SetDelegate { delegate: *delegate, allowance },
+            vec![
+                AccountMeta::new_readonly(user.pubkey(), true),
+                AccountMeta::new(self.user_state(&user.pubkey()), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        );
+        self.send(&[ix], &[user]);
+    }
+
+    pub fn revoke_delegate(&mut self, user: &Keypair) {
+        let ix = self.vault_instruction(
+            VaultInstruction::RevokeDelegate,
+            vec![
+                AccountMeta::new_readonly(user.pubkey(), true),
+                AccountMeta::new(self.user_state(&user.pubkey()), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        );
+        self.send(&[ix], &[user]);
+    }
+
+    pub fn set_flash_loan_fee(&mut self, fee_bps: u16) {
+        let ix = self.vault_instruction(
+            VaultInstruction::SetFlashLoanFee { fee_bps },
+            vec![
+                AccountMeta::new_readonly(self.payer.pubkey(), true),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+            ],
+        );
+        self.send(&[ix], &[]);
+    }
+
+    // Lends to the borrower's ATA, send it together with `flash_repay_ix`
+    pub fn flash_loan_ix(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::FlashLoan { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.user_ata(borrower), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_config(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ],
+        )
+    }
+
+    pub fn flash_repay_ix(&self, borrower: &Pubkey, amount: u64) -> Instruction {
+        self.vault_instruction(
+            VaultInstruction::FlashRepay { amount },
+            vec![
+                AccountMeta::new_readonly(*borrower, true),
+                AccountMeta::new(self.user_ata(borrower), false),
+                AccountMeta::new(self.vault_ata(), false),
+                AccountMeta::new(self.vault_state(), false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        )
+    }
+
+    // Run Reconcile and decode the report it returns
+    pub fn reconcile(&mut self, pause_if_insolvent: bool) -> ReconcileReport {
+        let vault_config = if pause_if_insolvent {
+            AccountMeta::new(self.vault_config(), false)
+        } else {
+            AccountMeta::new_readonly(self.vault_config(), false)
+        };
+        let ix = self.vault_instruction(
+            VaultInstruction::Reconcile { pause_if_insolvent },
+            vec![
+                AccountMeta::new_readonly(self.vault_state(), false),
+                AccountMeta::new_readonly(self.vault_ata(), false),
+                vault_config,
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.token_program, false),
+            ],
+        );
+        let meta = self.execute(&[ix], &[]).unwrap_or_else(|error| panic!("{error}"));
+        ReconcileReport::try_from_slice(&meta.return_data.data).unwrap()
+    }
+
+    // Amount of the user's position, `None` before InitializeUser
+    pub fn user_amount(&self, user: &Pubkey) -> Option<u64> {
+        let account = self.svm.get_account(&self.user_state(user))?;
+        assert_eq!(account.owner, self.program_id);
+        let state = UserState::unpack(&account.data[HEADER_LEN..]).unwrap();
+        Some(u64::from(state.amount))
+    }
+
+    // Whether the account was closed, or never existed
+    pub fn is_closed(&self, address: &Pubkey) -> bool {
+        self.svm.get_account(address).is_none_or(|account| account.lamports == 0 && account.data.is_empty())
+    }
+
+    pub fn user_state_data(&self, user: &Pubkey) -> UserState {
+        let account = self.svm.get_account(&self.user_state(user)).unwrap();
+        UserState::unpack(&account.data[HEADER_LEN..]).unwrap()
+    }
+
+    pub fn vault_state_data(&self) -> VaultState {
+        let account = self.svm.get_account(&self.vault_state()).unwrap();
+        VaultState::unpack(&account.data[HEADER_LEN..]).unwrap()
+    }
+
+    pub fn vault_config_data(&self) -> VaultConfig {
+        let account = self.svm.get_account(&self.vault_config()).unwrap();
+        VaultConfig::unpack(&account.data[HEADER_LEN..]).unwrap()
+    }
+
+    // Move the clock forward, lockups and cooldowns are checked against it
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp += seconds;
+        self.svm.set_sysvar(&clock);
+    }
+
+    // Balance of a token account of either token program
+    pub fn token_balance(&self, token_account: &Pubkey) -> u64 {
+        let account = self.svm.get_account(token_account).unwrap();
+        StateWithExtensions::<TokenAccount>::unpack(&account.data).unwrap().base.amount
+    }
+
+    // Overwrite the balance of a token account, stands in for tokens the vault deployed elsewhere
+    pub fn set_token_balance(&mut self, token_account: &Pubkey, amount: u64) {
+        let mut account = self.svm.get_account(token_account).unwrap();
+        let mut state = StateWithExtensionsMut::<TokenAccount>::unpack(&mut account.data).unwrap();
+        state.base.amount = amount;
+        state.pack_base();
+        self.svm.set_account(*token_account, account).unwrap();
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
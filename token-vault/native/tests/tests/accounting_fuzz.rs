@@ -0,0 +1,305 @@
+//! Stateful fuzzing of the deposit and withdraw accounting. Random sequences
+//! of InitializeVault, InitializeUser, Deposit and Withdraw run across several
+//! users and the vaults of two mints, some of them with an account swapped
+//! for a malicious one. A model of every position follows the transactions
+//! which succeed, and after each of them the vault ATA of every mint must
+//! hold exactly the sum of the positions, and no user may get back more than
+//! they deposited.
+//!
+//! Runs a fixed 256 cases, about half a minute, set `PROPTEST_CASES` for
+//! longer runs.
+
+use proptest::prelude::*;
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_vault::{
+    header::{HEADER_LEN, VaultAccount},
+    state::{LockupTier, UserState},
+};
+use spl_token_vault_tests::Harness;
+use std::collections::{BTreeMap, btree_map::Entry};
+
+/// Cases of a run when `PROPTEST_CASES` isn't set
+const CASES: u32 = 256;
+const USERS: usize = 3;
+const MINTS: usize = 2;
+/// Tokens of every mint each user starts with
+const FUNDS: u64 = 1_000_000;
+/// Cooldown of the vaults which only pay out through withdrawal tickets
+const COOLDOWN: i64 = 60 * 60;
+
+/// Account swapped into a deposit or a withdrawal
+#[derive(Clone, Copy, Debug)]
+enum Attack {
+    // The user state PDA of the next user
+    OtherUserState,
+    // The token account of the next user for the mint, in place of the vault ATA
+    OtherVaultAta,
+    // A copy of the user state claiming more tokens, owned by another program
+    ForeignUserState,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    InitializeVault { mint: usize, cooldown: bool },
+    InitializeUser { user: usize, mint: usize },
+    Deposit { user: usize, mint: usize, amount: u64, lockup: LockupTier, attack: Option<Attack> },
+    // `signer` withdraws from the position of `owner`, none of them has a delegate
+    Withdraw { signer: usize, owner: usize, mint: usize, amount: u64, attack: Option<Attack> },
+}
+
+/// What the model knows of a position
+#[derive(Clone, Copy, Debug, Default)]
+struct Position {
+    deposited: u64,
+    withdrawn: u64,
+    locked: bool,
+}
+
+impl Position {
+    fn amount(&self) -> u64 {
+        self.deposited - self.withdrawn
+    }
+}
+
+struct Fuzz {
+    harness: Harness,
+    users: Vec<Keypair>,
+    mints: [Pubkey; MINTS],
+    // Vaults by mint, with their cooldown
+    vaults: BTreeMap<usize, i64>,
+    positions: BTreeMap<(usize, usize), Position>,
+}
+
+impl Fuzz {
+    fn new() -> Self {
+        let mut harness = Harness::new();
+        harness.initialize_registry();
+        let mints = [harness.mint, harness.create_mint()];
+        let users: Vec<_> = (0..USERS).map(|_| harness.new_user()).collect();
+        for user in &users {
+            for mint in &mints {
+                harness.mint_to(&user.pubkey(), mint, FUNDS);
+            }
+        }
+        Fuzz { harness, users, mints, vaults: BTreeMap::new(), positions: BTreeMap::new() }
+    }
+
+    // The harness builds the accounts of the vault of its mint
+    fn vault(&mut self, mint: usize) -> &mut Harness {
+        self.harness.mint = self.mints[mint];
+        &mut self.harness
+    }
+
+    fn key(&self, user: usize) -> Pubkey {
+        self.users[user].pubkey()
+    }
+
+    fn wallet(&self, user: usize, mint: usize) -> u64 {
+        let ata = get_associated_token_address(&self.key(user), &self.mints[mint]);
+        self.harness.token_balance(&ata)
+    }
+
+    // Swap the account the attack targets, `user` is the owner of the position
+    fn attack(&mut self, ix: Instruction, attack: Attack, user: usize, mint: usize) -> Instruction {
+        let next = self.key((user + 1) % USERS);
+        let owner = self.key(user);
+        let harness = self.vault(mint);
+        let user_state = harness.user_state(&owner);
+        match attack {
+            Attack::OtherUserState => swap(ix, &user_state, harness.user_state(&next)),
+            Attack::OtherVaultAta => swap(ix, &harness.vault_ata(), harness.user_ata(&next)),
+            Attack::ForeignUserState => {
+                let mut data = vec![0; UserState::SPACE];
+                data[..8].copy_from_slice(&UserState::DISCRIMINATOR);
+                data[8] = UserState::VERSION;
+                let body = &mut data[HEADER_LEN..];
+                body[..32].copy_from_slice(owner.as_ref());
+                body[32..64].copy_from_slice(harness.mint.as_ref());
+                body[64..72].copy_from_slice(&(2 * FUNDS).to_le_bytes());
+                let forged = Pubkey::new_unique();
+                let lamports = harness.svm.minimum_balance_for_rent_exemption(data.len());
+                let account =
+                    Account { lamports, data, owner: Pubkey::new_unique(), executable: false, rent_epoch: 0 };
+                harness.svm.set_account(forged, account).unwrap();
+                swap(ix, &user_state, forged)
+            }
+        }
+    }
+
+    fn run(&mut self, op: &Op) -> Result<(), TestCaseError> {
+        match *op {
+            Op::InitializeVault { mint, cooldown } => {
+                let cooldown = if cooldown { COOLDOWN } else { 0 };
+                let ix = self.vault(mint).initialize_vault_ix(0, cooldown);
+                let initialized = self.harness.try_send(&[ix], &[]);
+                if self.vaults.contains_key(&mint) {
+                    prop_assert!(initialized.is_err(), "initialized vault {mint} again");
+                } else {
+                    prop_assert!(initialized.is_ok(), "can't initialize vault {mint}: {:?}", initialized);
+                    self.harness.register_vault();
+                    self.vaults.insert(mint, cooldown);
+                }
+            }
+            Op::InitializeUser { user, mint } => {
+                let key = self.key(user);
+                let ix = self.vault(mint).initialize_user_ix(&key);
+                let initialized = self.harness.try_send(&[ix], &[&self.users[user]]);
+                match self.positions.entry((user, mint)) {
+                    Entry::Occupied(_) => {
+                        prop_assert!(initialized.is_err(), "initialized user {user} of mint {mint} again");
+                    }
+                    Entry::Vacant(entry) => {
+                        prop_assert!(initialized.is_ok(), "can't initialize user {user}: {:?}", initialized);
+                        entry.insert(Position::default());
+                    }
+                }
+            }
+            Op::Deposit { user, mint, amount, lockup, attack } => {
+                let wallet = self.wallet(user, mint);
+                self.vault(mint);
+                let mut ix = self.harness.deposit_ix(&self.users[user], amount, lockup);
+                if let Some(attack) = attack {
+                    ix = self.attack(ix, attack, user, mint);
+                }
+                let deposited = self.harness.try_send(&[ix], &[&self.users[user]]);
+                let position = self.positions.get(&(user, mint)).copied();
+                let honest = attack.is_none()
+                    && self.vaults.contains_key(&mint)
+                    && position.is_some()
+                    && amount > 0
+                    && amount <= wallet;
+                prop_assert_eq!(deposited.is_ok(), honest, "{:?}: {:?}", op, deposited);
+                if let (Ok(_), Some(mut position)) = (deposited, position) {
+                    position.deposited += amount;
+                    position.locked |= lockup != LockupTier::None;
+                    self.positions.insert((user, mint), position);
+                }
+            }
+            Op::Withdraw { signer, owner, mint, amount, attack } => {
+                let owner_key = self.key(owner);
+                self.vault(mint);
+                let mut ix = self.harness.withdraw_ix(&self.users[signer], &owner_key, amount);
+                if let Some(attack) = attack {
+                    ix = self.attack(ix, attack, owner, mint);
+                }
+                let withdrawn = self.harness.try_send(&[ix], &[&self.users[signer]]);
+                let position = self.positions.get(&(owner, mint)).copied();
+                let honest = attack.is_none()
+                    && signer == owner
+                    && self.vaults.get(&mint) == Some(&0)
+                    && position.is_some_and(|position| {
+                        !position.locked && amount > 0 && amount <= position.amount()
+                    });
+                prop_assert_eq!(withdrawn.is_ok(), honest, "{:?}: {:?}", op, withdrawn);
+                if let (Ok(_), Some(mut position)) = (withdrawn, position) {
+                    position.withdrawn += amount;
+                    self.positions.insert((owner, mint), position);
+                }
+            }
+        }
+        self.check()
+    }
+
+    // The vaults hold the sum of their positions, and the wallets what the positions don't
+    fn check(&mut self) -> Result<(), TestCaseError> {
+        for mint in 0..MINTS {
+            let mut positions = 0;
+            for user in 0..USERS {
+                let key = self.key(user);
+                let amount = self.vault(mint).user_amount(&key);
+                let position = self.positions.get(&(user, mint));
+                prop_assert_eq!(amount, position.map(Position::amount), "position of user {}", user);
+                positions += amount.unwrap_or(0);
+
+                let wallet = self.wallet(user, mint);
+                prop_assert!(wallet <= FUNDS, "user {user} withdrew more than they deposited");
+                prop_assert_eq!(
+                    wallet,
+                    FUNDS - position.map_or(0, Position::amount),
+                    "wallet of user {}",
+                    user
+                );
+            }
+            if self.vaults.contains_key(&mint) {
+                let vault_ata = self.vault(mint).vault_ata();
+                let vault_balance = self.harness.token_balance(&vault_ata);
+                prop_assert_eq!(positions, vault_balance, "sum of the positions of mint {}", mint);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Same instruction with another account in place of `old`
+fn swap(mut ix: Instruction, old: &Pubkey, new: Pubkey) -> Instruction {
+    for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == *old) {
+        meta.pubkey = new;
+    }
+    ix
+}
+
+fn amount() -> impl Strategy<Value = u64> {
+    prop_oneof![Just(0), 1..=1_000u64, 1..=FUNDS, Just(FUNDS + 1), Just(u64::MAX)]
+}
+
+fn attack() -> impl Strategy<Value = Option<Attack>> {
+    prop::option::weighted(
+        0.25,
+        prop_oneof![
+            Just(Attack::OtherUserState),
+            Just(Attack::OtherVaultAta),
+            Just(Attack::ForeignUserState),
+        ],
+    )
+}
+
+fn lockup() -> impl Strategy<Value = LockupTier> {
+    prop_oneof![
+        8 => Just(LockupTier::None),
+        1 => Just(LockupTier::Days30),
+        1 => Just(LockupTier::Days180),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        1 => (0..MINTS, prop::bool::weighted(0.2))
+            .prop_map(|(mint, cooldown)| Op::InitializeVault { mint, cooldown }),
+        2 => (0..USERS, 0..MINTS).prop_map(|(user, mint)| Op::InitializeUser { user, mint }),
+        4 => (0..USERS, 0..MINTS, amount(), lockup(), attack()).prop_map(
+            |(user, mint, amount, lockup, attack)| Op::Deposit { user, mint, amount, lockup, attack }
+        ),
+        4 => (0..USERS, prop::bool::weighted(0.9), 0..MINTS, amount(), attack()).prop_map(
+            |(signer, own, mint, amount, attack)| Op::Withdraw {
+                signer,
+                owner: if own { signer } else { (signer + 1) % USERS },
+                mint,
+                amount,
+                attack,
+            }
+        ),
+    ]
+}
+
+fn config() -> ProptestConfig {
+    let cases = std::env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok());
+    ProptestConfig::with_cases(cases.unwrap_or(CASES))
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn positions_add_up_to_the_vault(ops in prop::collection::vec(op(), 1..40)) {
+        let mut fuzz = Fuzz::new();
+        for op in &ops {
+            fuzz.run(op)?;
+        }
+    }
+}
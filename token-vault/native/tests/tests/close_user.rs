@@ -0,0 +1,92 @@
+use solana_sdk::signature::Signer;
+use spl_token_vault::{
+    error::VaultError,
+    header::VaultAccount,
+    state::{LockupTier, UserState},
+};
+use spl_token_vault_tests::Harness;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn empty_user_state_is_closed_and_its_rent_returned() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::None);
+
+    let close = harness.close_user_ix(&user.pubkey());
+    let error = harness.try_send(&[close], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::UserStateNotEmpty)), "{error}");
+
+    // Only the owner closes its user state
+    harness.withdraw(&user, 1_000);
+    let stranger = harness.new_user();
+    let mut close = harness.close_user_ix(&user.pubkey());
+    close.accounts[0].pubkey = stranger.pubkey();
+    assert!(harness.try_send(&[close], &[&stranger]).is_err());
+
+    let lamports = harness.svm.get_account(&user.pubkey()).unwrap().lamports;
+    let rent = harness.svm.minimum_balance_for_rent_exemption(UserState::SPACE);
+    let close = harness.close_user_ix(&user.pubkey());
+    harness.send(&[close], &[&user]);
+    assert!(harness.is_closed(&harness.user_state(&user.pubkey())));
+    assert_eq!(harness.svm.get_account(&user.pubkey()).unwrap().lamports, lamports + rent);
+
+    // A closed user state starts over from scratch
+    harness.initialize_user(&user);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(0));
+}
+
+// The closed account can't be used again later in the same transaction
+#[test]
+fn closed_user_state_cannot_be_revived() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+
+    let close = harness.close_user_ix(&user.pubkey());
+    let deposit = harness.deposit_ix(&user, 1_000, LockupTier::None);
+    assert!(harness.try_send(&[close, deposit], &[&user]).is_err());
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(0));
+}
+
+#[test]
+fn draining_withdrawal_closes_the_user_state() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::None);
+
+    // A partial withdrawal leaves the position open
+    let withdraw = harness.withdraw_and_close_ix(&user, &user.pubkey(), 400, true);
+    harness.send(&[withdraw], &[&user]);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(600));
+
+    // Only the owner may close it, not its delegate
+    let delegate = harness.new_user();
+    harness.fund_user(&delegate.pubkey(), 0);
+    harness.set_delegate(&user, &delegate.pubkey(), None);
+    let withdraw = harness.withdraw_and_close_ix(&delegate, &user.pubkey(), 600, true);
+    let error = harness.try_send(&[withdraw], &[&delegate]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+
+    let lamports = harness.svm.get_account(&user.pubkey()).unwrap().lamports;
+    let rent = harness.svm.minimum_balance_for_rent_exemption(UserState::SPACE);
+    let withdraw = harness.withdraw_and_close_ix(&user, &user.pubkey(), 600, true);
+    harness.send(&[withdraw], &[&user]);
+    assert!(harness.is_closed(&harness.user_state(&user.pubkey())));
+    assert_eq!(harness.svm.get_account(&user.pubkey()).unwrap().lamports, lamports + rent);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 1_000);
+}
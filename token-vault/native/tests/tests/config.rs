@@ -0,0 +1,168 @@
+use solana_sdk::signature::Signer;
+use spl_token_vault::{error::VaultError, state::ConfigParams, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+// Config of a new vault, nothing is capped or paused
+fn open_config() -> ConfigParams {
+    ConfigParams {
+        user_deposit_cap: u64::MAX,
+        tvl_cap: u64::MAX,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: 0,
+        user_window_limit: u64::MAX,
+        vault_window_limit: u64::MAX,
+        early_exit_penalty_bps: 0,
+    }
+}
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn deposits_respect_the_caps_and_minimum() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.update_config(ConfigParams {
+        user_deposit_cap: 600,
+        tvl_cap: 1_000,
+        min_deposit: 100,
+        ..open_config()
+    });
+
+    let first = harness.new_user();
+    let second = harness.new_user();
+    for user in [&first, &second] {
+        harness.initialize_user(user);
+        harness.fund_user(&user.pubkey(), 1_000);
+    }
+
+    let deposit = harness.deposit_ix(&first, 99, LockupTier::None);
+    let error = harness.try_send(&[deposit], &[&first]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::BelowMinimum)), "{error}");
+
+    harness.deposit(&first, 500, LockupTier::None);
+    let deposit = harness.deposit_ix(&first, 101, LockupTier::None);
+    let error = harness.try_send(&[deposit], &[&first]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::UserCapExceeded)), "{error}");
+    harness.deposit(&first, 100, LockupTier::None);
+
+    let deposit = harness.deposit_ix(&second, 401, LockupTier::None);
+    let error = harness.try_send(&[deposit], &[&second]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::TvlCapExceeded)), "{error}");
+    harness.deposit(&second, 400, LockupTier::None);
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 1_000);
+}
+
+#[test]
+fn withdrawals_respect_the_minimum() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.update_config(ConfigParams { min_withdraw: 100, ..open_config() });
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::None);
+
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 99);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::BelowMinimum)), "{error}");
+    harness.withdraw(&user, 100);
+}
+
+#[test]
+fn paused_vault_rejects_deposits_and_withdrawals() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 500, LockupTier::None);
+
+    harness.update_config(ConfigParams { deposit_paused: true, ..open_config() });
+    let deposit = harness.deposit_ix(&user, 500, LockupTier::None);
+    let error = harness.try_send(&[deposit], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::DepositPaused)), "{error}");
+    // Withdrawals keep working while deposits are paused
+    harness.withdraw(&user, 100);
+
+    harness.update_config(ConfigParams { withdraw_paused: true, ..open_config() });
+    harness.deposit(&user, 500, LockupTier::None);
+    for withdraw in [
+        harness.withdraw_ix(&user, &user.pubkey(), 100),
+        harness.withdraw_early_ix(&user, 100),
+        harness.request_withdraw_ix(&user, 0, 100),
+    ] {
+        let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::WithdrawPaused)), "{error}");
+    }
+
+    harness.update_config(open_config());
+    harness.withdraw(&user, 900);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(0));
+}
+
+#[test]
+fn admin_is_transferred_in_two_steps() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let admin = harness.payer.pubkey();
+    let new_admin = harness.new_user();
+    let stranger = harness.new_user();
+
+    // Only the admin changes the config or proposes a new admin
+    let update = harness.update_config_ix(&stranger.pubkey(), open_config());
+    let error = harness.try_send(&[update], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+    let transfer = harness.transfer_admin_ix(&stranger.pubkey(), &stranger.pubkey());
+    let error = harness.try_send(&[transfer], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+
+    let transfer = harness.transfer_admin_ix(&admin, &new_admin.pubkey());
+    harness.send(&[transfer], &[]);
+    assert_eq!(harness.vault_config_data().pending_admin, new_admin.pubkey());
+
+    // The current admin stays in charge until the proposed one accepts
+    let update = harness.update_config_ix(&new_admin.pubkey(), open_config());
+    let error = harness.try_send(&[update], &[&new_admin]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+    let accept = harness.accept_admin_ix(&stranger.pubkey());
+    let error = harness.try_send(&[accept], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::NoPendingAdmin)), "{error}");
+
+    let accept = harness.accept_admin_ix(&new_admin.pubkey());
+    harness.send(&[accept], &[&new_admin]);
+    let config = harness.vault_config_data();
+    assert_eq!(config.admin, new_admin.pubkey());
+    assert_eq!(config.pending_admin, Default::default());
+
+    let update = harness.update_config_ix(&new_admin.pubkey(), ConfigParams { min_deposit: 10, ..open_config() });
+    harness.send(&[update], &[&new_admin]);
+    assert_eq!(harness.vault_config_data().min_deposit, 10);
+    let update = harness.update_config_ix(&admin, open_config());
+    let error = harness.try_send(&[update], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+}
+
+#[test]
+fn pending_admin_transfer_can_be_cancelled() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let admin = harness.payer.pubkey();
+    let new_admin = harness.new_user();
+
+    let transfer = harness.transfer_admin_ix(&admin, &new_admin.pubkey());
+    harness.send(&[transfer], &[]);
+    let cancel = harness.transfer_admin_ix(&admin, &Default::default());
+    harness.send(&[cancel], &[]);
+
+    let accept = harness.accept_admin_ix(&new_admin.pubkey());
+    let error = harness.try_send(&[accept], &[&new_admin]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::NoPendingAdmin)), "{error}");
+    assert_eq!(harness.vault_config_data().admin, admin);
+}
@@ -0,0 +1,60 @@
+use solana_sdk::signature::Signer;
+use spl_token_vault::{error::VaultError, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn hot_key_deposits_and_cold_key_withdraws() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let hot = harness.new_user();
+    let cold = harness.new_user();
+    harness.initialize_user(&hot);
+    harness.fund_user(&hot.pubkey(), 1_000_000);
+    harness.fund_user(&cold.pubkey(), 0);
+
+    // The hot key owns the position and lets the cold key withdraw up to a cap
+    harness.deposit(&hot, 400_000, LockupTier::None);
+    harness.set_delegate(&hot, &cold.pubkey(), Some(500_000));
+
+    // Anyone can top up the position
+    let other = harness.new_user();
+    harness.fund_user(&other.pubkey(), 200_000);
+    harness.deposit_for(&other, &hot.pubkey(), 200_000);
+
+    let withdraw = harness.withdraw_ix(&cold, &hot.pubkey(), 300_000);
+    harness.send(&[withdraw], &[&cold]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&cold.pubkey())), 300_000);
+
+    // Only 200_000 of the allowance is left
+    let withdraw = harness.withdraw_ix(&cold, &hot.pubkey(), 250_000);
+    let error = harness.try_send(&[withdraw], &[&cold]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::DelegateAllowanceExceeded)), "{error}");
+
+    // Revoked delegates can't withdraw at all
+    harness.revoke_delegate(&hot);
+    let withdraw = harness.withdraw_ix(&cold, &hot.pubkey(), 100_000);
+    let error = harness.try_send(&[withdraw], &[&cold]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+}
+
+#[test]
+fn strangers_cannot_withdraw() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    let stranger = harness.new_user();
+    harness.fund_user(&stranger.pubkey(), 0);
+    let withdraw = harness.withdraw_ix(&stranger, &user.pubkey(), 1);
+    let error = harness.try_send(&[withdraw], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+}
@@ -0,0 +1,117 @@
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_vault::{error::VaultError, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn taken_offers_pay_the_owner() {
+    let mut harness = Harness::new();
+    harness.load_escrow();
+    harness.setup_vault(0, 0);
+    let mint_b = harness.create_mint();
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+    harness.mint_to(&user.pubkey(), &mint_b, 0);
+
+    let ix = harness.make_escrow_offer_ix(&user.pubkey(), &mint_b, 1, 400_000, 50_000);
+    harness.send(&[ix], &[&user]);
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 600_000);
+    assert_eq!(harness.reconcile(false).total_deposits, 600_000);
+    // The escrow maker holds nothing between instructions
+    assert!(harness.svm.get_account(&harness.escrow_maker(&user.pubkey())).is_none());
+
+    let taker = harness.new_user();
+    harness.mint_to(&taker.pubkey(), &mint_b, 50_000);
+    let ix = harness.take_escrow_offer_ix(&taker.pubkey(), &user.pubkey(), &mint_b, 1);
+    harness.send(&[ix], &[&taker]);
+
+    assert_eq!(harness.token_balance(&harness.user_ata(&taker.pubkey())), 400_000);
+    let owner_ata_b = get_associated_token_address(&user.pubkey(), &mint_b);
+    assert_eq!(harness.token_balance(&owner_ata_b), 50_000);
+    assert!(harness.reconcile(false).solvent);
+}
+
+#[test]
+fn refunded_offers_return_to_the_position() {
+    let mut harness = Harness::new();
+    harness.load_escrow();
+    harness.setup_vault(0, 0);
+    let mint_b = harness.create_mint();
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    let ix = harness.make_escrow_offer_ix(&user.pubkey(), &mint_b, 7, 250_000, 10);
+    harness.send(&[ix], &[&user]);
+
+    // Earmarked tokens can't be withdrawn
+    assert!(harness.try_send(&[harness.withdraw_ix(&user, &user.pubkey(), 800_000)], &[&user]).is_err());
+
+    let ix = harness.refund_escrow_offer_ix(&user.pubkey(), &mint_b, 7);
+    harness.send(&[ix], &[&user]);
+    let report = harness.reconcile(false);
+    assert_eq!(report.vault_balance, 1_000_000);
+    assert_eq!(report.total_deposits, 1_000_000);
+
+    harness.withdraw(&user, 1_000_000);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 1_000_000);
+}
+
+#[test]
+fn only_the_owner_posts_offers() {
+    let mut harness = Harness::new();
+    harness.load_escrow();
+    harness.setup_vault(0, 0);
+    let mint_b = harness.create_mint();
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::None);
+
+    let thief = harness.new_user();
+    let mut ix = harness.make_escrow_offer_ix(&user.pubkey(), &mint_b, 1, 1_000, 1);
+    ix.accounts[0].pubkey = thief.pubkey();
+    assert!(harness.try_send(&[ix], &[&thief]).is_err());
+}
+
+// The escrow maker is checked against the bump stored in the user state, a
+// PDA of the same seeds with another bump isn't the maker
+#[test]
+fn only_the_canonical_escrow_maker_posts_offers() {
+    let mut harness = Harness::new();
+    harness.load_escrow();
+    harness.setup_vault(0, 0);
+    let mint_b = harness.create_mint();
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::None);
+
+    let user_state = harness.user_state(&user.pubkey());
+    let canonical = harness.escrow_maker(&user.pubkey());
+    let other_maker = (0..=u8::MAX)
+        .rev()
+        .filter_map(|bump| {
+            let seeds: &[&[u8]] = &[b"escrow_maker", user_state.as_ref(), &[bump]];
+            Pubkey::create_program_address(seeds, &harness.program_id).ok()
+        })
+        .find(|maker| *maker != canonical)
+        .unwrap();
+
+    let mut ix = harness.make_escrow_offer_ix(&user.pubkey(), &mint_b, 1, 1_000, 1);
+    ix.accounts[5].pubkey = other_maker;
+    ix.accounts[6].pubkey = get_associated_token_address(&other_maker, &harness.mint);
+    let error = harness.try_send(&[ix], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InvalidEscrowMaker)), "{error}");
+}
@@ -0,0 +1,87 @@
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token_vault::{error::VaultError, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+const FEE_BPS: u16 = 30;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+// Vault with a single depositor and flash loans enabled
+fn setup() -> (Harness, Keypair) {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.set_flash_loan_fee(FEE_BPS);
+
+    let depositor = harness.new_user();
+    harness.initialize_user(&depositor);
+    harness.fund_user(&depositor.pubkey(), 1_000_000);
+    harness.deposit(&depositor, 1_000_000, LockupTier::None);
+    (harness, depositor)
+}
+
+#[test]
+fn flash_loan_fee_goes_to_the_depositors() {
+    let (mut harness, depositor) = setup();
+    let borrower = harness.new_user();
+    // The borrower only needs to hold the fee
+    harness.fund_user(&borrower.pubkey(), 2_000);
+
+    let amount = 500_000;
+    let fee = 1_500;
+    let instructions = [
+        harness.flash_loan_ix(&borrower.pubkey(), amount),
+        harness.flash_repay_ix(&borrower.pubkey(), amount),
+    ];
+    harness.send(&instructions, &[&borrower]);
+
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 1_000_000 + fee);
+    assert_eq!(harness.token_balance(&harness.user_ata(&borrower.pubkey())), 2_000 - fee);
+
+    // The only depositor earned the whole fee and can withdraw it
+    harness.withdraw(&depositor, 1_000_000 + fee);
+    assert_eq!(harness.token_balance(&harness.user_ata(&depositor.pubkey())), 1_000_000 + fee);
+}
+
+#[test]
+fn flash_loan_without_repay_fails() {
+    let (mut harness, _depositor) = setup();
+    let borrower = harness.new_user();
+    harness.fund_user(&borrower.pubkey(), 2_000);
+
+    let loan = harness.flash_loan_ix(&borrower.pubkey(), 500_000);
+    let error = harness.try_send(&[loan], &[&borrower]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::MissingFlashRepay)), "{error}");
+}
+
+#[test]
+fn flash_repay_of_another_amount_fails() {
+    let (mut harness, _depositor) = setup();
+    let borrower = harness.new_user();
+    harness.fund_user(&borrower.pubkey(), 2_000);
+
+    let instructions = [
+        harness.flash_loan_ix(&borrower.pubkey(), 500_000),
+        harness.flash_repay_ix(&borrower.pubkey(), 400_000),
+    ];
+    let error = harness.try_send(&instructions, &[&borrower]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InvalidFlashRepay)), "{error}");
+}
+
+#[test]
+fn deposits_are_blocked_while_a_loan_is_open() {
+    let (mut harness, _depositor) = setup();
+    let borrower = harness.new_user();
+    harness.initialize_user(&borrower);
+    harness.fund_user(&borrower.pubkey(), 2_000);
+
+    // The borrower would otherwise deposit the loan and take most of its own fee
+    let instructions = [
+        harness.flash_loan_ix(&borrower.pubkey(), 500_000),
+        harness.deposit_ix(&borrower, 500_000, LockupTier::None),
+        harness.flash_repay_ix(&borrower.pubkey(), 500_000),
+    ];
+    let error = harness.try_send(&instructions, &[&borrower]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::FlashLoanInProgress)), "{error}");
+}
@@ -0,0 +1,133 @@
+use solana_sdk::{clock::Clock, signature::Signer};
+use spl_token_vault::{error::VaultError, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn locked_position_can_only_be_withdrawn_after_unlock() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::Days30);
+
+    let now = harness.svm.get_sysvar::<Clock>().unix_timestamp;
+    let state = harness.user_state_data(&user.pubkey());
+    assert_eq!(i64::from(state.unlock_ts), now + 30 * DAY);
+    assert_eq!(u16::from(state.boost_bps), 11_000);
+
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 1_000_000);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::PositionLocked)), "{error}");
+
+    harness.warp(30 * DAY - 1);
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 1_000_000);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::PositionLocked)), "{error}");
+
+    harness.warp(1);
+    harness.withdraw(&user, 1_000_000);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 1_000_000);
+}
+
+#[test]
+fn deposits_never_shorten_the_lock() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 3_000);
+    harness.deposit(&user, 1_000, LockupTier::Days180);
+    let unlock_ts = i64::from(harness.user_state_data(&user.pubkey()).unlock_ts);
+
+    harness.warp(DAY);
+    harness.deposit(&user, 1_000, LockupTier::None);
+    harness.deposit(&user, 1_000, LockupTier::Days30);
+
+    let state = harness.user_state_data(&user.pubkey());
+    assert_eq!(i64::from(state.unlock_ts), unlock_ts);
+    assert_eq!(u16::from(state.boost_bps), 15_000);
+    assert_eq!(u64::from(state.amount), 3_000);
+}
+
+// Once the lock ran out, a new deposit starts over with its own tier
+#[test]
+fn expired_lock_takes_the_new_tier() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 2_000);
+    harness.deposit(&user, 1_000, LockupTier::Days180);
+
+    harness.warp(180 * DAY);
+    harness.deposit(&user, 1_000, LockupTier::Days30);
+
+    let now = harness.svm.get_sysvar::<Clock>().unix_timestamp;
+    let state = harness.user_state_data(&user.pubkey());
+    assert_eq!(i64::from(state.unlock_ts), now + 30 * DAY);
+    assert_eq!(u16::from(state.boost_bps), 11_000);
+}
+
+#[test]
+fn early_exit_penalty_goes_to_the_treasury() {
+    let mut harness = Harness::new();
+    // 5% penalty
+    harness.setup_vault(500, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::Days90);
+
+    // 5% of 1_001 is 50.05, rounded up in favour of the vault
+    let withdraw = harness.withdraw_early_ix(&user, 1_001);
+    harness.send(&[withdraw], &[&user]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 950);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(998_999));
+    assert_eq!(u64::from(harness.vault_state_data().treasury), 51);
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 999_050);
+
+    // Only the vault admin can pay out the treasury
+    let admin = harness.payer.pubkey();
+    harness.fund_user(&admin, 0);
+    let stranger = harness.new_user();
+    let payout = harness.withdraw_treasury_ix(&stranger.pubkey(), &harness.user_ata(&admin), 51);
+    let error = harness.try_send(&[payout], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::Unauthorized)), "{error}");
+
+    let payout = harness.withdraw_treasury_ix(&admin, &harness.user_ata(&admin), 52);
+    let error = harness.try_send(&[payout], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientFunds)), "{error}");
+
+    let payout = harness.withdraw_treasury_ix(&admin, &harness.user_ata(&admin), 51);
+    harness.send(&[payout], &[]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&admin)), 51);
+    assert_eq!(u64::from(harness.vault_state_data().treasury), 0);
+}
+
+#[test]
+fn early_exit_of_unlocked_position_is_free() {
+    let mut harness = Harness::new();
+    harness.setup_vault(500, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::Days30);
+    harness.warp(30 * DAY);
+
+    let withdraw = harness.withdraw_early_ix(&user, 1_000);
+    harness.send(&[withdraw], &[&user]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 1_000);
+    assert_eq!(u64::from(harness.vault_state_data().treasury), 0);
+}
@@ -0,0 +1,132 @@
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signer};
+use spl_token_vault::{
+    error::VaultError,
+    legacy::{LegacyLayout, ProgramState, UserBalances},
+    state::LockupTier,
+};
+use spl_token_vault_tests::Harness;
+
+const LAYOUTS: [LegacyLayout; 2] = [LegacyLayout::UserBalances, LegacyLayout::ProgramState];
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+// Write the legacy account of the user as the legacy program left it
+fn set_legacy_account(harness: &mut Harness, user: &Pubkey, layout: LegacyLayout, balance: u64) -> u64 {
+    let data = match layout {
+        LegacyLayout::UserBalances => borsh::to_vec(&UserBalances { wallet: *user, balance }),
+        LegacyLayout::ProgramState => borsh::to_vec(&ProgramState { owner: *user, balance }),
+    }
+    .unwrap();
+    let lamports = harness.svm.minimum_balance_for_rent_exemption(data.len());
+    let account = Account { lamports, data, owner: harness.program_id, executable: false, rent_epoch: 0 };
+    harness.svm.set_account(harness.legacy_state(user), account).unwrap();
+    lamports
+}
+
+#[test]
+fn every_legacy_layout_is_migrated() {
+    for layout in LAYOUTS {
+        let mut harness = Harness::new();
+        harness.setup_vault(0, 0);
+        let authority = harness.payer.pubkey();
+
+        let user = harness.new_user();
+        let legacy_rent = set_legacy_account(&mut harness, &user.pubkey(), layout, 500);
+        let vault_state = harness.vault_state();
+        let mint = harness.mint;
+        harness.mint_to(&vault_state, &mint, 500);
+
+        let lamports = harness.svm.get_account(&user.pubkey()).unwrap().lamports;
+        let migrate = harness.migrate_user_state_ix(&authority, &user.pubkey(), layout);
+        harness.send(&[migrate], &[]);
+
+        // The legacy rent goes back to the user, the authority pays for the user state
+        assert_eq!(harness.user_amount(&user.pubkey()), Some(500), "{layout:?}");
+        assert!(harness.is_closed(&harness.legacy_state(&user.pubkey())));
+        assert_eq!(harness.svm.get_account(&user.pubkey()).unwrap().lamports, lamports + legacy_rent);
+        assert_eq!(u64::from(harness.vault_state_data().total_deposits), 500);
+
+        // The migrated position is a regular one
+        harness.fund_user(&user.pubkey(), 0);
+        harness.withdraw(&user, 500);
+        assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 500);
+    }
+}
+
+#[test]
+fn legacy_balance_is_added_to_an_existing_position() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let authority = harness.payer.pubkey();
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 300);
+    harness.deposit(&user, 300, LockupTier::None);
+
+    set_legacy_account(&mut harness, &user.pubkey(), LegacyLayout::UserBalances, 500);
+    let vault_state = harness.vault_state();
+    let mint = harness.mint;
+    harness.mint_to(&vault_state, &mint, 500);
+    let migrate = harness.migrate_user_state_ix(&authority, &user.pubkey(), LegacyLayout::UserBalances);
+    harness.send(&[migrate], &[]);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(800));
+}
+
+#[test]
+fn migration_needs_the_tokens_in_the_vault() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let authority = harness.payer.pubkey();
+
+    let user = harness.new_user();
+    set_legacy_account(&mut harness, &user.pubkey(), LegacyLayout::ProgramState, 500);
+    let vault_state = harness.vault_state();
+    let mint = harness.mint;
+    harness.mint_to(&vault_state, &mint, 499);
+
+    let migrate = harness.migrate_user_state_ix(&authority, &user.pubkey(), LegacyLayout::ProgramState);
+    let error = harness.try_send(&[migrate], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::MigrationNotBacked)), "{error}");
+    assert_eq!(harness.user_amount(&user.pubkey()), None);
+}
+
+#[test]
+fn only_the_upgrade_authority_migrates() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    set_legacy_account(&mut harness, &user.pubkey(), LegacyLayout::UserBalances, 500);
+    let vault_state = harness.vault_state();
+    let mint = harness.mint;
+    harness.mint_to(&vault_state, &mint, 500);
+
+    // Not even the user itself
+    let migrate = harness.migrate_user_state_ix(&user.pubkey(), &user.pubkey(), LegacyLayout::UserBalances);
+    let error = harness.try_send(&[migrate], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::NotUpgradeAuthority)), "{error}");
+}
+
+#[test]
+fn legacy_account_of_another_wallet_is_rejected() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let authority = harness.payer.pubkey();
+
+    // Stored under the user's PDA, but written for someone else
+    let user = harness.new_user();
+    let other = Pubkey::new_unique();
+    let data = borsh::to_vec(&UserBalances { wallet: other, balance: 500 }).unwrap();
+    let lamports = harness.svm.minimum_balance_for_rent_exemption(data.len());
+    let account = Account { lamports, data, owner: harness.program_id, executable: false, rent_epoch: 0 };
+    let legacy_state = harness.legacy_state(&user.pubkey());
+    harness.svm.set_account(legacy_state, account).unwrap();
+
+    let migrate = harness.migrate_user_state_ix(&authority, &user.pubkey(), LegacyLayout::UserBalances);
+    let error = harness.try_send(&[migrate], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InvalidLegacyAccount)), "{error}");
+    assert!(!harness.is_closed(&legacy_state));
+}
@@ -0,0 +1,109 @@
+use solana_sdk::{clock::Clock, signature::Signer};
+use spl_token_vault::{error::VaultError, state::ConfigParams, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+const WINDOW: i64 = 60 * 60;
+
+fn limits(user_window_limit: u64, vault_window_limit: u64) -> ConfigParams {
+    ConfigParams {
+        user_deposit_cap: u64::MAX,
+        tvl_cap: u64::MAX,
+        min_deposit: 0,
+        min_withdraw: 0,
+        deposit_paused: false,
+        withdraw_paused: false,
+        rate_limit_window: WINDOW,
+        user_window_limit,
+        vault_window_limit,
+        early_exit_penalty_bps: 0,
+    }
+}
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn user_limit_resets_with_the_window() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.update_config(limits(100_000, u64::MAX));
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    harness.withdraw(&user, 60_000);
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 60_000);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::UserRateLimitExceeded)), "{error}");
+
+    // A new window starts once the previous one ran out
+    let mut clock = harness.svm.get_sysvar::<Clock>();
+    clock.unix_timestamp += WINDOW;
+    harness.svm.set_sysvar(&clock);
+    harness.withdraw(&user, 60_000);
+}
+
+#[test]
+fn vault_limit_spans_all_users() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.update_config(limits(u64::MAX, 100_000));
+
+    let first = harness.new_user();
+    let second = harness.new_user();
+    for user in [&first, &second] {
+        harness.initialize_user(user);
+        harness.fund_user(&user.pubkey(), 1_000_000);
+        harness.deposit(user, 1_000_000, LockupTier::None);
+    }
+
+    harness.withdraw(&first, 60_000);
+    let withdraw = harness.withdraw_ix(&second, &second.pubkey(), 60_000);
+    let error = harness.try_send(&[withdraw], &[&second]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::VaultRateLimitExceeded)), "{error}");
+}
+
+// Without a cooldown a ticket can be requested and claimed in one transaction,
+// it counts against the window like a withdrawal
+#[test]
+fn tickets_count_against_the_window() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    harness.update_config(limits(100_000, u64::MAX));
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    let instructions =
+        [harness.request_withdraw_ix(&user, 0, 60_000), harness.claim_withdraw_ix(&user, 0)];
+    harness.send(&instructions, &[&user]);
+    let instructions =
+        [harness.request_withdraw_ix(&user, 1, 60_000), harness.claim_withdraw_ix(&user, 1)];
+    let error = harness.try_send(&instructions, &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::UserRateLimitExceeded)), "{error}");
+}
+
+// On a vault with a cooldown tickets are the only way out, the limits apply
+// when they are requested
+#[test]
+fn cooldown_vault_limits_requests() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, WINDOW);
+    harness.update_config(limits(u64::MAX, 100_000));
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    let request = harness.request_withdraw_ix(&user, 0, 60_000);
+    harness.send(&[request], &[&user]);
+    let request = harness.request_withdraw_ix(&user, 1, 60_000);
+    let error = harness.try_send(&[request], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::VaultRateLimitExceeded)), "{error}");
+}
@@ -0,0 +1,48 @@
+use solana_sdk::signature::Signer;
+use spl_token_vault::state::LockupTier;
+use spl_token_vault_tests::Harness;
+
+#[test]
+fn deposits_and_withdrawals_keep_the_vault_solvent() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 700_000, LockupTier::None);
+    harness.withdraw(&user, 200_000);
+
+    let report = harness.reconcile(false);
+    assert_eq!(report.vault_balance, 500_000);
+    assert_eq!(report.total_deposits, 500_000);
+    assert_eq!(report.liabilities, 500_000);
+    assert!(report.solvent);
+    assert!(!report.paused);
+}
+
+#[test]
+fn donations_show_up_as_a_surplus() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    // Tokens sent straight to the vault ATA belong to no position
+    let donor = harness.new_user();
+    harness.fund_user(&donor.pubkey(), 1_000);
+    let donation = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &harness.user_ata(&donor.pubkey()),
+        &harness.vault_ata(),
+        &donor.pubkey(),
+        &[],
+        1_000,
+    )
+    .unwrap();
+    harness.send(&[donation], &[&donor]);
+
+    let report = harness.reconcile(true);
+    assert_eq!(report.vault_balance, 1_000);
+    assert_eq!(report.liabilities, 0);
+    assert!(report.solvent);
+    assert!(!report.paused);
+}
@@ -0,0 +1,106 @@
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signer};
+use spl_token_2022::extension::{ExtensionType, transfer_fee, transfer_hook};
+use spl_token_vault::{error::VaultError, state::LockupTier};
+use spl_token_vault_tests::Harness;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+#[test]
+fn token_2022_mint_deposits_and_withdraws() {
+    let mut harness = Harness::new();
+    harness.use_token_2022_mint(&[], |_| Vec::new());
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 1_000_000);
+
+    harness.withdraw(&user, 400_000);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(600_000));
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 400_000);
+}
+
+// The fee is withheld in the receiving account, the position is only credited what arrived
+#[test]
+fn transfer_fee_is_not_credited() {
+    let mut harness = Harness::new();
+    let authority = harness.payer.pubkey();
+    // 1% fee
+    harness.use_token_2022_mint(&[ExtensionType::TransferFeeConfig], |mint| {
+        vec![
+            transfer_fee::instruction::initialize_transfer_fee_config(
+                &spl_token_2022::id(),
+                mint,
+                Some(&authority),
+                Some(&authority),
+                100,
+                u64::MAX,
+            )
+            .unwrap(),
+        ]
+    });
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(990_000));
+    assert_eq!(harness.token_balance(&harness.vault_ata()), 990_000);
+    assert_eq!(u64::from(harness.vault_state_data().total_deposits), 990_000);
+
+    // The position can't take out more than the vault received
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 990_001);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientFunds)), "{error}");
+
+    // The fee of the way out is paid by the user as well
+    harness.withdraw(&user, 990_000);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(0));
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 980_100);
+}
+
+// Instruction initializing one of the extensions the vault rejects
+fn init_unsupported_extension(extension: ExtensionType, mint: &Pubkey, authority: &Pubkey) -> Instruction {
+    match extension {
+        ExtensionType::NonTransferable => {
+            spl_token_2022::instruction::initialize_non_transferable_mint(&spl_token_2022::id(), mint)
+        }
+        ExtensionType::TransferHook => transfer_hook::instruction::initialize(
+            &spl_token_2022::id(),
+            mint,
+            Some(*authority),
+            Some(Pubkey::new_unique()),
+        ),
+        ExtensionType::PermanentDelegate => {
+            spl_token_2022::instruction::initialize_permanent_delegate(&spl_token_2022::id(), mint, authority)
+        }
+        _ => unreachable!(),
+    }
+    .unwrap()
+}
+
+#[test]
+fn mints_the_vault_cannot_custody_are_rejected() {
+    for extension in [
+        ExtensionType::NonTransferable,
+        ExtensionType::TransferHook,
+        ExtensionType::PermanentDelegate,
+    ] {
+        let mut harness = Harness::new();
+        let authority = harness.payer.pubkey();
+        harness.use_token_2022_mint(&[extension], |mint| {
+            vec![init_unsupported_extension(extension, mint, &authority)]
+        });
+        harness.initialize_registry();
+
+        let initialize = harness.initialize_vault_ix(0, 0);
+        let error = harness.try_send(&[initialize], &[]).unwrap_err();
+        assert!(error.contains(&custom_error(VaultError::UnsupportedMint)), "{extension:?}: {error}");
+        assert!(harness.is_closed(&harness.vault_state()));
+    }
+}
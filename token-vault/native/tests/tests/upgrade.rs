@@ -0,0 +1,369 @@
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signer};
+use spl_token_vault::{
+    error::VaultError,
+    escrow::escrow_maker_address,
+    header::{AccountHeader, AccountKind, HEADER_LEN, VaultAccount, parse_header},
+    legacy::{
+        UserStateV0, UserStateV1, UserStateV2, UserStateV3, UserStateV4, UserStateV5,
+        UserStateV6, VaultConfigV0, VaultConfigV1, VaultStateV0, VaultStateV1, VaultStateV2,
+        VaultStateV3, VaultStateV4, WithdrawTicketV0,
+    },
+    state::{
+        ConfigParams, LockupTier, RateWindow, UNSYNCED_FEE_INDEX, UserState, VaultConfig,
+        VaultState, WithdrawTicket,
+    },
+};
+use spl_token_vault_tests::Harness;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+// Account data as the given version of the program wrote it, unversioned
+// accounts were written before headers existed
+fn account_data<T: VaultAccount>(version: Option<u8>, bump: u8, body: &[u8]) -> Vec<u8> {
+    let mut data = match version {
+        Some(version) => {
+            let header = AccountHeader { discriminator: T::DISCRIMINATOR, version, bump };
+            borsh::to_vec(&header).unwrap()
+        }
+        None => Vec::new(),
+    };
+    data.extend_from_slice(body);
+    data
+}
+
+// Replace the account with older data, funded for its own size only
+fn set_old_account(harness: &mut Harness, address: &Pubkey, data: Vec<u8>) {
+    let lamports = harness.svm.minimum_balance_for_rent_exemption(data.len());
+    let account = Account { lamports, data, owner: harness.program_id, executable: false, rent_epoch: 0 };
+    harness.svm.set_account(*address, account).unwrap();
+}
+
+// Upgrade the account and check it was resized, funded and given the current header
+fn upgrade<T: VaultAccount>(harness: &mut Harness, kind: AccountKind, address: &Pubkey, bump: u8) -> T {
+    let upgrade = harness.upgrade_account_ix(kind, address);
+    harness.send(&[upgrade], &[]);
+
+    let account = harness.svm.get_account(address).unwrap();
+    assert_eq!(account.data.len(), T::SPACE);
+    assert_eq!(account.lamports, harness.svm.minimum_balance_for_rent_exemption(T::SPACE));
+    let header = parse_header(&account.data).unwrap();
+    assert_eq!(header, AccountHeader { discriminator: T::DISCRIMINATOR, version: T::VERSION, bump });
+    T::unpack(&account.data[HEADER_LEN..]).unwrap()
+}
+
+// Body of a user state as each older version laid it out
+fn old_user_state(version: Option<u8>, user: Pubkey, mint: Pubkey, delegate: Pubkey) -> Vec<u8> {
+    let (amount, unlock_ts, boost_bps) = (700, 1_234, 12_500);
+    let fee_index = UNSYNCED_FEE_INDEX.into();
+    let outflow = RateWindow { start: 9i64.into(), withdrawn: 10u64.into() };
+    match version {
+        None => {
+            let v0 = UserStateV0 { user, mint, amount, is_initialized: true, unlock_ts, boost_bps };
+            borsh::to_vec(&v0).unwrap()
+        }
+        Some(1) => borsh::to_vec(&UserStateV1 { user, mint, amount, unlock_ts, boost_bps }).unwrap(),
+        Some(2) => bytemuck::bytes_of(&UserStateV2 {
+            user,
+            mint,
+            amount: amount.into(),
+            unlock_ts: unlock_ts.into(),
+            boost_bps: boost_bps.into(),
+        })
+        .to_vec(),
+        Some(3) => bytemuck::bytes_of(&UserStateV3 {
+            user,
+            mint,
+            amount: amount.into(),
+            unlock_ts: unlock_ts.into(),
+            boost_bps: boost_bps.into(),
+            fee_index,
+        })
+        .to_vec(),
+        Some(4) => bytemuck::bytes_of(&UserStateV4 {
+            user,
+            mint,
+            amount: amount.into(),
+            unlock_ts: unlock_ts.into(),
+            boost_bps: boost_bps.into(),
+            fee_index,
+            delegate,
+            delegate_allowance: 50u64.into(),
+        })
+        .to_vec(),
+        Some(5) => bytemuck::bytes_of(&UserStateV5 {
+            user,
+            mint,
+            amount: amount.into(),
+            unlock_ts: unlock_ts.into(),
+            boost_bps: boost_bps.into(),
+            fee_index,
+            delegate,
+            delegate_allowance: 50u64.into(),
+            outflow,
+        })
+        .to_vec(),
+        Some(6) => bytemuck::bytes_of(&UserStateV6 {
+            user,
+            mint,
+            amount: amount.into(),
+            unlock_ts: unlock_ts.into(),
+            boost_bps: boost_bps.into(),
+            fee_index,
+            delegate,
+            delegate_allowance: 50u64.into(),
+            outflow,
+            earmarked: 40u64.into(),
+        })
+        .to_vec(),
+        Some(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn user_state_of_every_version_is_upgraded() {
+    for version in [None, Some(1), Some(2), Some(3), Some(4), Some(5), Some(6)] {
+        let mut harness = Harness::new();
+        harness.setup_vault(0, 0);
+        let user = harness.new_user();
+        harness.fund_user(&user.pubkey(), 100);
+
+        let delegate = Pubkey::new_unique();
+        let (address, bump) = Pubkey::find_program_address(
+            &[b"user", user.pubkey().as_ref(), harness.mint.as_ref()],
+            &harness.program_id,
+        );
+        let body = old_user_state(version, user.pubkey(), harness.mint, delegate);
+        set_old_account(&mut harness, &address, account_data::<UserState>(version, bump, &body));
+
+        // Outdated accounts are rejected until they're upgraded
+        let deposit = harness.deposit_ix(&user, 100, LockupTier::None);
+        let error = harness.try_send(&[deposit], &[&user]).unwrap_err();
+        if version.is_some() {
+            assert!(error.contains(&custom_error(VaultError::OutdatedAccount)), "{version:?}: {error}");
+        }
+
+        let state = upgrade::<UserState>(&mut harness, AccountKind::UserState, &address, bump);
+        assert_eq!(state.user, user.pubkey());
+        assert_eq!(state.mint, harness.mint);
+        assert_eq!(u64::from(state.amount), 700);
+        assert_eq!(i64::from(state.unlock_ts), 1_234);
+        assert_eq!(u16::from(state.boost_bps), 12_500);
+        assert_eq!(u128::from(state.fee_index), UNSYNCED_FEE_INDEX);
+        assert_eq!(u64::from(state.earmarked), if version >= Some(6) { 40 } else { 0 });
+        assert_eq!(state.escrow_maker_bump, escrow_maker_address(&harness.program_id, &address).1);
+        if version >= Some(4) {
+            assert_eq!(state.delegate, delegate);
+            assert_eq!(u64::from(state.delegate_allowance), 50);
+        } else {
+            assert_eq!(state.delegate, Pubkey::default());
+            assert_eq!(u64::from(state.delegate_allowance), 0);
+        }
+        let outflow = (i64::from(state.outflow.start), u64::from(state.outflow.withdrawn));
+        assert_eq!(outflow, if version >= Some(5) { (9, 10) } else { (0, 0) });
+
+        harness.deposit(&user, 100, LockupTier::None);
+        assert_eq!(harness.user_amount(&user.pubkey()), Some(800));
+    }
+}
+
+#[test]
+fn vault_state_of_every_version_is_upgraded() {
+    for version in [None, Some(1), Some(2), Some(3), Some(4)] {
+        let mut harness = Harness::new();
+        harness.setup_vault(0, 0);
+        let mint = harness.mint;
+        let (address, bump) = Pubkey::find_program_address(&[b"vault", mint.as_ref()], &harness.program_id);
+
+        let v1 = VaultStateV1 {
+            mint,
+            early_exit_penalty_bps: 250,
+            treasury: 7,
+            withdraw_cooldown: 60,
+            next_ticket_id: 3,
+            queued_total: 10,
+            claimed_total: 5,
+        };
+        let body = match version {
+            None => borsh::to_vec(&VaultStateV0 {
+                mint,
+                early_exit_penalty_bps: 250,
+                treasury: 7,
+                is_initialized: true,
+                withdraw_cooldown: 60,
+                next_ticket_id: 3,
+                queued_total: 10,
+                claimed_total: 5,
+            })
+            .unwrap(),
+            Some(1) => borsh::to_vec(&v1).unwrap(),
+            Some(2) => bytemuck::bytes_of(&VaultStateV2::from(v1)).to_vec(),
+            Some(3) => {
+                let mut v3 = VaultStateV3::from(VaultStateV2::from(v1));
+                v3.flash_fee_bps = 9.into();
+                bytemuck::bytes_of(&v3).to_vec()
+            }
+            Some(4) => {
+                let mut v3 = VaultStateV3::from(VaultStateV2::from(v1));
+                v3.flash_fee_bps = 9.into();
+                let mut v4 = VaultStateV4::from(v3);
+                v4.outflow.withdrawn = 4.into();
+                bytemuck::bytes_of(&v4).to_vec()
+            }
+            Some(_) => unreachable!(),
+        };
+        set_old_account(&mut harness, &address, account_data::<VaultState>(version, bump, &body));
+
+        let user = harness.new_user();
+        let initialize = harness.initialize_user_ix(&user.pubkey());
+        harness.send(&[initialize], &[&user]);
+        harness.fund_user(&user.pubkey(), 100);
+        let deposit = harness.deposit_ix(&user, 100, LockupTier::None);
+        let error = harness.try_send(&[deposit], &[&user]).unwrap_err();
+        if version.is_some() {
+            assert!(error.contains(&custom_error(VaultError::OutdatedAccount)), "{version:?}: {error}");
+        }
+
+        let state = upgrade::<VaultState>(&mut harness, AccountKind::VaultState, &address, bump);
+        assert_eq!(state.mint, mint);
+        assert_eq!(u16::from(state.early_exit_penalty_bps), 250);
+        assert_eq!(u64::from(state.treasury), 7);
+        assert_eq!(i64::from(state.withdraw_cooldown), 60);
+        assert_eq!(u64::from(state.next_ticket_id), 3);
+        assert_eq!(u64::from(state.queued_total), 10);
+        assert_eq!(u64::from(state.claimed_total), 5);
+        assert_eq!(u64::from(state.funded_total), 5);
+        assert_eq!(u16::from(state.flash_fee_bps), if version >= Some(3) { 9 } else { 0 });
+        assert_eq!(u64::from(state.flash_loan_amount), 0);
+        assert_eq!(u64::from(state.total_deposits), 0);
+        assert_eq!(u128::from(state.fee_index), 0);
+        assert_eq!(u64::from(state.outflow.withdrawn), if version == Some(4) { 4 } else { 0 });
+
+        harness.deposit(&user, 100, LockupTier::None);
+        assert_eq!(u64::from(harness.vault_state_data().total_deposits), 100);
+    }
+}
+
+#[test]
+fn vault_config_of_every_version_is_upgraded() {
+    for version in [None, Some(1)] {
+        let mut harness = Harness::new();
+        harness.setup_vault(0, 0);
+        let vault = harness.vault_state();
+        let admin = harness.payer.pubkey();
+        let (address, bump) = Pubkey::find_program_address(&[b"config", vault.as_ref()], &harness.program_id);
+
+        let v1 = VaultConfigV1 {
+            vault,
+            admin,
+            pending_admin: Pubkey::default(),
+            user_deposit_cap: 1_000,
+            tvl_cap: 5_000,
+            min_deposit: 10,
+            min_withdraw: 20,
+            deposit_paused: false,
+            withdraw_paused: true,
+        };
+        let body = match version {
+            None => borsh::to_vec(&VaultConfigV0 {
+                vault,
+                admin,
+                pending_admin: Pubkey::default(),
+                user_deposit_cap: 1_000,
+                tvl_cap: 5_000,
+                min_deposit: 10,
+                min_withdraw: 20,
+                deposit_paused: false,
+                withdraw_paused: true,
+                is_initialized: true,
+            })
+            .unwrap(),
+            Some(_) => borsh::to_vec(&v1).unwrap(),
+        };
+        set_old_account(&mut harness, &address, account_data::<VaultConfig>(version, bump, &body));
+
+        let config = upgrade::<VaultConfig>(&mut harness, AccountKind::VaultConfig, &address, bump);
+        assert_eq!(config.vault, vault);
+        assert_eq!(config.admin, admin);
+        assert_eq!(config.pending_admin, Pubkey::default());
+        assert_eq!((config.user_deposit_cap, config.tvl_cap), (1_000, 5_000));
+        assert_eq!((config.min_deposit, config.min_withdraw), (10, 20));
+        assert!(!config.deposit_paused && config.withdraw_paused);
+        // Rate limits are off for upgraded configs
+        assert_eq!(config.rate_limit_window, 0);
+        assert_eq!((config.user_window_limit, config.vault_window_limit), (u64::MAX, u64::MAX));
+
+        // The admin kept its rights
+        harness.update_config(ConfigParams {
+            user_deposit_cap: u64::MAX,
+            tvl_cap: u64::MAX,
+            min_deposit: 0,
+            min_withdraw: 0,
+            deposit_paused: false,
+            withdraw_paused: false,
+            rate_limit_window: 0,
+            user_window_limit: u64::MAX,
+            vault_window_limit: u64::MAX,
+            early_exit_penalty_bps: 0,
+        });
+    }
+}
+
+#[test]
+fn unversioned_ticket_is_upgraded() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let vault = harness.vault_state();
+    let owner = Pubkey::new_unique();
+    let (address, bump) = Pubkey::find_program_address(
+        &[b"ticket", vault.as_ref(), &4u64.to_le_bytes()],
+        &harness.program_id,
+    );
+
+    let v0 = WithdrawTicketV0 {
+        owner,
+        mint: harness.mint,
+        id: 4,
+        amount: 300,
+        queue_end: 900,
+        claimable_after: 77,
+        is_initialized: true,
+    };
+    set_old_account(&mut harness, &address, borsh::to_vec(&v0).unwrap());
+
+    let ticket = upgrade::<WithdrawTicket>(&mut harness, AccountKind::WithdrawTicket, &address, bump);
+    assert_eq!((ticket.owner, ticket.mint), (owner, harness.mint));
+    assert_eq!((ticket.id, ticket.amount, ticket.queue_end), (4, 300, 900));
+    assert_eq!(ticket.claimable_after, 77);
+}
+
+#[test]
+fn only_older_accounts_at_their_own_address_are_upgraded() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    let address = harness.user_state(&user.pubkey());
+
+    let upgrade = harness.upgrade_account_ix(AccountKind::UserState, &address);
+    let error = harness.try_send(&[upgrade], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::AccountUpToDate)), "{error}");
+
+    // Written by a newer program version
+    let mut account = harness.svm.get_account(&address).unwrap();
+    account.data[8] = UserState::VERSION + 1;
+    harness.svm.set_account(address, account).unwrap();
+    let upgrade = harness.upgrade_account_ix(AccountKind::UserState, &address);
+    let error = harness.try_send(&[upgrade], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::UnsupportedAccountVersion)), "{error}");
+
+    // The position of someone else doesn't belong at this address
+    let other = Pubkey::new_unique();
+    let body = old_user_state(Some(2), other, harness.mint, Pubkey::default());
+    let bump = parse_header(&harness.svm.get_account(&address).unwrap().data).unwrap().bump;
+    set_old_account(&mut harness, &address, account_data::<UserState>(Some(2), bump, &body));
+    let upgrade = harness.upgrade_account_ix(AccountKind::UserState, &address);
+    let error = harness.try_send(&[upgrade], &[]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InvalidDiscriminator)), "{error}");
+}
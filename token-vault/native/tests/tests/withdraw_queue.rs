@@ -0,0 +1,225 @@
+use solana_sdk::signature::Signer;
+use solana_system_interface::instruction as system_instruction;
+use spl_token_vault::{
+    error::VaultError,
+    header::{HEADER_LEN, VaultAccount},
+    state::{LockupTier, WithdrawTicket},
+};
+use spl_token_vault_tests::Harness;
+
+const COOLDOWN: i64 = 7 * 24 * 60 * 60;
+
+fn custom_error(error: VaultError) -> String {
+    format!("Custom({})", error as u32)
+}
+
+// Ticket addresses are predictable, anyone can send lamports to the next one
+// before its owner requests it
+#[test]
+fn pre_funded_ticket_address_is_still_created() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    let attacker = harness.new_user();
+    let lamports = harness.svm.minimum_balance_for_rent_exemption(0);
+    let transfer = system_instruction::transfer(&attacker.pubkey(), &harness.ticket(0), lamports);
+    harness.send(&[transfer], &[&attacker]);
+
+    let request = harness.request_withdraw_ix(&user, 0, 400_000);
+    harness.send(&[request], &[&user]);
+
+    let ticket = harness.svm.get_account(&harness.ticket(0)).unwrap();
+    assert_eq!(ticket.owner, harness.program_id);
+    assert_eq!(ticket.data.len(), WithdrawTicket::SPACE);
+    assert_eq!(ticket.lamports, harness.svm.minimum_balance_for_rent_exemption(WithdrawTicket::SPACE));
+    let ticket = WithdrawTicket::unpack(&ticket.data[HEADER_LEN..]).unwrap();
+    assert_eq!(ticket.owner, user.pubkey());
+    assert_eq!(ticket.amount, 400_000);
+
+    let claim = harness.claim_withdraw_ix(&user, 0);
+    harness.send(&[claim], &[&user]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 400_000);
+}
+
+// The user state is a PDA of the user's key, the same goes for it
+#[test]
+fn pre_funded_user_state_is_still_created() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, 0);
+
+    let user = harness.new_user();
+    let attacker = harness.new_user();
+    let lamports = harness.svm.minimum_balance_for_rent_exemption(0);
+    let transfer = system_instruction::transfer(&attacker.pubkey(), &harness.user_state(&user.pubkey()), lamports);
+    harness.send(&[transfer], &[&attacker]);
+
+    harness.initialize_user(&user);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(0));
+}
+
+#[test]
+fn tickets_are_claimed_after_the_cooldown() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, COOLDOWN);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000_000);
+    harness.deposit(&user, 1_000_000, LockupTier::None);
+
+    // Vaults with a cooldown only pay out through tickets
+    let withdraw = harness.withdraw_ix(&user, &user.pubkey(), 400_000);
+    let error = harness.try_send(&[withdraw], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InstantWithdrawDisabled)), "{error}");
+
+    let request = harness.request_withdraw_ix(&user, 0, 400_000);
+    harness.send(&[request], &[&user]);
+    assert_eq!(harness.user_amount(&user.pubkey()), Some(600_000));
+    let vault = harness.vault_state_data();
+    assert_eq!(u64::from(vault.next_ticket_id), 1);
+    assert_eq!(u64::from(vault.queued_total), 400_000);
+
+    // Someone else's ticket can't be claimed
+    let stranger = harness.new_user();
+    harness.fund_user(&stranger.pubkey(), 0);
+    harness.warp(COOLDOWN);
+    let claim = harness.claim_withdraw_ix(&stranger, 0);
+    let error = harness.try_send(&[claim], &[&stranger]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InvalidOwner)), "{error}");
+    harness.warp(-1);
+
+    let claim = harness.claim_withdraw_ix(&user, 0);
+    let error = harness.try_send(&[claim], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::CooldownActive)), "{error}");
+
+    harness.warp(1);
+    let lamports = harness.svm.get_account(&user.pubkey()).unwrap().lamports;
+    let claim = harness.claim_withdraw_ix(&user, 0);
+    harness.send(&[claim], &[&user]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&user.pubkey())), 400_000);
+    assert_eq!(u64::from(harness.vault_state_data().claimed_total), 400_000);
+
+    // The ticket is closed and its rent goes back to the user
+    let ticket_rent = harness.svm.minimum_balance_for_rent_exemption(WithdrawTicket::SPACE);
+    assert!(harness.is_closed(&harness.ticket(0)));
+    assert_eq!(harness.svm.get_account(&user.pubkey()).unwrap().lamports, lamports + ticket_rent);
+}
+
+// The vault ATA only holds part of the deposits, the rest is deployed elsewhere
+#[test]
+fn tickets_are_paid_in_order_when_liquidity_is_short() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, COOLDOWN);
+
+    let first = harness.new_user();
+    let second = harness.new_user();
+    for user in [&first, &second] {
+        harness.initialize_user(user);
+        harness.fund_user(&user.pubkey(), 500_000);
+        harness.deposit(user, 500_000, LockupTier::None);
+    }
+    let request = harness.request_withdraw_ix(&first, 0, 400_000);
+    harness.send(&[request], &[&first]);
+    let request = harness.request_withdraw_ix(&second, 1, 300_000);
+    harness.send(&[request], &[&second]);
+    harness.warp(COOLDOWN);
+
+    let vault_ata = harness.vault_ata();
+    harness.set_token_balance(&vault_ata, 500_000);
+
+    // Enough for either ticket alone, but the second can't jump the queue
+    let claim = harness.claim_withdraw_ix(&second, 1);
+    let error = harness.try_send(&[claim], &[&second]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientLiquidity)), "{error}");
+
+    let claim = harness.claim_withdraw_ix(&first, 0);
+    harness.send(&[claim], &[&first]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&first.pubkey())), 400_000);
+
+    let claim = harness.claim_withdraw_ix(&second, 1);
+    let error = harness.try_send(&[claim], &[&second]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientLiquidity)), "{error}");
+
+    // The tokens come back to the vault
+    harness.set_token_balance(&vault_ata, 300_000);
+    let claim = harness.claim_withdraw_ix(&second, 1);
+    harness.send(&[claim], &[&second]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&second.pubkey())), 300_000);
+}
+
+// Claiming a later ticket first must not count the earlier one as paid, its
+// tokens stay set aside and the treasury never pays for it
+#[test]
+fn tickets_claimed_out_of_order_never_take_the_treasury() {
+    let mut harness = Harness::new();
+    harness.setup_vault(500, 0);
+
+    // An early exit fills the treasury
+    let exiter = harness.new_user();
+    harness.initialize_user(&exiter);
+    harness.fund_user(&exiter.pubkey(), 1_000_000);
+    harness.deposit(&exiter, 1_000_000, LockupTier::Days90);
+    let withdraw = harness.withdraw_early_ix(&exiter, 1_000_000);
+    harness.send(&[withdraw], &[&exiter]);
+    let treasury = u64::from(harness.vault_state_data().treasury);
+    assert_eq!(treasury, 50_000);
+
+    let first = harness.new_user();
+    let second = harness.new_user();
+    for user in [&first, &second] {
+        harness.initialize_user(user);
+        harness.fund_user(&user.pubkey(), 500_000);
+        harness.deposit(user, 500_000, LockupTier::None);
+    }
+    let request = harness.request_withdraw_ix(&first, 0, 40_000);
+    harness.send(&[request], &[&first]);
+    let request = harness.request_withdraw_ix(&second, 1, 300_000);
+    harness.send(&[request], &[&second]);
+
+    // Enough for both tickets, the second one is claimed first
+    let vault_ata = harness.vault_ata();
+    harness.set_token_balance(&vault_ata, treasury + 340_000);
+    let claim = harness.claim_withdraw_ix(&second, 1);
+    harness.send(&[claim], &[&second]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&second.pubkey())), 300_000);
+    assert_eq!(u64::from(harness.vault_state_data().funded_total), 340_000);
+
+    // The tokens set aside for the first ticket leave the vault
+    harness.set_token_balance(&vault_ata, treasury);
+    let claim = harness.claim_withdraw_ix(&first, 0);
+    let error = harness.try_send(&[claim], &[&first]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientLiquidity)), "{error}");
+    assert_eq!(harness.token_balance(&vault_ata), treasury);
+    assert_eq!(u64::from(harness.vault_state_data().treasury), treasury);
+
+    harness.set_token_balance(&vault_ata, treasury + 40_000);
+    let claim = harness.claim_withdraw_ix(&first, 0);
+    harness.send(&[claim], &[&first]);
+    assert_eq!(harness.token_balance(&harness.user_ata(&first.pubkey())), 40_000);
+    assert_eq!(harness.token_balance(&vault_ata), treasury);
+}
+
+#[test]
+fn locked_positions_cannot_request_a_ticket() {
+    let mut harness = Harness::new();
+    harness.setup_vault(0, COOLDOWN);
+
+    let user = harness.new_user();
+    harness.initialize_user(&user);
+    harness.fund_user(&user.pubkey(), 1_000);
+    harness.deposit(&user, 1_000, LockupTier::Days30);
+
+    let request = harness.request_withdraw_ix(&user, 0, 1_000);
+    let error = harness.try_send(&[request], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::PositionLocked)), "{error}");
+
+    let request = harness.request_withdraw_ix(&user, 0, 1_001);
+    harness.warp(30 * 24 * 60 * 60);
+    let error = harness.try_send(&[request], &[&user]).unwrap_err();
+    assert!(error.contains(&custom_error(VaultError::InsufficientFunds)), "{error}");
+}
@@ -0,0 +1,142 @@
+use pinocchio::program_error::ProgramError;
+
+// Same codes as the native vault, in the same order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultError {
+    // Vault is already initialized
+    AlreadyInitialized,
+    // Invalid Vault account PDA
+    InvalidVaultAuthority,
+    // Invalid User account PDA
+    InvalidUserAccount,
+    // Invalid token mint
+    InvalidMint,
+    // Invalid token account owner
+    InvalidOwner,
+    // Source and destination mint mismatch
+    MintMismatch,
+    // Insufficient user balance
+    InsufficientFunds,
+    // Overflow occurred
+    Overflow,
+    // Account not rent exempt
+    NotRentExempt,
+    // Invalid instruction
+    InvalidInstruction,
+    // Missing required signer
+    NotSigner,
+    // General failure
+    GenericError,
+    // Account is not writable
+    NotWritable,
+    // Invalid user state account data
+    InvalidUserState,
+    // Failed to serialize account data
+    SerializationError,
+    // Invalid Vault state account
+    InvalidVaultState,
+    // Invalid Vault token account
+    InvalidVaultTokenAccount,
+    // Amount must be greater than zero
+    InvalidAmount,
+    // Position is still locked
+    PositionLocked,
+    // Penalty must not exceed 10000 basis points
+    InvalidPenalty,
+    // Vault only allows withdrawals through a withdrawal request
+    InstantWithdrawDisabled,
+    // Invalid withdrawal ticket
+    InvalidTicket,
+    // Withdrawal ticket is still cooling down
+    CooldownActive,
+    // Not enough liquidity in the vault for this ticket
+    InsufficientLiquidity,
+    // Cooldown must not be negative
+    InvalidCooldown,
+    // Invalid Vault config account
+    InvalidVaultConfig,
+    // Signer is not the vault admin
+    Unauthorized,
+    // No admin transfer is pending for this signer
+    NoPendingAdmin,
+    // Deposits are paused
+    DepositPaused,
+    // Withdrawals are paused
+    WithdrawPaused,
+    // Amount is below the vault minimum
+    BelowMinimum,
+    // Deposit exceeds the per user cap
+    UserCapExceeded,
+    // Deposit exceeds the vault TVL cap
+    TvlCapExceeded,
+    // User state still holds a balance
+    UserStateNotEmpty,
+    // Token program must be SPL Token or Token-2022
+    InvalidTokenProgram,
+    // Mint has an extension the vault can't custody
+    UnsupportedMint,
+    // Invalid legacy user account
+    InvalidLegacyAccount,
+    // Account is not owned by the vault program
+    InvalidAccountOwner,
+    // Account data has the wrong size
+    InvalidAccountSize,
+    // Account discriminator does not match the expected type
+    InvalidDiscriminator,
+    // Account was written by an older version, upgrade it first
+    OutdatedAccount,
+    // Account version is newer than this program supports
+    UnsupportedAccountVersion,
+    // Account already has the current version
+    AccountUpToDate,
+    // Invalid vault registry account
+    InvalidRegistry,
+    // Mint has no registered vault
+    MintNotRegistered,
+    // Mint already has a registered vault
+    MintAlreadyRegistered,
+    // Vault registry is full
+    RegistryFull,
+    // Flash loans are disabled for this vault
+    FlashLoansDisabled,
+    // A flash loan is open on this vault
+    FlashLoanInProgress,
+    // Flash loan is not followed by a matching flash repay
+    MissingFlashRepay,
+    // Flash repay does not match the open flash loan
+    InvalidFlashRepay,
+    // Flash loans can't be taken through a CPI
+    FlashLoanViaCpi,
+    // Fee exceeds 100%
+    InvalidFee,
+    // Vault has no deposits to share the fee
+    NoDepositors,
+    // Delegate can't be the default key or the user
+    InvalidDelegate,
+    // Withdrawal exceeds the delegate's allowance
+    DelegateAllowanceExceeded,
+    // Rate limit window must not be negative
+    InvalidRateLimitWindow,
+    // Withdrawal exceeds the user's limit for the current window
+    UserRateLimitExceeded,
+    // Withdrawal exceeds the vault's limit for the current window
+    VaultRateLimitExceeded,
+    // Invalid escrow program
+    InvalidEscrowProgram,
+    // Invalid escrow offer
+    InvalidOffer,
+    // Invalid escrow maker account
+    InvalidEscrowMaker,
+    // Invalid program data account of the vault program
+    InvalidProgramData,
+    // Signer is not the upgrade authority of the vault program
+    NotUpgradeAuthority,
+    // Vault token account doesn't hold the migrated balance
+    MigrationNotBacked,
+}
+
+impl From<VaultError> for ProgramError {
+    fn from(e: VaultError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
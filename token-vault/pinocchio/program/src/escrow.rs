@@ -0,0 +1,229 @@
+//! Interface of the token escrow program in `token-escrow`, which vault users
+//! can post offers to straight from their position.
+//!
+//! The escrow needs the maker to sign its instructions, so offers are made by
+//! an escrow maker PDA of the user state. It holds no data and is only funded
+//! for the duration of an instruction.
+//!
+//! The escrow has no partial fills, `take_offer` pays out the whole offered
+//! amount and closes the offer. A take therefore settles everything the offer
+//! earmarks and a refund returns all of it to the position, nothing is left
+//! over to flow back. To sell part of an offer, refund it and post a smaller
+//! one.
+
+use crate::error::VaultError;
+use crate::pod::PodU64;
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::invoke_signed,
+    instruction::{AccountMeta, Instruction, Signer},
+    pubkey::{Pubkey, find_program_address},
+    ProgramResult,
+};
+use pinocchio_pubkey::pubkey;
+
+/// Program id of the escrow
+pub const ESCROW_PROGRAM_ID: Pubkey = pubkey!("6qDkztsEJ4r73Sqk1pDdL1degbzjyccqAX2uPDCsJJWf");
+
+// First 8 bytes of sha256("account:Offer")
+pub const OFFER_DISCRIMINATOR: [u8; 8] = [215, 88, 60, 71, 170, 162, 73, 229];
+// First 8 bytes of sha256("global:<instruction name>")
+const MAKE_OFFER: [u8; 8] = [214, 98, 97, 35, 59, 12, 44, 178];
+const TAKE_OFFER: [u8; 8] = [128, 156, 242, 207, 237, 192, 103, 240];
+const REFUND_OFFER: [u8; 8] = [171, 18, 70, 32, 244, 121, 60, 75];
+
+// Size of an offer account, discriminator included
+pub const OFFER_SPACE: usize = 8 + 8 + 32 + 32 + 32 + 8 + 8 + 1;
+
+/// Offer account of the escrow, behind its 8 byte discriminator
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Offer {
+    pub id: PodU64,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_a_offered_amount: PodU64,
+    pub token_b_wanted_amount: PodU64,
+    pub bump: u8,
+}
+
+// Read an offer account of the escrow program
+pub fn load_offer(offer: &AccountInfo) -> Result<Offer, VaultError> {
+    if !offer.is_owned_by(&ESCROW_PROGRAM_ID) {
+        return Err(VaultError::InvalidOffer);
+    }
+    let data = offer.try_borrow_data().map_err(|_| VaultError::InvalidOffer)?;
+    if data.len() < OFFER_SPACE || data[..8] != OFFER_DISCRIMINATOR {
+        return Err(VaultError::InvalidOffer);
+    }
+    // SAFETY: the size was checked and `Offer` is made of byte arrays
+    Ok(unsafe { core::ptr::read_unaligned(data[8..].as_ptr() as *const Offer) })
+}
+
+// PDA making the escrow offers of a user state
+pub fn escrow_maker_address(program_id: &Pubkey, user_state: &Pubkey) -> (Pubkey, u8) {
+    find_program_address(&[b"escrow_maker", user_state], program_id)
+}
+
+// Instruction data, the discriminator followed by the little endian arguments
+fn data<const N: usize>(discriminator: [u8; 8], args: [u64; N]) -> [u8; 32] {
+    let mut data = [0; 32];
+    data[..8].copy_from_slice(&discriminator);
+    for (i, arg) in args.iter().enumerate() {
+        data[8 + i * 8..16 + i * 8].copy_from_slice(&arg.to_le_bytes());
+    }
+    data
+}
+
+// The accounts are the escrow's MakeOffer accounts, in its order
+#[allow(clippy::too_many_arguments)]
+pub fn make_offer(
+    maker: &AccountInfo,
+    token_mint_a: &AccountInfo,
+    token_mint_b: &AccountInfo,
+    maker_ata_a: &AccountInfo,
+    offer: &AccountInfo,
+    offer_vault_ata: &AccountInfo,
+    token_prog: &AccountInfo,
+    ata_prog: &AccountInfo,
+    system_prog: &AccountInfo,
+    id: u64,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+    maker_signer: Signer,
+) -> ProgramResult {
+    let data = data(MAKE_OFFER, [id, token_a_offered_amount, token_b_wanted_amount]);
+    invoke_signed(
+        &Instruction {
+            program_id: &ESCROW_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable_signer(maker.key()),
+                AccountMeta::readonly(token_mint_a.key()),
+                AccountMeta::readonly(token_mint_b.key()),
+                AccountMeta::writable(maker_ata_a.key()),
+                AccountMeta::writable(offer.key()),
+                AccountMeta::writable(offer_vault_ata.key()),
+                AccountMeta::readonly(token_prog.key()),
+                AccountMeta::readonly(ata_prog.key()),
+                AccountMeta::readonly(system_prog.key()),
+            ],
+            data: &data[..32],
+        },
+        &[
+            maker,
+            token_mint_a,
+            token_mint_b,
+            maker_ata_a,
+            offer,
+            offer_vault_ata,
+            token_prog,
+            ata_prog,
+            system_prog,
+        ],
+        &[maker_signer],
+    )
+}
+
+// The accounts are the escrow's TakeOffer accounts, in its order
+#[allow(clippy::too_many_arguments)]
+pub fn take_offer(
+    taker: &AccountInfo,
+    maker: &AccountInfo,
+    token_mint_a: &AccountInfo,
+    token_mint_b: &AccountInfo,
+    taker_ata_a: &AccountInfo,
+    taker_ata_b: &AccountInfo,
+    maker_ata_b: &AccountInfo,
+    offer: &AccountInfo,
+    offer_vault_ata: &AccountInfo,
+    token_prog: &AccountInfo,
+    ata_prog: &AccountInfo,
+    system_prog: &AccountInfo,
+    id: u64,
+    maker_signer: Signer,
+) -> ProgramResult {
+    let data = data(TAKE_OFFER, [id]);
+    invoke_signed(
+        &Instruction {
+            program_id: &ESCROW_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable_signer(taker.key()),
+                AccountMeta::writable_signer(maker.key()),
+                AccountMeta::readonly(token_mint_a.key()),
+                AccountMeta::readonly(token_mint_b.key()),
+                AccountMeta::writable(taker_ata_a.key()),
+                AccountMeta::writable(taker_ata_b.key()),
+                AccountMeta::writable(maker_ata_b.key()),
+                AccountMeta::writable(offer.key()),
+                AccountMeta::writable(offer_vault_ata.key()),
+                AccountMeta::readonly(token_prog.key()),
+                AccountMeta::readonly(ata_prog.key()),
+                AccountMeta::readonly(system_prog.key()),
+            ],
+            data: &data[..16],
+        },
+        &[
+            taker,
+            maker,
+            token_mint_a,
+            token_mint_b,
+            taker_ata_a,
+            taker_ata_b,
+            maker_ata_b,
+            offer,
+            offer_vault_ata,
+            token_prog,
+            ata_prog,
+            system_prog,
+        ],
+        &[maker_signer],
+    )
+}
+
+// The accounts are the escrow's RefundOffer accounts, in its order
+#[allow(clippy::too_many_arguments)]
+pub fn refund_offer(
+    maker: &AccountInfo,
+    token_mint_a: &AccountInfo,
+    token_mint_b: &AccountInfo,
+    maker_ata_a: &AccountInfo,
+    offer: &AccountInfo,
+    offer_vault_ata: &AccountInfo,
+    token_prog: &AccountInfo,
+    ata_prog: &AccountInfo,
+    system_prog: &AccountInfo,
+    id: u64,
+    maker_signer: Signer,
+) -> ProgramResult {
+    let data = data(REFUND_OFFER, [id]);
+    invoke_signed(
+        &Instruction {
+            program_id: &ESCROW_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable_signer(maker.key()),
+                AccountMeta::readonly(token_mint_a.key()),
+                AccountMeta::readonly(token_mint_b.key()),
+                AccountMeta::writable(maker_ata_a.key()),
+                AccountMeta::writable(offer.key()),
+                AccountMeta::writable(offer_vault_ata.key()),
+                AccountMeta::readonly(token_prog.key()),
+                AccountMeta::readonly(ata_prog.key()),
+                AccountMeta::readonly(system_prog.key()),
+            ],
+            data: &data[..16],
+        },
+        &[
+            maker,
+            token_mint_a,
+            token_mint_b,
+            maker_ata_a,
+            offer,
+            offer_vault_ata,
+            token_prog,
+            ata_prog,
+            system_prog,
+        ],
+        &[maker_signer],
+    )
+}
@@ -0,0 +1,215 @@
+//! Header every account of the vault program starts with, followed by the
+//! body of the account type.
+//!
+//! Layout: `[discriminator: 8][version: 1][bump: 1][body]`
+//!
+//! Every body is a `#[repr(C)]` struct of byte arrays read in place, the
+//! bytes are the same as the native program's Pod and borsh layouts.
+
+use crate::error::VaultError;
+use crate::instructions::shared::system_transfer;
+use pinocchio::{
+    account_info::{AccountInfo, Ref, RefMut},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{Sysvar, rent::Rent},
+    ProgramResult,
+};
+
+// Size of the header
+pub const HEADER_LEN: usize = 8 + 1 + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountHeader {
+    // First 8 bytes of sha256("account:<TypeName>")
+    pub discriminator: [u8; 8],
+    // Layout version of the body
+    pub version: u8,
+    // Bump of the PDA holding the account
+    pub bump: u8,
+}
+
+/// Account types which can be passed to `UpgradeAccount`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKind {
+    VaultState,
+    VaultConfig,
+    UserState,
+    WithdrawTicket,
+}
+impl AccountKind {
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::VaultState),
+            1 => Some(Self::VaultConfig),
+            2 => Some(Self::UserState),
+            3 => Some(Self::WithdrawTicket),
+            _ => None,
+        }
+    }
+}
+
+/// Implemented by every account type stored behind an `AccountHeader`
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` structs of byte arrays, with a size of
+/// `LEN` and an alignment of 1, for which any bytes are a valid value.
+pub unsafe trait VaultAccount: Sized {
+    const DISCRIMINATOR: [u8; 8];
+    // Version written by this build of the program
+    const VERSION: u8;
+    // Size of the body of the current version
+    const LEN: usize;
+    // Size of the account, header included
+    const SPACE: usize = HEADER_LEN + Self::LEN;
+
+    /// Decode the data of an account written by an older version. `None` is
+    /// for accounts created before headers existed, `data` is then the whole
+    /// account, otherwise only the body behind the header.
+    fn from_version(version: Option<u8>, data: &[u8]) -> Result<Self, VaultError>;
+
+    /// PDA the account is stored under and its bump
+    fn find_address(&self, program_id: &Pubkey) -> (Pubkey, u8);
+
+    /// Find the bumps of other PDAs the account stores, for accounts decoded
+    /// from a version which didn't store them yet
+    fn find_bumps(&mut self, _program_id: &Pubkey, _address: &Pubkey) {}
+}
+
+// Check the owner and read the header of a program account
+pub fn read_header(program_id: &Pubkey, account: &AccountInfo) -> Result<AccountHeader, ProgramError> {
+    if !account.is_owned_by(program_id) {
+        return Err(VaultError::InvalidAccountOwner.into());
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < HEADER_LEN {
+        return Err(VaultError::InvalidAccountSize.into());
+    }
+    Ok(AccountHeader {
+        discriminator: data[..8].try_into().unwrap(),
+        version: data[8],
+        bump: data[9],
+    })
+}
+
+// Check the owner, size, discriminator and version of an account
+pub fn check<T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<AccountHeader, ProgramError> {
+    let header = read_header(program_id, account)?;
+    if header.discriminator != T::DISCRIMINATOR {
+        return Err(VaultError::InvalidDiscriminator.into());
+    }
+    if header.version < T::VERSION {
+        return Err(VaultError::OutdatedAccount.into());
+    }
+    if header.version > T::VERSION {
+        return Err(VaultError::UnsupportedAccountVersion.into());
+    }
+    if account.data_len() != T::SPACE {
+        return Err(VaultError::InvalidAccountSize.into());
+    }
+    Ok(header)
+}
+
+// Read only view into the body of a checked account, nothing is copied.
+// The view holds a borrow of the account data, drop it before a CPI which
+// writes to the account.
+pub fn load_ref<'a, T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<Ref<'a, T>, ProgramError> {
+    check::<T>(program_id, account)?;
+    let data = account.try_borrow_data()?;
+    // SAFETY: the size was checked and `T` has an alignment of 1
+    Ok(Ref::map(data, |data| unsafe { &*(data[HEADER_LEN..].as_ptr() as *const T) }))
+}
+
+// Mutable view into the body of a checked account, writes go straight to the
+// account data. Drop it before any CPI the account is passed to.
+pub fn load_mut<'a, T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<RefMut<'a, T>, ProgramError> {
+    check::<T>(program_id, account)?;
+    let data = account.try_borrow_mut_data()?;
+    // SAFETY: the size was checked and `T` has an alignment of 1
+    Ok(RefMut::map(data, |data| unsafe { &mut *(data[HEADER_LEN..].as_mut_ptr() as *mut T) }))
+}
+
+// Write the header and body of a freshly created account
+pub fn init<T: VaultAccount>(account: &AccountInfo, bump: u8, body: &T) -> ProgramResult {
+    init_header::<T>(account, bump)?;
+    // SAFETY: `T` is `LEN` bytes without padding
+    let bytes = unsafe { core::slice::from_raw_parts(body as *const T as *const u8, T::LEN) };
+    account.try_borrow_mut_data()?[HEADER_LEN..].copy_from_slice(bytes);
+    Ok(())
+}
+
+// Write only the header of a freshly created account, the body stays zeroed.
+// Used for accounts too large to build on the stack.
+pub fn init_header<T: VaultAccount>(account: &AccountInfo, bump: u8) -> ProgramResult {
+    if account.data_len() != T::SPACE {
+        return Err(VaultError::InvalidAccountSize.into());
+    }
+    let mut data = account.try_borrow_mut_data()?;
+    data[..8].copy_from_slice(&T::DISCRIMINATOR);
+    data[8] = T::VERSION;
+    data[9] = bump;
+    Ok(())
+}
+
+// Rewrite an account of an older version with the current layout. The account
+// is resized in place and the payer tops up the rent when it grows.
+pub fn upgrade<T: VaultAccount>(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    payer: &AccountInfo,
+) -> ProgramResult {
+    if !account.is_owned_by(program_id) {
+        return Err(VaultError::InvalidAccountOwner.into());
+    }
+
+    // Accounts without our discriminator are from before headers existed
+    let mut body = {
+        let data = account.try_borrow_data()?;
+        let version = match data.get(..HEADER_LEN) {
+            Some(header) if header[..8] == T::DISCRIMINATOR => Some(header[8]),
+            _ => None,
+        };
+        match version {
+            Some(version) if version == T::VERSION => {
+                return Err(VaultError::AccountUpToDate.into());
+            }
+            Some(version) if version > T::VERSION => {
+                return Err(VaultError::UnsupportedAccountVersion.into());
+            }
+            Some(version) => {
+                msg!("Upgrading account");
+                T::from_version(Some(version), &data[HEADER_LEN..])?
+            }
+            None => {
+                msg!("Upgrading unversioned account");
+                T::from_version(None, &data)?
+            }
+        }
+    };
+
+    // The address ties the decoded data to the account type
+    let (address, bump) = body.find_address(program_id);
+    if address != *account.key() {
+        return Err(VaultError::InvalidDiscriminator.into());
+    }
+    body.find_bumps(program_id, account.key());
+
+    let lamports = Rent::get()?.minimum_balance(T::SPACE);
+    if account.lamports() < lamports {
+        system_transfer(payer, account, lamports - account.lamports(), &[])?;
+    }
+    // Every byte is written below
+    account.realloc(T::SPACE, false)?;
+    init(account, bump, &body)
+}
@@ -0,0 +1,44 @@
+use super::shared::load_vault_config_mut;
+use crate::error::VaultError;
+use crate::state::DEFAULT_PUBKEY;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, find_program_address},
+    ProgramResult,
+};
+
+/// Second step of an admin transfer, the proposed admin accepts the role.
+///
+/// Accounts:
+/// [signer pending_admin]
+/// [writable vault_config]
+/// [readonly token_mint]
+pub fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [pending_admin, vault_config, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !pending_admin.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_config.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let (vault_pda, _vault_bump) = find_program_address(&[b"vault", token_mint.key()], program_id);
+    let mut vault_config_data = load_vault_config_mut(program_id, vault_config, &vault_pda)?;
+    if vault_config_data.pending_admin == DEFAULT_PUBKEY
+        || vault_config_data.pending_admin != *pending_admin.key()
+    {
+        return Err(VaultError::NoPendingAdmin.into());
+    }
+
+    msg!("Pending admin is now the vault admin");
+    vault_config_data.admin = *pending_admin.key();
+    vault_config_data.pending_admin = DEFAULT_PUBKEY;
+
+    Ok(())
+}
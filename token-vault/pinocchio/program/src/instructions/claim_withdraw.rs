@@ -0,0 +1,106 @@
+use super::shared::{
+    check_vault_ata, close_account, is_pda, load_vault_config, load_vault_state,
+    load_vault_state_mut, token_account_amount, transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::header::{load_ref, read_header};
+use crate::state::WithdrawTicket;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{Sysvar, clock::Clock},
+    ProgramResult,
+};
+
+/// Second step of a withdrawal, pays out a ticket once its cooldown has passed
+/// and closes it. When the vault is short on liquidity tickets are served in
+/// the order they were requested.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_ata]
+/// [writable vault_ata]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [writable ticket]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn claim_withdraw(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [payer, user_ata, vault_ata, vault_state, vault_config, ticket, token_mint, token_prog, ..] = accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_ata.is_writable()
+        || !vault_ata.is_writable()
+        || !vault_state.is_writable()
+        || !ticket.is_writable()
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    load_vault_state(program_id, vault_state, token_mint.key())?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+    if bool::from(load_vault_config(program_id, vault_config, vault_state.key())?.withdraw_paused) {
+        return Err(VaultError::WithdrawPaused.into());
+    }
+
+    // Verify and copy the ticket, it is closed at the end
+    let ticket_bump = read_header(program_id, ticket)?.bump;
+    let ticket_data = *load_ref::<WithdrawTicket>(program_id, ticket)?;
+    let id_bytes = u64::from(ticket_data.id).to_le_bytes();
+    if !is_pda(program_id, ticket.key(), &[b"ticket", vault_state.key(), &id_bytes, &[ticket_bump]]) {
+        return Err(VaultError::InvalidTicket.into());
+    }
+    if ticket_data.owner != *payer.key() {
+        return Err(VaultError::InvalidOwner.into());
+    }
+
+    if Clock::get()?.unix_timestamp < i64::from(ticket_data.claimable_after) {
+        msg!("Ticket can't be claimed yet");
+        return Err(VaultError::CooldownActive.into());
+    }
+
+    // Tickets are funded in the order they were requested, from the liquidity
+    // which isn't set aside for earlier tickets yet, so a later ticket can't
+    // jump the queue while the vault is short on liquidity. Funded tickets
+    // can be claimed in any order.
+    let amount = u64::from(ticket_data.amount);
+    let vault_balance = token_account_amount(vault_ata)?;
+    {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+        // Lent out tokens are not in the vault ATA until the loan is repaid
+        if u64::from(vault_state_data.flash_loan_amount) != 0 {
+            return Err(VaultError::FlashLoanInProgress.into());
+        }
+        let liquidity = vault_balance.saturating_sub(vault_state_data.treasury.into());
+        let funded_total = vault_state_data.fund_tickets(liquidity);
+        // Tokens set aside can have left the vault since, the treasury is never paid out
+        if u64::from(ticket_data.queue_end) > funded_total || amount > liquidity {
+            return Err(VaultError::InsufficientLiquidity.into());
+        }
+    }
+
+    // Pay out the ticket
+    msg!("Claiming withdrawal ticket");
+    transfer_from_vault(program_id, vault_ata, user_ata, vault_state, token_mint, token_prog, amount)?;
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    vault_state_data.claimed_total = u64::from(vault_state_data.claimed_total)
+        .checked_add(amount)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    drop(vault_state_data);
+
+    // Close the ticket and return the rent to the user
+    close_account(ticket, payer)?;
+    msg!("Withdrawal ticket claimed");
+
+    Ok(())
+}
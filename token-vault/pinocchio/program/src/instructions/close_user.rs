@@ -0,0 +1,39 @@
+use super::shared::{close_account, load_user_state};
+use crate::error::VaultError;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Close an empty user state and return its rent to the user
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable user_state]
+/// [readonly token_mint]
+pub fn close_user(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [payer, user_state, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable() || !user_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // Verify the user's state PDA, open escrow offers still need it
+    let user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint.key())?;
+    if u64::from(user_state_data.amount) != 0 || u64::from(user_state_data.earmarked) != 0 {
+        return Err(VaultError::UserStateNotEmpty.into());
+    }
+    drop(user_state_data);
+
+    msg!("Closing user state");
+    close_account(user_state, payer)
+}
@@ -0,0 +1,158 @@
+use super::shared::{
+    check_vault_ata, load_registry, load_user_state, load_vault_config, load_vault_state_mut,
+    mint_decimals, token_account_amount, transfer_checked,
+};
+use crate::error::VaultError;
+use crate::state::LockupTier;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{Sysvar, clock::Clock},
+    ProgramResult,
+};
+
+/// Deposit into the payer's own position and lock it for the chosen tier.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_ata]
+/// [writable vault_ata]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly registry]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn deposit_tokens(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    deposit_amount: u64,
+    lockup: LockupTier,
+) -> ProgramResult {
+    deposit(program_id, accounts, None, deposit_amount, Some(lockup))
+}
+
+/// Deposit from the payer's tokens into the position of `owner`, e.g. from a
+/// hot key into the position of a cold key. The lock of the position is left
+/// as it is. Same accounts as `deposit_tokens`, with the user state of `owner`.
+pub fn deposit_for(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    owner: Pubkey,
+    deposit_amount: u64,
+) -> ProgramResult {
+    deposit(program_id, accounts, Some(&owner), deposit_amount, None)
+}
+
+// Tokens come from the payer's ATA and are credited to the position of `owner`,
+// the payer's own position when not set
+fn deposit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    owner: Option<&Pubkey>,
+    deposit_amount: u64,
+    lockup: Option<LockupTier>,
+) -> ProgramResult {
+    let [payer, user_ata, vault_ata, user_state, vault_state, vault_config, registry, token_mint, token_prog, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable()
+        || !user_ata.is_writable()
+        || !vault_ata.is_writable()
+        || !vault_state.is_writable()
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+    if deposit_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // Verify and borrow the user's state PDA
+    let owner = owner.unwrap_or(payer.key());
+    let mut user_state_data = load_user_state(program_id, user_state, owner, token_mint.key())?;
+
+    // Tokens must land in the vault ATA, otherwise the user would be credited for nothing
+    let decimals = mint_decimals(token_mint, token_prog)?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+
+    // Only mints the registry admin listed can be deposited
+    {
+        let registry_data = load_registry(program_id, registry)?;
+        let entry = registry_data
+            .find(token_mint.key())
+            .ok_or(VaultError::MintNotRegistered)?;
+        if entry.vault_state != *vault_state.key() || entry.vault_ata != *vault_ata.key() {
+            return Err(VaultError::InvalidRegistry.into());
+        }
+    }
+    let vault_balance = token_account_amount(vault_ata)?;
+
+    // Fees earned so far count towards the user's cap
+    vault_state_data.settle_fees(&mut user_state_data)?;
+
+    // Enforce the limits set by the vault admin
+    {
+        let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key())?;
+        if bool::from(vault_config_data.deposit_paused) {
+            return Err(VaultError::DepositPaused.into());
+        }
+        if deposit_amount < u64::from(vault_config_data.min_deposit) {
+            return Err(VaultError::BelowMinimum.into());
+        }
+        let user_total = u64::from(user_state_data.amount)
+            .checked_add(deposit_amount)
+            .ok_or(VaultError::Overflow)?;
+        if user_total > u64::from(vault_config_data.user_deposit_cap) {
+            return Err(VaultError::UserCapExceeded.into());
+        }
+        let vault_total = vault_balance
+            .checked_add(deposit_amount)
+            .ok_or(VaultError::Overflow)?;
+        if vault_total > u64::from(vault_config_data.tvl_cap) {
+            return Err(VaultError::TvlCapExceeded.into());
+        }
+    }
+
+    // Transfer tokens from user ATA to vault ATA, the state views aren't passed to the CPI
+    msg!("Transferring tokens from user ATA to vault ATA");
+    transfer_checked(user_ata, token_mint, vault_ata, payer, token_prog, deposit_amount, decimals, &[])?;
+
+    // Mints with a transfer fee deliver less than what was sent, only credit what the vault received
+    let received = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+
+    msg!("Updating user state");
+    user_state_data.amount = u64::from(user_state_data.amount)
+        .checked_add(received)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    vault_state_data.add_deposits(received)?;
+
+    // A deposit can only extend the lock of the position, never shorten it
+    if let Some(lockup) = lockup {
+        let now = Clock::get()?.unix_timestamp;
+        let was_locked = user_state_data.is_locked(now);
+        let unlock_ts = now.checked_add(lockup.duration()).ok_or(VaultError::Overflow)?;
+        if unlock_ts > i64::from(user_state_data.unlock_ts) {
+            user_state_data.unlock_ts = unlock_ts.into();
+        }
+        if !was_locked || lockup.boost_bps() > u16::from(user_state_data.boost_bps) {
+            user_state_data.boost_bps = lockup.boost_bps().into();
+        }
+    }
+
+    // The user state is updated in place, nothing to serialize
+    msg!("Deposit successful!");
+    Ok(())
+}
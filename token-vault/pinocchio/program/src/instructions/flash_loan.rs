@@ -0,0 +1,167 @@
+use super::shared::{
+    INSTRUCTIONS_SYSVAR_ID, check_vault_ata, load_vault_config, load_vault_state_mut, stack_height,
+    transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::processor::VaultInstruction;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Stack height of an instruction called by the transaction itself
+const TRANSACTION_LEVEL_STACK_HEIGHT: u64 = 1;
+
+/// Lend `amount` from the vault ATA for the rest of the transaction. A
+/// `FlashRepay` of the same amount for this vault must follow later in the
+/// transaction, it pays back the principal and the fee, which goes to the
+/// depositors.
+///
+/// Accounts:
+/// [signer borrower]
+/// [writable vault_ata]
+/// [writable borrower_ata]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly token_mint]
+/// [readonly token program]
+/// [readonly instructions sysvar]
+pub fn flash_loan(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let [borrower, vault_ata, borrower_ata, vault_state, vault_config, token_mint, token_prog, instructions_sysvar, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !borrower.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_ata.is_writable() || !borrower_ata.is_writable() || !vault_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+    if amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // The repayment is looked up among the instructions of the transaction, a
+    // program calling in through a CPI could skip it once it has the tokens
+    if stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Err(VaultError::FlashLoanViaCpi.into());
+    }
+
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+    if bool::from(load_vault_config(program_id, vault_config, vault_state.key())?.withdraw_paused) {
+        return Err(VaultError::WithdrawPaused.into());
+    }
+
+    // The vault state signs the transfer, so its view is dropped before it
+    {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+        if u16::from(vault_state_data.flash_fee_bps) == 0 {
+            return Err(VaultError::FlashLoansDisabled.into());
+        }
+        if u64::from(vault_state_data.flash_loan_amount) != 0 {
+            return Err(VaultError::FlashLoanInProgress.into());
+        }
+        if u64::from(vault_state_data.total_deposits) == 0 {
+            return Err(VaultError::NoDepositors.into());
+        }
+        find_flash_repay(program_id, instructions_sysvar, vault_ata.key(), vault_state.key(), amount)?;
+        vault_state_data.flash_loan_amount = amount.into();
+    }
+
+    msg!("Lending tokens");
+    transfer_from_vault(
+        program_id,
+        vault_ata,
+        borrower_ata,
+        vault_state,
+        token_mint,
+        token_prog,
+        amount,
+    )
+}
+
+// Find the first FlashRepay for this vault after the current instruction, it
+// must repay exactly the amount lent
+fn find_flash_repay(
+    program_id: &Pubkey,
+    instructions_sysvar: &AccountInfo,
+    vault_ata: &Pubkey,
+    vault_state: &Pubkey,
+    amount: u64,
+) -> ProgramResult {
+    if *instructions_sysvar.key() != INSTRUCTIONS_SYSVAR_ID {
+        return Err(ProgramError::UnsupportedSysvar);
+    }
+    let data = instructions_sysvar.try_borrow_data()?;
+    // The index of the current instruction is stored in the last two bytes
+    let current_index = read_u16(&data, data.len().saturating_sub(2))? as usize;
+    let count = read_u16(&data, 0)? as usize;
+
+    for index in current_index + 1..count {
+        let instruction = SysvarInstruction::parse(&data, read_u16(&data, 2 + index * 2)? as usize)?;
+        if instruction.program_id != program_id {
+            continue;
+        }
+        let Ok(VaultInstruction::FlashRepay { amount: repaid }) = VaultInstruction::unpack(instruction.data)
+        else {
+            continue;
+        };
+        // FlashRepay accounts: borrower, borrower_ata, vault_ata, vault_state, ...
+        let for_this_vault = instruction.account(2) == Some(&vault_ata[..])
+            && instruction.account(3) == Some(&vault_state[..]);
+        if !for_this_vault {
+            continue;
+        }
+        if repaid != amount {
+            return Err(VaultError::InvalidFlashRepay.into());
+        }
+        return Ok(());
+    }
+    Err(VaultError::MissingFlashRepay.into())
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, ProgramError> {
+    let bytes = data.get(at..at + 2).ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+// Instruction serialized in the instructions sysvar:
+// `[num_accounts: u16][(flags: u8, key: 32) * num_accounts][program_id: 32][data_len: u16][data]`
+struct SysvarInstruction<'a> {
+    accounts: &'a [u8],
+    program_id: &'a [u8],
+    data: &'a [u8],
+}
+
+impl<'a> SysvarInstruction<'a> {
+    const ACCOUNT_LEN: usize = 1 + 32;
+
+    fn parse(data: &'a [u8], offset: usize) -> Result<Self, ProgramError> {
+        let num_accounts = read_u16(data, offset)? as usize;
+        let accounts_start = offset + 2;
+        let program_id_start = accounts_start + num_accounts * Self::ACCOUNT_LEN;
+        let data_len = read_u16(data, program_id_start + 32)? as usize;
+        let data_start = program_id_start + 32 + 2;
+        Ok(Self {
+            accounts: data
+                .get(accounts_start..program_id_start)
+                .ok_or(ProgramError::InvalidAccountData)?,
+            program_id: &data[program_id_start..program_id_start + 32],
+            data: data
+                .get(data_start..data_start + data_len)
+                .ok_or(ProgramError::InvalidAccountData)?,
+        })
+    }
+
+    // Key of the account at `index`, skipping its flags byte
+    fn account(&self, index: usize) -> Option<&'a [u8]> {
+        let start = index * Self::ACCOUNT_LEN + 1;
+        self.accounts.get(start..start + 32)
+    }
+}
@@ -0,0 +1,68 @@
+use super::shared::{
+    check_vault_ata, load_vault_state, load_vault_state_mut, mint_decimals, token_account_amount,
+    transfer_checked,
+};
+use crate::error::VaultError;
+use crate::pod::PodU64;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Pay back the open flash loan of the vault plus its fee. The fee is shared
+/// between the depositors in proportion to their positions.
+///
+/// Accounts:
+/// [signer borrower]
+/// [writable borrower_ata]
+/// [writable vault_ata]
+/// [writable vault_state]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn flash_repay(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let [borrower, borrower_ata, vault_ata, vault_state, token_mint, token_prog, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !borrower.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !borrower_ata.is_writable() || !vault_ata.is_writable() || !vault_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let decimals = mint_decimals(token_mint, token_prog)?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+    let fee = {
+        let vault_state_data = load_vault_state(program_id, vault_state, token_mint.key())?;
+        if amount == 0 || u64::from(vault_state_data.flash_loan_amount) != amount {
+            return Err(VaultError::InvalidFlashRepay.into());
+        }
+        vault_state_data.flash_fee(amount)?
+    };
+    let repayment = amount.checked_add(fee).ok_or(VaultError::Overflow)?;
+
+    // Transfer the principal and the fee from the borrower ATA to the vault ATA
+    msg!("Repaying flash loan");
+    let vault_balance = token_account_amount(vault_ata)?;
+    transfer_checked(borrower_ata, token_mint, vault_ata, borrower, token_prog, repayment, decimals, &[])?;
+
+    // Mints with a transfer fee deliver less than what was sent
+    let received = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+    if received < repayment {
+        return Err(VaultError::InvalidFlashRepay.into());
+    }
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    vault_state_data.accrue_fee(fee)?;
+    vault_state_data.flash_loan_amount = PodU64::from(0);
+    msg!("Flash loan repaid");
+
+    Ok(())
+}
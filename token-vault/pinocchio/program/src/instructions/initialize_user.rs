@@ -0,0 +1,84 @@
+use super::shared::create_pda_account;
+use crate::error::VaultError;
+use crate::escrow::escrow_maker_address;
+use crate::header::{VaultAccount, init};
+use crate::pod::{PodI64, PodU64};
+use crate::state::{DEFAULT_PUBKEY, LockupTier, RateWindow, UNSYNCED_FEE_INDEX, UserState};
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, find_program_address},
+    sysvars::rent::Rent,
+    ProgramResult,
+};
+
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [readonly token_mint]
+/// [readonly token program]
+/// [readonly system program]
+/// [readonly rent sysvar]
+pub fn initialize_user(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [payer, user_state, token_mint, _token_prog, _system_prog, rent_sysvar, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // Derive a PDA for user's state
+    let (state_pda, state_bump) =
+        find_program_address(&[b"user", payer.key(), token_mint.key()], program_id);
+    if state_pda != *user_state.key() {
+        return Err(VaultError::InvalidUserAccount.into());
+    }
+
+    if !user_state.data_is_empty() {
+        return Err(VaultError::AlreadyInitialized.into());
+    }
+
+    // Create user state
+    msg!("Creating state account for the user and mint");
+    let lamports = Rent::from_account_info(rent_sysvar)?.minimum_balance(UserState::SPACE);
+    let bump = [state_bump];
+    let state_seeds = [
+        Seed::from(b"user"),
+        Seed::from(payer.key()),
+        Seed::from(token_mint.key()),
+        Seed::from(&bump),
+    ];
+    create_pda_account(
+        program_id,
+        payer,
+        user_state,
+        UserState::SPACE,
+        lamports,
+        Signer::from(&state_seeds),
+    )?;
+
+    // Initialize default state for this user
+    let initial_state = UserState {
+        user: *payer.key(),
+        mint: *token_mint.key(),
+        amount: PodU64::from(0),
+        unlock_ts: PodI64::from(0),
+        boost_bps: LockupTier::None.boost_bps().into(),
+        fee_index: UNSYNCED_FEE_INDEX.into(),
+        delegate: DEFAULT_PUBKEY,
+        delegate_allowance: PodU64::from(0),
+        outflow: RateWindow::default(),
+        earmarked: PodU64::from(0),
+        escrow_maker_bump: escrow_maker_address(program_id, user_state.key()).1,
+    };
+    init(user_state, state_bump, &initial_state)?;
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use super::shared::{
+    ata_address, check_mint_extensions, check_vault_creator, create_ata, create_pda_account, mint_decimals,
+};
+use crate::error::VaultError;
+use crate::header::{VaultAccount, init};
+use crate::pod::{PodBool, PodI64, PodU16, PodU64, PodU128};
+use crate::state::{BPS_DENOMINATOR, DEFAULT_PUBKEY, RateWindow, VaultConfig, VaultState};
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, find_program_address},
+    sysvars::{Sysvar, rent::Rent},
+    ProgramResult,
+};
+
+/// Create the vault of a mint, the payer becomes its admin. The payer must be
+/// the registry admin, or the upgrade authority of the program.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable vault_state]
+/// [writable vault_config]
+/// [writable vault_ATA]
+/// [readonly token_mint]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly registry]
+/// [readonly program_data]
+pub fn initialize_vault(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    early_exit_penalty_bps: u16,
+    withdraw_cooldown: i64,
+) -> ProgramResult {
+    let [payer, vault_state, vault_config, vault_ata, token_mint, token_prog, _ata_prog, system_prog, registry, program_data, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_state.is_writable() || !vault_config.is_writable() || !vault_ata.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+    check_vault_creator(program_id, registry, program_data, payer)?;
+    if u64::from(early_exit_penalty_bps) > BPS_DENOMINATOR {
+        return Err(VaultError::InvalidPenalty.into());
+    }
+    if withdraw_cooldown < 0 {
+        return Err(VaultError::InvalidCooldown.into());
+    }
+    mint_decimals(token_mint, token_prog)?;
+    check_mint_extensions(token_mint)?;
+
+    // The vault state PDA holds the vault state and owns the vault ATA
+    let (vault_pda, vault_bump) = find_program_address(&[b"vault", token_mint.key()], program_id);
+    if vault_pda != *vault_state.key() {
+        return Err(VaultError::InvalidVaultAuthority.into());
+    }
+    if *vault_ata.key() != ata_address(&vault_pda, token_mint.key(), token_prog.key()) {
+        return Err(VaultError::InvalidVaultTokenAccount.into());
+    }
+    let (config_pda, config_bump) = find_program_address(&[b"config", &vault_pda], program_id);
+    if config_pda != *vault_config.key() {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
+
+    if !vault_state.data_is_empty() || !vault_config.data_is_empty() || !vault_ata.data_is_empty() {
+        return Err(VaultError::AlreadyInitialized.into());
+    }
+
+    // Create the vault state
+    msg!("Creating vault state account");
+    let rent = Rent::get()?;
+    let bump = [vault_bump];
+    let vault_seeds = [Seed::from(b"vault"), Seed::from(token_mint.key()), Seed::from(&bump)];
+    create_pda_account(
+        program_id,
+        payer,
+        vault_state,
+        VaultState::SPACE,
+        rent.minimum_balance(VaultState::SPACE),
+        Signer::from(&vault_seeds),
+    )?;
+
+    let initial_state = VaultState {
+        mint: *token_mint.key(),
+        early_exit_penalty_bps: early_exit_penalty_bps.into(),
+        treasury: PodU64::from(0),
+        withdraw_cooldown: withdraw_cooldown.into(),
+        next_ticket_id: PodU64::from(0),
+        queued_total: PodU64::from(0),
+        claimed_total: PodU64::from(0),
+        funded_total: PodU64::from(0),
+        flash_fee_bps: PodU16::from(0),
+        flash_loan_amount: PodU64::from(0),
+        total_deposits: PodU64::from(0),
+        fee_index: PodU128::from(0),
+        outflow: RateWindow::default(),
+    };
+    init(vault_state, vault_bump, &initial_state)?;
+
+    // Create the vault config, the payer becomes the admin of the vault
+    msg!("Creating vault config account");
+    let bump = [config_bump];
+    let config_seeds = [Seed::from(b"config"), Seed::from(&vault_pda), Seed::from(&bump)];
+    create_pda_account(
+        program_id,
+        payer,
+        vault_config,
+        VaultConfig::SPACE,
+        rent.minimum_balance(VaultConfig::SPACE),
+        Signer::from(&config_seeds),
+    )?;
+
+    let initial_config = VaultConfig {
+        vault: vault_pda,
+        admin: *payer.key(),
+        pending_admin: DEFAULT_PUBKEY,
+        user_deposit_cap: u64::MAX.into(),
+        tvl_cap: u64::MAX.into(),
+        min_deposit: PodU64::from(0),
+        min_withdraw: PodU64::from(0),
+        deposit_paused: PodBool::from(false),
+        withdraw_paused: PodBool::from(false),
+        rate_limit_window: PodI64::from(0),
+        user_window_limit: u64::MAX.into(),
+        vault_window_limit: u64::MAX.into(),
+    };
+    init(vault_config, config_bump, &initial_config)?;
+
+    // The vault ATA is owned by the vault state PDA
+    msg!("Creating program token vault account");
+    create_ata(payer, vault_ata, vault_state, token_mint, system_prog, token_prog, false)?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+pub mod initialize_vault;
+pub mod initialize_user;
+pub mod deposit_tokens;
+pub mod withdraw_tokens;
+pub mod withdraw_early;
+pub mod request_withdraw;
+pub mod claim_withdraw;
+pub mod update_config;
+pub mod transfer_admin;
+pub mod accept_admin;
+pub mod close_user;
+pub mod migrate_user_state;
+pub mod upgrade_account;
+pub mod initialize_registry;
+pub mod register_vault;
+pub mod unregister_vault;
+pub mod flash_loan;
+pub mod flash_repay;
+pub mod set_flash_loan_fee;
+pub mod set_delegate;
+pub mod revoke_delegate;
+pub mod reconcile;
+pub mod make_escrow_offer;
+pub mod take_escrow_offer;
+pub mod refund_escrow_offer;
+pub mod withdraw_treasury;
+pub mod shared;
+
+pub use initialize_vault::initialize_vault;
+pub use initialize_user::initialize_user;
+pub use deposit_tokens::{deposit_for, deposit_tokens};
+pub use withdraw_tokens::withdraw_tokens;
+pub use withdraw_early::withdraw_early;
+pub use request_withdraw::request_withdraw;
+pub use claim_withdraw::claim_withdraw;
+pub use update_config::update_config;
+pub use transfer_admin::transfer_admin;
+pub use accept_admin::accept_admin;
+pub use close_user::close_user;
+pub use migrate_user_state::migrate_user_state;
+pub use upgrade_account::upgrade_account;
+pub use initialize_registry::initialize_registry;
+pub use register_vault::register_vault;
+pub use unregister_vault::unregister_vault;
+pub use flash_loan::flash_loan;
+pub use flash_repay::flash_repay;
+pub use set_flash_loan_fee::set_flash_loan_fee;
+pub use set_delegate::set_delegate;
+pub use revoke_delegate::revoke_delegate;
+pub use reconcile::{ReconcileReport, reconcile};
+pub use make_escrow_offer::make_escrow_offer;
+pub use take_escrow_offer::take_escrow_offer;
+pub use refund_escrow_offer::refund_escrow_offer;
+pub use withdraw_treasury::withdraw_treasury;
@@ -0,0 +1,126 @@
+use super::shared::{
+    check_vault_ata, load_vault_config_mut, load_vault_state, token_account_amount,
+};
+use crate::error::VaultError;
+use crate::pod::PodBool;
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::set_return_data,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Outcome of `Reconcile`, set as the return data of the instruction in the
+/// borsh encoding of the native `ReconcileReport`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileReport {
+    // Tokens held by the vault ATA
+    pub vault_balance: u64,
+    // Sum of the synced user positions
+    pub total_deposits: u64,
+    // Early exit penalties kept by the vault
+    pub treasury: u64,
+    // Requested withdrawals which have not been claimed yet
+    pub pending_tickets: u64,
+    // What the vault owes, the vault is solvent while its balance covers it
+    pub liabilities: u64,
+    pub solvent: bool,
+    // Whether this call paused the vault
+    pub paused: bool,
+}
+impl ReconcileReport {
+    // Size of the borsh encoding
+    pub const LEN: usize = 5 * 8 + 2;
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        let amounts = [
+            self.vault_balance,
+            self.total_deposits,
+            self.treasury,
+            self.pending_tickets,
+            self.liabilities,
+        ];
+        for (chunk, amount) in bytes.chunks_exact_mut(8).zip(amounts) {
+            chunk.copy_from_slice(&amount.to_le_bytes());
+        }
+        bytes[40] = u8::from(self.solvent);
+        bytes[41] = u8::from(self.paused);
+        bytes
+    }
+}
+
+/// Compare what the vault owes with the balance of its ATA. Anyone can call
+/// it, the report is set as return data. With `pause_if_insolvent` an
+/// insolvent vault has its deposits and withdrawals paused until the admin
+/// resumes them.
+///
+/// Unsettled flash loan fees and positions not synced since an upgrade are
+/// not in `total_deposits`, they only ever show up as a surplus.
+///
+/// Accounts:
+/// [readonly vault_state]
+/// [readonly vault_ata]
+/// [readonly vault_config], writable with `pause_if_insolvent`
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn reconcile(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pause_if_insolvent: bool,
+) -> ProgramResult {
+    let [vault_state, vault_ata, vault_config, token_mint, token_prog, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if pause_if_insolvent && !vault_config.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let vault_state_data = load_vault_state(program_id, vault_state, token_mint.key())?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+    if !vault_ata.is_owned_by(token_prog.key()) {
+        return Err(VaultError::InvalidVaultTokenAccount.into());
+    }
+    // Lent out tokens are not in the vault ATA until the loan is repaid
+    if u64::from(vault_state_data.flash_loan_amount) != 0 {
+        return Err(VaultError::FlashLoanInProgress.into());
+    }
+
+    let vault_balance = token_account_amount(vault_ata)?;
+    let total_deposits = u64::from(vault_state_data.total_deposits);
+    let treasury = u64::from(vault_state_data.treasury);
+    let pending_tickets = vault_state_data.pending_tickets()?;
+    let liabilities = vault_state_data.liabilities()?;
+    let solvent = vault_balance >= liabilities;
+
+    let mut paused = false;
+    if !solvent && pause_if_insolvent {
+        let mut vault_config_data = load_vault_config_mut(program_id, vault_config, vault_state.key())?;
+        if !bool::from(vault_config_data.deposit_paused) || !bool::from(vault_config_data.withdraw_paused) {
+            msg!("Vault is insolvent, pausing deposits and withdrawals");
+            vault_config_data.deposit_paused = PodBool::from(true);
+            vault_config_data.withdraw_paused = PodBool::from(true);
+            paused = true;
+        }
+    }
+
+    let report = ReconcileReport {
+        vault_balance,
+        total_deposits,
+        treasury,
+        pending_tickets,
+        liabilities,
+        solvent,
+        paused,
+    };
+    if !solvent {
+        msg!("Reconcile: vault is insolvent");
+    }
+    set_return_data(&report.to_bytes());
+
+    Ok(())
+}
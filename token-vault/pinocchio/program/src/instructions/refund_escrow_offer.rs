@@ -0,0 +1,124 @@
+use super::shared::{
+    ata_address, check_escrow_maker, check_vault_ata, create_ata, empty_maker_ata, load_user_state,
+    load_vault_state_mut, release_escrow_maker, token_account_amount,
+};
+use crate::error::VaultError;
+use crate::escrow::{self, load_offer};
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Cancel an escrow offer posted from the user's position. The offered tokens
+/// go back into the vault and are credited to the position again, the rent of
+/// the offer accounts is returned to the user.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable user_state]
+/// [writable vault_state]
+/// [writable vault_ata]
+/// [writable escrow_maker]
+/// [writable maker_ata_a]
+/// [writable offer]
+/// [writable offer_vault_ata]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly escrow program]
+pub fn refund_escrow_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let [payer, user_state, vault_state, vault_ata, escrow_maker, maker_ata_a, offer, offer_vault_ata, token_mint_a, token_mint_b, token_prog, ata_prog, system_prog, escrow_prog, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable()
+        || !user_state.is_writable()
+        || !vault_state.is_writable()
+        || !vault_ata.is_writable()
+        || !escrow_maker.is_writable()
+        || !maker_ata_a.is_writable()
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // Only the owner of the position cancels its offers
+    let maker_bump =
+        [load_user_state(program_id, user_state, payer.key(), token_mint_a.key())?.escrow_maker_bump];
+    check_escrow_maker(program_id, escrow_maker, user_state.key(), maker_bump[0], escrow_prog)?;
+    let maker_seeds = [Seed::from(b"escrow_maker"), Seed::from(user_state.key()), Seed::from(&maker_bump)];
+    if *maker_ata_a.key() != ata_address(escrow_maker.key(), token_mint_a.key(), token_prog.key()) {
+        return Err(VaultError::InvalidEscrowMaker.into());
+    }
+    load_vault_state_mut(program_id, vault_state, token_mint_a.key())?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint_a.key(), token_prog.key())?;
+
+    let offer_data = load_offer(offer)?;
+    if offer_data.maker != *escrow_maker.key()
+        || u64::from(offer_data.id) != id
+        || offer_data.token_mint_a != *token_mint_a.key()
+        || offer_data.token_mint_b != *token_mint_b.key()
+    {
+        return Err(VaultError::InvalidOffer.into());
+    }
+
+    // The escrow refunds into the escrow maker's ATA, which is emptied into the vault
+    msg!("Refunding escrow offer");
+    create_ata(payer, maker_ata_a, escrow_maker, token_mint_a, system_prog, token_prog, true)?;
+    escrow::refund_offer(
+        escrow_maker,
+        token_mint_a,
+        token_mint_b,
+        maker_ata_a,
+        offer,
+        offer_vault_ata,
+        token_prog,
+        ata_prog,
+        system_prog,
+        id,
+        Signer::from(&maker_seeds),
+    )?;
+
+    // Credit what actually reached the vault, transfer fees included
+    let vault_balance = token_account_amount(vault_ata)?;
+    empty_maker_ata(
+        maker_ata_a,
+        vault_ata,
+        payer,
+        escrow_maker,
+        token_mint_a,
+        token_prog,
+        &maker_seeds,
+    )?;
+    release_escrow_maker(escrow_maker, payer, &maker_seeds)?;
+    let credited = token_account_amount(vault_ata)?
+        .checked_sub(vault_balance)
+        .ok_or(VaultError::Overflow)?;
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint_a.key())?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint_a.key())?;
+    vault_state_data.settle_fees(&mut user_state_data)?;
+    user_state_data.amount = u64::from(user_state_data.amount)
+        .checked_add(credited)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    user_state_data.earmarked = u64::from(user_state_data.earmarked)
+        .checked_sub(offer_data.token_a_offered_amount.into())
+        .ok_or(VaultError::Overflow)?
+        .into();
+    vault_state_data.add_deposits(credited)?;
+    msg!("Escrow offer refunded");
+
+    Ok(())
+}
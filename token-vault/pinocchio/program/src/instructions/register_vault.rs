@@ -0,0 +1,59 @@
+use super::shared::{check_vault_ata, load_registry_mut, load_vault_state};
+use crate::error::VaultError;
+use crate::state::{MAX_VAULTS, RegistryEntry};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Add an initialized vault to the registry, deposits into it are accepted from then on
+///
+/// Accounts:
+/// [signer admin]
+/// [writable registry]
+/// [readonly vault_state]
+/// [readonly vault_ata]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn register_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [admin, registry, vault_state, vault_ata, token_mint, token_prog, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !admin.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !registry.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // Only initialized vaults can be registered
+    load_vault_state(program_id, vault_state, token_mint.key())?;
+    check_vault_ata(vault_ata, vault_state.key(), token_mint.key(), token_prog.key())?;
+
+    let mut registry_data = load_registry_mut(program_id, registry)?;
+    if registry_data.admin != *admin.key() {
+        return Err(VaultError::Unauthorized.into());
+    }
+    if registry_data.find(token_mint.key()).is_some() {
+        return Err(VaultError::MintAlreadyRegistered.into());
+    }
+    let count = u32::from(registry_data.count) as usize;
+    if count >= MAX_VAULTS {
+        return Err(VaultError::RegistryFull.into());
+    }
+
+    msg!("Registering vault");
+    registry_data.entries[count] = RegistryEntry {
+        mint: *token_mint.key(),
+        vault_state: *vault_state.key(),
+        vault_ata: *vault_ata.key(),
+    };
+    registry_data.count = (count as u32 + 1).into();
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use super::shared::{
+    check_withdraw_allowed, create_pda_account, load_user_state, load_vault_config, load_vault_state_mut,
+    record_outflow,
+};
+use crate::error::VaultError;
+use crate::header::{VaultAccount, init};
+use crate::state::WithdrawTicket;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, find_program_address},
+    sysvars::{Sysvar, clock::Clock, rent::Rent},
+    ProgramResult,
+};
+
+/// First step of a withdrawal, the amount is taken out of the user's position
+/// and moved into a withdrawal ticket which can be claimed after the vault's cooldown.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [writable ticket]
+/// [readonly token_mint]
+/// [readonly system program]
+pub fn request_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    withdraw_amount: u64,
+) -> ProgramResult {
+    let [payer, user_state, vault_state, vault_config, ticket, token_mint, _system_prog, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable() || !vault_state.is_writable() || !ticket.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+    if withdraw_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // Verify and borrow the user and vault state PDAs
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint.key())?;
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    vault_state_data.settle_fees(&mut user_state_data)?;
+
+    // Enforce the limits set by the vault admin
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key())?;
+    check_withdraw_allowed(&vault_config_data, withdraw_amount)?;
+
+    let amount = u64::from(user_state_data.amount);
+    if amount < withdraw_amount {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if user_state_data.is_locked(now) {
+        msg!("Position is still locked");
+        return Err(VaultError::PositionLocked.into());
+    }
+    // The ticket is the only way out of a vault with a cooldown, and a zero
+    // cooldown ticket can be claimed right away, so the amount counts now
+    record_outflow(&vault_config_data, &mut vault_state_data, &mut user_state_data, withdraw_amount, now)?;
+    drop(vault_config_data);
+
+    // Derive the PDA of the next ticket
+    let ticket_id = u64::from(vault_state_data.next_ticket_id);
+    let id_bytes = ticket_id.to_le_bytes();
+    let (ticket_pda, ticket_bump) =
+        find_program_address(&[b"ticket", vault_state.key(), &id_bytes], program_id);
+    if ticket_pda != *ticket.key() {
+        return Err(VaultError::InvalidTicket.into());
+    }
+
+    // Create the ticket, only the payer and the ticket are passed to the CPI
+    msg!("Creating withdrawal ticket");
+    let bump = [ticket_bump];
+    let ticket_seeds = [
+        Seed::from(b"ticket"),
+        Seed::from(vault_state.key()),
+        Seed::from(&id_bytes),
+        Seed::from(&bump),
+    ];
+    create_pda_account(
+        program_id,
+        payer,
+        ticket,
+        WithdrawTicket::SPACE,
+        Rent::get()?.minimum_balance(WithdrawTicket::SPACE),
+        Signer::from(&ticket_seeds),
+    )?;
+
+    // Queue the amount behind every earlier ticket
+    let queued_total = u64::from(vault_state_data.queued_total)
+        .checked_add(withdraw_amount)
+        .ok_or(VaultError::Overflow)?;
+    vault_state_data.next_ticket_id = ticket_id.checked_add(1).ok_or(VaultError::Overflow)?.into();
+    vault_state_data.queued_total = queued_total.into();
+    vault_state_data.sub_deposits(withdraw_amount)?;
+
+    let ticket_data = WithdrawTicket {
+        owner: *payer.key(),
+        mint: *token_mint.key(),
+        id: ticket_id.into(),
+        amount: withdraw_amount.into(),
+        queue_end: queued_total.into(),
+        claimable_after: now
+            .checked_add(vault_state_data.withdraw_cooldown.into())
+            .ok_or(VaultError::Overflow)?
+            .into(),
+    };
+    init(ticket, ticket_bump, &ticket_data)?;
+
+    // The requested amount leaves the user's position
+    user_state_data.amount = (amount - withdraw_amount).into();
+    msg!("Withdrawal requested");
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use super::shared::load_user_state;
+use crate::error::VaultError;
+use crate::pod::PodU64;
+use crate::state::DEFAULT_PUBKEY;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Remove the withdraw delegate of the position
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [readonly token_mint]
+pub fn revoke_delegate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [payer, user_state, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint.key())?;
+    msg!("Revoking withdraw delegate");
+    user_state_data.delegate = DEFAULT_PUBKEY;
+    user_state_data.delegate_allowance = PodU64::from(0);
+
+    Ok(())
+}
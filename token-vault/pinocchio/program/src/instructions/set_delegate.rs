@@ -0,0 +1,47 @@
+use super::shared::load_user_state;
+use crate::error::VaultError;
+use crate::state::{DEFAULT_PUBKEY, UNLIMITED_ALLOWANCE};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Let another key withdraw from the position, up to `allowance` when set.
+/// Replaces any earlier delegate and its remaining allowance.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_state]
+/// [readonly token_mint]
+pub fn set_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    allowance: Option<u64>,
+) -> ProgramResult {
+    let [payer, user_state, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+    // The default key marks a position without delegate
+    if delegate == DEFAULT_PUBKEY || delegate == *payer.key() {
+        return Err(VaultError::InvalidDelegate.into());
+    }
+
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint.key())?;
+    msg!("Setting withdraw delegate");
+    user_state_data.delegate = delegate;
+    user_state_data.delegate_allowance = allowance.unwrap_or(UNLIMITED_ALLOWANCE).into();
+
+    Ok(())
+}
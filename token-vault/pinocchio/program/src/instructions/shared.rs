@@ -0,0 +1,635 @@
+//! Account checks and CPIs shared by the instructions. The token, associated
+//! token and system instructions are built here, `pinocchio-token` only
+//! targets the SPL Token program and the vault works with Token-2022 as well.
+
+use crate::error::VaultError;
+use crate::escrow::ESCROW_PROGRAM_ID;
+use crate::header::{load_mut, load_ref, read_header};
+use crate::state::{UserState, VaultConfig, VaultRegistry, VaultState};
+use pinocchio::{
+    account_info::{AccountInfo, Ref, RefMut},
+    cpi::{invoke, invoke_signed},
+    instruction::{AccountMeta, Instruction, Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, create_program_address, find_program_address},
+    ProgramResult,
+};
+use pinocchio_pubkey::pubkey;
+
+pub const SYSTEM_PROGRAM_ID: Pubkey = [0; 32];
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ATA_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+pub const INSTRUCTIONS_SYSVAR_ID: Pubkey = pubkey!("Sysvar1nstructions1111111111111111111111111");
+pub const BPF_LOADER_UPGRADEABLE_ID: Pubkey = pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+// Tag, slot and upgrade authority in front of the program in a ProgramData account
+const PROGRAM_DATA_METADATA_LEN: usize = 45;
+
+// Base layouts shared by both token programs, Token-2022 extensions follow
+// the account type byte behind the size of a token account
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const MINT_INITIALIZED_OFFSET: usize = 45;
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+const TLV_START: usize = TOKEN_ACCOUNT_LEN + 1;
+
+// Token-2022 extension types, the numbers of its `ExtensionType`
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const TRANSFER_FEE_AMOUNT: u16 = 2;
+const IMMUTABLE_OWNER: u16 = 7;
+const NON_TRANSFERABLE: u16 = 9;
+const PERMANENT_DELEGATE: u16 = 12;
+const NON_TRANSFERABLE_ACCOUNT: u16 = 13;
+const TRANSFER_HOOK: u16 = 14;
+const TRANSFER_HOOK_ACCOUNT: u16 = 15;
+const PAUSABLE: u16 = 26;
+const PAUSABLE_ACCOUNT: u16 = 27;
+
+// Mint extensions which make a mint unusable for the vault:
+// - NonTransferable tokens can't be moved into or out of the vault
+// - TransferHook needs extra accounts the vault doesn't pass along
+// - PermanentDelegate lets a third party move tokens out of the vault ATA
+const UNSUPPORTED_MINT_EXTENSIONS: [u16; 3] = [NON_TRANSFERABLE, TRANSFER_HOOK, PERMANENT_DELEGATE];
+
+// Whether the key is the PDA of the seeds, the last one being the bump stored at init time.
+// A single create_program_address is much cheaper than find_program_address searching for the bump.
+pub fn is_pda(program_id: &Pubkey, key: &Pubkey, seeds: &[&[u8]]) -> bool {
+    create_program_address(seeds, program_id).is_ok_and(|pda| pda == *key)
+}
+
+// Check the user state PDA of the signer for the given mint and borrow it in place
+pub fn load_user_state<'a>(
+    program_id: &Pubkey,
+    user_state: &'a AccountInfo,
+    user: &Pubkey,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, UserState>, ProgramError> {
+    let state_bump = read_header(program_id, user_state)?.bump;
+    if !is_pda(program_id, user_state.key(), &[b"user", user, mint, &[state_bump]]) {
+        return Err(VaultError::InvalidUserAccount.into());
+    }
+
+    let user_state_data = load_mut::<UserState>(program_id, user_state)?;
+    if user_state_data.user != *user {
+        return Err(VaultError::InvalidUserState.into());
+    }
+    Ok(user_state_data)
+}
+
+// Check a user state PDA against the user stored in it and borrow it in place,
+// for handlers the owner or its delegate can sign
+pub fn load_position<'a>(
+    program_id: &Pubkey,
+    user_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, UserState>, ProgramError> {
+    let state_bump = read_header(program_id, user_state)?.bump;
+    let user_state_data = load_mut::<UserState>(program_id, user_state)?;
+    if !is_pda(program_id, user_state.key(), &[b"user", &user_state_data.user, mint, &[state_bump]]) {
+        return Err(VaultError::InvalidUserAccount.into());
+    }
+    Ok(user_state_data)
+}
+
+// Check the vault state PDA for the given mint and borrow it read only
+pub fn load_vault_state<'a>(
+    program_id: &Pubkey,
+    vault_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<Ref<'a, VaultState>, ProgramError> {
+    vault_bump(program_id, vault_state, mint)?;
+    load_ref::<VaultState>(program_id, vault_state)
+}
+
+// Same as `load_vault_state` for handlers which update the vault state
+pub fn load_vault_state_mut<'a>(
+    program_id: &Pubkey,
+    vault_state: &'a AccountInfo,
+    mint: &Pubkey,
+) -> Result<RefMut<'a, VaultState>, ProgramError> {
+    vault_bump(program_id, vault_state, mint)?;
+    load_mut::<VaultState>(program_id, vault_state)
+}
+
+// Check the vault state PDA for the given mint with the bump stored in its header
+pub fn vault_bump(
+    program_id: &Pubkey,
+    vault_state: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<u8, ProgramError> {
+    if !vault_state.is_owned_by(program_id) {
+        return Err(VaultError::InvalidVaultState.into());
+    }
+    let vault_bump = read_header(program_id, vault_state)?.bump;
+    if !is_pda(program_id, vault_state.key(), &[b"vault", mint, &[vault_bump]]) {
+        return Err(VaultError::InvalidVaultAuthority.into());
+    }
+    Ok(vault_bump)
+}
+
+// Address of the associated token account of the owner for the mint
+pub fn ata_address(owner: &Pubkey, mint: &Pubkey, token_prog: &Pubkey) -> Pubkey {
+    find_program_address(&[owner, token_prog, mint], &ATA_PROGRAM_ID).0
+}
+
+// The vault ATA is the associated token account of the already checked vault PDA for the mint
+pub fn check_vault_ata(
+    vault_ata: &AccountInfo,
+    vault_state: &Pubkey,
+    mint: &Pubkey,
+    token_prog: &Pubkey,
+) -> Result<(), VaultError> {
+    if *vault_ata.key() != ata_address(vault_state, mint, token_prog) {
+        return Err(VaultError::InvalidVaultTokenAccount);
+    }
+    Ok(())
+}
+
+// The vault works with both the SPL Token and the Token-2022 program
+pub fn check_token_program(token_prog: &AccountInfo) -> Result<(), VaultError> {
+    if *token_prog.key() != TOKEN_PROGRAM_ID && *token_prog.key() != TOKEN_2022_PROGRAM_ID {
+        return Err(VaultError::InvalidTokenProgram);
+    }
+    Ok(())
+}
+
+// Check the mint belongs to the token program and return its decimals
+pub fn mint_decimals(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<u8, ProgramError> {
+    check_token_program(token_prog)?;
+    if !token_mint.is_owned_by(token_prog.key()) {
+        return Err(VaultError::InvalidMint.into());
+    }
+    let mint_data = token_mint.try_borrow_data()?;
+    if mint_data.len() < MINT_LEN || mint_data[MINT_INITIALIZED_OFFSET] != 1 {
+        return Err(VaultError::InvalidMint.into());
+    }
+    Ok(mint_data[MINT_DECIMALS_OFFSET])
+}
+
+// Types of the Token-2022 extensions of a mint, none for SPL Token mints
+fn mint_extension_types(mint_data: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    let mut offset = TLV_START;
+    core::iter::from_fn(move || {
+        let header = mint_data.get(offset..offset + 4)?;
+        let extension_type = u16::from_le_bytes([header[0], header[1]]);
+        // Uninitialized space is zeroed
+        if extension_type == 0 {
+            return None;
+        }
+        offset += 4 + u16::from_le_bytes([header[2], header[3]]) as usize;
+        Some(extension_type)
+    })
+}
+
+// Reject mints with extensions the vault can't custody
+pub fn check_mint_extensions(token_mint: &AccountInfo) -> ProgramResult {
+    let mint_data = token_mint.try_borrow_data()?;
+    if mint_extension_types(&mint_data).any(|extension| UNSUPPORTED_MINT_EXTENSIONS.contains(&extension)) {
+        return Err(VaultError::UnsupportedMint.into());
+    }
+    Ok(())
+}
+
+// Token balance of an initialized token account of either token program
+pub fn token_account_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let account_data = token_account.try_borrow_data()?;
+    if account_data.len() < TOKEN_ACCOUNT_LEN || account_data[TOKEN_ACCOUNT_STATE_OFFSET] == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let amount = &account_data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8];
+    Ok(u64::from_le_bytes(amount.try_into().unwrap()))
+}
+
+// Transfer tokens with TransferChecked, `signers` are needed when the authority is a PDA
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked(
+    from: &AccountInfo,
+    token_mint: &AccountInfo,
+    to: &AccountInfo,
+    authority: &AccountInfo,
+    token_prog: &AccountInfo,
+    amount: u64,
+    decimals: u8,
+    signers: &[Signer],
+) -> ProgramResult {
+    let mut data = [0; 10];
+    data[0] = 12;
+    data[1..9].copy_from_slice(&amount.to_le_bytes());
+    data[9] = decimals;
+    invoke_signed(
+        &Instruction {
+            program_id: token_prog.key(),
+            accounts: &[
+                AccountMeta::writable(from.key()),
+                AccountMeta::readonly(token_mint.key()),
+                AccountMeta::writable(to.key()),
+                AccountMeta::readonly_signer(authority.key()),
+            ],
+            data: &data,
+        },
+        &[from, token_mint, to, authority],
+        signers,
+    )
+}
+
+// Transfer tokens out of the vault ATA, the vault state PDA signs as the owner of the vault
+pub fn transfer_from_vault(
+    program_id: &Pubkey,
+    vault_ata: &AccountInfo,
+    destination: &AccountInfo,
+    vault_state: &AccountInfo,
+    token_mint: &AccountInfo,
+    token_prog: &AccountInfo,
+    amount: u64,
+) -> ProgramResult {
+    let vault_bump = [vault_bump(program_id, vault_state, token_mint.key())?];
+    let vault_seeds = [Seed::from(b"vault"), Seed::from(token_mint.key()), Seed::from(&vault_bump)];
+    transfer_checked(
+        vault_ata,
+        token_mint,
+        destination,
+        vault_state,
+        token_prog,
+        amount,
+        mint_decimals(token_mint, token_prog)?,
+        &[Signer::from(&vault_seeds)],
+    )
+}
+
+// Close a program owned account and send its rent to the destination
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    let mut destination_lamports = destination.try_borrow_mut_lamports()?;
+    *destination_lamports = destination_lamports
+        .checked_add(lamports)
+        .ok_or(VaultError::Overflow)?;
+    drop(destination_lamports);
+
+    account.try_borrow_mut_data()?.fill(0);
+    // Zeroes the lamports, data length and owner, the owner becomes the system program
+    account.close()
+}
+
+// Check the config PDA of the vault and borrow it read only
+pub fn load_vault_config<'a>(
+    program_id: &Pubkey,
+    vault_config: &'a AccountInfo,
+    vault_state: &Pubkey,
+) -> Result<Ref<'a, VaultConfig>, ProgramError> {
+    let config_bump = read_header(program_id, vault_config)?.bump;
+    if !is_pda(program_id, vault_config.key(), &[b"config", vault_state, &[config_bump]]) {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
+
+    let vault_config_data = load_ref::<VaultConfig>(program_id, vault_config)?;
+    if vault_config_data.vault != *vault_state {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
+    Ok(vault_config_data)
+}
+
+// Same as `load_vault_config` for the admin instructions which change it
+pub fn load_vault_config_mut<'a>(
+    program_id: &Pubkey,
+    vault_config: &'a AccountInfo,
+    vault_state: &Pubkey,
+) -> Result<RefMut<'a, VaultConfig>, ProgramError> {
+    let config_bump = read_header(program_id, vault_config)?.bump;
+    if !is_pda(program_id, vault_config.key(), &[b"config", vault_state, &[config_bump]]) {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
+
+    let vault_config_data = load_mut::<VaultConfig>(program_id, vault_config)?;
+    if vault_config_data.vault != *vault_state {
+        return Err(VaultError::InvalidVaultConfig.into());
+    }
+    Ok(vault_config_data)
+}
+
+// Check the registry PDA and borrow it read only
+pub fn load_registry<'a>(
+    program_id: &Pubkey,
+    registry: &'a AccountInfo,
+) -> Result<Ref<'a, VaultRegistry>, ProgramError> {
+    let registry_bump = read_header(program_id, registry)?.bump;
+    if !is_pda(program_id, registry.key(), &[b"registry", &[registry_bump]]) {
+        return Err(VaultError::InvalidRegistry.into());
+    }
+    load_ref::<VaultRegistry>(program_id, registry)
+}
+
+// Same as `load_registry` for the admin instructions which change it
+pub fn load_registry_mut<'a>(
+    program_id: &Pubkey,
+    registry: &'a AccountInfo,
+) -> Result<RefMut<'a, VaultRegistry>, ProgramError> {
+    let registry_bump = read_header(program_id, registry)?.bump;
+    if !is_pda(program_id, registry.key(), &[b"registry", &[registry_bump]]) {
+        return Err(VaultError::InvalidRegistry.into());
+    }
+    load_mut::<VaultRegistry>(program_id, registry)
+}
+
+// Check the signer is the upgrade authority of this program. The upgradeable
+// loader keeps it in the ProgramData account at the PDA of the program id,
+// behind the `ProgramData` tag (3u32) and the deployment slot, as an Option.
+pub fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+    authority: &AccountInfo,
+) -> ProgramResult {
+    let (program_data_pda, _) = find_program_address(&[program_id], &BPF_LOADER_UPGRADEABLE_ID);
+    if *program_data.key() != program_data_pda || !program_data.is_owned_by(&BPF_LOADER_UPGRADEABLE_ID) {
+        return Err(VaultError::InvalidProgramData.into());
+    }
+    let data = program_data.try_borrow_data()?;
+    if data.len() < PROGRAM_DATA_METADATA_LEN || data[..4] != 3u32.to_le_bytes() {
+        return Err(VaultError::InvalidProgramData.into());
+    }
+    // An immutable program has no authority, nobody can sign for it
+    if data[12] != 1 || data[13..PROGRAM_DATA_METADATA_LEN] != *authority.key() {
+        return Err(VaultError::NotUpgradeAuthority.into());
+    }
+    if !authority.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    Ok(())
+}
+
+// Vaults are created by the registry admin, or by the upgrade authority before
+// there is a registry. The payer becomes the admin of the vault, anyone else
+// could take the vault of a mint before its real admin.
+pub fn check_vault_creator(
+    program_id: &Pubkey,
+    registry: &AccountInfo,
+    program_data: &AccountInfo,
+    payer: &AccountInfo,
+) -> ProgramResult {
+    if !registry.data_is_empty() && load_registry(program_id, registry)?.admin == *payer.key() {
+        return Ok(());
+    }
+    check_upgrade_authority(program_id, program_data, payer).map_err(|_| VaultError::Unauthorized.into())
+}
+
+// Outflows share the pause flag and the minimum amount of the vault config
+pub fn check_withdraw_allowed(vault_config: &VaultConfig, amount: u64) -> Result<(), VaultError> {
+    if bool::from(vault_config.withdraw_paused) {
+        return Err(VaultError::WithdrawPaused);
+    }
+    if amount < u64::from(vault_config.min_withdraw) {
+        return Err(VaultError::BelowMinimum);
+    }
+    Ok(())
+}
+
+// Count a withdrawal against the user's and the vault's rate limit windows,
+// the error tells which limit was hit
+pub fn record_outflow(
+    vault_config: &VaultConfig,
+    vault_state: &mut VaultState,
+    user_state: &mut UserState,
+    amount: u64,
+    now: i64,
+) -> Result<(), VaultError> {
+    let window = i64::from(vault_config.rate_limit_window);
+    let user_limit = u64::from(vault_config.user_window_limit);
+    if user_state.outflow.record(amount, window, user_limit, now).is_err() {
+        msg!("User withdrawal limit reached");
+        return Err(VaultError::UserRateLimitExceeded);
+    }
+    let vault_limit = u64::from(vault_config.vault_window_limit);
+    if vault_state.outflow.record(amount, window, vault_limit, now).is_err() {
+        msg!("Vault withdrawal limit reached");
+        return Err(VaultError::VaultRateLimitExceeded);
+    }
+    Ok(())
+}
+
+// Size of a new associated token account for the mint, the extensions are the
+// ones Token-2022 requires for the mint's extensions
+pub fn token_account_space(token_mint: &AccountInfo, token_prog: &AccountInfo) -> Result<usize, ProgramError> {
+    if *token_prog.key() != TOKEN_2022_PROGRAM_ID {
+        return Ok(TOKEN_ACCOUNT_LEN);
+    }
+    // (type, length) of the account extensions, Token-2022 ATAs are created with an immutable owner
+    let mut extensions = [(IMMUTABLE_OWNER, 0); 5];
+    let mut count = 1;
+    let mint_data = token_mint.try_borrow_data()?;
+    for extension in mint_extension_types(&mint_data) {
+        let required = match extension {
+            TRANSFER_FEE_CONFIG => (TRANSFER_FEE_AMOUNT, 8),
+            NON_TRANSFERABLE => (NON_TRANSFERABLE_ACCOUNT, 0),
+            TRANSFER_HOOK => (TRANSFER_HOOK_ACCOUNT, 1),
+            PAUSABLE => (PAUSABLE_ACCOUNT, 0),
+            _ => continue,
+        };
+        if count < extensions.len() && !extensions[..count].contains(&required) {
+            extensions[count] = required;
+            count += 1;
+        }
+    }
+    Ok(extensions[..count].iter().fold(TLV_START, |len, (_, value_len)| len + 4 + value_len))
+}
+
+// Check the escrow program and the escrow maker PDA of the user state, with
+// the bump stored in the user state
+pub fn check_escrow_maker(
+    program_id: &Pubkey,
+    escrow_maker: &AccountInfo,
+    user_state: &Pubkey,
+    maker_bump: u8,
+    escrow_prog: &AccountInfo,
+) -> Result<(), VaultError> {
+    if *escrow_prog.key() != ESCROW_PROGRAM_ID {
+        return Err(VaultError::InvalidEscrowProgram);
+    }
+    if !is_pda(program_id, escrow_maker.key(), &[b"escrow_maker", user_state, &[maker_bump]])
+        || !escrow_maker.is_owned_by(&SYSTEM_PROGRAM_ID)
+    {
+        return Err(VaultError::InvalidEscrowMaker);
+    }
+    Ok(())
+}
+
+// Create a PDA owned by the program, the payer pays its rent. An address which
+// already holds lamports can't go through CreateAccount, it's topped up,
+// allocated and assigned instead.
+pub fn create_pda_account(
+    program_id: &Pubkey,
+    payer: &AccountInfo,
+    account: &AccountInfo,
+    space: usize,
+    lamports: u64,
+    signer: Signer,
+) -> ProgramResult {
+    if !account.data_is_empty() || !account.is_owned_by(&SYSTEM_PROGRAM_ID) {
+        return Err(VaultError::AlreadyInitialized.into());
+    }
+    let signers = [signer];
+    if account.lamports() == 0 {
+        let mut data = [0; 52];
+        data[4..12].copy_from_slice(&lamports.to_le_bytes());
+        data[12..20].copy_from_slice(&(space as u64).to_le_bytes());
+        data[20..52].copy_from_slice(program_id);
+        return invoke_signed(
+            &Instruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                accounts: &[
+                    AccountMeta::writable_signer(payer.key()),
+                    AccountMeta::writable_signer(account.key()),
+                ],
+                data: &data,
+            },
+            &[payer, account],
+            &signers,
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_transfer(payer, account, shortfall, &[])?;
+    }
+    // Allocate
+    let mut data = [0; 12];
+    data[..4].copy_from_slice(&8u32.to_le_bytes());
+    data[4..].copy_from_slice(&(space as u64).to_le_bytes());
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            accounts: &[AccountMeta::writable_signer(account.key())],
+            data: &data,
+        },
+        &[account],
+        &signers,
+    )?;
+    // Assign
+    let mut data = [0; 36];
+    data[..4].copy_from_slice(&1u32.to_le_bytes());
+    data[4..].copy_from_slice(program_id);
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            accounts: &[AccountMeta::writable_signer(account.key())],
+            data: &data,
+        },
+        &[account],
+        &signers,
+    )
+}
+
+// Move lamports out of a system account, `signers` when it's a PDA
+pub fn system_transfer(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    lamports: u64,
+    signers: &[Signer],
+) -> ProgramResult {
+    let mut data = [0; 12];
+    data[..4].copy_from_slice(&2u32.to_le_bytes());
+    data[4..].copy_from_slice(&lamports.to_le_bytes());
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            accounts: &[AccountMeta::writable_signer(from.key()), AccountMeta::writable(to.key())],
+            data: &data,
+        },
+        &[from, to],
+        signers,
+    )
+}
+
+// Create the associated token account of the owner, the payer pays its rent.
+// The idempotent variant leaves an existing account alone.
+pub fn create_ata(
+    payer: &AccountInfo,
+    ata: &AccountInfo,
+    owner: &AccountInfo,
+    token_mint: &AccountInfo,
+    system_prog: &AccountInfo,
+    token_prog: &AccountInfo,
+    idempotent: bool,
+) -> ProgramResult {
+    invoke(
+        &Instruction {
+            program_id: &ATA_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable_signer(payer.key()),
+                AccountMeta::writable(ata.key()),
+                AccountMeta::readonly(owner.key()),
+                AccountMeta::readonly(token_mint.key()),
+                AccountMeta::readonly(system_prog.key()),
+                AccountMeta::readonly(token_prog.key()),
+            ],
+            data: &[u8::from(idempotent)],
+        },
+        &[payer, ata, owner, token_mint, system_prog, token_prog],
+    )
+}
+
+// Move tokens out of the escrow maker's ATA and close it, the rent goes to `rent_destination`
+pub fn empty_maker_ata(
+    maker_ata: &AccountInfo,
+    destination: &AccountInfo,
+    rent_destination: &AccountInfo,
+    escrow_maker: &AccountInfo,
+    token_mint: &AccountInfo,
+    token_prog: &AccountInfo,
+    maker_seeds: &[Seed],
+) -> ProgramResult {
+    let amount = token_account_amount(maker_ata)?;
+    if amount > 0 {
+        transfer_checked(
+            maker_ata,
+            token_mint,
+            destination,
+            escrow_maker,
+            token_prog,
+            amount,
+            mint_decimals(token_mint, token_prog)?,
+            &[Signer::from(maker_seeds)],
+        )?;
+    }
+    invoke_signed(
+        &Instruction {
+            program_id: token_prog.key(),
+            accounts: &[
+                AccountMeta::writable(maker_ata.key()),
+                AccountMeta::writable(rent_destination.key()),
+                AccountMeta::readonly_signer(escrow_maker.key()),
+            ],
+            data: &[9],
+        },
+        &[maker_ata, rent_destination, escrow_maker],
+        &[Signer::from(maker_seeds)],
+    )
+}
+
+// Send every lamport left on the escrow maker to the destination, so it
+// never stays behind below the rent exempt minimum
+pub fn release_escrow_maker(
+    escrow_maker: &AccountInfo,
+    destination: &AccountInfo,
+    maker_seeds: &[Seed],
+) -> ProgramResult {
+    let lamports = escrow_maker.lamports();
+    if lamports == 0 {
+        return Ok(());
+    }
+    system_transfer(escrow_maker, destination, lamports, &[Signer::from(maker_seeds)])
+}
+
+// Depth of the current instruction, 1 when it was called by the transaction itself
+#[cfg(target_os = "solana")]
+pub fn stack_height() -> u64 {
+    // SAFETY: the syscall has no arguments
+    unsafe { pinocchio::syscalls::sol_get_stack_height() }
+}
+
+#[cfg(not(target_os = "solana"))]
+pub fn stack_height() -> u64 {
+    0
+}
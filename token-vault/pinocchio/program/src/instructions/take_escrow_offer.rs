@@ -0,0 +1,124 @@
+use super::shared::{
+    ata_address, check_escrow_maker, empty_maker_ata, load_position, release_escrow_maker,
+};
+use crate::error::VaultError;
+use crate::escrow::{self, load_offer};
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Fill an escrow offer posted from a vault position. The taker pays mint B to
+/// the escrow maker, which forwards it to the owner's mint B ATA. The rent of
+/// the offer account goes back to the owner, the escrow gives the rent of its
+/// token account to the taker.
+/// Offers are only filled whole, see the `escrow` module.
+///
+/// Accounts:
+/// [signer writable taker]
+/// [writable user_state]
+/// [writable escrow_maker]
+/// [writable owner]
+/// [writable taker_ata_a]
+/// [writable taker_ata_b]
+/// [writable maker_ata_b]
+/// [writable owner_ata_b]
+/// [writable offer]
+/// [writable offer_vault_ata]
+/// [readonly token_mint_a]
+/// [readonly token_mint_b]
+/// [readonly token program]
+/// [readonly associated token program]
+/// [readonly system program]
+/// [readonly escrow program]
+pub fn take_escrow_offer(program_id: &Pubkey, accounts: &[AccountInfo], id: u64) -> ProgramResult {
+    let [taker, user_state, escrow_maker, owner, taker_ata_a, taker_ata_b, maker_ata_b, owner_ata_b, offer, offer_vault_ata, token_mint_a, token_mint_b, token_prog, ata_prog, system_prog, escrow_prog, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !taker.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !taker.is_writable()
+        || !user_state.is_writable()
+        || !escrow_maker.is_writable()
+        || !owner.is_writable()
+        || !maker_ata_b.is_writable()
+        || !owner_ata_b.is_writable()
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    // The proceeds and the rent go to the owner of the position
+    let maker_bump = {
+        let position = load_position(program_id, user_state, token_mint_a.key())?;
+        if position.user != *owner.key() {
+            return Err(VaultError::InvalidOwner.into());
+        }
+        [position.escrow_maker_bump]
+    };
+    check_escrow_maker(program_id, escrow_maker, user_state.key(), maker_bump[0], escrow_prog)?;
+    let maker_seeds = [Seed::from(b"escrow_maker"), Seed::from(user_state.key()), Seed::from(&maker_bump)];
+    if *maker_ata_b.key() != ata_address(escrow_maker.key(), token_mint_b.key(), token_prog.key()) {
+        return Err(VaultError::InvalidEscrowMaker.into());
+    }
+    if *owner_ata_b.key() != ata_address(owner.key(), token_mint_b.key(), token_prog.key()) {
+        return Err(VaultError::InvalidOwner.into());
+    }
+
+    // The offer is closed by the escrow, copy what it earmarks first
+    let offer_data = load_offer(offer)?;
+    if offer_data.maker != *escrow_maker.key()
+        || u64::from(offer_data.id) != id
+        || offer_data.token_mint_a != *token_mint_a.key()
+        || offer_data.token_mint_b != *token_mint_b.key()
+    {
+        return Err(VaultError::InvalidOffer.into());
+    }
+
+    msg!("Taking escrow offer");
+    escrow::take_offer(
+        taker,
+        escrow_maker,
+        token_mint_a,
+        token_mint_b,
+        taker_ata_a,
+        taker_ata_b,
+        maker_ata_b,
+        offer,
+        offer_vault_ata,
+        token_prog,
+        ata_prog,
+        system_prog,
+        id,
+        Signer::from(&maker_seeds),
+    )?;
+
+    // Forward the proceeds to the owner, the taker paid the maker ATA and gets its rent back
+    empty_maker_ata(
+        maker_ata_b,
+        owner_ata_b,
+        taker,
+        escrow_maker,
+        token_mint_b,
+        token_prog,
+        &maker_seeds,
+    )?;
+    release_escrow_maker(escrow_maker, owner, &maker_seeds)?;
+
+    let mut user_state_data = load_position(program_id, user_state, token_mint_a.key())?;
+    user_state_data.earmarked = u64::from(user_state_data.earmarked)
+        .checked_sub(offer_data.token_a_offered_amount.into())
+        .ok_or(VaultError::Overflow)?
+        .into();
+    msg!("Escrow offer taken");
+
+    Ok(())
+}
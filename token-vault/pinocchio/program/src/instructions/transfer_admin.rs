@@ -0,0 +1,45 @@
+use super::shared::load_vault_config_mut;
+use crate::error::VaultError;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::{Pubkey, find_program_address},
+    ProgramResult,
+};
+
+/// First step of an admin transfer, the current admin proposes the new admin.
+/// Proposing the default key cancels a pending transfer.
+///
+/// Accounts:
+/// [signer admin]
+/// [writable vault_config]
+/// [readonly token_mint]
+pub fn transfer_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+) -> ProgramResult {
+    let [admin, vault_config, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !admin.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_config.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let (vault_pda, _vault_bump) = find_program_address(&[b"vault", token_mint.key()], program_id);
+    let mut vault_config_data = load_vault_config_mut(program_id, vault_config, &vault_pda)?;
+    if vault_config_data.admin != *admin.key() {
+        return Err(VaultError::Unauthorized.into());
+    }
+
+    msg!("Proposing a new vault admin");
+    vault_config_data.pending_admin = new_admin;
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use super::shared::load_registry_mut;
+use crate::error::VaultError;
+use crate::state::RegistryEntry;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Remove a vault from the registry. Deposits into it are refused afterwards,
+/// withdrawals keep working so users can still exit.
+///
+/// Accounts:
+/// [signer admin]
+/// [writable registry]
+/// [readonly token_mint]
+pub fn unregister_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [admin, registry, token_mint, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !admin.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !registry.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    let mut registry_data = load_registry_mut(program_id, registry)?;
+    if registry_data.admin != *admin.key() {
+        return Err(VaultError::Unauthorized.into());
+    }
+    let index = registry_data
+        .vaults()
+        .iter()
+        .position(|entry| entry.mint == *token_mint.key())
+        .ok_or(VaultError::MintNotRegistered)?;
+
+    // Move the last entry into the freed slot so the used entries stay contiguous
+    msg!("Unregistering vault");
+    let last = u32::from(registry_data.count) as usize - 1;
+    registry_data.entries[index] = registry_data.entries[last];
+    registry_data.entries[last] = RegistryEntry::default();
+    registry_data.count = (last as u32).into();
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use super::shared::{load_vault_config_mut, load_vault_state_mut};
+use crate::error::VaultError;
+use crate::state::{BPS_DENOMINATOR, ConfigParams};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Accounts:
+/// [signer admin]
+/// [writable vault_config]
+/// [readonly token_mint]
+/// [writable vault_state]
+pub fn update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    params: ConfigParams,
+) -> ProgramResult {
+    let [admin, vault_config, token_mint, vault_state, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !admin.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !vault_config.is_writable() || !vault_state.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    if params.rate_limit_window < 0 {
+        return Err(VaultError::InvalidRateLimitWindow.into());
+    }
+    if u64::from(params.early_exit_penalty_bps) > BPS_DENOMINATOR {
+        return Err(VaultError::InvalidPenalty.into());
+    }
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    let mut vault_config_data = load_vault_config_mut(program_id, vault_config, vault_state.key())?;
+    if vault_config_data.admin != *admin.key() {
+        return Err(VaultError::Unauthorized.into());
+    }
+
+    // Written in place
+    msg!("Updating vault config");
+    vault_config_data.user_deposit_cap = params.user_deposit_cap.into();
+    vault_config_data.tvl_cap = params.tvl_cap.into();
+    vault_config_data.min_deposit = params.min_deposit.into();
+    vault_config_data.min_withdraw = params.min_withdraw.into();
+    vault_config_data.deposit_paused = params.deposit_paused.into();
+    vault_config_data.withdraw_paused = params.withdraw_paused.into();
+    vault_config_data.rate_limit_window = params.rate_limit_window.into();
+    vault_config_data.user_window_limit = params.user_window_limit.into();
+    vault_config_data.vault_window_limit = params.vault_window_limit.into();
+    vault_state_data.early_exit_penalty_bps = params.early_exit_penalty_bps.into();
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use crate::error::VaultError;
+use crate::header::{AccountKind, upgrade};
+use crate::state::{UserState, VaultConfig, VaultState, WithdrawTicket};
+use pinocchio::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+/// Rewrite an account created by an older version of the program with the
+/// current header and layout. The account keeps its address and balance, the
+/// payer covers the extra rent when it grows.
+///
+/// Accounts:
+/// [signer writable payer]
+/// [writable account]
+/// [readonly system program]
+pub fn upgrade_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    kind: AccountKind,
+) -> ProgramResult {
+    let [payer, account, _system_prog, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !payer.is_writable() || !account.is_writable() {
+        return Err(VaultError::NotWritable.into());
+    }
+
+    match kind {
+        AccountKind::VaultState => upgrade::<VaultState>(program_id, account, payer),
+        AccountKind::VaultConfig => upgrade::<VaultConfig>(program_id, account, payer),
+        AccountKind::UserState => upgrade::<UserState>(program_id, account, payer),
+        AccountKind::WithdrawTicket => upgrade::<WithdrawTicket>(program_id, account, payer),
+    }
+}
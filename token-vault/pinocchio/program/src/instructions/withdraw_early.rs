@@ -0,0 +1,120 @@
+use super::shared::{
+    check_withdraw_allowed, load_user_state, load_vault_config, load_vault_state_mut, record_outflow,
+    transfer_from_vault,
+};
+use crate::error::VaultError;
+use crate::state::BPS_DENOMINATOR;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{Sysvar, clock::Clock},
+    ProgramResult,
+};
+
+/// Withdraw from a position before its unlock time. The vault's early exit
+/// penalty is kept in the vault and added to the treasury.
+///
+/// Accounts:
+/// [signer payer]
+/// [writable user_ata]
+/// [writable vault_ata]
+/// [writable user_state]
+/// [writable vault_state]
+/// [readonly vault_config]
+/// [readonly token_mint]
+/// [readonly token program]
+pub fn withdraw_early(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    withdraw_amount: u64,
+) -> ProgramResult {
+    let [payer, user_ata, vault_ata, user_state, vault_state, vault_config, token_mint, token_prog, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // Basic checks
+    if !payer.is_signer() {
+        return Err(VaultError::NotSigner.into());
+    }
+    if !user_state.is_writable()
+        || !user_ata.is_writable()
+        || !vault_ata.is_writable()
+        || !vault_state.is_writable()
+    {
+        return Err(VaultError::NotWritable.into());
+    }
+    if withdraw_amount == 0 {
+        return Err(VaultError::InvalidAmount.into());
+    }
+
+    // Verify and borrow the user's state PDA
+    let mut user_state_data = load_user_state(program_id, user_state, payer.key(), token_mint.key())?;
+
+    // Credit the fees earned so far before checking the balance.
+    // The vault state is passed to the transfer below, so only copy what is needed from it
+    let (withdraw_cooldown, early_exit_penalty_bps) = {
+        let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+        vault_state_data.settle_fees(&mut user_state_data)?;
+        (
+            i64::from(vault_state_data.withdraw_cooldown),
+            u16::from(vault_state_data.early_exit_penalty_bps),
+        )
+    };
+    let amount = u64::from(user_state_data.amount);
+    if amount < withdraw_amount {
+        return Err(VaultError::InsufficientFunds.into());
+    }
+
+    // Enforce the limits set by the vault admin
+    let vault_config_data = load_vault_config(program_id, vault_config, vault_state.key())?;
+    check_withdraw_allowed(&vault_config_data, withdraw_amount)?;
+
+    // Vaults with a cooldown only pay out through withdrawal tickets
+    if withdraw_cooldown > 0 {
+        return Err(VaultError::InstantWithdrawDisabled.into());
+    }
+
+    // Unlocked positions exit for free
+    let now = Clock::get()?.unix_timestamp;
+    // Rounded up, so splitting an exit into small withdrawals doesn't dodge the penalty
+    let penalty = if user_state_data.is_locked(now) {
+        (withdraw_amount as u128 * early_exit_penalty_bps as u128)
+            .div_ceil(BPS_DENOMINATOR as u128) as u64
+    } else {
+        0
+    };
+    let payout = withdraw_amount - penalty;
+
+    // Transfer what is left after the penalty from vault ATA to user ATA
+    msg!("Early exit, the penalty goes to the treasury");
+    if payout > 0 {
+        transfer_from_vault(
+            program_id,
+            vault_ata,
+            user_ata,
+            vault_state,
+            token_mint,
+            token_prog,
+            payout,
+        )?;
+    }
+
+    // Update user and vault state
+    user_state_data.amount = (amount - withdraw_amount).into();
+
+    let mut vault_state_data = load_vault_state_mut(program_id, vault_state, token_mint.key())?;
+    vault_state_data.treasury = u64::from(vault_state_data.treasury)
+        .checked_add(penalty)
+        .ok_or(VaultError::Overflow)?
+        .into();
+    vault_state_data.sub_deposits(withdraw_amount)?;
+    // Only what leaves the vault counts against the rate limits
+    record_outflow(&vault_config_data, &mut vault_state_data, &mut user_state_data, payout, now)?;
+    msg!("Early withdrawal successful, user state updated");
+
+    Ok(())
+}
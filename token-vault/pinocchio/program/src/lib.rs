@@ -0,0 +1,50 @@
+//! Pinocchio port of the native vault in `token-vault/native`, `no_std` and
+//! without an allocator.
+//!
+//! Instructions, account layouts and error codes are the same as the native
+//! program's, so clients and existing accounts work with either build.
+
+#![no_std]
+
+pub mod processor;
+pub mod instructions;
+pub mod error;
+pub mod state;
+pub mod legacy;
+pub mod header;
+pub mod escrow;
+pub mod pod;
+
+#[cfg(not(feature = "no-entrypoint"))]
+use {crate::processor::process_instruction, pinocchio::{no_allocator, program_entrypoint}};
+
+#[cfg(not(feature = "no-entrypoint"))]
+program_entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+no_allocator!();
+
+// Same as pinocchio's `nostd_panic_handler!`, which newer compilers reject
+// because it puts `#[no_mangle]` on the panic handler
+#[cfg(all(not(feature = "no-entrypoint"), target_os = "solana"))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    if let Some(location) = info.location() {
+        // SAFETY: the file name is a valid string for the syscall to read
+        unsafe {
+            pinocchio::syscalls::sol_panic_(
+                location.file().as_ptr(),
+                location.file().len() as u64,
+                location.line() as u64,
+                location.column() as u64,
+            )
+        }
+    } else {
+        pinocchio::log::sol_log("** PANICKED **");
+        // SAFETY: abort takes no arguments
+        unsafe { pinocchio::syscalls::abort() }
+    }
+}
+
+// Off chain the crate links std, which brings its own panic handler
+#[cfg(all(not(feature = "no-entrypoint"), not(target_os = "solana")))]
+extern crate std;
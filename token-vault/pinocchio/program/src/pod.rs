@@ -0,0 +1,52 @@
+//! Little endian integers with an alignment of 1, the same as the `spl-pod`
+//! primitives the native program stores, so account bodies can be read in place.
+
+macro_rules! pod_int {
+    ($name:ident, $int:ty) => {
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub struct $name([u8; core::mem::size_of::<$int>()]);
+
+        impl From<$int> for $name {
+            fn from(n: $int) -> Self {
+                Self(n.to_le_bytes())
+            }
+        }
+
+        impl From<$name> for $int {
+            fn from(pod: $name) -> Self {
+                <$int>::from_le_bytes(pod.0)
+            }
+        }
+    };
+}
+
+pod_int!(PodU16, u16);
+pod_int!(PodU32, u32);
+pod_int!(PodU64, u64);
+pod_int!(PodI64, i64);
+pod_int!(PodU128, u128);
+
+/// Bool stored as a byte, borsh writes them as 0 or 1
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PodBool(u8);
+
+impl From<bool> for PodBool {
+    fn from(b: bool) -> Self {
+        Self(u8::from(b))
+    }
+}
+
+impl PodBool {
+    // Borsh only decodes 0 and 1
+    pub fn is_valid(self) -> bool {
+        self.0 <= 1
+    }
+}
+
+impl From<PodBool> for bool {
+    fn from(pod: PodBool) -> Self {
+        pod.0 != 0
+    }
+}
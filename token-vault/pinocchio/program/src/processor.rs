@@ -0,0 +1,242 @@
+use pinocchio::{account_info::AccountInfo, pubkey::Pubkey, ProgramResult};
+
+use crate::error::VaultError;
+use crate::instructions::{
+    initialize_vault::initialize_vault,
+    initialize_user::initialize_user,
+    deposit_tokens::{deposit_for, deposit_tokens},
+    withdraw_tokens::withdraw_tokens,
+    withdraw_early::withdraw_early,
+    request_withdraw::request_withdraw,
+    claim_withdraw::claim_withdraw,
+    update_config::update_config,
+    transfer_admin::transfer_admin,
+    accept_admin::accept_admin,
+    close_user::close_user,
+    migrate_user_state::migrate_user_state,
+    upgrade_account::upgrade_account,
+    initialize_registry::initialize_registry,
+    register_vault::register_vault,
+    unregister_vault::unregister_vault,
+    flash_loan::flash_loan,
+    flash_repay::flash_repay,
+    set_flash_loan_fee::set_flash_loan_fee,
+    set_delegate::set_delegate,
+    revoke_delegate::revoke_delegate,
+    reconcile::reconcile,
+    make_escrow_offer::make_escrow_offer,
+    take_escrow_offer::take_escrow_offer,
+    refund_escrow_offer::refund_escrow_offer,
+    withdraw_treasury::withdraw_treasury,
+};
+use crate::header::AccountKind;
+use crate::legacy::LegacyLayout;
+use crate::state::{ConfigParams, LockupTier};
+
+/// Same instructions as the native vault, with the same borsh encoding: the
+/// variant index as one byte followed by the fields
+#[derive(Debug, Clone, Copy)]
+pub enum VaultInstruction {
+    InitializeVault { early_exit_penalty_bps: u16, withdraw_cooldown: i64 },
+    InitializeUser,
+    Deposit { amount: u64, lockup: LockupTier },
+    Withdraw { amount: u64, close_if_empty: bool },
+    WithdrawEarly { amount: u64 },
+    RequestWithdraw { amount: u64 },
+    ClaimWithdraw,
+    UpdateConfig { params: ConfigParams },
+    TransferAdmin { new_admin: Pubkey },
+    AcceptAdmin,
+    CloseUser,
+    MigrateUserState { layout: LegacyLayout },
+    UpgradeAccount { kind: AccountKind },
+    InitializeRegistry,
+    RegisterVault,
+    UnregisterVault,
+    FlashLoan { amount: u64 },
+    FlashRepay { amount: u64 },
+    SetFlashLoanFee { fee_bps: u16 },
+    DepositFor { owner: Pubkey, amount: u64 },
+    SetDelegate { delegate: Pubkey, allowance: Option<u64> },
+    RevokeDelegate,
+    Reconcile { pause_if_insolvent: bool },
+    MakeEscrowOffer { id: u64, amount: u64, wanted_amount: u64 },
+    TakeEscrowOffer { id: u64 },
+    RefundEscrowOffer { id: u64 },
+    WithdrawTreasury { amount: u64 },
+}
+
+// Reads borsh encoded fields off the instruction data
+struct Args<'a>(&'a [u8]);
+
+impl Args<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], VaultError> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>().ok_or(VaultError::InvalidInstruction)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, VaultError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, VaultError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, VaultError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn i64(&mut self) -> Result<i64, VaultError> {
+        Ok(i64::from_le_bytes(self.take()?))
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, VaultError> {
+        self.take()
+    }
+
+    // Borsh only accepts 0 and 1
+    fn bool(&mut self) -> Result<bool, VaultError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(VaultError::InvalidInstruction),
+        }
+    }
+
+    fn option_u64(&mut self) -> Result<Option<u64>, VaultError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.u64()?)),
+            _ => Err(VaultError::InvalidInstruction),
+        }
+    }
+
+    fn enum_tag<T>(&mut self, from_u8: fn(u8) -> Option<T>) -> Result<T, VaultError> {
+        from_u8(self.u8()?).ok_or(VaultError::InvalidInstruction)
+    }
+}
+
+impl VaultInstruction {
+    /// Decode the instruction data, like borsh's `try_from_slice` every byte must be used
+    pub fn unpack(data: &[u8]) -> Result<Self, VaultError> {
+        let mut args = Args(data);
+        let instruction = match args.u8()? {
+            0 => Self::InitializeVault {
+                early_exit_penalty_bps: args.u16()?,
+                withdraw_cooldown: args.i64()?,
+            },
+            1 => Self::InitializeUser,
+            2 => Self::Deposit { amount: args.u64()?, lockup: args.enum_tag(LockupTier::from_u8)? },
+            3 => Self::Withdraw { amount: args.u64()?, close_if_empty: args.bool()? },
+            4 => Self::WithdrawEarly { amount: args.u64()? },
+            5 => Self::RequestWithdraw { amount: args.u64()? },
+            6 => Self::ClaimWithdraw,
+            7 => Self::UpdateConfig {
+                params: ConfigParams {
+                    user_deposit_cap: args.u64()?,
+                    tvl_cap: args.u64()?,
+                    min_deposit: args.u64()?,
+                    min_withdraw: args.u64()?,
+                    deposit_paused: args.bool()?,
+                    withdraw_paused: args.bool()?,
+                    rate_limit_window: args.i64()?,
+                    user_window_limit: args.u64()?,
+                    vault_window_limit: args.u64()?,
+                    early_exit_penalty_bps: args.u16()?,
+                },
+            },
+            8 => Self::TransferAdmin { new_admin: args.pubkey()? },
+            9 => Self::AcceptAdmin,
+            10 => Self::CloseUser,
+            11 => Self::MigrateUserState { layout: args.enum_tag(LegacyLayout::from_u8)? },
+            12 => Self::UpgradeAccount { kind: args.enum_tag(AccountKind::from_u8)? },
+            13 => Self::InitializeRegistry,
+            14 => Self::RegisterVault,
+            15 => Self::UnregisterVault,
+            16 => Self::FlashLoan { amount: args.u64()? },
+            17 => Self::FlashRepay { amount: args.u64()? },
+            18 => Self::SetFlashLoanFee { fee_bps: args.u16()? },
+            19 => Self::DepositFor { owner: args.pubkey()?, amount: args.u64()? },
+            20 => Self::SetDelegate { delegate: args.pubkey()?, allowance: args.option_u64()? },
+            21 => Self::RevokeDelegate,
+            22 => Self::Reconcile { pause_if_insolvent: args.bool()? },
+            23 => Self::MakeEscrowOffer {
+                id: args.u64()?,
+                amount: args.u64()?,
+                wanted_amount: args.u64()?,
+            },
+            24 => Self::TakeEscrowOffer { id: args.u64()? },
+            25 => Self::RefundEscrowOffer { id: args.u64()? },
+            26 => Self::WithdrawTreasury { amount: args.u64()? },
+            _ => return Err(VaultError::InvalidInstruction),
+        };
+        if !args.0.is_empty() {
+            return Err(VaultError::InvalidInstruction);
+        }
+        Ok(instruction)
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match VaultInstruction::unpack(instruction_data)? {
+        VaultInstruction::InitializeVault { early_exit_penalty_bps, withdraw_cooldown } => {
+            initialize_vault(program_id, accounts, early_exit_penalty_bps, withdraw_cooldown)
+        }
+        VaultInstruction::InitializeUser => initialize_user(program_id, accounts),
+        VaultInstruction::Deposit { amount, lockup } => {
+            deposit_tokens(program_id, accounts, amount, lockup)
+        }
+        VaultInstruction::Withdraw { amount, close_if_empty } => {
+            withdraw_tokens(program_id, accounts, amount, close_if_empty)
+        }
+        VaultInstruction::WithdrawEarly { amount } => withdraw_early(program_id, accounts, amount),
+        VaultInstruction::RequestWithdraw { amount } => {
+            request_withdraw(program_id, accounts, amount)
+        }
+        VaultInstruction::ClaimWithdraw => claim_withdraw(program_id, accounts),
+        VaultInstruction::UpdateConfig { params } => update_config(program_id, accounts, params),
+        VaultInstruction::TransferAdmin { new_admin } => {
+            transfer_admin(program_id, accounts, new_admin)
+        }
+        VaultInstruction::AcceptAdmin => accept_admin(program_id, accounts),
+        VaultInstruction::CloseUser => close_user(program_id, accounts),
+        VaultInstruction::MigrateUserState { layout } => {
+            migrate_user_state(program_id, accounts, layout)
+        }
+        VaultInstruction::UpgradeAccount { kind } => upgrade_account(program_id, accounts, kind),
+        VaultInstruction::InitializeRegistry => initialize_registry(program_id, accounts),
+        VaultInstruction::RegisterVault => register_vault(program_id, accounts),
+        VaultInstruction::UnregisterVault => unregister_vault(program_id, accounts),
+        VaultInstruction::FlashLoan { amount } => flash_loan(program_id, accounts, amount),
+        VaultInstruction::FlashRepay { amount } => flash_repay(program_id, accounts, amount),
+        VaultInstruction::SetFlashLoanFee { fee_bps } => {
+            set_flash_loan_fee(program_id, accounts, fee_bps)
+        }
+        VaultInstruction::DepositFor { owner, amount } => {
+            deposit_for(program_id, accounts, owner, amount)
+        }
+        VaultInstruction::SetDelegate { delegate, allowance } => {
+            set_delegate(program_id, accounts, delegate, allowance)
+        }
+        VaultInstruction::RevokeDelegate => revoke_delegate(program_id, accounts),
+        VaultInstruction::Reconcile { pause_if_insolvent } => {
+            reconcile(program_id, accounts, pause_if_insolvent)
+        }
+        VaultInstruction::MakeEscrowOffer { id, amount, wanted_amount } => {
+            make_escrow_offer(program_id, accounts, id, amount, wanted_amount)
+        }
+        VaultInstruction::TakeEscrowOffer { id } => take_escrow_offer(program_id, accounts, id),
+        VaultInstruction::RefundEscrowOffer { id } => {
+            refund_escrow_offer(program_id, accounts, id)
+        }
+        VaultInstruction::WithdrawTreasury { amount } => {
+            withdraw_treasury(program_id, accounts, amount)
+        }
+    }
+}